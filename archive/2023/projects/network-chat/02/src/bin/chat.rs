@@ -1,6 +1,7 @@
-use chat::chat::Chat;
+use chat::chat::{Chat, DEFAULT_QUEUE_SIZE};
 use chat::client::client_loop;
 use chat::messages::{ClientToServerMsg, ServerToClientMsg};
+use chat::outbox::OverflowPolicy;
 use chat::reader::MessageReader;
 use chat::server::handle_client;
 use chat::writer::MessageWriter;
@@ -25,6 +26,12 @@ struct ServerArgs {
     /// Port on which will the server listen.
     #[arg(long, default_value_t = 5555)]
     port: u16,
+    /// Number of messages that can wait for a slow client.
+    #[arg(long, default_value_t = DEFAULT_QUEUE_SIZE)]
+    queue_size: usize,
+    /// What happens when the queue of a slow client is full.
+    #[arg(long, value_enum, default_value_t = OverflowPolicy::default())]
+    overflow_policy: OverflowPolicy,
 }
 
 #[derive(Parser)]
@@ -42,7 +49,7 @@ fn main() -> ChatResult<()> {
     let args = Args::parse();
     match args {
         Args::Server(args) => {
-            run_server(args.port)?;
+            run_server(args)?;
         }
         Args::Client(args) => {
             run_client(args.port)?;
@@ -59,13 +66,13 @@ fn run_client(port: u16) -> ChatResult<()> {
 
 const MAX_CLIENTS: usize = 3;
 
-fn run_server(port: u16) -> ChatResult<()> {
-    log::info!("Server listening on port {port}");
+fn run_server(args: ServerArgs) -> ChatResult<()> {
+    log::info!("Server listening on port {}", args.port);
 
-    let server = std::net::TcpListener::bind(("127.0.0.1", port))?;
+    let server = std::net::TcpListener::bind(("127.0.0.1", args.port))?;
     let mut connected_clients: Vec<JoinHandle<()>> = vec![];
 
-    let mut chat = Arc::new(Mutex::new(Chat::default()));
+    let mut chat = Arc::new(Mutex::new(Chat::new(args.queue_size, args.overflow_policy)));
 
     loop {
         let (client, address) = server.accept()?;
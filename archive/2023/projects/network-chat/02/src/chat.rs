@@ -1,38 +1,210 @@
 use crate::messages::ServerToClientMsg;
-use crate::writer::MessageWriter;
+use crate::outbox::{write_messages, Outbox, OverflowPolicy, Pushed};
 use std::collections::HashMap;
 use std::net::{SocketAddr, TcpStream};
 use std::sync::Arc;
 
+/// Number of messages that can wait for a client before the [OverflowPolicy] is applied.
+pub const DEFAULT_QUEUE_SIZE: usize = 64;
+
 pub struct Client {
-    socket: Arc<TcpStream>,
+    outbox: Arc<Outbox>,
 }
 
-#[derive(Default)]
 pub struct Chat {
     clients: HashMap<SocketAddr, Client>,
+    queue_size: usize,
+    policy: OverflowPolicy,
+}
+
+impl Default for Chat {
+    fn default() -> Self {
+        Self::new(DEFAULT_QUEUE_SIZE, OverflowPolicy::default())
+    }
 }
 
 impl Chat {
+    /// Each client has its own queue of up to `queue_size` outgoing messages, which is written
+    /// by a separate thread, so a client that does not read cannot block the others.
+    pub fn new(queue_size: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            clients: HashMap::new(),
+            queue_size,
+            policy,
+        }
+    }
+
     pub fn add_client(&mut self, address: SocketAddr, socket: Arc<TcpStream>) {
         log::info!("Connected client {address}");
         self.broadcast(address, format!("[{address} connected]"));
-        assert!(self.clients.insert(address, Client { socket }).is_none());
+        let outbox = Arc::new(Outbox::new(self.queue_size, self.policy));
+        let writer_outbox = outbox.clone();
+        std::thread::spawn(move || write_messages(socket, writer_outbox));
+        assert!(self.clients.insert(address, Client { outbox }).is_none());
     }
     pub fn remove_client(&mut self, address: SocketAddr) {
         log::info!("Disconnected client {address}");
         self.broadcast(address, format!("[{address} disconnected]"));
-        assert!(self.clients.remove(&address).is_some());
+        let client = self.clients.remove(&address).unwrap();
+        client.outbox.close();
     }
     pub fn broadcast(&self, source: SocketAddr, message: String) {
         self.clients
             .iter()
             .filter(|(address, _)| **address != source)
-            .for_each(|(address, socket)| {
+            .for_each(|(address, client)| {
                 let content = format!("{source}: {message}");
-                let mut writer = MessageWriter::new(&socket.socket);
                 log::info!("Sending {content} to {address}");
-                writer.send(ServerToClientMsg::Message(content)).unwrap();
+                match client.outbox.push(ServerToClientMsg::Message(content)) {
+                    Pushed::Queued | Pushed::Closed => {}
+                    Pushed::Dropped => log::warn!("Dropped a message for slow client {address}"),
+                }
             })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Chat;
+    use crate::messages::ServerToClientMsg;
+    use crate::outbox::OverflowPolicy;
+    use std::io::{BufRead, BufReader};
+    use std::net::{SocketAddr, TcpListener, TcpStream};
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    /// Large enough to fill the socket buffers of a client that does not read.
+    const FLOOD: usize = 2000;
+    const QUEUE_SIZE: usize = 16;
+
+    #[test]
+    fn drop_oldest() {
+        let (mut chat, mut client) = stalled_client(QUEUE_SIZE, OverflowPolicy::DropOldest);
+        flood(&chat);
+
+        let (messages, error) = read_messages(&mut client);
+        assert_eq!(error, None);
+        assert!(messages.len() < FLOOD);
+        // The newest messages were kept
+        let kept = &messages[messages.len() - QUEUE_SIZE..];
+        assert!(kept.iter().eq(&messages_between(FLOOD - QUEUE_SIZE, FLOOD)));
+        chat.remove_client(address(&chat));
+    }
+
+    #[test]
+    fn drop_newest() {
+        let (mut chat, mut client) = stalled_client(QUEUE_SIZE, OverflowPolicy::DropNewest);
+        flood(&chat);
+
+        let (messages, error) = read_messages(&mut client);
+        assert_eq!(error, None);
+        assert!(messages.len() < FLOOD);
+        // Messages that arrived while the queue was full were dropped
+        let indices = messages.iter().map(|msg| index(msg)).collect::<Vec<_>>();
+        assert!(indices.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(*indices.last().unwrap() < FLOOD - QUEUE_SIZE);
+
+        // Messages are delivered again once the client has caught up
+        chat.broadcast(source(), "after".to_string());
+        assert!(matches!(
+            read_message(&mut client),
+            Some(ServerToClientMsg::Message(msg)) if msg == format!("{}: after", source())
+        ));
+        chat.remove_client(address(&chat));
+    }
+
+    #[test]
+    fn disconnect() {
+        // The queue can hold the whole flood, so the writer is blocked in the middle of a message
+        // when the queue overflows
+        let (chat, mut client) = stalled_client(FLOOD, OverflowPolicy::Disconnect);
+        flood(&chat);
+        for i in FLOOD..FLOOD * 2 {
+            chat.broadcast(source(), payload(i));
+        }
+
+        // The client can read only the messages sent before the overflow, followed by the error
+        let (messages, error) = read_messages(&mut client);
+        assert!(messages.len() < FLOOD);
+        assert!(messages.iter().eq(&messages_between(0, messages.len())));
+        assert_eq!(error.as_deref(), Some("Outbound queue is full"));
+        // Then the connection is closed
+        assert_eq!(client.read_line(&mut String::new()).unwrap(), 0);
+    }
+
+    /// Creates a chat with a single client, which does not read anything until the test does.
+    fn stalled_client(queue_size: usize, policy: OverflowPolicy) -> (Chat, BufReader<TcpStream>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        client
+            .set_read_timeout(Some(Duration::from_millis(500)))
+            .unwrap();
+        let (socket, address) = listener.accept().unwrap();
+        let mut chat = Chat::new(queue_size, policy);
+        chat.add_client(address, Arc::new(socket));
+        (chat, BufReader::new(client))
+    }
+
+    /// Broadcasts many large messages, which must not block even though the client is not reading.
+    fn flood(chat: &Chat) {
+        let start = Instant::now();
+        for i in 0..FLOOD {
+            chat.broadcast(source(), payload(i));
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+        // Let the writer fill the socket buffers
+        std::thread::sleep(Duration::from_millis(500));
+    }
+
+    /// Reads messages until an error arrives, the connection is closed or nothing arrives for
+    /// a while. Returns the contents of the messages and the error.
+    fn read_messages(client: &mut BufReader<TcpStream>) -> (Vec<String>, Option<String>) {
+        let mut messages = vec![];
+        loop {
+            match read_message(client) {
+                Some(ServerToClientMsg::Message(message)) => messages.push(message),
+                Some(ServerToClientMsg::Error(error)) => return (messages, Some(error)),
+                None => return (messages, None),
+            }
+        }
+    }
+
+    fn read_message(reader: &mut BufReader<TcpStream>) -> Option<ServerToClientMsg> {
+        let mut line = String::new();
+        match reader.read_line(&mut line) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => {
+                assert!(
+                    line.ends_with('\n'),
+                    "The writer gave up in the middle of a message"
+                );
+                Some(serde_json::from_str(&line).unwrap())
+            }
+        }
+    }
+
+    fn address(chat: &Chat) -> SocketAddr {
+        *chat.clients.keys().next().unwrap()
+    }
+
+    fn source() -> SocketAddr {
+        "127.0.0.1:1".parse().unwrap()
+    }
+
+    fn payload(index: usize) -> String {
+        format!("{index:08}{}", "x".repeat(16 * 1024))
+    }
+
+    /// Index of the broadcast that was received as `message`.
+    fn index(message: &str) -> usize {
+        let payload = message.strip_prefix(&format!("{}: ", source())).unwrap();
+        payload[..8].parse().unwrap()
+    }
+
+    /// Messages received by the client from broadcasts with indices `start..end`.
+    fn messages_between(start: usize, end: usize) -> Vec<String> {
+        (start..end)
+            .map(|index| format!("{}: {}", source(), payload(index)))
+            .collect()
+    }
+}
@@ -28,6 +28,9 @@ pub fn client_loop(socket: TcpStream) -> ChatResult<()> {
             ServerToClientMsg::Message(message) => {
                 println!("{message}");
             }
+            ServerToClientMsg::Error(error) => {
+                eprintln!("Error: {error}");
+            }
         }
     }
 
@@ -1,6 +1,7 @@
 pub mod chat;
 pub mod client;
 pub mod messages;
+pub mod outbox;
 pub mod reader;
 pub mod server;
 pub mod writer;
@@ -1,6 +1,8 @@
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub enum ServerToClientMsg {
     Message(String),
+    /// Sent before the server disconnects the client.
+    Error(String),
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
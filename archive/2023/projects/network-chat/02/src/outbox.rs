@@ -0,0 +1,169 @@
+use crate::messages::ServerToClientMsg;
+use std::collections::VecDeque;
+use std::io::{ErrorKind, Write};
+use std::net::{Shutdown, TcpStream};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often a writer blocked by a slow client checks whether it should give up.
+const WRITE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How long a client that is being disconnected has to read the rest of its messages.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// What happens when a message is sent to a client whose outbound queue is full.
+#[derive(clap::ValueEnum, Copy, Clone, Debug, Default, PartialEq)]
+pub enum OverflowPolicy {
+    /// Drop the oldest queued message to make space for the new one.
+    DropOldest,
+    /// Drop the new message.
+    DropNewest,
+    /// Send an error to the client and disconnect it.
+    #[default]
+    Disconnect,
+}
+
+/// Result of [Outbox::push].
+#[derive(Debug, PartialEq)]
+pub enum Pushed {
+    Queued,
+    /// A message was dropped because the queue was full.
+    Dropped,
+    /// The queue was full and the client is being disconnected (or it is already disconnected).
+    Closed,
+}
+
+struct State {
+    queue: VecDeque<ServerToClientMsg>,
+    closed: bool,
+}
+
+/// Bounded queue of messages waiting to be written to a single client.
+pub struct Outbox {
+    state: Mutex<State>,
+    changed: Condvar,
+    capacity: usize,
+    policy: OverflowPolicy,
+}
+
+impl Outbox {
+    pub fn new(capacity: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            state: Mutex::new(State {
+                queue: VecDeque::with_capacity(capacity),
+                closed: false,
+            }),
+            changed: Condvar::new(),
+            capacity,
+            policy,
+        }
+    }
+
+    /// Queues a message without blocking, even if the client does not read anything.
+    pub fn push(&self, msg: ServerToClientMsg) -> Pushed {
+        let mut state = self.state.lock().unwrap();
+        if state.closed {
+            return Pushed::Closed;
+        }
+        let pushed = if state.queue.len() < self.capacity {
+            state.queue.push_back(msg);
+            Pushed::Queued
+        } else {
+            match self.policy {
+                OverflowPolicy::DropOldest => {
+                    state.queue.pop_front();
+                    state.queue.push_back(msg);
+                    Pushed::Dropped
+                }
+                OverflowPolicy::DropNewest => Pushed::Dropped,
+                OverflowPolicy::Disconnect => {
+                    // The error is the last thing that the client receives
+                    state.queue.clear();
+                    state.queue.push_back(ServerToClientMsg::Error(
+                        "Outbound queue is full".to_string(),
+                    ));
+                    state.closed = true;
+                    Pushed::Closed
+                }
+            }
+        };
+        self.changed.notify_one();
+        pushed
+    }
+
+    /// Tells the writer to stop after writing the messages that are already queued.
+    pub fn close(&self) {
+        self.state.lock().unwrap().closed = true;
+        self.changed.notify_one();
+    }
+
+    /// Waits for the next message, returns `None` when the outbox is closed and empty.
+    fn pop(&self) -> Option<ServerToClientMsg> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(msg) = state.queue.pop_front() {
+                return Some(msg);
+            }
+            if state.closed {
+                return None;
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    fn is_closed(&self) -> bool {
+        self.state.lock().unwrap().closed
+    }
+}
+
+/// Writes messages from `outbox` to `socket` until the outbox is closed or writing fails,
+/// then shuts the socket down, which also stops the thread reading from the client.
+/// This should run on a separate thread for each client.
+///
+/// Once the outbox is closed, the remaining messages (e.g. the error of
+/// [OverflowPolicy::Disconnect]) are written only if the client reads them within
+/// [CLOSE_TIMEOUT].
+pub fn write_messages(socket: Arc<TcpStream>, outbox: Arc<Outbox>) {
+    let mut deadline = None;
+    if socket.set_write_timeout(Some(WRITE_POLL_INTERVAL)).is_ok() {
+        while let Some(msg) = outbox.pop() {
+            let Ok(mut data) = serde_json::to_vec(&msg) else {
+                continue;
+            };
+            data.push(b'\n');
+            if !write_all(&socket, &data, &outbox, &mut deadline) {
+                break;
+            }
+        }
+    }
+    let _ = socket.shutdown(Shutdown::Both);
+}
+
+/// Writes the whole `data`, returns `false` if the connection has failed or if the outbox was
+/// closed and the client has not read the data before the `deadline`, which is set when the
+/// writer first notices that the outbox is closed.
+fn write_all(
+    mut socket: &TcpStream,
+    mut data: &[u8],
+    outbox: &Outbox,
+    deadline: &mut Option<Instant>,
+) -> bool {
+    while !data.is_empty() {
+        match socket.write(data) {
+            Ok(0) => return false,
+            Ok(written) => data = &data[written..],
+            Err(error) if matches!(error.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                // A partially written message has to be finished before anything else can be
+                // sent, but a client that does not read at all cannot keep the writer forever
+                if outbox.is_closed() {
+                    let deadline = *deadline.get_or_insert_with(|| Instant::now() + CLOSE_TIMEOUT);
+                    if Instant::now() >= deadline {
+                        return false;
+                    }
+                }
+            }
+            Err(error) if error.kind() == ErrorKind::Interrupted => {}
+            Err(_) => return false,
+        }
+    }
+    true
+}
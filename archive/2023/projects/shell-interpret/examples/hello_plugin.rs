@@ -0,0 +1,68 @@
+//! Example of a `benzina` plugin implemented as a dynamic library.
+//!
+//! Build it with `cargo build --example hello-plugin` and copy
+//! `target/debug/examples/libhello_plugin.so` to `~/.benzina/plugins`.
+
+use benzina::plugin_abi::{
+    CommandInvocation, PluginCommandInfo, PluginDescriptor, PLUGIN_ABI_VERSION,
+};
+use std::ffi::{c_char, CStr};
+
+static COMMANDS: [PluginCommandInfo; 2] = [
+    PluginCommandInfo {
+        name: c"hello".as_ptr(),
+        description: c"greet someone".as_ptr(),
+    },
+    PluginCommandInfo {
+        name: c"rev".as_ptr(),
+        description: c"reverse the characters of each line of the input".as_ptr(),
+    },
+];
+
+static DESCRIPTOR: PluginDescriptor = PluginDescriptor {
+    abi_version: PLUGIN_ABI_VERSION,
+    command_count: COMMANDS.len(),
+    commands: COMMANDS.as_ptr(),
+    execute,
+};
+
+#[no_mangle]
+pub extern "C" fn benzina_plugin_v1() -> *const PluginDescriptor {
+    &DESCRIPTOR
+}
+
+unsafe extern "C" fn execute(invocation: *const CommandInvocation) -> i32 {
+    let invocation = unsafe { &*invocation };
+    let args: Vec<String> = unsafe { std::slice::from_raw_parts(invocation.argv, invocation.argc) }
+        .iter()
+        .map(|arg| to_string(*arg))
+        .collect();
+    let input = unsafe { std::slice::from_raw_parts(invocation.input, invocation.input_len) };
+    let write = |text: &str| unsafe {
+        (invocation.write)(invocation.context, text.as_ptr(), text.len());
+    };
+
+    match args[0].as_str() {
+        "hello" => {
+            let name = match args.get(1) {
+                Some(name) => name.clone(),
+                None => format!("world from {}", to_string(invocation.workdir)),
+            };
+            write(&format!("Hello, {name}!\n"));
+            0
+        }
+        "rev" => {
+            for line in String::from_utf8_lossy(input).lines() {
+                write(&format!("{}\n", line.chars().rev().collect::<String>()));
+            }
+            0
+        }
+        _ => 127,
+    }
+}
+
+fn to_string(text: *const c_char) -> String {
+    unsafe { CStr::from_ptr(text) }
+        .to_string_lossy()
+        .into_owned()
+}
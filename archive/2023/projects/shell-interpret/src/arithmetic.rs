@@ -0,0 +1,382 @@
+//! Evaluation of arithmetic expansions (`$((expression))`).
+//!
+//! Supports signed 64-bit integers (decimal, `0x` hexadecimal and `0` octal), variables, the
+//! C operators except `++` and `--` (including `?:`, `&&`, `||` and assignments like `+=`) and
+//! `**` for exponentiation.
+
+use crate::command::CommandError;
+use std::collections::BTreeMap;
+
+/// Evaluates `expression`, whose parameters and command substitutions were already expanded.
+/// Names of variables are looked up in (and assigned to) `env`, unset or empty variables are 0.
+/// An empty expression is 0 too.
+pub fn evaluate(expression: &str, env: &mut BTreeMap<String, String>) -> Result<i64, CommandError> {
+    let tokens = lex(expression)?;
+    if tokens.is_empty() {
+        return Ok(0);
+    }
+    let mut evaluator = Evaluator {
+        tokens,
+        position: 0,
+        env,
+    };
+    let value = evaluator.assignment(true)?;
+    match evaluator.tokens.get(evaluator.position) {
+        None => Ok(value),
+        Some(token) => Err(syntax_error(&format!("unexpected {}", describe(token)))),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Number(i64),
+    Name(String),
+    Operator(&'static str),
+}
+
+/// Operators sorted so that longer ones are matched first.
+const OPERATORS: &[&str] = &[
+    "<<=", ">>=", "**", "<<", ">>", "<=", ">=", "==", "!=", "&&", "||", "+=", "-=", "*=", "/=",
+    "%=", "&=", "^=", "|=", "+", "-", "*", "/", "%", "<", ">", "&", "^", "|", "!", "~", "?", ":",
+    "=", "(", ")",
+];
+
+/// Binary operators from the lowest to the highest precedence, `?:`, `**` and assignments are
+/// handled separately.
+const BINARY_OPERATORS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["|"],
+    &["^"],
+    &["&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["<<", ">>"],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+fn lex(expression: &str) -> Result<Vec<Token>, CommandError> {
+    let mut tokens = vec![];
+    let mut rest = expression.trim_start();
+    while let Some(c) = rest.chars().next() {
+        if c.is_ascii_digit() {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Number(parse_number(&rest[..end])?));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+                .unwrap_or(rest.len());
+            tokens.push(Token::Name(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if let Some(operator) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Operator(operator));
+            rest = &rest[operator.len()..];
+        } else {
+            return Err(syntax_error(&format!("invalid character `{c}`")));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse_number(text: &str) -> Result<i64, CommandError> {
+    let res = if let Some(hex) = text.strip_prefix("0x").or(text.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16)
+    } else if text.len() > 1 && text.starts_with('0') {
+        i64::from_str_radix(&text[1..], 8)
+    } else {
+        text.parse()
+    };
+    res.map_err(|_| syntax_error(&format!("invalid number `{text}`")))
+}
+
+struct Evaluator<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    env: &'a mut BTreeMap<String, String>,
+}
+
+impl Evaluator<'_> {
+    /// Every method parses a part of the expression, but only computes its value (with side
+    /// effects like assignments and errors like division by zero) if `evaluate` is `true`, so
+    /// that `&&`, `||` and `?:` can skip operands.
+    fn assignment(&mut self, evaluate: bool) -> Result<i64, CommandError> {
+        if let (Some(Token::Name(name)), Some(Token::Operator(operator))) = (
+            self.tokens.get(self.position),
+            self.tokens.get(self.position + 1),
+        ) {
+            if operator.ends_with('=') && !matches!(*operator, "==" | "!=" | "<=" | ">=") {
+                let name = name.clone();
+                let operator = operator.strip_suffix('=').unwrap();
+                self.position += 2;
+                let value = self.assignment(evaluate)?;
+                if !evaluate {
+                    return Ok(0);
+                }
+                let value = if operator.is_empty() {
+                    value
+                } else {
+                    apply(operator, self.variable(&name)?, value)?
+                };
+                self.env.insert(name, value.to_string());
+                return Ok(value);
+            }
+        }
+        self.conditional(evaluate)
+    }
+
+    fn conditional(&mut self, evaluate: bool) -> Result<i64, CommandError> {
+        let condition = self.binary(0, evaluate)?;
+        if !self.next_if("?") {
+            return Ok(condition);
+        }
+        let when_true = self.assignment(evaluate && condition != 0)?;
+        self.expect(":")?;
+        let when_false = self.conditional(evaluate && condition == 0)?;
+        Ok(if condition != 0 {
+            when_true
+        } else {
+            when_false
+        })
+    }
+
+    /// Parses operators of `BINARY_OPERATORS[level]` and higher, all of them are left
+    /// associative.
+    fn binary(&mut self, level: usize, evaluate: bool) -> Result<i64, CommandError> {
+        let Some(operators) = BINARY_OPERATORS.get(level) else {
+            return self.power(evaluate);
+        };
+        let mut left = self.binary(level + 1, evaluate)?;
+        while let Some(operator) = self.next_operator(operators) {
+            // The right operand of `&&` and `||` is only evaluated if it decides the result
+            let evaluate_right = match operator {
+                "&&" => evaluate && left != 0,
+                "||" => evaluate && left == 0,
+                _ => evaluate,
+            };
+            let right = self.binary(level + 1, evaluate_right)?;
+            left = match operator {
+                "&&" => (left != 0 && right != 0) as i64,
+                "||" => (left != 0 || right != 0) as i64,
+                _ if evaluate => apply(operator, left, right)?,
+                _ => 0,
+            };
+        }
+        Ok(left)
+    }
+
+    /// `**` is right associative and binds tighter than the other binary operators.
+    fn power(&mut self, evaluate: bool) -> Result<i64, CommandError> {
+        let base = self.unary(evaluate)?;
+        if !self.next_if("**") {
+            return Ok(base);
+        }
+        let exponent = self.power(evaluate)?;
+        if evaluate {
+            apply("**", base, exponent)
+        } else {
+            Ok(0)
+        }
+    }
+
+    fn unary(&mut self, evaluate: bool) -> Result<i64, CommandError> {
+        if let Some(operator) = self.next_operator(&["+", "-", "!", "~"]) {
+            let value = self.unary(evaluate)?;
+            return Ok(match operator {
+                "-" => value.wrapping_neg(),
+                "!" => (value == 0) as i64,
+                "~" => !value,
+                _ => value,
+            });
+        }
+        self.primary(evaluate)
+    }
+
+    fn primary(&mut self, evaluate: bool) -> Result<i64, CommandError> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        match token {
+            Some(Token::Number(number)) => Ok(number),
+            Some(Token::Name(name)) if evaluate => self.variable(&name),
+            Some(Token::Name(_)) => Ok(0),
+            Some(Token::Operator("(")) => {
+                let value = self.assignment(evaluate)?;
+                self.expect(")")?;
+                Ok(value)
+            }
+            Some(token) => Err(syntax_error(&format!("unexpected {}", describe(&token)))),
+            None => Err(syntax_error("unexpected end of expression")),
+        }
+    }
+
+    fn variable(&self, name: &str) -> Result<i64, CommandError> {
+        match self.env.get(name).map(|value| value.trim()) {
+            None | Some("") => Ok(0),
+            Some(value) => {
+                let (negative, digits) = match value.strip_prefix('-') {
+                    Some(digits) => (true, digits),
+                    None => (false, value.strip_prefix('+').unwrap_or(value)),
+                };
+                let number = parse_number(digits).map_err(|_| {
+                    syntax_error(&format!("value of `{name}` is not a number: `{value}`"))
+                })?;
+                Ok(if negative {
+                    number.wrapping_neg()
+                } else {
+                    number
+                })
+            }
+        }
+    }
+
+    fn next_operator(&mut self, operators: &[&'static str]) -> Option<&'static str> {
+        match self.tokens.get(self.position) {
+            Some(Token::Operator(operator)) if operators.contains(operator) => {
+                self.position += 1;
+                Some(operator)
+            }
+            _ => None,
+        }
+    }
+
+    fn next_if(&mut self, operator: &'static str) -> bool {
+        self.next_operator(&[operator]).is_some()
+    }
+
+    fn expect(&mut self, operator: &'static str) -> Result<(), CommandError> {
+        if self.next_if(operator) {
+            return Ok(());
+        }
+        match self.tokens.get(self.position) {
+            Some(token) => Err(syntax_error(&format!(
+                "expected `{operator}`, found {}",
+                describe(token)
+            ))),
+            None => Err(syntax_error(&format!("missing `{operator}`"))),
+        }
+    }
+}
+
+fn apply(operator: &str, left: i64, right: i64) -> Result<i64, CommandError> {
+    let value = match operator {
+        "+" => left.wrapping_add(right),
+        "-" => left.wrapping_sub(right),
+        "*" => left.wrapping_mul(right),
+        "/" | "%" if right == 0 => {
+            return Err(CommandError::IO(std::io::Error::other(
+                "arithmetic: division by zero",
+            )))
+        }
+        "/" => left.wrapping_div(right),
+        "%" => left.wrapping_rem(right),
+        "**" => {
+            let exponent = u32::try_from(right)
+                .map_err(|_| syntax_error(&format!("negative exponent `{right}`")))?;
+            left.wrapping_pow(exponent)
+        }
+        "<<" => left.wrapping_shl(right as u32),
+        ">>" => left.wrapping_shr(right as u32),
+        "<" => (left < right) as i64,
+        "<=" => (left <= right) as i64,
+        ">" => (left > right) as i64,
+        ">=" => (left >= right) as i64,
+        "==" => (left == right) as i64,
+        "!=" => (left != right) as i64,
+        "&" => left & right,
+        "^" => left ^ right,
+        "|" => left | right,
+        _ => unreachable!("unknown operator `{operator}`"),
+    };
+    Ok(value)
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(number) => format!("`{number}`"),
+        Token::Name(name) => format!("`{name}`"),
+        Token::Operator(operator) => format!("`{operator}`"),
+    }
+}
+
+fn syntax_error(message: &str) -> CommandError {
+    CommandError::InvalidSyntax(format!("arithmetic: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::arithmetic::evaluate;
+    use crate::command::CommandError;
+    use std::collections::BTreeMap;
+
+    fn eval(expression: &str) -> i64 {
+        evaluate(expression, &mut BTreeMap::new()).unwrap()
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(eval("1 + 2 * 3"), 7);
+        assert_eq!(eval("(1 + 2) * 3"), 9);
+        assert_eq!(eval("10 - 4 - 3"), 3);
+        assert_eq!(eval("2 ** 3 ** 2"), 512);
+        assert_eq!(eval("-2 ** 2"), 4);
+        assert_eq!(eval("7 / 2 + 7 % 2"), 4);
+        assert_eq!(eval("1 << 4 | 1"), 17);
+        assert_eq!(eval("1 < 2 == 2 > 1"), 1);
+        assert_eq!(eval("!0 + ~0"), 0);
+        assert_eq!(eval("0x1f + 010"), 39);
+        assert_eq!(eval(" "), 0);
+    }
+
+    #[test]
+    fn logical_operators() {
+        assert_eq!(eval("2 && 3"), 1);
+        assert_eq!(eval("0 || 0"), 0);
+        assert_eq!(eval("1 ? 2 : 3"), 2);
+        assert_eq!(eval("0 ? 2 : 0 ? 3 : 4"), 4);
+        // Operands that are not evaluated cannot fail
+        assert_eq!(eval("0 && 1 / 0"), 0);
+        assert_eq!(eval("1 || 1 / 0"), 1);
+        assert_eq!(eval("1 ? 5 : 1 / 0"), 5);
+    }
+
+    #[test]
+    fn variables() {
+        let mut env = BTreeMap::new();
+        env.insert("x".to_string(), "5".to_string());
+        env.insert("text".to_string(), "abc".to_string());
+        assert_eq!(evaluate("x * 2 + missing", &mut env).unwrap(), 10);
+        assert_eq!(evaluate("y = x += 2", &mut env).unwrap(), 7);
+        assert_eq!(env["x"], "7");
+        assert_eq!(env["y"], "7");
+        assert_eq!(evaluate("0 && (z = 1)", &mut env).unwrap(), 0);
+        assert!(!env.contains_key("z"));
+        assert!(matches!(
+            evaluate("text + 1", &mut env),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        let mut env = BTreeMap::new();
+        assert!(matches!(
+            evaluate("1 / 0", &mut env),
+            Err(CommandError::IO(error)) if error.to_string() == "arithmetic: division by zero"
+        ));
+        for expression in [
+            "1 +", "(1", "1 2", "2 ** -1", "1 ? 2", "3 = 4", "a $ b", "09",
+        ] {
+            assert!(
+                matches!(
+                    evaluate(expression, &mut env),
+                    Err(CommandError::InvalidSyntax(_))
+                ),
+                "{expression}"
+            );
+        }
+    }
+}
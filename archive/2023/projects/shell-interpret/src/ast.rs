@@ -0,0 +1,93 @@
+use crate::lexer::Word;
+use crate::pipeline::Pipeline;
+use std::rc::Rc;
+
+/// A parsed shell statement.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Statement {
+    Pipeline(Pipeline),
+    /// `left && right`
+    And(Box<Statement>, Box<Statement>),
+    /// `left || right`
+    Or(Box<Statement>, Box<Statement>),
+    /// `if cond; then body; elif cond; then body; else otherwise; fi`
+    If {
+        /// Pairs of (condition, body), the first one is the `if` branch, the rest are `elif`s
+        branches: Vec<(Vec<Statement>, Vec<Statement>)>,
+        otherwise: Option<Vec<Statement>>,
+    },
+    /// `while cond; do body; done`
+    While {
+        condition: Vec<Statement>,
+        body: Vec<Statement>,
+    },
+    /// `for variable in items; do body; done`
+    For {
+        variable: String,
+        items: Vec<Word>,
+        body: Vec<Statement>,
+    },
+    /// `{ body; }`
+    Group(Vec<Statement>),
+    /// `( body )`, changes of the state of the shell are discarded afterwards
+    Subshell(Vec<Statement>),
+    /// `name() { body; }` or `function name { body; }`
+    FunctionDefinition {
+        name: String,
+        /// The body is shared with the function table of the shell
+        body: Rc<Vec<Statement>>,
+    },
+}
+
+impl Statement {
+    /// Command line of this statement, without quotes and redirections.
+    pub fn line(&self) -> String {
+        match self {
+            Statement::Pipeline(pipeline) => pipeline.line(),
+            Statement::And(left, right) => format!("{} && {}", left.line(), right.line()),
+            Statement::Or(left, right) => format!("{} || {}", left.line(), right.line()),
+            Statement::If {
+                branches,
+                otherwise,
+            } => {
+                let mut line = String::new();
+                for (index, (condition, body)) in branches.iter().enumerate() {
+                    let keyword = if index == 0 { "if" } else { "elif" };
+                    line += &format!("{keyword} {}; then {}; ", lines(condition), lines(body));
+                }
+                if let Some(otherwise) = otherwise {
+                    line += &format!("else {}; ", lines(otherwise));
+                }
+                line + "fi"
+            }
+            Statement::While { condition, body } => {
+                format!("while {}; do {}; done", lines(condition), lines(body))
+            }
+            Statement::For {
+                variable,
+                items,
+                body,
+            } => {
+                let items: Vec<String> = items.iter().map(|item| item.text()).collect();
+                format!(
+                    "for {variable} in {}; do {}; done",
+                    items.join(" "),
+                    lines(body)
+                )
+            }
+            Statement::Group(body) => format!("{{ {}; }}", lines(body)),
+            Statement::Subshell(body) => format!("({})", lines(body)),
+            Statement::FunctionDefinition { name, body } => {
+                format!("{name}() {{ {}; }}", lines(body))
+            }
+        }
+    }
+}
+
+fn lines(statements: &[Statement]) -> String {
+    let lines: Vec<String> = statements
+        .iter()
+        .map(|statement| statement.line())
+        .collect();
+    lines.join("; ")
+}
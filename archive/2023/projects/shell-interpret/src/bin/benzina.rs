@@ -1,18 +1,133 @@
-use benzina::{ChangeWorkdir, PrintWorkdir, Shell};
-use std::io::BufRead;
+use benzina::{install_signal_handlers, ExternalCommand, History, LineEditor, Shell};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
 
 fn main() {
-    let mut shell = Shell::default();
-    shell.add_command(PrintWorkdir);
-    shell.add_command(ChangeWorkdir);
+    // SIGINT interrupts the foreground program instead of the shell, SIGTERM and SIGHUP are
+    // forwarded to jobs
+    if let Err(error) = install_signal_handlers() {
+        eprintln!("benzina: cannot install signal handlers: {error}");
+    }
+
+    let mut shell = match Shell::new() {
+        Ok(shell) => shell,
+        Err(error) => {
+            eprintln!("benzina: cannot get the current directory: {error}");
+            std::process::exit(1);
+        }
+    };
+    shell.add_builtins();
+    // Must be registered last, because it handles all commands
+    shell.add_command(ExternalCommand);
+    shell.inherit_stdin();
+
+    let home = std::env::var_os("HOME").map(PathBuf::from);
+    if let Some(home) = &home {
+        load_plugins(&mut shell, &home.join(".benzina/plugins"));
+        shell.set_directory_database(home.join(".benzina_z"));
+    }
 
-    let stdin = std::io::stdin().lock();
     let mut stdout = std::io::stdout().lock();
 
-    shell.print_prompt(&mut stdout).unwrap();
-    for line in stdin.lines() {
-        let line = line.unwrap();
-        shell.execute_line(&line, &mut stdout);
-        shell.print_prompt(&mut stdout).unwrap();
+    // `benzina -c 'commands' [name args...]` or `benzina script.sh [args...]`
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(first) = args.first() {
+        let status = if first == "-c" {
+            let Some(script) = args.get(1) else {
+                eprintln!("benzina: -c: option requires an argument");
+                std::process::exit(2);
+            };
+            let mut positional = args[2..].to_vec();
+            if positional.is_empty() {
+                positional.push("benzina".to_string());
+            }
+            shell.execute_script(script, positional, &mut stdout)
+        } else {
+            let script = match std::fs::read_to_string(first) {
+                Ok(script) => script,
+                Err(error) => {
+                    eprintln!("benzina: {first}: {error}");
+                    std::process::exit(127);
+                }
+            };
+            shell.execute_script(&script, args, &mut stdout)
+        };
+        shell.run_exit_trap(&mut stdout);
+        stdout.flush().unwrap();
+        std::process::exit(status.code());
+    }
+
+    // The startup file is only executed by interactive shells, like `~/.bashrc`
+    if let Some(home) = &home {
+        let path = home.join(".benzinarc");
+        match shell.source_file(&path, &mut stdout) {
+            Ok(_) => {}
+            Err(error) if error.kind() == ErrorKind::NotFound => {}
+            Err(error) => eprintln!("benzina: {}: {error}", path.display()),
+        }
+    }
+
+    let history = match &home {
+        Some(home) => History::load(home.join(".benzina_history")),
+        None => History::default(),
+    };
+    let mut editor = LineEditor::new(history);
+    let mut text = String::new();
+    loop {
+        let prompt = if text.is_empty() {
+            shell.print_job_notifications(&mut stdout).unwrap();
+            shell.prompt()
+        } else {
+            "> ".to_string()
+        };
+        let line = match editor.read_line(&prompt, &shell, &mut stdout) {
+            Ok(Some(line)) => line,
+            Ok(None) => break,
+            // Ctrl-C cancels the line, including the previous lines of an unfinished statement
+            Err(error) if error.kind() == ErrorKind::Interrupted => {
+                text.clear();
+                continue;
+            }
+            Err(error) => {
+                eprintln!("benzina: {error}");
+                break;
+            }
+        };
+        shell.handle_signals(&mut stdout);
+        if shell.is_terminated() {
+            break;
+        }
+        text.push_str(&line);
+        text.push('\n');
+        // Statements like `if` or `while` can span multiple lines
+        if Shell::is_incomplete(&text) {
+            continue;
+        }
+        shell.execute_line(text.trim_end(), &mut stdout);
+        text.clear();
+        if shell.is_terminated() {
+            break;
+        }
+    }
+    // Like other shells, exit with the status of the last command when the input ends
+    shell.handle_signals(&mut stdout);
+    shell.run_exit_trap(&mut stdout);
+    stdout.flush().unwrap();
+    std::process::exit(shell.last_status().code());
+}
+
+/// Loads all plugins (dynamic libraries and executables) from `dir`, if it exists.
+fn load_plugins(shell: &mut Shell, dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .collect();
+    paths.sort();
+    for path in paths {
+        if let Err(error) = shell.load_plugin(&path) {
+            eprintln!("benzina: {error}");
+        }
     }
 }
@@ -2,11 +2,68 @@
 // ChangeWorkdir
 
 use crate::state::InterpreterState;
-use std::io::Write;
+use std::fmt::{Display, Formatter};
+use std::io::{Read, Write};
 
 #[derive(Debug)]
 pub enum CommandError {
     IO(std::io::Error),
+    /// The input cannot be parsed.
+    InvalidSyntax(String),
+    /// A command was invoked with invalid arguments.
+    Usage(String),
+    CommandNotFound(String),
+    /// The program exists, but it cannot be executed.
+    PermissionDenied(String),
+    /// The input has ended in the middle of a statement, more lines are needed.
+    IncompleteInput(String),
+}
+
+impl CommandError {
+    /// Exit status of a command that has failed with this error, following the conventions of
+    /// POSIX shells.
+    pub fn exit_status(&self) -> ExitStatus {
+        match self {
+            CommandError::IO(_) => ExitStatus(1),
+            CommandError::InvalidSyntax(_)
+            | CommandError::Usage(_)
+            | CommandError::IncompleteInput(_) => ExitStatus(2),
+            CommandError::PermissionDenied(_) => ExitStatus(126),
+            CommandError::CommandNotFound(_) => ExitStatus(127),
+        }
+    }
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::IO(error) => write!(f, "{error}"),
+            CommandError::InvalidSyntax(message) | CommandError::IncompleteInput(message) => {
+                write!(f, "syntax error: {message}")
+            }
+            CommandError::Usage(message) => write!(f, "{message}"),
+            CommandError::CommandNotFound(name) => write!(f, "{name}: command not found"),
+            CommandError::PermissionDenied(name) => write!(f, "{name}: permission denied"),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Exit status of a command, zero means success.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExitStatus(pub i32);
+
+impl ExitStatus {
+    pub const SUCCESS: ExitStatus = ExitStatus(0);
+
+    pub fn success(self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn code(self) -> i32 {
+        self.0
+    }
 }
 
 pub enum CommandResponse {
@@ -15,10 +72,39 @@ pub enum CommandResponse {
 }
 
 pub trait Command {
+    /// Name of the builtin, it is used e.g. for tab completion.
+    /// Commands that do not handle a single fixed name (like external programs) return `None`.
+    fn name(&self) -> Option<&str> {
+        None
+    }
+
+    /// Short description of the builtin, it is printed by `help`.
+    fn description(&self) -> Option<&str> {
+        None
+    }
+
+    /// Executes the command.
+    /// `args` contains the expanded words of the command line, the first one is the name of the
+    /// command and it is always present.
+    /// `input` contains the output of the previous command in a pipeline (or the contents of a
+    /// file redirected with `<`). The first command of a pipeline reads the standard input of
+    /// the shell if it is inherited (see `Shell::inherit_stdin`), otherwise the input is empty.
     fn execute(
         &self,
-        line: &str,
+        args: &[String],
         state: &mut InterpreterState,
+        input: &mut dyn Read,
         output: &mut dyn Write,
     ) -> CommandResponse;
+
+    /// Prepares the command to be started as a separate process, so that the shell can run it
+    /// at the same time as the other commands of a pipeline, connected with OS pipes.
+    /// Commands that are not programs return `None`, they are executed with `execute` instead.
+    fn program(
+        &self,
+        _args: &[String],
+        _state: &InterpreterState,
+    ) -> Option<Result<std::process::Command, CommandError>> {
+        None
+    }
 }
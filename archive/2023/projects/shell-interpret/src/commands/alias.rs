@@ -0,0 +1,131 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::lexer::{tokenize, Token};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `alias [NAME=VALUE]...` defines aliases, `alias NAME` prints the definition of an alias.
+/// Without arguments, it prints all aliases.
+///
+/// When the first word of a command is an alias, it is replaced by the words of its value.
+/// An alias can only expand to a simple command (words without operators like `|` or `;`).
+pub struct DefineAlias;
+
+impl Command for DefineAlias {
+    fn name(&self) -> Option<&str> {
+        Some("alias")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("define or print aliases")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "alias" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(alias(&args[1..], state, output))
+    }
+}
+
+fn alias(
+    args: &[String],
+    state: &mut InterpreterState,
+    output: &mut dyn Write,
+) -> Result<(), CommandError> {
+    if args.is_empty() {
+        for (name, value) in &state.aliases {
+            print_alias(name, value, output)?;
+        }
+        return Ok(());
+    }
+    for arg in args {
+        match arg.split_once('=') {
+            Some((name, value)) => {
+                if !is_valid_alias_name(name) {
+                    return Err(CommandError::Usage(format!(
+                        "alias: `{name}`: invalid alias name"
+                    )));
+                }
+                let is_simple = tokenize(value)?
+                    .iter()
+                    .all(|token| matches!(token, Token::Word(_) | Token::Newline));
+                if !is_simple {
+                    return Err(CommandError::Usage(format!(
+                        "alias: `{value}`: an alias must expand to a simple command"
+                    )));
+                }
+                state.aliases.insert(name.to_string(), value.to_string());
+            }
+            None => match state.aliases.get(arg) {
+                Some(value) => print_alias(arg, value, output)?,
+                None => return Err(CommandError::Usage(format!("alias: {arg}: not found"))),
+            },
+        }
+    }
+    Ok(())
+}
+
+/// Prints the alias in a form that can be used to define it again.
+fn print_alias(name: &str, value: &str, output: &mut dyn Write) -> Result<(), CommandError> {
+    let value = value.replace('\'', r"'\''");
+    writeln!(output, "alias {name}='{value}'").map_err(CommandError::IO)
+}
+
+fn is_valid_alias_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name
+            .chars()
+            .any(|c| c.is_whitespace() || "/\\'\"$`|&;<>()=".contains(c))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::CommandError;
+    use crate::commands::alias::DefineAlias;
+    use crate::commands::common::execute_test_command;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn define_and_print_aliases() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        execute_test_command(&DefineAlias, "alias ll=ls", &mut state, "").unwrap();
+        execute_test_command(&DefineAlias, "alias say=echo", &mut state, "").unwrap();
+        state
+            .aliases
+            .insert("q".to_string(), "echo it's".to_string());
+        assert_eq!(
+            execute_test_command(&DefineAlias, "alias", &mut state, "").unwrap(),
+            "alias ll='ls'\nalias q='echo it'\\''s'\nalias say='echo'\n"
+        );
+        assert_eq!(
+            execute_test_command(&DefineAlias, "alias say", &mut state, "").unwrap(),
+            "alias say='echo'\n"
+        );
+        assert!(matches!(
+            execute_test_command(&DefineAlias, "alias missing", &mut state, ""),
+            Err(CommandError::Usage(message)) if message == "alias: missing: not found"
+        ));
+    }
+
+    #[test]
+    fn invalid_aliases() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert!(matches!(
+            execute_test_command(&DefineAlias, "alias a/b=ls", &mut state, ""),
+            Err(CommandError::Usage(_))
+        ));
+        assert!(matches!(
+            execute_test_command(&DefineAlias, "alias a=ls|wc", &mut state, ""),
+            Err(CommandError::Usage(_))
+        ));
+        assert!(state.aliases.is_empty());
+    }
+}
@@ -0,0 +1,43 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::jobs::JobStatus;
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `bg [%n]` resumes a stopped job in the background.
+pub struct BackgroundJob;
+
+impl Command for BackgroundJob {
+    fn name(&self) -> Option<&str> {
+        Some("bg")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("resume a stopped job in the background")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "bg" {
+            return CommandResponse::Unhandled;
+        }
+
+        let id = match state.jobs.find_for_command("bg", args.get(1)) {
+            Ok(id) => id,
+            Err(error) => return CommandResponse::Handled(Err(CommandError::IO(error))),
+        };
+        state.jobs.refresh();
+        let job = state.jobs.get_mut(id).unwrap();
+        if job.status() == JobStatus::Stopped {
+            if let Err(error) = job.signal(libc::SIGCONT) {
+                return CommandResponse::Handled(Err(CommandError::IO(error)));
+            }
+        }
+        writeln!(output, "[{id}] {} &", job.command).unwrap();
+        CommandResponse::Handled(Ok(()))
+    }
+}
@@ -0,0 +1,92 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::common::{open_file, path_error, Options};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `cat [FILE]...` prints the contents of files, or its input if no file (or `-`) is given.
+pub struct ConcatenateFiles;
+
+impl Command for ConcatenateFiles {
+    fn name(&self) -> Option<&str> {
+        Some("cat")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("concatenate files")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "cat" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(cat(&args[1..], state, input, output))
+    }
+}
+
+fn cat(
+    args: &[String],
+    state: &InterpreterState,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+) -> Result<(), CommandError> {
+    let options = Options::parse("cat", args, "", "")?;
+    let mut operands = options.operands;
+    if operands.is_empty() {
+        operands.push("-");
+    }
+    for path in operands {
+        if path == "-" {
+            std::io::copy(input, output).map_err(CommandError::IO)?;
+        } else {
+            let mut file = open_file("cat", path, state)?;
+            std::io::copy(&mut file, output).map_err(|error| path_error("cat", path, error))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::CommandError;
+    use crate::commands::cat::ConcatenateFiles;
+    use crate::commands::common::execute_test_command;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn test_cat_1() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "a\n").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "b").unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute_test_command(
+                &ConcatenateFiles,
+                "cat a.txt - b.txt",
+                &mut state,
+                "input\n"
+            )
+            .unwrap(),
+            "a\ninput\nb"
+        );
+    }
+
+    #[test]
+    fn test_cat_2() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute_test_command(&ConcatenateFiles, "cat", &mut state, "foo").unwrap(),
+            "foo"
+        );
+        assert!(matches!(
+            execute_test_command(&ConcatenateFiles, "cat missing.txt", &mut state, ""),
+            Err(CommandError::IO(error)) if error.to_string().starts_with("cat: missing.txt: ")
+        ));
+    }
+}
@@ -1,57 +1,276 @@
 use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::common::path_error;
+use crate::frecency::record_visit;
 use crate::state::InterpreterState;
-use std::io::Write;
-use std::path::PathBuf;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
 
+/// `cd [DIR]` changes the working directory to `DIR` (or to `HOME`), `cd -` returns to the
+/// previous directory.
 pub struct ChangeWorkdir;
 
-struct ParsedCommand<'a> {
-    command: &'a str,
-    args: Vec<&'a str>,
-}
-
 impl Command for ChangeWorkdir {
+    fn name(&self) -> Option<&str> {
+        Some("cd")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("change the working directory")
+    }
+
     fn execute(
         &self,
-        line: &str,
+        args: &[String],
         state: &mut InterpreterState,
+        _input: &mut dyn Read,
         output: &mut dyn Write,
     ) -> CommandResponse {
-        let line = line.trim();
-        let Some((cmd, args)) = line.split_once(' ') else {
-            return CommandResponse::Unhandled;
-        };
-
-        if cmd != "cd" {
+        if args[0] != "cd" {
             return CommandResponse::Unhandled;
         }
 
-        let dir = PathBuf::from(args);
-        let res = match std::fs::canonicalize(dir) {
-            Ok(dir) => {
-                state.workdir = dir;
-                Ok(())
+        // `cd -` and directories found using `CDPATH` print the new working directory
+        let (target, dir, print_workdir) = match args {
+            [_] => match state.env.get("HOME") {
+                Some(home) => (home.as_str(), state.resolve_path(home), false),
+                None => {
+                    return CommandResponse::Handled(Err(CommandError::Usage(
+                        "cd: HOME is not set".to_string(),
+                    )))
+                }
+            },
+            [_, dir] if dir == "-" => match state.env.get("OLDPWD") {
+                Some(dir) => (dir.as_str(), state.resolve_path(dir), true),
+                None => {
+                    return CommandResponse::Handled(Err(CommandError::Usage(
+                        "cd: OLDPWD is not set".to_string(),
+                    )))
+                }
+            },
+            [_, dir] => {
+                let (path, from_cdpath) = find_directory(dir, state);
+                (dir.as_str(), path, from_cdpath)
+            }
+            _ => {
+                return CommandResponse::Handled(Err(CommandError::Usage(
+                    "cd: too many arguments".to_string(),
+                )))
             }
-            Err(err) => Err(CommandError::IO(err)),
         };
+        let target = target.to_string();
+        let res = change_workdir("cd", &target, &dir, state).and_then(|_| {
+            if print_workdir {
+                writeln!(output, "{}", state.workdir.display()).map_err(CommandError::IO)?;
+            }
+            Ok(())
+        });
         CommandResponse::Handled(res)
     }
 }
 
+/// Finds the directory that `cd` (or `pushd`) should change to.
+///
+/// Relative paths that do not start with `.` or `..` are first searched in the directories
+/// listed in `CDPATH` (an empty item stands for the working directory). Returns the directory
+/// and `true` if it was found in a non-empty item of `CDPATH`.
+pub fn find_directory(target: &str, state: &InterpreterState) -> (PathBuf, bool) {
+    let path = Path::new(target);
+    let is_explicit = path.is_absolute()
+        || matches!(
+            path.components().next(),
+            Some(Component::CurDir | Component::ParentDir)
+        );
+    if let (false, Some(cdpath)) = (is_explicit, state.env.get("CDPATH")) {
+        for dir in cdpath.split(':') {
+            let candidate = state.resolve_path(dir).join(target);
+            if candidate.is_dir() {
+                return (candidate, !dir.is_empty());
+            }
+        }
+    }
+    (state.resolve_path(target), false)
+}
+
+/// Changes the working directory to `dir` and updates `PWD` and `OLDPWD`.
+/// The new directory is recorded in the database of `z`, if it is enabled.
+/// `target` is the directory as entered by the user, it is used in error messages.
+pub fn change_workdir(
+    command: &str,
+    target: &str,
+    dir: &Path,
+    state: &mut InterpreterState,
+) -> Result<(), CommandError> {
+    let dir = std::fs::canonicalize(dir).map_err(|error| path_error(command, target, error))?;
+    if !dir.is_dir() {
+        return Err(path_error(
+            command,
+            target,
+            std::io::Error::from(std::io::ErrorKind::NotADirectory),
+        ));
+    }
+    let previous = std::mem::replace(&mut state.workdir, dir);
+    state.env.insert(
+        "OLDPWD".to_string(),
+        previous.to_string_lossy().into_owned(),
+    );
+    state.env.insert(
+        "PWD".to_string(),
+        state.workdir.to_string_lossy().into_owned(),
+    );
+    if let Some(database) = &state.directory_database {
+        // A broken database should not prevent changing the directory
+        let _ = record_visit(database, &state.workdir);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::command::Command;
+    use crate::command::{Command, CommandError};
     use crate::commands::change_dir::ChangeWorkdir;
+    use crate::commands::common::execute_test_command;
+    use crate::frecency::DirectoryDatabase;
     use crate::state::InterpreterState;
     use std::path::PathBuf;
 
+    fn cd(arg: &str, state: &mut InterpreterState) -> Result<String, CommandError> {
+        execute_test_command(&ChangeWorkdir, &format!("cd {arg}"), state, "")
+    }
+
     #[test]
     fn test_cd_1() {
-        let cd = ChangeWorkdir;
-        let mut state = InterpreterState::new(PathBuf::from("/foo"));
-        let mut buffer = vec![];
-        let res = cd.execute("cd /bar", &mut state, &mut buffer);
-        assert!(matches!(res, crate::CommandResponse::Handled));
-        assert_eq!(state.workdir, PathBuf::from("/bar"));
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("bar")).unwrap();
+        let mut state = InterpreterState::new(PathBuf::from("/"));
+        let bar = root.join("bar");
+        cd(bar.to_str().unwrap(), &mut state).unwrap();
+        assert_eq!(state.workdir, bar);
+        assert_eq!(state.env["PWD"], bar.to_str().unwrap());
+        assert_eq!(state.env["OLDPWD"], "/");
+        cd("..", &mut state).unwrap();
+        assert_eq!(state.workdir, root);
+    }
+
+    #[test]
+    fn cd_invalid_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("file"), "").unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert!(matches!(
+            cd("missing", &mut state),
+            Err(CommandError::IO(error)) if error.to_string().starts_with("cd: missing: ")
+        ));
+        assert!(matches!(cd("file", &mut state), Err(CommandError::IO(_))));
+        assert!(matches!(cd("a b", &mut state), Err(CommandError::Usage(_))));
+        assert_eq!(state.workdir, dir.path());
+    }
+
+    #[test]
+    fn cd_search_cdpath() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("projects/benzina/src")).unwrap();
+        std::fs::create_dir_all(root.join("work/src")).unwrap();
+        let mut state = InterpreterState::new(root.join("work"));
+        state.env.insert(
+            "CDPATH".to_string(),
+            format!(":{}", root.join("projects").display()),
+        );
+        // Directories found through CDPATH are printed
+        assert_eq!(
+            cd("benzina", &mut state).unwrap(),
+            format!("{}\n", root.join("projects/benzina").display())
+        );
+        // The empty item stands for the working directory
+        assert_eq!(cd("src", &mut state).unwrap(), "");
+        assert_eq!(state.workdir, root.join("projects/benzina/src"));
+        // Explicitly relative paths do not use CDPATH
+        state.workdir = root.clone();
+        assert!(cd("./benzina", &mut state).is_err());
+    }
+
+    #[test]
+    fn cd_records_visits() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("a")).unwrap();
+        let mut state = InterpreterState::new(root.clone());
+        state.directory_database = Some(root.join("z"));
+        cd("a", &mut state).unwrap();
+        cd("..", &mut state).unwrap();
+        cd("a", &mut state).unwrap();
+        let database = DirectoryDatabase::load(&root.join("z"));
+        assert_eq!(database.entries.len(), 2);
+        assert_eq!(database.entries[0].path, root.join("a"));
+        assert_eq!(database.entries[0].rank, 2.0);
+    }
+
+    #[test]
+    fn cd_path_with_spaces() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("foo bar")).unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        let res = ChangeWorkdir.execute(
+            &["cd".to_string(), "foo bar".to_string()],
+            &mut state,
+            &mut std::io::empty(),
+            &mut vec![],
+        );
+        assert!(matches!(
+            res,
+            crate::command::CommandResponse::Handled(Ok(()))
+        ));
+        assert_eq!(
+            state.workdir,
+            dir.path().canonicalize().unwrap().join("foo bar")
+        );
+    }
+
+    #[test]
+    fn cd_home() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path().canonicalize().unwrap().join("home");
+        std::fs::create_dir(&home).unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        state
+            .env
+            .insert("HOME".to_string(), home.to_string_lossy().into_owned());
+        let res = ChangeWorkdir.execute(
+            &["cd".to_string()],
+            &mut state,
+            &mut std::io::empty(),
+            &mut vec![],
+        );
+        assert!(matches!(
+            res,
+            crate::command::CommandResponse::Handled(Ok(()))
+        ));
+        assert_eq!(state.workdir, home);
+    }
+
+    #[test]
+    fn cd_previous_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("a")).unwrap();
+        let mut state = InterpreterState::new(root.clone());
+        let mut cd = |arg: &str| {
+            let mut output = vec![];
+            let res = ChangeWorkdir.execute(
+                &["cd".to_string(), arg.to_string()],
+                &mut state,
+                &mut std::io::empty(),
+                &mut output,
+            );
+            assert!(matches!(
+                res,
+                crate::command::CommandResponse::Handled(Ok(()))
+            ));
+            (state.workdir.clone(), String::from_utf8(output).unwrap())
+        };
+        assert_eq!(cd("a"), (root.join("a"), String::new()));
+        assert_eq!(cd("-"), (root.clone(), format!("{}\n", root.display())));
+        assert_eq!(cd("-").0, root.join("a"));
     }
 }
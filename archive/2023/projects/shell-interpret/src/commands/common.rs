@@ -0,0 +1,249 @@
+//! Helpers shared by the file manipulation builtins.
+
+use crate::command::CommandError;
+use crate::state::InterpreterState;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::PathBuf;
+
+/// Parsed command line of a builtin: single-letter options and operands.
+#[derive(Debug, Default)]
+pub struct Options<'a> {
+    flags: Vec<(char, Option<&'a str>)>,
+    pub operands: Vec<&'a str>,
+}
+
+impl<'a> Options<'a> {
+    /// Parses `args` (without the command name).
+    /// `flags` lists the allowed options without a value, `value_flags` the allowed options that
+    /// take a value (`-n 5` or `-n5`). Options can be combined (`-rf`) and `--` ends them, a lone
+    /// `-` is an operand.
+    pub fn parse(
+        command: &str,
+        args: &'a [String],
+        flags: &str,
+        value_flags: &str,
+    ) -> Result<Self, CommandError> {
+        let mut options = Options::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if arg == "--" {
+                options.operands.extend(args.map(|arg| arg.as_str()));
+                break;
+            }
+            let Some(letters) = arg.strip_prefix('-').filter(|letters| !letters.is_empty()) else {
+                options.operands.push(arg);
+                continue;
+            };
+            for (index, flag) in letters.char_indices() {
+                if flags.contains(flag) {
+                    options.flags.push((flag, None));
+                } else if value_flags.contains(flag) {
+                    let rest = &letters[index + flag.len_utf8()..];
+                    let value = if rest.is_empty() {
+                        args.next().map(|arg| arg.as_str()).ok_or_else(|| {
+                            CommandError::Usage(format!(
+                                "{command}: option requires an argument -- '{flag}'"
+                            ))
+                        })?
+                    } else {
+                        rest
+                    };
+                    options.flags.push((flag, Some(value)));
+                    break;
+                } else {
+                    return Err(CommandError::Usage(format!(
+                        "{command}: invalid option -- '{flag}'"
+                    )));
+                }
+            }
+        }
+        Ok(options)
+    }
+
+    pub fn has(&self, flag: char) -> bool {
+        self.flags.iter().any(|(f, _)| *f == flag)
+    }
+
+    /// Returns the value of the last occurrence of `flag`.
+    pub fn value(&self, flag: char) -> Option<&'a str> {
+        self.flags
+            .iter()
+            .rev()
+            .find(|(f, _)| *f == flag)
+            .and_then(|(_, value)| *value)
+    }
+
+    /// Parses the numeric value of `flag`, returns `default` if it is not present.
+    pub fn number(&self, command: &str, flag: char, default: usize) -> Result<usize, CommandError> {
+        match self.value(flag) {
+            Some(value) => value
+                .parse()
+                .map_err(|_| CommandError::Usage(format!("{command}: invalid number `{value}`"))),
+            None => Ok(default),
+        }
+    }
+}
+
+/// Adds the command name and the path to an IO error, e.g. `cat: foo.txt: No such file or
+/// directory`.
+pub fn path_error(command: &str, path: &str, error: std::io::Error) -> CommandError {
+    CommandError::IO(std::io::Error::new(
+        error.kind(),
+        format!("{command}: {path}: {error}"),
+    ))
+}
+
+/// Opens a file relative to the working directory.
+pub fn open_file(
+    command: &str,
+    path: &str,
+    state: &InterpreterState,
+) -> Result<File, CommandError> {
+    File::open(state.resolve_path(path)).map_err(|error| path_error(command, path, error))
+}
+
+/// Reads the whole contents of the file at `path`, or of `input` if `path` is `None` or `-`.
+pub fn read_input(
+    command: &str,
+    path: Option<&str>,
+    state: &InterpreterState,
+    input: &mut dyn Read,
+) -> Result<Vec<u8>, CommandError> {
+    let mut data = vec![];
+    match path {
+        None | Some("-") => input.read_to_end(&mut data).map_err(CommandError::IO)?,
+        Some(path) => open_file(command, path, state)?
+            .read_to_end(&mut data)
+            .map_err(|error| path_error(command, path, error))?,
+    };
+    Ok(data)
+}
+
+/// Reads at most `count` lines of the file at `path`, or of `input` if `path` is `None` or `-`.
+/// Unlike `read_input`, it does not wait for the end of an input that never ends (e.g. the
+/// output of `yes`).
+pub fn read_first_lines(
+    command: &str,
+    path: Option<&str>,
+    state: &InterpreterState,
+    input: &mut dyn Read,
+    count: usize,
+) -> Result<Vec<u8>, CommandError> {
+    let mut data = vec![];
+    let (mut reader, path): (Box<dyn BufRead>, _) = match path {
+        None | Some("-") => (Box::new(BufReader::new(input)), None),
+        Some(path) => (
+            Box::new(BufReader::new(open_file(command, path, state)?)),
+            Some(path),
+        ),
+    };
+    for _ in 0..count {
+        let read = reader
+            .read_until(b'\n', &mut data)
+            .map_err(|error| match path {
+                Some(path) => path_error(command, path, error),
+                None => CommandError::IO(error),
+            })?;
+        if read == 0 {
+            break;
+        }
+    }
+    Ok(data)
+}
+
+/// Resolves the operands of `cp` and `mv`: either `SOURCE DEST`, or `SOURCE... DIR` if the last
+/// operand is an existing directory. Returns pairs of (source operand, destination path).
+pub fn copy_targets<'a>(
+    command: &str,
+    operands: &[&'a str],
+    state: &InterpreterState,
+) -> Result<Vec<(&'a str, PathBuf)>, CommandError> {
+    let Some((destination, sources)) = operands.split_last().filter(|(_, s)| !s.is_empty()) else {
+        return Err(CommandError::Usage(format!(
+            "{command}: missing destination operand"
+        )));
+    };
+    let destination = state.resolve_path(destination);
+    if destination.is_dir() {
+        sources
+            .iter()
+            .map(|source| {
+                let source_path = state.resolve_path(source);
+                let name = source_path.file_name().ok_or_else(|| {
+                    CommandError::Usage(format!("{command}: {source}: invalid source"))
+                })?;
+                Ok((*source, destination.join(name)))
+            })
+            .collect()
+    } else if let [source] = sources {
+        Ok(vec![(*source, destination)])
+    } else {
+        Err(CommandError::Usage(format!(
+            "{command}: target `{}` is not a directory",
+            destination.display()
+        )))
+    }
+}
+
+/// Splits data into lines, each line keeps its trailing newline (the last one may lack it).
+pub fn lines(data: &[u8]) -> Vec<&[u8]> {
+    data.split_inclusive(|byte| *byte == b'\n').collect()
+}
+
+/// Executes `command` with arguments split on whitespace and returns its output.
+#[cfg(test)]
+pub fn execute_test_command(
+    command: &dyn crate::command::Command,
+    line: &str,
+    state: &mut InterpreterState,
+    input: &str,
+) -> Result<String, CommandError> {
+    let args: Vec<String> = line.split_whitespace().map(|arg| arg.to_string()).collect();
+    let mut output = vec![];
+    match command.execute(&args, state, &mut input.as_bytes(), &mut output) {
+        crate::command::CommandResponse::Handled(res) => {
+            res.map(|_| String::from_utf8(output).unwrap())
+        }
+        crate::command::CommandResponse::Unhandled => panic!("Command `{line}` was not handled"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::CommandError;
+    use crate::commands::common::{lines, Options};
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn parse_options() {
+        let args = args("-rf a -n5 - -v -n 3 -- -x");
+        let options = Options::parse("test", &args, "rfv", "n").unwrap();
+        assert!(options.has('r') && options.has('f') && options.has('v'));
+        assert!(!options.has('x'));
+        assert_eq!(options.value('n'), Some("3"));
+        assert_eq!(options.number("test", 'n', 10).unwrap(), 3);
+        assert_eq!(options.operands, vec!["a", "-", "-x"]);
+    }
+
+    #[test]
+    fn invalid_options() {
+        assert!(matches!(
+            Options::parse("test", &args("-x"), "r", ""),
+            Err(CommandError::Usage(message)) if message == "test: invalid option -- 'x'"
+        ));
+        assert!(matches!(
+            Options::parse("test", &args("a -n"), "", "n"),
+            Err(CommandError::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn split_lines() {
+        assert_eq!(lines(b"a\nb\n\nc"), vec![&b"a\n"[..], b"b\n", b"\n", b"c"]);
+        assert!(lines(b"").is_empty());
+    }
+}
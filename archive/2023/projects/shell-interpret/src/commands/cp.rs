@@ -0,0 +1,161 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::common::{copy_targets, path_error, Options};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+use std::path::Path;
+
+/// `cp [-r] SOURCE DEST` or `cp [-r] SOURCE... DIR` copies files.
+/// Directories are only copied with `-r`.
+pub struct CopyFiles;
+
+impl Command for CopyFiles {
+    fn name(&self) -> Option<&str> {
+        Some("cp")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("copy files and directories")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        _output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "cp" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(cp(&args[1..], state))
+    }
+}
+
+fn cp(args: &[String], state: &InterpreterState) -> Result<(), CommandError> {
+    let options = Options::parse("cp", args, "rR", "")?;
+    let recursive = options.has('r') || options.has('R');
+    for (source, destination) in copy_targets("cp", &options.operands, state)? {
+        let path = state.resolve_path(source);
+        if path.is_dir() && !recursive {
+            return Err(CommandError::Usage(format!(
+                "cp: -r not specified, omitting directory `{source}`"
+            )));
+        }
+        if destination.starts_with(&path) && path.is_dir() {
+            return Err(CommandError::Usage(format!(
+                "cp: cannot copy directory `{source}` into itself"
+            )));
+        }
+        if is_same_file(&path, &destination) {
+            return Err(CommandError::Usage(format!(
+                "cp: `{source}` and `{}` are the same file",
+                destination.display()
+            )));
+        }
+        copy_path(&path, &destination).map_err(|error| path_error("cp", source, error))?;
+    }
+    Ok(())
+}
+
+/// Returns `true` if both paths exist and refer to the same file, copying would truncate it.
+fn is_same_file(source: &Path, destination: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (source.metadata(), destination.metadata()) {
+        (Ok(source), Ok(destination)) => {
+            source.dev() == destination.dev() && source.ino() == destination.ino()
+        }
+        _ => false,
+    }
+}
+
+fn copy_path(source: &Path, destination: &Path) -> std::io::Result<()> {
+    if source.is_dir() {
+        std::fs::create_dir_all(destination)?;
+        for entry in std::fs::read_dir(source)? {
+            let entry = entry?;
+            copy_path(&entry.path(), &destination.join(entry.file_name()))?;
+        }
+    } else {
+        std::fs::copy(source, destination)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::CommandError;
+    use crate::commands::common::execute_test_command;
+    use crate::commands::cp::CopyFiles;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn test_cp_1() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "a").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "b").unwrap();
+        std::fs::create_dir(dir.path().join("dir")).unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+
+        execute_test_command(&CopyFiles, "cp a.txt c.txt", &mut state, "").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("c.txt")).unwrap(),
+            "a"
+        );
+        execute_test_command(&CopyFiles, "cp a.txt b.txt dir", &mut state, "").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("dir/b.txt")).unwrap(),
+            "b"
+        );
+        assert!(dir.path().join("a.txt").exists());
+        assert!(matches!(
+            execute_test_command(&CopyFiles, "cp a.txt b.txt c.txt", &mut state, ""),
+            Err(CommandError::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn test_cp_2() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        std::fs::write(dir.path().join("src/nested/a.txt"), "a").unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+
+        assert!(matches!(
+            execute_test_command(&CopyFiles, "cp src dst", &mut state, ""),
+            Err(CommandError::Usage(_))
+        ));
+        execute_test_command(&CopyFiles, "cp -r src dst", &mut state, "").unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("dst/nested/a.txt")).unwrap(),
+            "a"
+        );
+        assert!(matches!(
+            execute_test_command(&CopyFiles, "cp -r src src/nested", &mut state, ""),
+            Err(CommandError::Usage(_))
+        ));
+    }
+
+    #[test]
+    fn test_cp_3() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("dir")).unwrap();
+        std::fs::write(dir.path().join("dir/a.txt"), "a").unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+
+        for line in [
+            "cp dir/a.txt dir/a.txt",
+            "cp dir/a.txt dir",
+            "cp dir/a.txt dir/",
+        ] {
+            assert!(matches!(
+                execute_test_command(&CopyFiles, line, &mut state, ""),
+                Err(CommandError::Usage(message)) if message.ends_with("are the same file")
+            ));
+        }
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("dir/a.txt")).unwrap(),
+            "a"
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::common::Options;
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `dirs [-c] [-p] [-v]` prints the directory stack, starting with the working directory.
+///
+/// `-p` prints one directory per line, `-v` also prints the position of each directory and `-c`
+/// clears the stack.
+pub struct PrintDirectoryStack;
+
+impl Command for PrintDirectoryStack {
+    fn name(&self) -> Option<&str> {
+        Some("dirs")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("print the directory stack")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "dirs" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(dirs(&args[1..], state, output))
+    }
+}
+
+fn dirs(
+    args: &[String],
+    state: &mut InterpreterState,
+    output: &mut dyn Write,
+) -> Result<(), CommandError> {
+    let options = Options::parse("dirs", args, "cpv", "")?;
+    if let Some(operand) = options.operands.first() {
+        return Err(CommandError::Usage(format!(
+            "dirs: {operand}: invalid argument"
+        )));
+    }
+    if options.has('c') {
+        state.dir_stack.clear();
+        return Ok(());
+    }
+    let dirs = stack(state);
+    if options.has('v') {
+        for (index, dir) in dirs.iter().enumerate() {
+            writeln!(output, "{index:2}  {dir}").map_err(CommandError::IO)?;
+        }
+    } else if options.has('p') {
+        for dir in dirs {
+            writeln!(output, "{dir}").map_err(CommandError::IO)?;
+        }
+    } else {
+        print_stack(state, output)?;
+    }
+    Ok(())
+}
+
+/// Returns the working directory followed by the directories of the stack (from its top),
+/// formatted for the user.
+fn stack(state: &InterpreterState) -> Vec<String> {
+    std::iter::once(&state.workdir)
+        .chain(state.dir_stack.iter().rev())
+        .map(|dir| state.abbreviate_home(dir))
+        .collect()
+}
+
+/// Prints the directory stack on a single line, like `dirs`, `pushd` and `popd` do.
+pub fn print_stack(state: &InterpreterState, output: &mut dyn Write) -> Result<(), CommandError> {
+    writeln!(output, "{}", stack(state).join(" ")).map_err(CommandError::IO)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::common::execute_test_command;
+    use crate::commands::dirs::PrintDirectoryStack;
+    use crate::state::InterpreterState;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_dirs_1() {
+        let mut state = InterpreterState::new(PathBuf::from("/home/ferris/src"));
+        state
+            .env
+            .insert("HOME".to_string(), "/home/ferris".to_string());
+        state.dir_stack = vec![PathBuf::from("/tmp"), PathBuf::from("/home/ferris")];
+        let mut dirs =
+            |line: &str| execute_test_command(&PrintDirectoryStack, line, &mut state, "").unwrap();
+        assert_eq!(dirs("dirs"), "~/src ~ /tmp\n");
+        assert_eq!(dirs("dirs -p"), "~/src\n~\n/tmp\n");
+        assert_eq!(dirs("dirs -v"), " 0  ~/src\n 1  ~\n 2  /tmp\n");
+        assert_eq!(dirs("dirs -c"), "");
+        assert_eq!(dirs("dirs"), "~/src\n");
+    }
+}
@@ -0,0 +1,70 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `echo [-n] ARG...` prints its arguments separated by spaces.
+/// With `-n`, the trailing newline is not printed.
+pub struct PrintArguments;
+
+impl Command for PrintArguments {
+    fn name(&self) -> Option<&str> {
+        Some("echo")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("print arguments")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        _state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "echo" {
+            return CommandResponse::Unhandled;
+        }
+
+        let (newline, args) = match args.get(1) {
+            Some(flag) if flag == "-n" => (false, &args[2..]),
+            _ => (true, &args[1..]),
+        };
+        let mut res = write!(output, "{}", args.join(" "));
+        if newline {
+            res = res.and_then(|_| writeln!(output));
+        }
+        CommandResponse::Handled(res.map_err(CommandError::IO))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::common::execute_test_command;
+    use crate::commands::echo::PrintArguments;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn test_echo_1() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute_test_command(&PrintArguments, "echo hello  world", &mut state, "").unwrap(),
+            "hello world\n"
+        );
+        assert_eq!(
+            execute_test_command(&PrintArguments, "echo", &mut state, "").unwrap(),
+            "\n"
+        );
+    }
+
+    #[test]
+    fn test_echo_2() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute_test_command(&PrintArguments, "echo -n a -n", &mut state, "").unwrap(),
+            "a -n"
+        );
+    }
+}
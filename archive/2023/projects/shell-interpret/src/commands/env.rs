@@ -0,0 +1,73 @@
+use crate::command::{Command, CommandResponse};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `env` prints all exported variables, which form the environment of programs.
+/// With arguments, it is left to the external `env` program.
+pub struct PrintEnvironment;
+
+impl Command for PrintEnvironment {
+    fn name(&self) -> Option<&str> {
+        Some("env")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("print environment variables")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args != ["env"] {
+            return CommandResponse::Unhandled;
+        }
+
+        for (name, value) in state.exported_env() {
+            writeln!(output, "{name}={value}").unwrap();
+        }
+        CommandResponse::Handled(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::{Command, CommandResponse};
+    use crate::commands::env::PrintEnvironment;
+    use crate::state::InterpreterState;
+    use std::path::PathBuf;
+
+    #[test]
+    fn print_environment() {
+        let mut state = InterpreterState::new(PathBuf::from("/foo"));
+        state.env.clear();
+        state.env.insert("B".to_string(), "2".to_string());
+        state.env.insert("A".to_string(), "1".to_string());
+        state.env.insert("C".to_string(), "3".to_string());
+        state.exported = ["A", "B"].map(|name| name.to_string()).into();
+        let mut output = vec![];
+        let res = PrintEnvironment.execute(
+            &["env".to_string()],
+            &mut state,
+            &mut std::io::empty(),
+            &mut output,
+        );
+        assert!(matches!(res, CommandResponse::Handled(Ok(()))));
+        assert_eq!(String::from_utf8(output).unwrap(), "A=1\nB=2\n");
+    }
+
+    #[test]
+    fn ignore_arguments() {
+        let mut state = InterpreterState::new(PathBuf::from("/foo"));
+        let res = PrintEnvironment.execute(
+            &["env".to_string(), "ls".to_string()],
+            &mut state,
+            &mut std::io::empty(),
+            &mut vec![],
+        );
+        assert!(matches!(res, CommandResponse::Unhandled));
+    }
+}
@@ -0,0 +1,123 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `export NAME[=value]...` marks variables to be passed to external programs, and sets their
+/// values. Without arguments, it prints all exported variables.
+pub struct ExportVariable;
+
+impl Command for ExportVariable {
+    fn name(&self) -> Option<&str> {
+        Some("export")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("export variables to programs")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "export" {
+            return CommandResponse::Unhandled;
+        }
+
+        if args.len() == 1 {
+            for (name, value) in state.exported_env() {
+                writeln!(output, "export {name}={value}").unwrap();
+            }
+            return CommandResponse::Handled(Ok(()));
+        }
+
+        for arg in &args[1..] {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (arg.as_str(), None),
+            };
+            if !is_valid_name(name) {
+                return CommandResponse::Handled(Err(CommandError::Usage(format!(
+                    "export: `{name}` is not a valid variable name"
+                ))));
+            }
+            // A variable exported without a value is passed to programs once it is set
+            if let Some(value) = value {
+                state.env.insert(name.to_string(), value.to_string());
+            }
+            state.exported.insert(name.to_string());
+        }
+        CommandResponse::Handled(Ok(()))
+    }
+}
+
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::{Command, CommandError, CommandResponse};
+    use crate::commands::export::ExportVariable;
+    use crate::state::InterpreterState;
+    use std::path::PathBuf;
+
+    fn export(args: &[&str], state: &mut InterpreterState) -> (CommandResponse, String) {
+        let mut args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        args.insert(0, "export".to_string());
+        let mut output = vec![];
+        let res = ExportVariable.execute(&args, state, &mut std::io::empty(), &mut output);
+        (res, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn export_variables() {
+        let mut state = InterpreterState::new(PathBuf::from("/foo"));
+        let (res, _) = export(&["A=1", "B=x=y", "C="], &mut state);
+        assert!(matches!(res, CommandResponse::Handled(Ok(()))));
+        assert_eq!(state.env["A"], "1");
+        assert_eq!(state.env["B"], "x=y");
+        assert_eq!(state.env["C"], "");
+        assert!(state.exported.contains("B"));
+    }
+
+    #[test]
+    fn export_without_value() {
+        let mut state = InterpreterState::new(PathBuf::from("/foo"));
+        state.env.insert("A".to_string(), "1".to_string());
+        export(&["A", "MISSING"], &mut state);
+        assert_eq!(state.env["A"], "1");
+        assert!(!state.env.contains_key("MISSING"));
+        assert!(state.exported.contains("A"));
+        assert!(state.exported.contains("MISSING"));
+    }
+
+    #[test]
+    fn export_invalid_name() {
+        let mut state = InterpreterState::new(PathBuf::from("/foo"));
+        let (res, _) = export(&["1A=2"], &mut state);
+        assert!(matches!(
+            res,
+            CommandResponse::Handled(Err(CommandError::Usage(_)))
+        ));
+        assert!(!state.env.contains_key("1A"));
+    }
+
+    #[test]
+    fn export_list() {
+        let mut state = InterpreterState::new(PathBuf::from("/foo"));
+        state.env.clear();
+        state.env.insert("B".to_string(), "2".to_string());
+        state.env.insert("A".to_string(), "1".to_string());
+        state.env.insert("C".to_string(), "3".to_string());
+        state.exported = ["A", "B"].map(|name| name.to_string()).into();
+        let (_, output) = export(&[], &mut state);
+        assert_eq!(output, "export A=1\nexport B=2\n");
+    }
+}
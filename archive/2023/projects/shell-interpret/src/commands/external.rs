@@ -0,0 +1,281 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::signals::Foreground;
+use crate::state::InterpreterState;
+use std::io::{ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+
+/// Executes external programs found in `PATH`.
+///
+/// It handles every command, so it should be registered as the last one.
+pub struct ExternalCommand;
+
+impl Command for ExternalCommand {
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        let program = &args[0];
+        CommandResponse::Handled(
+            find_executable(program, state)
+                .and_then(|path| run_program(&path, &args[1..], state, input, output)),
+        )
+    }
+
+    fn program(
+        &self,
+        args: &[String],
+        state: &InterpreterState,
+    ) -> Option<Result<std::process::Command, CommandError>> {
+        Some(find_executable(&args[0], state).map(|path| program_command(&path, &args[1..], state)))
+    }
+}
+
+fn run_program(
+    path: &Path,
+    args: &[String],
+    state: &mut InterpreterState,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+) -> Result<(), CommandError> {
+    let mut stdin = vec![];
+    input.read_to_end(&mut stdin).map_err(CommandError::IO)?;
+
+    let mut command = program_command(path, args, state);
+    command.stdin(Stdio::piped()).stdout(Stdio::piped());
+    if state.stderr.is_captured() {
+        command.stderr(Stdio::piped());
+    }
+    let mut child = command.spawn().map_err(|error| spawn_error(path, error))?;
+
+    // Feed the input from a separate thread, otherwise the child could block on a full stdout
+    // pipe while we are still writing its stdin.
+    let mut child_stdin = child.stdin.take().unwrap();
+    let writer = std::thread::spawn(move || {
+        // The program does not have to read all of its input
+        let _ = child_stdin.write_all(&stdin);
+    });
+
+    // The error output is collected by another thread for the same reason
+    let error_reader = child.stderr.take().map(|mut child_stderr| {
+        std::thread::spawn(move || {
+            let mut data = vec![];
+            let _ = child_stderr.read_to_end(&mut data);
+            data
+        })
+    });
+
+    let foreground = Foreground::set(child.id() as libc::pid_t);
+    let mut child_stdout = child.stdout.take().unwrap();
+    let res = std::io::copy(&mut child_stdout, output);
+    let status = child.wait().map_err(CommandError::IO)?;
+    drop(foreground);
+    writer.join().unwrap();
+    if let Some(error_reader) = error_reader {
+        state
+            .stderr
+            .write_all(&error_reader.join().unwrap())
+            .map_err(CommandError::IO)?;
+    }
+
+    state.last_status = exit_code(status);
+    res.map_err(CommandError::IO)?;
+    Ok(())
+}
+
+/// Prepares the execution of a program in the working directory and environment of the shell.
+pub fn program_command(
+    path: &Path,
+    args: &[String],
+    state: &InterpreterState,
+) -> std::process::Command {
+    let mut command = std::process::Command::new(path);
+    command
+        .args(args)
+        .current_dir(&state.workdir)
+        .env_clear()
+        .envs(state.exported_env());
+    command
+}
+
+/// Converts an error from starting a program, so that a program that cannot be executed is
+/// reported as such.
+pub fn spawn_error(path: &Path, error: std::io::Error) -> CommandError {
+    match error.kind() {
+        ErrorKind::PermissionDenied => CommandError::PermissionDenied(path.display().to_string()),
+        _ => CommandError::IO(error),
+    }
+}
+
+/// Finds the executable that should be executed for `program`.
+/// Programs containing a slash are resolved relative to the working directory, other programs
+/// are looked up in `PATH`.
+///
+/// A program with a slash that exists, but is not executable, results in
+/// `CommandError::PermissionDenied`.
+pub fn find_executable(program: &str, state: &InterpreterState) -> Result<PathBuf, CommandError> {
+    if program.contains('/') {
+        let path = state.resolve_path(program);
+        return if is_executable(&path) {
+            Ok(path)
+        } else if path.exists() {
+            Err(CommandError::PermissionDenied(program.to_string()))
+        } else {
+            Err(CommandError::CommandNotFound(program.to_string()))
+        };
+    }
+    state
+        .env
+        .get("PATH")
+        .and_then(|paths| {
+            std::env::split_paths(paths)
+                .map(|dir| state.resolve_path(dir).join(program))
+                .find(|path| is_executable(path))
+        })
+        .ok_or_else(|| CommandError::CommandNotFound(program.to_string()))
+}
+
+#[cfg(unix)]
+pub fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[cfg(not(unix))]
+pub fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+/// Converts the exit status of a program to a number, in the same way as POSIX shells do
+/// (programs killed by a signal get `128 + <signal number>`).
+pub fn exit_code(status: ExitStatus) -> i32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
+        }
+    }
+    status.code().unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::{Command, CommandError, CommandResponse};
+    use crate::commands::external::ExternalCommand;
+    use crate::state::InterpreterState;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(|arg| arg.to_string()).collect()
+    }
+
+    fn execute(line: &str, state: &mut InterpreterState, input: &str) -> String {
+        let mut output = vec![];
+        let res = ExternalCommand.execute(&args(line), state, &mut input.as_bytes(), &mut output);
+        assert!(matches!(res, CommandResponse::Handled(Ok(()))));
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn run_program_from_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute("echo hello  world", &mut state, ""),
+            "hello world\n"
+        );
+        assert_eq!(state.last_status, 0);
+    }
+
+    #[test]
+    fn run_in_workdir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("foo.txt"), "").unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(execute("ls", &mut state, ""), "foo.txt\n");
+    }
+
+    #[test]
+    fn pass_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(execute("cat", &mut state, "abc\ndef"), "abc\ndef");
+    }
+
+    #[test]
+    fn record_exit_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        execute("false", &mut state, "");
+        assert_eq!(state.last_status, 1);
+        execute("true", &mut state, "");
+        assert_eq!(state.last_status, 0);
+    }
+
+    #[test]
+    fn pass_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        state
+            .env
+            .insert("BENZINA_TEST".to_string(), "foo".to_string());
+        state
+            .env
+            .insert("BENZINA_LOCAL".to_string(), "bar".to_string());
+        state.exported.insert("BENZINA_TEST".to_string());
+        let output = execute("env", &mut state, "");
+        assert!(output.lines().any(|line| line == "BENZINA_TEST=foo"));
+        assert!(!output.contains("BENZINA_LOCAL"));
+    }
+
+    #[test]
+    fn search_shell_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        state.env.remove("PATH");
+        assert!(matches!(
+            ExternalCommand.execute(&args("ls"), &mut state, &mut std::io::empty(), &mut vec![]),
+            CommandResponse::Handled(Err(CommandError::CommandNotFound(_)))
+        ));
+    }
+
+    #[test]
+    fn command_not_found() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        let res = ExternalCommand.execute(
+            &args("benzina-does-not-exist"),
+            &mut state,
+            &mut std::io::empty(),
+            &mut vec![],
+        );
+        assert!(matches!(
+            res,
+            CommandResponse::Handled(Err(CommandError::CommandNotFound(name))) if name == "benzina-does-not-exist"
+        ));
+    }
+
+    #[test]
+    fn permission_denied() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("script.sh"), "echo hello").unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        let res = ExternalCommand.execute(
+            &args("./script.sh"),
+            &mut state,
+            &mut std::io::empty(),
+            &mut vec![],
+        );
+        assert!(matches!(
+            res,
+            CommandResponse::Handled(Err(CommandError::PermissionDenied(name))) if name == "./script.sh"
+        ));
+    }
+}
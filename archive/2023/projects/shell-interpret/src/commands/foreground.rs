@@ -0,0 +1,60 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::jobs::JobStatus;
+use crate::signals::{Foreground, TerminalOwner};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `fg [%n]` gives the terminal to a job, resumes it (if it was stopped) and waits until it
+/// finishes or it is stopped again.
+pub struct ForegroundJob;
+
+impl Command for ForegroundJob {
+    fn name(&self) -> Option<&str> {
+        Some("fg")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("move a job to the foreground")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "fg" {
+            return CommandResponse::Unhandled;
+        }
+
+        let id = match state.jobs.find_for_command("fg", args.get(1)) {
+            Ok(id) => id,
+            Err(error) => return CommandResponse::Handled(Err(CommandError::IO(error))),
+        };
+        let job = state.jobs.get_mut(id).unwrap();
+        writeln!(output, "{}", job.command).unwrap();
+        let command = job.command.clone();
+        let terminal = TerminalOwner::give(job.process_group());
+        if job.status() == JobStatus::Stopped {
+            if let Err(error) = job.signal(libc::SIGCONT) {
+                return CommandResponse::Handled(Err(CommandError::IO(error)));
+            }
+        }
+
+        // Signals received by the shell are forwarded to the job
+        let foreground = Foreground::set(-job.process_group());
+        let status = state.jobs.wait(id);
+        drop(foreground);
+        drop(terminal);
+        match status {
+            Some(JobStatus::Stopped) => {
+                writeln!(output, "[{id}] Stopped\t{command}").unwrap();
+                state.last_status = 128 + libc::SIGTSTP;
+            }
+            Some(status) => state.last_status = status.exit_code().unwrap_or_default(),
+            None => {}
+        }
+        CommandResponse::Handled(Ok(()))
+    }
+}
@@ -0,0 +1,301 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::common::{lines, read_input, Options};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `grep [-i] [-v] [-n] [-c] PATTERN [FILE]...` prints lines of files (or of its input) that
+/// match a basic regular expression.
+///
+/// Supported syntax: `.` (any character), `*` (repetition of the previous item), `[a-z]` and
+/// `[^a-z]` classes, `^` and `$` anchors and `\` escapes.
+/// If no line is selected, the exit status is 1.
+pub struct SearchLines;
+
+impl Command for SearchLines {
+    fn name(&self) -> Option<&str> {
+        Some("grep")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("print lines that match a pattern")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "grep" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(grep(&args[1..], state, input, output))
+    }
+}
+
+fn grep(
+    args: &[String],
+    state: &mut InterpreterState,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+) -> Result<(), CommandError> {
+    let options = Options::parse("grep", args, "ivnc", "")?;
+    let Some((pattern, paths)) = options.operands.split_first() else {
+        return Err(CommandError::Usage("grep: missing pattern".to_string()));
+    };
+    let ignore_case = options.has('i');
+    let regex = if ignore_case {
+        Regex::parse(&pattern.to_lowercase())?
+    } else {
+        Regex::parse(pattern)?
+    };
+    let paths = if paths.is_empty() { &["-"][..] } else { paths };
+
+    let mut selected = 0;
+    for path in paths {
+        let prefix = if paths.len() > 1 {
+            format!("{path}:")
+        } else {
+            String::new()
+        };
+        let data = read_input("grep", Some(path), state, input)?;
+        let mut count = 0;
+        for (index, line) in lines(&data).into_iter().enumerate() {
+            let text = String::from_utf8_lossy(line);
+            let text = text.trim_end_matches('\n');
+            let matches = if ignore_case {
+                regex.is_match(&text.to_lowercase())
+            } else {
+                regex.is_match(text)
+            };
+            if matches == options.has('v') {
+                continue;
+            }
+            count += 1;
+            if options.has('c') {
+                continue;
+            }
+            let number = if options.has('n') {
+                format!("{}:", index + 1)
+            } else {
+                String::new()
+            };
+            writeln!(output, "{prefix}{number}{text}").map_err(CommandError::IO)?;
+        }
+        if options.has('c') {
+            writeln!(output, "{prefix}{count}").map_err(CommandError::IO)?;
+        }
+        selected += count;
+    }
+    state.last_status = if selected > 0 { 0 } else { 1 };
+    Ok(())
+}
+
+#[derive(Debug)]
+enum Atom {
+    Any,
+    Char(char),
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl Atom {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Atom::Any => true,
+            Atom::Char(expected) => *expected == c,
+            Atom::Class { negated, ranges } => {
+                ranges
+                    .iter()
+                    .any(|(start, end)| (*start..=*end).contains(&c))
+                    != *negated
+            }
+        }
+    }
+}
+
+/// A basic regular expression.
+#[derive(Debug)]
+struct Regex {
+    /// Items of the expression, `true` if the item can be repeated (`*`)
+    items: Vec<(Atom, bool)>,
+    anchored_start: bool,
+    anchored_end: bool,
+}
+
+impl Regex {
+    fn parse(pattern: &str) -> Result<Self, CommandError> {
+        let mut chars: Vec<char> = pattern.chars().collect();
+        let anchored_start = chars.first() == Some(&'^');
+        if anchored_start {
+            chars.remove(0);
+        }
+        // `\$` at the end is a literal dollar
+        let anchored_end = chars.last() == Some(&'$')
+            && chars
+                .iter()
+                .rev()
+                .skip(1)
+                .take_while(|c| **c == '\\')
+                .count()
+                % 2
+                == 0;
+        if anchored_end {
+            chars.pop();
+        }
+
+        let mut items: Vec<(Atom, bool)> = vec![];
+        let mut index = 0;
+        while index < chars.len() {
+            let atom = match chars[index] {
+                '.' => Atom::Any,
+                '*' if !items.is_empty() => {
+                    items.last_mut().unwrap().1 = true;
+                    index += 1;
+                    continue;
+                }
+                '\\' => {
+                    index += 1;
+                    match chars.get(index) {
+                        Some(c) => Atom::Char(*c),
+                        None => {
+                            return Err(CommandError::Usage("grep: trailing backslash".to_string()))
+                        }
+                    }
+                }
+                '[' => {
+                    let (class, length) = parse_class(&chars[index + 1..])?;
+                    index += length;
+                    class
+                }
+                c => Atom::Char(c),
+            };
+            items.push((atom, false));
+            index += 1;
+        }
+        Ok(Self {
+            items,
+            anchored_start,
+            anchored_end,
+        })
+    }
+
+    fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        if self.anchored_start {
+            self.matches_at(&self.items, &text)
+        } else {
+            (0..=text.len()).any(|start| self.matches_at(&self.items, &text[start..]))
+        }
+    }
+
+    fn matches_at(&self, items: &[(Atom, bool)], text: &[char]) -> bool {
+        match items.split_first() {
+            None => !self.anchored_end || text.is_empty(),
+            Some(((atom, true), rest)) => {
+                let count = text.iter().take_while(|c| atom.matches(**c)).count();
+                (0..=count)
+                    .rev()
+                    .any(|count| self.matches_at(rest, &text[count..]))
+            }
+            Some(((atom, false), rest)) => match text.split_first() {
+                Some((c, text)) => atom.matches(*c) && self.matches_at(rest, text),
+                None => false,
+            },
+        }
+    }
+}
+
+/// Parses a character class that follows `[`.
+/// Returns the class and the number of characters that it has consumed.
+fn parse_class(chars: &[char]) -> Result<(Atom, usize), CommandError> {
+    let mut index = 0;
+    let negated = chars.first() == Some(&'^');
+    if negated {
+        index += 1;
+    }
+    let mut ranges = vec![];
+    // `]` right at the start is a literal character
+    let start = index;
+    while index < chars.len() {
+        let c = chars[index];
+        if c == ']' && index > start {
+            return Ok((Atom::Class { negated, ranges }, index + 1));
+        }
+        if chars.get(index + 1) == Some(&'-') && chars.get(index + 2).is_some_and(|c| *c != ']') {
+            ranges.push((c, chars[index + 2]));
+            index += 3;
+        } else {
+            ranges.push((c, c));
+            index += 1;
+        }
+    }
+    Err(CommandError::Usage(
+        "grep: unterminated character class".to_string(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::common::execute_test_command;
+    use crate::commands::grep::{Regex, SearchLines};
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn test_grep_1() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        let input = "apple\nBanana\ncherry\n";
+        assert_eq!(
+            execute_test_command(&SearchLines, "grep an", &mut state, input).unwrap(),
+            "Banana\n"
+        );
+        assert_eq!(state.last_status, 0);
+        assert_eq!(
+            execute_test_command(&SearchLines, "grep -in ^b", &mut state, input).unwrap(),
+            "2:Banana\n"
+        );
+        assert_eq!(
+            execute_test_command(&SearchLines, "grep -vc rr", &mut state, input).unwrap(),
+            "2\n"
+        );
+        assert_eq!(
+            execute_test_command(&SearchLines, "grep kiwi", &mut state, input).unwrap(),
+            ""
+        );
+        assert_eq!(state.last_status, 1);
+    }
+
+    #[test]
+    fn test_grep_2() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "fn main() {}\nlet x = 1;\n").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "fn foo() {}\n").unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute_test_command(&SearchLines, "grep ^fn a.txt b.txt", &mut state, "").unwrap(),
+            "a.txt:fn main() {}\nb.txt:fn foo() {}\n"
+        );
+    }
+
+    #[test]
+    fn regex() {
+        let regex = |pattern: &str| Regex::parse(pattern).unwrap();
+        assert!(regex("a.c").is_match("xabcx"));
+        assert!(!regex("a.c").is_match("ac"));
+        assert!(regex("^ab*c$").is_match("ac"));
+        assert!(regex("^ab*c$").is_match("abbbc"));
+        assert!(!regex("^ab*c$").is_match("abbbcd"));
+        assert!(regex("[0-9][0-9]*").is_match("version 42"));
+        assert!(!regex("^[^a-z]").is_match("abc"));
+        assert!(regex("a\\.b").is_match("a.b"));
+        assert!(!regex("a\\.b").is_match("axb"));
+        assert!(regex("cost\\$").is_match("cost$ 5"));
+        assert!(regex("*a").is_match("*a"));
+        assert!(regex("").is_match("anything"));
+        assert!(Regex::parse("[abc").is_err());
+    }
+}
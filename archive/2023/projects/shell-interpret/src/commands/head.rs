@@ -0,0 +1,113 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::common::{lines, read_first_lines, read_input, Options};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+use std::ops::Range;
+
+/// `head [-n COUNT] [FILE]...` prints the first lines (10 by default) of files or of its input.
+pub struct PrintFirstLines;
+
+impl Command for PrintFirstLines {
+    fn name(&self) -> Option<&str> {
+        Some("head")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("print the first lines of files")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "head" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(print_lines(
+            "head",
+            &args[1..],
+            state,
+            input,
+            output,
+            |length, count| 0..count.min(length),
+            true,
+        ))
+    }
+}
+
+/// Implementation of `head` and `tail`. `select` receives the number of lines and the requested
+/// count and returns the range of lines that should be printed.
+/// If there are multiple files, each of them is preceded by a `==> name <==` header.
+/// With `first_only`, only the first lines are read, so that e.g. `yes | head` ends.
+pub fn print_lines(
+    command: &str,
+    args: &[String],
+    state: &InterpreterState,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+    select: fn(usize, usize) -> Range<usize>,
+    first_only: bool,
+) -> Result<(), CommandError> {
+    let options = Options::parse(command, args, "", "n")?;
+    let count = options.number(command, 'n', 10)?;
+    let mut operands = options.operands;
+    if operands.is_empty() {
+        operands.push("-");
+    }
+    let headers = operands.len() > 1;
+    for (index, path) in operands.iter().enumerate() {
+        let data = if first_only {
+            read_first_lines(command, Some(path), state, input, count)?
+        } else {
+            read_input(command, Some(path), state, input)?
+        };
+        if headers {
+            let separator = if index > 0 { "\n" } else { "" };
+            let name = if *path == "-" { "standard input" } else { path };
+            writeln!(output, "{separator}==> {name} <==").map_err(CommandError::IO)?;
+        }
+        let lines = lines(&data);
+        for line in &lines[select(lines.len(), count)] {
+            output.write_all(line).map_err(CommandError::IO)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::common::execute_test_command;
+    use crate::commands::head::PrintFirstLines;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn test_head_1() {
+        let dir = tempfile::tempdir().unwrap();
+        let content: String = (1..=20).map(|line| format!("{line}\n")).collect();
+        std::fs::write(dir.path().join("a.txt"), &content).unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute_test_command(&PrintFirstLines, "head a.txt", &mut state, "").unwrap(),
+            content[..21]
+        );
+        assert_eq!(
+            execute_test_command(&PrintFirstLines, "head -n 2", &mut state, "a\nb\nc\n").unwrap(),
+            "a\nb\n"
+        );
+    }
+
+    #[test]
+    fn test_head_2() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "a1\na2\n").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "b1").unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute_test_command(&PrintFirstLines, "head -n1 a.txt b.txt", &mut state, "").unwrap(),
+            "==> a.txt <==\na1\n\n==> b.txt <==\nb1"
+        );
+    }
+}
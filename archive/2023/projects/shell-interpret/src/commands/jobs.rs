@@ -0,0 +1,36 @@
+use crate::command::{Command, CommandResponse};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `jobs` lists background jobs and their status.
+pub struct ListJobs;
+
+impl Command for ListJobs {
+    fn name(&self) -> Option<&str> {
+        Some("jobs")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("list background jobs")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "jobs" {
+            return CommandResponse::Unhandled;
+        }
+
+        state.jobs.refresh();
+        for job in state.jobs.iter() {
+            writeln!(output, "[{}] {}\t{}", job.id, job.status(), job.command).unwrap();
+        }
+        // Finished jobs were reported, so they can be forgotten
+        state.jobs.take_finished();
+        CommandResponse::Handled(Ok(()))
+    }
+}
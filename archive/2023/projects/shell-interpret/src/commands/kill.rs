@@ -0,0 +1,67 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::jobs::parse_signal;
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `kill [-SIGNAL] %n...` sends a signal (`SIGTERM` by default) to jobs.
+/// Arguments without `%` are treated as PIDs.
+/// If no job is referenced, the command is left to the external `kill` program.
+pub struct KillJob;
+
+impl Command for KillJob {
+    fn name(&self) -> Option<&str> {
+        Some("kill")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("send a signal to jobs or processes")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        _output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "kill" || !args.iter().any(|arg| arg.starts_with('%')) {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(kill(&args[1..], state))
+    }
+}
+
+fn kill(mut args: &[String], state: &mut InterpreterState) -> Result<(), CommandError> {
+    let mut signal = libc::SIGTERM;
+    if let Some(name) = args.first().and_then(|arg| arg.strip_prefix('-')) {
+        let name = if name == "s" {
+            args = &args[1..];
+            args.first().map(|arg| arg.as_str()).unwrap_or_default()
+        } else {
+            name
+        };
+        signal = parse_signal(name).ok_or_else(|| {
+            CommandError::Usage(format!("kill: {name}: invalid signal specification"))
+        })?;
+        args = &args[1..];
+    }
+
+    for target in args {
+        if target.starts_with('%') {
+            let id = state
+                .jobs
+                .find_for_command("kill", Some(target))
+                .map_err(CommandError::IO)?;
+            let job = state.jobs.get_mut(id).unwrap();
+            job.signal(signal).map_err(CommandError::IO)?;
+        } else {
+            let pid: libc::pid_t = target.parse().map_err(|_| {
+                CommandError::Usage(format!("kill: {target}: arguments must be PIDs or job IDs"))
+            })?;
+            if unsafe { libc::kill(pid, signal) } != 0 {
+                return Err(CommandError::IO(std::io::Error::last_os_error()));
+            }
+        }
+    }
+    Ok(())
+}
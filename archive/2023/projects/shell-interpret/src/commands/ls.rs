@@ -0,0 +1,196 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::common::{path_error, Options};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `ls [-a] [-l] [PATH]...` lists the contents of directories (the working directory by
+/// default), one entry per line.
+/// With `-a`, hidden files are also listed, with `-l`, the type, permissions and size of each
+/// entry are printed.
+pub struct ListDirectory;
+
+impl Command for ListDirectory {
+    fn name(&self) -> Option<&str> {
+        Some("ls")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("list directory contents")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "ls" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(ls(&args[1..], state, output))
+    }
+}
+
+fn ls(
+    args: &[String],
+    state: &InterpreterState,
+    output: &mut dyn Write,
+) -> Result<(), CommandError> {
+    let options = Options::parse("ls", args, "al", "")?;
+    let long = options.has('l');
+    let mut operands = options.operands.clone();
+    if operands.is_empty() {
+        operands.push(".");
+    }
+
+    // Files are listed first, then the contents of directories
+    let mut files = vec![];
+    let mut dirs = vec![];
+    for operand in &operands {
+        let path = state.resolve_path(operand);
+        let metadata = path
+            .metadata()
+            .map_err(|error| path_error("ls", operand, error))?;
+        if metadata.is_dir() {
+            dirs.push(*operand);
+        } else {
+            files.push((operand.to_string(), metadata));
+        }
+    }
+    let mut sections = 0;
+    if !files.is_empty() {
+        print_entries(output, files, long)?;
+        sections += 1;
+    }
+    for dir in dirs {
+        let mut entries = vec![];
+        let read_dir = std::fs::read_dir(state.resolve_path(dir))
+            .map_err(|error| path_error("ls", dir, error))?;
+        for entry in read_dir {
+            let entry = entry.map_err(|error| path_error("ls", dir, error))?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if name.starts_with('.') && !options.has('a') {
+                continue;
+            }
+            let metadata = entry
+                .metadata()
+                .map_err(|error| path_error("ls", dir, error))?;
+            entries.push((name, metadata));
+        }
+        if operands.len() > 1 {
+            let separator = if sections > 0 { "\n" } else { "" };
+            writeln!(output, "{separator}{dir}:").map_err(CommandError::IO)?;
+        }
+        print_entries(output, entries, long)?;
+        sections += 1;
+    }
+    Ok(())
+}
+
+fn print_entries(
+    output: &mut dyn Write,
+    mut entries: Vec<(String, std::fs::Metadata)>,
+    long: bool,
+) -> Result<(), CommandError> {
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let size_width = entries
+        .iter()
+        .map(|(_, metadata)| metadata.len().to_string().len())
+        .max()
+        .unwrap_or(0);
+    for (name, metadata) in entries {
+        let res = if long {
+            writeln!(
+                output,
+                "{} {:>size_width$} {name}",
+                mode(&metadata),
+                metadata.len()
+            )
+        } else {
+            writeln!(output, "{name}")
+        };
+        res.map_err(CommandError::IO)?;
+    }
+    Ok(())
+}
+
+/// Formats the type and permissions of a file, e.g. `drwxr-xr-x`.
+fn mode(metadata: &std::fs::Metadata) -> String {
+    use std::os::unix::fs::PermissionsExt;
+
+    let kind = if metadata.is_dir() {
+        'd'
+    } else if metadata.is_symlink() {
+        'l'
+    } else {
+        '-'
+    };
+    let mode = metadata.permissions().mode();
+    let permissions = (0..9).rev().map(|bit| {
+        if mode & (1 << bit) == 0 {
+            '-'
+        } else {
+            ['x', 'w', 'r'][bit % 3]
+        }
+    });
+    std::iter::once(kind).chain(permissions).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::CommandError;
+    use crate::commands::common::execute_test_command;
+    use crate::commands::ls::ListDirectory;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn test_ls_1() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("b.txt"), "").unwrap();
+        std::fs::write(dir.path().join("a.txt"), "").unwrap();
+        std::fs::write(dir.path().join(".hidden"), "").unwrap();
+        std::fs::create_dir(dir.path().join("dir")).unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute_test_command(&ListDirectory, "ls", &mut state, "").unwrap(),
+            "a.txt\nb.txt\ndir\n"
+        );
+        assert_eq!(
+            execute_test_command(&ListDirectory, "ls -a", &mut state, "").unwrap(),
+            ".hidden\na.txt\nb.txt\ndir\n"
+        );
+    }
+
+    #[test]
+    fn test_ls_2() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("dir")).unwrap();
+        std::fs::write(dir.path().join("dir/x"), "").unwrap();
+        std::fs::write(dir.path().join("a.txt"), "abc").unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute_test_command(&ListDirectory, "ls dir a.txt", &mut state, "").unwrap(),
+            "a.txt\n\ndir:\nx\n"
+        );
+        assert!(matches!(
+            execute_test_command(&ListDirectory, "ls missing", &mut state, ""),
+            Err(CommandError::IO(_))
+        ));
+    }
+
+    #[test]
+    fn test_ls_3() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "hello").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute_test_command(&ListDirectory, "ls -l a.txt", &mut state, "").unwrap(),
+            "-rw-r----- 5 a.txt\n"
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::common::{path_error, Options};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `mkdir [-p] DIR...` creates directories.
+/// With `-p`, parent directories are created as needed and existing directories are not an
+/// error.
+pub struct MakeDirectory;
+
+impl Command for MakeDirectory {
+    fn name(&self) -> Option<&str> {
+        Some("mkdir")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("create directories")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        _output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "mkdir" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(mkdir(&args[1..], state))
+    }
+}
+
+fn mkdir(args: &[String], state: &InterpreterState) -> Result<(), CommandError> {
+    let options = Options::parse("mkdir", args, "p", "")?;
+    if options.operands.is_empty() {
+        return Err(CommandError::Usage("mkdir: missing operand".to_string()));
+    }
+    for dir in &options.operands {
+        let path = state.resolve_path(dir);
+        let res = if options.has('p') {
+            std::fs::create_dir_all(path)
+        } else {
+            std::fs::create_dir(path)
+        };
+        res.map_err(|error| path_error("mkdir", dir, error))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::CommandError;
+    use crate::commands::common::execute_test_command;
+    use crate::commands::mkdir::MakeDirectory;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn test_mkdir_1() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        execute_test_command(&MakeDirectory, "mkdir a b", &mut state, "").unwrap();
+        assert!(dir.path().join("a").is_dir());
+        assert!(dir.path().join("b").is_dir());
+        assert!(matches!(
+            execute_test_command(&MakeDirectory, "mkdir a", &mut state, ""),
+            Err(CommandError::IO(_))
+        ));
+        assert!(matches!(
+            execute_test_command(&MakeDirectory, "mkdir c/d", &mut state, ""),
+            Err(CommandError::IO(_))
+        ));
+    }
+
+    #[test]
+    fn test_mkdir_2() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        execute_test_command(&MakeDirectory, "mkdir -p a/b/c a", &mut state, "").unwrap();
+        assert!(dir.path().join("a/b/c").is_dir());
+    }
+}
@@ -1,2 +1,33 @@
+pub mod alias;
+pub mod background;
+pub mod cat;
 pub mod change_dir;
+pub mod common;
+pub mod cp;
+pub mod dirs;
+pub mod echo;
+pub mod env;
+pub mod export;
+pub mod external;
+pub mod foreground;
+pub mod grep;
+pub mod head;
+pub mod jobs;
+pub mod kill;
+pub mod ls;
+pub mod mkdir;
+pub mod mv;
+pub mod popd;
 pub mod print_workdir;
+pub mod pushd;
+pub mod read;
+pub mod rm;
+pub mod set;
+pub mod tail;
+pub mod touch;
+pub mod trap;
+pub mod unalias;
+pub mod unset;
+pub mod wait;
+pub mod wc;
+pub mod z;
@@ -0,0 +1,77 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::common::{copy_targets, path_error, Options};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `mv SOURCE DEST` or `mv SOURCE... DIR` moves (renames) files and directories.
+pub struct MoveFiles;
+
+impl Command for MoveFiles {
+    fn name(&self) -> Option<&str> {
+        Some("mv")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("move or rename files")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        _output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "mv" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(mv(&args[1..], state))
+    }
+}
+
+fn mv(args: &[String], state: &InterpreterState) -> Result<(), CommandError> {
+    let options = Options::parse("mv", args, "", "")?;
+    for (source, destination) in copy_targets("mv", &options.operands, state)? {
+        std::fs::rename(state.resolve_path(source), destination)
+            .map_err(|error| path_error("mv", source, error))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::CommandError;
+    use crate::commands::common::execute_test_command;
+    use crate::commands::mv::MoveFiles;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn test_mv_1() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "a").unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        execute_test_command(&MoveFiles, "mv a.txt b.txt", &mut state, "").unwrap();
+        assert!(!dir.path().join("a.txt").exists());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("b.txt")).unwrap(),
+            "a"
+        );
+        assert!(matches!(
+            execute_test_command(&MoveFiles, "mv a.txt c.txt", &mut state, ""),
+            Err(CommandError::IO(_))
+        ));
+    }
+
+    #[test]
+    fn test_mv_2() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "a").unwrap();
+        std::fs::create_dir_all(dir.path().join("src/nested")).unwrap();
+        std::fs::create_dir(dir.path().join("dir")).unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        execute_test_command(&MoveFiles, "mv a.txt src dir", &mut state, "").unwrap();
+        assert!(dir.path().join("dir/a.txt").is_file());
+        assert!(dir.path().join("dir/src/nested").is_dir());
+        assert!(!dir.path().join("src").exists());
+    }
+}
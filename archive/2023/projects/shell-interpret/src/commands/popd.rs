@@ -0,0 +1,78 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::change_dir::change_workdir;
+use crate::commands::dirs::print_stack;
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `popd` removes the top directory from the directory stack and changes to it.
+/// The resulting stack is printed like with `dirs`.
+pub struct PopDirectory;
+
+impl Command for PopDirectory {
+    fn name(&self) -> Option<&str> {
+        Some("popd")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("return to the directory saved by pushd")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "popd" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(popd(&args[1..], state, output))
+    }
+}
+
+fn popd(
+    args: &[String],
+    state: &mut InterpreterState,
+    output: &mut dyn Write,
+) -> Result<(), CommandError> {
+    if !args.is_empty() {
+        return Err(CommandError::Usage("popd: too many arguments".to_string()));
+    }
+    let Some(top) = state.dir_stack.pop() else {
+        return Err(CommandError::Usage(
+            "popd: directory stack empty".to_string(),
+        ));
+    };
+    // The directory is removed from the stack even if it does not exist anymore
+    change_workdir("popd", &top.to_string_lossy(), &top, state)?;
+    print_stack(state, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::CommandError;
+    use crate::commands::common::execute_test_command;
+    use crate::commands::popd::PopDirectory;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn test_popd_1() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("a")).unwrap();
+        let mut state = InterpreterState::new(root.join("a"));
+        state.env.remove("HOME");
+        state.dir_stack = vec![root.join("missing"), root.clone()];
+
+        let output = execute_test_command(&PopDirectory, "popd", &mut state, "").unwrap();
+        assert_eq!(output, format!("{0} {0}/missing\n", root.display()));
+        assert_eq!(state.workdir, root);
+        assert!(execute_test_command(&PopDirectory, "popd", &mut state, "").is_err());
+        assert_eq!(state.workdir, root);
+        assert!(matches!(
+            execute_test_command(&PopDirectory, "popd", &mut state, ""),
+            Err(CommandError::Usage(message)) if message == "popd: directory stack empty"
+        ));
+    }
+}
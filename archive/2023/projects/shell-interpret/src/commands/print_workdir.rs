@@ -1,18 +1,26 @@
 use crate::command::{Command, CommandResponse};
 use crate::state::InterpreterState;
-use std::io::Write;
+use std::io::{Read, Write};
 
 pub struct PrintWorkdir;
 
 impl Command for PrintWorkdir {
+    fn name(&self) -> Option<&str> {
+        Some("pwd")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("print the working directory")
+    }
+
     fn execute(
         &self,
-        line: &str,
+        args: &[String],
         state: &mut InterpreterState,
+        _input: &mut dyn Read,
         output: &mut dyn Write,
     ) -> CommandResponse {
-        let line = line.trim();
-        if line != "pwd" {
+        if args[0] != "pwd" {
             return CommandResponse::Unhandled;
         }
 
@@ -33,7 +41,12 @@ mod tests {
         let pwd = PrintWorkdir;
         let mut state = InterpreterState::new(PathBuf::from("/foo"));
         let mut buffer = vec![];
-        pwd.execute("pwd", &mut state, &mut buffer);
+        pwd.execute(
+            &["pwd".to_string()],
+            &mut state,
+            &mut std::io::empty(),
+            &mut buffer,
+        );
         assert_eq!(String::from_utf8(buffer).unwrap(), "/foo\n");
     }
 }
@@ -0,0 +1,100 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::change_dir::{change_workdir, find_directory};
+use crate::commands::dirs::print_stack;
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `pushd DIR` saves the working directory on the directory stack and changes to `DIR`.
+/// Without arguments, it exchanges the working directory with the top of the stack.
+/// The resulting stack is printed like with `dirs`.
+pub struct PushDirectory;
+
+impl Command for PushDirectory {
+    fn name(&self) -> Option<&str> {
+        Some("pushd")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("save the working directory and change it")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "pushd" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(pushd(&args[1..], state, output))
+    }
+}
+
+fn pushd(
+    args: &[String],
+    state: &mut InterpreterState,
+    output: &mut dyn Write,
+) -> Result<(), CommandError> {
+    let previous = state.workdir.clone();
+    match args {
+        [] => {
+            let Some(top) = state.dir_stack.pop() else {
+                return Err(CommandError::Usage("pushd: no other directory".to_string()));
+            };
+            if let Err(error) = change_workdir("pushd", &top.to_string_lossy(), &top, state) {
+                state.dir_stack.push(top);
+                return Err(error);
+            }
+        }
+        [dir] => {
+            let (path, _) = find_directory(dir, state);
+            change_workdir("pushd", dir, &path, state)?;
+        }
+        _ => return Err(CommandError::Usage("pushd: too many arguments".to_string())),
+    }
+    state.dir_stack.push(previous);
+    print_stack(state, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::CommandError;
+    use crate::commands::common::execute_test_command;
+    use crate::commands::pushd::PushDirectory;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn test_pushd_1() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        let mut state = InterpreterState::new(root.clone());
+        state.env.remove("HOME");
+
+        let output = execute_test_command(&PushDirectory, "pushd a", &mut state, "").unwrap();
+        assert_eq!(output, format!("{0}/a {0}\n", root.display()));
+        execute_test_command(&PushDirectory, "pushd b", &mut state, "").unwrap();
+        assert_eq!(state.workdir, root.join("a/b"));
+        assert_eq!(state.dir_stack, vec![root.clone(), root.join("a")]);
+
+        // Without arguments, the top of the stack is exchanged with the working directory
+        let output = execute_test_command(&PushDirectory, "pushd", &mut state, "").unwrap();
+        assert_eq!(output, format!("{0}/a {0}/a/b {0}\n", root.display()));
+        assert_eq!(state.workdir, root.join("a"));
+    }
+
+    #[test]
+    fn test_pushd_2() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert!(matches!(
+            execute_test_command(&PushDirectory, "pushd", &mut state, ""),
+            Err(CommandError::Usage(message)) if message == "pushd: no other directory"
+        ));
+        assert!(execute_test_command(&PushDirectory, "pushd missing", &mut state, "").is_err());
+        assert!(state.dir_stack.is_empty());
+        assert_eq!(state.workdir, dir.path());
+    }
+}
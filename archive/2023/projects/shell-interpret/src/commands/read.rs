@@ -0,0 +1,174 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::common::Options;
+use crate::commands::export::is_valid_name;
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `read [-r] [NAME]...` reads a line of the input and assigns its words to the variables, the
+/// last variable gets the rest of the line. Without names, the line is assigned to `REPLY`.
+///
+/// Unless `-r` is given, a backslash escapes the next character and a backslash at the end of
+/// a line joins it with the next one. At the end of the input, the exit status is 1.
+pub struct ReadVariables;
+
+impl Command for ReadVariables {
+    fn name(&self) -> Option<&str> {
+        Some("read")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("read a line into variables")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        input: &mut dyn Read,
+        _output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "read" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(read(&args[1..], state, input))
+    }
+}
+
+fn read(
+    args: &[String],
+    state: &mut InterpreterState,
+    input: &mut dyn Read,
+) -> Result<(), CommandError> {
+    let options = Options::parse("read", args, "r", "")?;
+    let names = if options.operands.is_empty() {
+        &["REPLY"][..]
+    } else {
+        &options.operands
+    };
+    if let Some(name) = names.iter().find(|name| !is_valid_name(name)) {
+        return Err(CommandError::Usage(format!(
+            "read: `{name}` is not a valid variable name"
+        )));
+    }
+
+    let (line, complete) = read_line(input, options.has('r')).map_err(CommandError::IO)?;
+    let mut words = split_words(&line, names.len()).into_iter();
+    for name in names {
+        let word = words.next().unwrap_or_default();
+        state.env.insert(name.to_string(), word);
+    }
+    state.last_status = if complete { 0 } else { 1 };
+    Ok(())
+}
+
+/// Reads a single line, one byte at a time, so that the rest of the input is left for the next
+/// commands. Returns the characters of the line, with a flag that marks the escaped ones, and
+/// whether the line ended with a newline.
+fn read_line(input: &mut dyn Read, raw: bool) -> std::io::Result<(Vec<(u8, bool)>, bool)> {
+    let mut line = vec![];
+    let mut escaped = false;
+    let mut byte = [0];
+    loop {
+        if input.read(&mut byte)? == 0 {
+            return Ok((line, false));
+        }
+        match byte[0] {
+            // The line continues after an escaped newline
+            b'\n' if escaped => escaped = false,
+            b'\n' => return Ok((line, true)),
+            b'\\' if !raw && !escaped => escaped = true,
+            byte => {
+                line.push((byte, escaped));
+                escaped = false;
+            }
+        }
+    }
+}
+
+/// Splits the line into at most `count` words at unescaped spaces and tabs, the last word is the
+/// rest of the line.
+fn split_words(line: &[(u8, bool)], count: usize) -> Vec<String> {
+    let is_separator = |&(byte, escaped): &(u8, bool)| !escaped && matches!(byte, b' ' | b'\t');
+    let text = |chars: &[(u8, bool)]| {
+        let bytes: Vec<u8> = chars.iter().map(|(byte, _)| *byte).collect();
+        String::from_utf8_lossy(&bytes).into_owned()
+    };
+
+    let mut words = vec![];
+    let mut rest = line;
+    while !rest.is_empty() {
+        let start = rest
+            .iter()
+            .position(|c| !is_separator(c))
+            .unwrap_or(rest.len());
+        rest = &rest[start..];
+        if rest.is_empty() {
+            break;
+        }
+        if words.len() + 1 == count {
+            let end = rest.iter().rposition(|c| !is_separator(c)).unwrap();
+            words.push(text(&rest[..=end]));
+            break;
+        }
+        let end = rest.iter().position(is_separator).unwrap_or(rest.len());
+        words.push(text(&rest[..end]));
+        rest = &rest[end..];
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::{Command, CommandResponse};
+    use crate::commands::common::execute_test_command;
+    use crate::commands::read::ReadVariables;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn read_words() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        let args = ["read", "A", "B", "C"].map(|arg| arg.to_string());
+        let mut input = "  a  b \t c d  \nnext\n".as_bytes();
+        let res = ReadVariables.execute(&args, &mut state, &mut input, &mut vec![]);
+        assert!(matches!(res, CommandResponse::Handled(Ok(()))));
+        // The rest of the input is left for the next commands
+        assert_eq!(input, b"next\n");
+        assert_eq!(state.env["A"], "a");
+        assert_eq!(state.env["B"], "b");
+        assert_eq!(state.env["C"], "c d");
+        assert_eq!(state.last_status, 0);
+
+        execute_test_command(&ReadVariables, "read A B", &mut state, "x\n").unwrap();
+        assert_eq!(state.env["A"], "x");
+        assert_eq!(state.env["B"], "");
+        execute_test_command(&ReadVariables, "read", &mut state, " whole  line \n").unwrap();
+        assert_eq!(state.env["REPLY"], "whole  line");
+    }
+
+    #[test]
+    fn read_escapes() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        let input = "a\\ b\\\\ c\\\nd\n";
+        execute_test_command(&ReadVariables, "read A B", &mut state, input).unwrap();
+        assert_eq!(state.env["A"], "a b\\");
+        assert_eq!(state.env["B"], "cd");
+        execute_test_command(&ReadVariables, "read -r A B", &mut state, input).unwrap();
+        assert_eq!(state.env["A"], "a\\");
+        assert_eq!(state.env["B"], "b\\\\ c\\");
+    }
+
+    #[test]
+    fn read_end_of_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        execute_test_command(&ReadVariables, "read A", &mut state, "last").unwrap();
+        assert_eq!(state.env["A"], "last");
+        assert_eq!(state.last_status, 1);
+        execute_test_command(&ReadVariables, "read A", &mut state, "").unwrap();
+        assert_eq!(state.env["A"], "");
+        assert_eq!(state.last_status, 1);
+        assert!(execute_test_command(&ReadVariables, "read 1A", &mut state, "").is_err());
+    }
+}
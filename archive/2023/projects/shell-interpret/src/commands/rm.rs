@@ -0,0 +1,133 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::common::{path_error, Options};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `rm [-r] [-f] PATH...` removes files.
+/// Directories are only removed with `-r` (together with their contents). With `-f`, missing
+/// files are ignored.
+pub struct RemoveFiles;
+
+impl Command for RemoveFiles {
+    fn name(&self) -> Option<&str> {
+        Some("rm")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("remove files and directories")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        _output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "rm" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(rm(&args[1..], state))
+    }
+}
+
+fn rm(args: &[String], state: &InterpreterState) -> Result<(), CommandError> {
+    let options = Options::parse("rm", args, "rRf", "")?;
+    let recursive = options.has('r') || options.has('R');
+    let force = options.has('f');
+    if options.operands.is_empty() && !force {
+        return Err(CommandError::Usage("rm: missing operand".to_string()));
+    }
+    for operand in options.operands {
+        let path = state.resolve_path(operand);
+        // Like GNU rm, which would otherwise remove the working directory (or its parent) with
+        // `rm -r .`
+        let name = operand.trim_end_matches('/').rsplit('/').next();
+        if matches!(name, Some("." | "..")) {
+            return Err(CommandError::Usage(format!(
+                "rm: refusing to remove `.` or `..` directory: skipping `{operand}`"
+            )));
+        }
+        if path
+            .canonicalize()
+            .is_ok_and(|path| path.parent().is_none())
+        {
+            return Err(CommandError::Usage(
+                "rm: it is dangerous to operate recursively on `/`".to_string(),
+            ));
+        }
+        let res = match path.symlink_metadata() {
+            Ok(metadata) if metadata.is_dir() => {
+                if !recursive {
+                    return Err(CommandError::Usage(format!(
+                        "rm: {operand}: is a directory"
+                    )));
+                }
+                std::fs::remove_dir_all(path)
+            }
+            Ok(_) => std::fs::remove_file(path),
+            Err(error) if force && error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(error) => Err(error),
+        };
+        res.map_err(|error| path_error("rm", operand, error))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::CommandError;
+    use crate::commands::common::execute_test_command;
+    use crate::commands::rm::RemoveFiles;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn test_rm_1() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "").unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        execute_test_command(&RemoveFiles, "rm a.txt", &mut state, "").unwrap();
+        assert!(!dir.path().join("a.txt").exists());
+        assert!(dir.path().join("b.txt").exists());
+        assert!(matches!(
+            execute_test_command(&RemoveFiles, "rm a.txt", &mut state, ""),
+            Err(CommandError::IO(_))
+        ));
+        execute_test_command(&RemoveFiles, "rm -f a.txt", &mut state, "").unwrap();
+    }
+
+    #[test]
+    fn test_rm_2() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        std::fs::write(dir.path().join("a/b/c.txt"), "").unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert!(matches!(
+            execute_test_command(&RemoveFiles, "rm a", &mut state, ""),
+            Err(CommandError::Usage(_))
+        ));
+        execute_test_command(&RemoveFiles, "rm -rf a", &mut state, "").unwrap();
+        assert!(!dir.path().join("a").exists());
+    }
+
+    #[test]
+    fn test_rm_3() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("a/b")).unwrap();
+        let mut state = InterpreterState::new(dir.path().join("a/b"));
+        for line in [
+            "rm -rf .",
+            "rm -rf ..",
+            "rm -rf ./",
+            "rm -rf ../b/..",
+            "rm -rf /",
+        ] {
+            assert!(matches!(
+                execute_test_command(&RemoveFiles, line, &mut state, ""),
+                Err(CommandError::Usage(_))
+            ));
+        }
+        assert!(dir.path().join("a/b").is_dir());
+    }
+}
@@ -0,0 +1,117 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `set [-e|+e] [-o errexit|+o errexit] [-- ARG...]` changes options of the shell.
+///
+/// `-e` (`errexit`) stops the execution of a script after the first command that fails, `+e`
+/// turns it off again. `set -o` prints the state of the options and `set -- ARG...` replaces the
+/// positional parameters.
+pub struct SetOption;
+
+impl Command for SetOption {
+    fn name(&self) -> Option<&str> {
+        Some("set")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("change shell options and positional parameters")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "set" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(set(&args[1..], state, output))
+    }
+}
+
+fn set(
+    args: &[String],
+    state: &mut InterpreterState,
+    output: &mut dyn Write,
+) -> Result<(), CommandError> {
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let enable = match arg.chars().next() {
+            Some('-') => true,
+            Some('+') => false,
+            _ => return Err(CommandError::Usage(format!("set: {arg}: invalid option"))),
+        };
+        match &arg[1..] {
+            "-" if enable => {
+                state.positional.truncate(1);
+                state.positional.extend(args.cloned());
+                break;
+            }
+            "e" => state.errexit = enable,
+            "o" => match args.next().map(|name| name.as_str()) {
+                Some("errexit") => state.errexit = enable,
+                Some(name) => {
+                    return Err(CommandError::Usage(format!(
+                        "set: {name}: invalid option name"
+                    )))
+                }
+                None => {
+                    let value = if state.errexit { "on" } else { "off" };
+                    writeln!(output, "errexit\t{value}").map_err(CommandError::IO)?;
+                }
+            },
+            _ => return Err(CommandError::Usage(format!("set: {arg}: invalid option"))),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::CommandError;
+    use crate::commands::common::execute_test_command;
+    use crate::commands::set::SetOption;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn toggle_errexit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute_test_command(&SetOption, "set -o", &mut state, "").unwrap(),
+            "errexit\toff\n"
+        );
+        execute_test_command(&SetOption, "set -e", &mut state, "").unwrap();
+        assert!(state.errexit);
+        execute_test_command(&SetOption, "set +o errexit", &mut state, "").unwrap();
+        assert!(!state.errexit);
+        execute_test_command(&SetOption, "set -o errexit", &mut state, "").unwrap();
+        assert!(state.errexit);
+    }
+
+    #[test]
+    fn set_positional_parameters() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        execute_test_command(&SetOption, "set -e -- a -b", &mut state, "").unwrap();
+        assert!(state.errexit);
+        assert_eq!(state.positional, vec!["benzina", "a", "-b"]);
+    }
+
+    #[test]
+    fn invalid_option() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert!(matches!(
+            execute_test_command(&SetOption, "set -x", &mut state, ""),
+            Err(CommandError::Usage(message)) if message == "set: -x: invalid option"
+        ));
+        assert!(matches!(
+            execute_test_command(&SetOption, "set -o nounset", &mut state, ""),
+            Err(CommandError::Usage(_))
+        ));
+    }
+}
@@ -0,0 +1,75 @@
+use crate::command::{Command, CommandResponse};
+use crate::commands::head::print_lines;
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `tail [-n COUNT] [FILE]...` prints the last lines (10 by default) of files or of its input.
+pub struct PrintLastLines;
+
+impl Command for PrintLastLines {
+    fn name(&self) -> Option<&str> {
+        Some("tail")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("print the last lines of files")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "tail" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(print_lines(
+            "tail",
+            &args[1..],
+            state,
+            input,
+            output,
+            |length, count| length.saturating_sub(count)..length,
+            false,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::common::execute_test_command;
+    use crate::commands::tail::PrintLastLines;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn test_tail_1() {
+        let dir = tempfile::tempdir().unwrap();
+        let content: String = (1..=20).map(|line| format!("{line}\n")).collect();
+        std::fs::write(dir.path().join("a.txt"), &content).unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute_test_command(&PrintLastLines, "tail a.txt", &mut state, "").unwrap(),
+            content[content.len() - 30..]
+        );
+        assert_eq!(
+            execute_test_command(&PrintLastLines, "tail -n 2", &mut state, "a\nb\nc").unwrap(),
+            "b\nc"
+        );
+    }
+
+    #[test]
+    fn test_tail_2() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute_test_command(&PrintLastLines, "tail -n 5", &mut state, "a\n").unwrap(),
+            "a\n"
+        );
+        assert_eq!(
+            execute_test_command(&PrintLastLines, "tail -n 0", &mut state, "a\n").unwrap(),
+            ""
+        );
+    }
+}
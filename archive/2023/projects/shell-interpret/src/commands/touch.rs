@@ -0,0 +1,87 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::common::{path_error, Options};
+use crate::state::InterpreterState;
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::time::SystemTime;
+
+/// `touch FILE...` creates empty files, or updates the modification time of existing files.
+pub struct TouchFiles;
+
+impl Command for TouchFiles {
+    fn name(&self) -> Option<&str> {
+        Some("touch")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("create files or update their modification time")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        _output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "touch" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(touch(&args[1..], state))
+    }
+}
+
+fn touch(args: &[String], state: &InterpreterState) -> Result<(), CommandError> {
+    let options = Options::parse("touch", args, "", "")?;
+    if options.operands.is_empty() {
+        return Err(CommandError::Usage(
+            "touch: missing file operand".to_string(),
+        ));
+    }
+    for path in options.operands {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(state.resolve_path(path))
+            .and_then(|file| file.set_modified(SystemTime::now()))
+            .map_err(|error| path_error("touch", path, error))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::common::execute_test_command;
+    use crate::commands::touch::TouchFiles;
+    use crate::state::InterpreterState;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_touch_1() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        execute_test_command(&TouchFiles, "touch a.txt b.txt", &mut state, "").unwrap();
+        assert!(dir.path().join("a.txt").is_file());
+        assert!(dir.path().join("b.txt").is_file());
+    }
+
+    #[test]
+    fn test_touch_2() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.txt");
+        std::fs::write(&path, "content").unwrap();
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        execute_test_command(&TouchFiles, "touch a.txt", &mut state, "").unwrap();
+        let modified = path.metadata().unwrap().modified().unwrap();
+        assert!(modified > old + Duration::from_secs(60));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "content");
+    }
+}
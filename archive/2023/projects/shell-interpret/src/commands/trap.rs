@@ -0,0 +1,164 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::jobs::{parse_signal, signal_name, SIGNALS};
+use crate::signals::{update_handler, EXIT, TRAPPABLE_SIGNALS};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `trap [ACTION SIGNAL...]` sets commands that are executed when the shell receives a signal.
+///
+/// `ACTION` is executed like a line of the shell, an empty `ACTION` ignores the signal and `-`
+/// restores the default behavior. The `EXIT` (or `0`) pseudo-signal is triggered when the shell
+/// exits. Without arguments (or with `-p`), the traps are printed, `-l` lists the signals.
+pub struct SetTrap;
+
+impl Command for SetTrap {
+    fn name(&self) -> Option<&str> {
+        Some("trap")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("execute commands when a signal arrives")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "trap" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(trap(&args[1..], state, output))
+    }
+}
+
+fn trap(
+    args: &[String],
+    state: &mut InterpreterState,
+    output: &mut dyn Write,
+) -> Result<(), CommandError> {
+    let args: Vec<&str> = args.iter().map(|arg| arg.as_str()).collect();
+    match args[..] {
+        [] | ["-p"] => {
+            let signals: Vec<libc::c_int> = state.traps.keys().copied().collect();
+            print_traps(&signals, state, output)
+        }
+        ["-p", ref signals @ ..] => {
+            let signals = signals
+                .iter()
+                .map(|signal| parse_trap_signal(signal))
+                .collect::<Result<Vec<_>, _>>()?;
+            print_traps(&signals, state, output)
+        }
+        ["-l"] => {
+            let signals = SIGNALS
+                .iter()
+                .filter(|(_, signal)| TRAPPABLE_SIGNALS.contains(signal));
+            for (name, signal) in signals {
+                writeln!(output, "{signal}) SIG{name}").map_err(CommandError::IO)?;
+            }
+            Ok(())
+        }
+        // `trap SIGNAL` resets a single signal, like `trap - SIGNAL`
+        [signal] => set_trap(state, parse_trap_signal(signal)?, None),
+        [action, ref signals @ ..] => {
+            let action = if action == "-" { None } else { Some(action) };
+            for signal in signals {
+                set_trap(state, parse_trap_signal(signal)?, action)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+fn set_trap(
+    state: &mut InterpreterState,
+    signal: libc::c_int,
+    action: Option<&str>,
+) -> Result<(), CommandError> {
+    match action {
+        Some(action) => state.traps.insert(signal, action.to_string()),
+        None => state.traps.remove(&signal),
+    };
+    if signal != EXIT {
+        update_handler(signal, action.is_some()).map_err(CommandError::IO)?;
+    }
+    Ok(())
+}
+
+/// Prints the traps of `signals` in a form that can be used to set them again.
+fn print_traps(
+    signals: &[libc::c_int],
+    state: &InterpreterState,
+    output: &mut dyn Write,
+) -> Result<(), CommandError> {
+    for signal in signals {
+        let Some(action) = state.traps.get(signal) else {
+            continue;
+        };
+        let action = action.replace('\'', r"'\''");
+        let name = signal_name(*signal).unwrap_or("EXIT");
+        writeln!(output, "trap -- '{action}' {name}").map_err(CommandError::IO)?;
+    }
+    Ok(())
+}
+
+/// Parses a signal that can be trapped: `EXIT`, a number or a name with or without `SIG`.
+fn parse_trap_signal(signal: &str) -> Result<libc::c_int, CommandError> {
+    if signal == "EXIT" {
+        return Ok(EXIT);
+    }
+    match parse_signal(signal) {
+        Some(number) if number == EXIT || TRAPPABLE_SIGNALS.contains(&number) => Ok(number),
+        _ => Err(CommandError::Usage(format!(
+            "trap: {signal}: invalid signal specification"
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::CommandError;
+    use crate::commands::common::execute_test_command;
+    use crate::commands::trap::SetTrap;
+    use crate::signals::EXIT;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn set_and_print_traps() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        execute_test_command(&SetTrap, "trap cleanup EXIT SIGTERM", &mut state, "").unwrap();
+        execute_test_command(&SetTrap, "trap it's 2", &mut state, "").unwrap();
+        assert_eq!(state.traps.get(&EXIT).unwrap(), "cleanup");
+        assert_eq!(
+            execute_test_command(&SetTrap, "trap", &mut state, "").unwrap(),
+            "trap -- 'cleanup' EXIT\ntrap -- 'it'\\''s' INT\ntrap -- 'cleanup' TERM\n"
+        );
+        assert_eq!(
+            execute_test_command(&SetTrap, "trap -p INT", &mut state, "").unwrap(),
+            "trap -- 'it'\\''s' INT\n"
+        );
+
+        execute_test_command(&SetTrap, "trap - INT TERM", &mut state, "").unwrap();
+        execute_test_command(&SetTrap, "trap EXIT", &mut state, "").unwrap();
+        assert!(state.traps.is_empty());
+    }
+
+    #[test]
+    fn invalid_signal() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert!(matches!(
+            execute_test_command(&SetTrap, "trap cleanup KILL", &mut state, ""),
+            Err(CommandError::Usage(message)) if message == "trap: KILL: invalid signal specification"
+        ));
+        assert!(matches!(
+            execute_test_command(&SetTrap, "trap cleanup NOTHING", &mut state, ""),
+            Err(CommandError::Usage(_))
+        ));
+        assert!(state.traps.is_empty());
+    }
+}
@@ -0,0 +1,69 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `unalias NAME...` removes aliases, `unalias -a` removes all of them.
+pub struct RemoveAlias;
+
+impl Command for RemoveAlias {
+    fn name(&self) -> Option<&str> {
+        Some("unalias")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("remove aliases")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        _output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "unalias" {
+            return CommandResponse::Unhandled;
+        }
+
+        if args.len() == 1 {
+            return CommandResponse::Handled(Err(CommandError::Usage(
+                "unalias: usage: unalias [-a] NAME...".to_string(),
+            )));
+        }
+        for name in &args[1..] {
+            if name == "-a" {
+                state.aliases.clear();
+            } else if state.aliases.remove(name).is_none() {
+                return CommandResponse::Handled(Err(CommandError::Usage(format!(
+                    "unalias: {name}: not found"
+                ))));
+            }
+        }
+        CommandResponse::Handled(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::CommandError;
+    use crate::commands::common::execute_test_command;
+    use crate::commands::unalias::RemoveAlias;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn remove_aliases() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        for name in ["a", "b", "c"] {
+            state.aliases.insert(name.to_string(), "ls".to_string());
+        }
+        execute_test_command(&RemoveAlias, "unalias a", &mut state, "").unwrap();
+        assert_eq!(state.aliases.keys().collect::<Vec<_>>(), ["b", "c"]);
+        assert!(matches!(
+            execute_test_command(&RemoveAlias, "unalias a", &mut state, ""),
+            Err(CommandError::Usage(message)) if message == "unalias: a: not found"
+        ));
+        execute_test_command(&RemoveAlias, "unalias -a", &mut state, "").unwrap();
+        assert!(state.aliases.is_empty());
+    }
+}
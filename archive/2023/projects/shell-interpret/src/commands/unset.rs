@@ -0,0 +1,58 @@
+use crate::command::{Command, CommandResponse};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `unset NAME...` removes variables.
+pub struct UnsetVariable;
+
+impl Command for UnsetVariable {
+    fn name(&self) -> Option<&str> {
+        Some("unset")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("remove variables")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        _output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "unset" {
+            return CommandResponse::Unhandled;
+        }
+
+        for name in &args[1..] {
+            state.env.remove(name);
+            state.exported.remove(name);
+        }
+        CommandResponse::Handled(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::{Command, CommandResponse};
+    use crate::commands::unset::UnsetVariable;
+    use crate::state::InterpreterState;
+    use std::path::PathBuf;
+
+    #[test]
+    fn unset_variables() {
+        let mut state = InterpreterState::new(PathBuf::from("/foo"));
+        state.env.insert("A".to_string(), "1".to_string());
+        state.env.insert("B".to_string(), "2".to_string());
+        state.env.insert("C".to_string(), "3".to_string());
+        state.exported.insert("A".to_string());
+        let args = ["unset", "A", "C", "MISSING"].map(|arg| arg.to_string());
+        let res = UnsetVariable.execute(&args, &mut state, &mut std::io::empty(), &mut vec![]);
+        assert!(matches!(res, CommandResponse::Handled(Ok(()))));
+        assert!(!state.env.contains_key("A"));
+        assert_eq!(state.env["B"], "2");
+        assert!(!state.env.contains_key("C"));
+        assert!(!state.exported.contains("A"));
+    }
+}
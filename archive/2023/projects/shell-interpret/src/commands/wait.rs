@@ -0,0 +1,48 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::jobs::JobStatus;
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `wait [%n...]` waits until the given jobs (or all jobs) finish.
+pub struct WaitForJobs;
+
+impl Command for WaitForJobs {
+    fn name(&self) -> Option<&str> {
+        Some("wait")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("wait until background jobs finish")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        _output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "wait" {
+            return CommandResponse::Unhandled;
+        }
+
+        let ids: Vec<usize> = if args.len() == 1 {
+            state.jobs.iter().map(|job| job.id).collect()
+        } else {
+            let mut ids = vec![];
+            for spec in &args[1..] {
+                match state.jobs.find_for_command("wait", Some(spec)) {
+                    Ok(id) => ids.push(id),
+                    Err(error) => return CommandResponse::Handled(Err(CommandError::IO(error))),
+                }
+            }
+            ids
+        };
+        for id in ids {
+            if let Some(code) = state.jobs.wait(id).and_then(JobStatus::exit_code) {
+                state.last_status = code;
+            }
+        }
+        CommandResponse::Handled(Ok(()))
+    }
+}
@@ -0,0 +1,116 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::common::{read_input, Options};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `wc [-l] [-w] [-c] [FILE]...` counts lines, words and bytes of files or of its input.
+/// Without options, all three counts are printed. If there are multiple files, a total is
+/// printed at the end.
+pub struct WordCount;
+
+impl Command for WordCount {
+    fn name(&self) -> Option<&str> {
+        Some("wc")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("count lines, words and bytes")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "wc" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(wc(&args[1..], state, input, output))
+    }
+}
+
+fn wc(
+    args: &[String],
+    state: &InterpreterState,
+    input: &mut dyn Read,
+    output: &mut dyn Write,
+) -> Result<(), CommandError> {
+    let options = Options::parse("wc", args, "lwc", "")?;
+    let mut selected = [options.has('l'), options.has('w'), options.has('c')];
+    if selected == [false; 3] {
+        selected = [true; 3];
+    }
+
+    let print = |output: &mut dyn Write, counts: [usize; 3], name: Option<&str>| {
+        let mut columns: Vec<String> = counts
+            .iter()
+            .zip(selected)
+            .filter(|(_, selected)| *selected)
+            .map(|(count, _)| count.to_string())
+            .collect();
+        columns.extend(name.map(|name| name.to_string()));
+        writeln!(output, "{}", columns.join(" ")).map_err(CommandError::IO)
+    };
+
+    if options.operands.is_empty() {
+        let data = read_input("wc", None, state, input)?;
+        return print(output, count(&data), None);
+    }
+    let mut total = [0; 3];
+    for path in &options.operands {
+        let counts = count(&read_input("wc", Some(path), state, input)?);
+        for (total, count) in total.iter_mut().zip(counts) {
+            *total += count;
+        }
+        print(output, counts, Some(path))?;
+    }
+    if options.operands.len() > 1 {
+        print(output, total, Some("total"))?;
+    }
+    Ok(())
+}
+
+/// Returns the number of lines, words and bytes.
+fn count(data: &[u8]) -> [usize; 3] {
+    let lines = data.iter().filter(|byte| **byte == b'\n').count();
+    let words = data
+        .split(|byte| byte.is_ascii_whitespace())
+        .filter(|word| !word.is_empty())
+        .count();
+    [lines, words, data.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::commands::common::execute_test_command;
+    use crate::commands::wc::WordCount;
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn test_wc_1() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute_test_command(&WordCount, "wc", &mut state, "hello  world\nfoo\n").unwrap(),
+            "2 3 17\n"
+        );
+        assert_eq!(
+            execute_test_command(&WordCount, "wc -lc", &mut state, "a b").unwrap(),
+            "0 3\n"
+        );
+    }
+
+    #[test]
+    fn test_wc_2() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "a\nb\n").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "c\n").unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert_eq!(
+            execute_test_command(&WordCount, "wc -l a.txt b.txt", &mut state, "").unwrap(),
+            "2 a.txt\n1 b.txt\n3 total\n"
+        );
+    }
+}
@@ -0,0 +1,136 @@
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::change_dir::change_workdir;
+use crate::commands::common::Options;
+use crate::frecency::{now, DirectoryDatabase};
+use crate::state::InterpreterState;
+use std::io::{Read, Write};
+
+/// `z FRAGMENT...` changes to the most "frecent" (frequently and recently visited) directory
+/// whose path contains all fragments in the given order, ignoring case.
+///
+/// `z -l [FRAGMENT]...` (or `z` without arguments) lists the matching directories with their
+/// scores, the best match is printed last.
+/// Directories are recorded whenever the working directory changes.
+pub struct JumpToDirectory;
+
+impl Command for JumpToDirectory {
+    fn name(&self) -> Option<&str> {
+        Some("z")
+    }
+
+    fn description(&self) -> Option<&str> {
+        Some("jump to a frequently visited directory")
+    }
+
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        _input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if args[0] != "z" {
+            return CommandResponse::Unhandled;
+        }
+        CommandResponse::Handled(z(&args[1..], state, output))
+    }
+}
+
+fn z(
+    args: &[String],
+    state: &mut InterpreterState,
+    output: &mut dyn Write,
+) -> Result<(), CommandError> {
+    let options = Options::parse("z", args, "l", "")?;
+    let Some(path) = &state.directory_database else {
+        return Err(CommandError::Usage(
+            "z: directory tracking is disabled".to_string(),
+        ));
+    };
+    let database = DirectoryDatabase::load(path);
+    let now = now();
+    // Directories that were removed since the last visit are ignored
+    let matches: Vec<_> = database
+        .find(&options.operands, now)
+        .into_iter()
+        .filter(|entry| entry.path.is_dir())
+        .collect();
+
+    if options.has('l') || options.operands.is_empty() {
+        for entry in matches.iter().rev() {
+            let score = format!("{:.1}", entry.frecency(now));
+            writeln!(output, "{score:<10} {}", entry.path.display()).map_err(CommandError::IO)?;
+        }
+        return Ok(());
+    }
+    let Some(entry) = matches.first() else {
+        return Err(CommandError::IO(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("z: no directory matches `{}`", options.operands.join(" ")),
+        )));
+    };
+    let dir = entry.path.clone();
+    change_workdir("z", &dir.to_string_lossy(), &dir, state)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::CommandError;
+    use crate::commands::common::execute_test_command;
+    use crate::commands::z::JumpToDirectory;
+    use crate::frecency::{now, DirectoryDatabase};
+    use crate::state::InterpreterState;
+
+    #[test]
+    fn test_z_1() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        for path in ["src/benzina", "src/benzina-old", "docs", "removed"] {
+            std::fs::create_dir_all(root.join(path)).unwrap();
+        }
+        let mut database = DirectoryDatabase::default();
+        let now = now();
+        database.visit(&root.join("src/benzina-old"), now - 30 * 24 * 60 * 60);
+        database.visit(&root.join("src/benzina-old"), now - 30 * 24 * 60 * 60);
+        database.visit(&root.join("src/benzina"), now);
+        database.visit(&root.join("removed"), now);
+        database.save(&root.join("z")).unwrap();
+        std::fs::remove_dir(root.join("removed")).unwrap();
+
+        let mut state = InterpreterState::new(root.join("docs"));
+        state.directory_database = Some(root.join("z"));
+        assert_eq!(
+            execute_test_command(&JumpToDirectory, "z -l BENZ", &mut state, "").unwrap(),
+            format!(
+                "0.5        {0}/src/benzina-old\n4.0        {0}/src/benzina\n",
+                root.display()
+            )
+        );
+        execute_test_command(&JumpToDirectory, "z benz", &mut state, "").unwrap();
+        assert_eq!(state.workdir, root.join("src/benzina"));
+        execute_test_command(&JumpToDirectory, "z src old", &mut state, "").unwrap();
+        assert_eq!(state.workdir, root.join("src/benzina-old"));
+        // Jumps are recorded as visits too
+        let database = DirectoryDatabase::load(&root.join("z"));
+        assert_eq!(database.entries[0].rank, 3.0);
+        assert!(matches!(
+            execute_test_command(&JumpToDirectory, "z removed", &mut state, ""),
+            Err(CommandError::IO(_))
+        ));
+    }
+
+    #[test]
+    fn test_z_2() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        assert!(matches!(
+            execute_test_command(&JumpToDirectory, "z foo", &mut state, ""),
+            Err(CommandError::Usage(_))
+        ));
+        state.directory_database = Some(dir.path().join("z"));
+        assert_eq!(
+            execute_test_command(&JumpToDirectory, "z", &mut state, "").unwrap(),
+            ""
+        );
+    }
+}
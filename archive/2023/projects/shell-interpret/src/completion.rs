@@ -0,0 +1,274 @@
+use crate::commands::external::is_executable;
+use crate::help::SHELL_BUILTINS;
+use crate::Shell;
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+/// Keywords after which a command name is expected.
+const COMMAND_KEYWORDS: &[&str] = &["if", "then", "elif", "else", "while", "do"];
+
+/// Possible completions of the word that ends at the cursor.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Completion {
+    /// Byte offset of the start of the completed word
+    pub start: usize,
+    /// Possible replacements of the word, already escaped, sorted alphabetically
+    pub candidates: Vec<String>,
+}
+
+impl Shell {
+    /// Completes the last word of `line` (the text before the cursor).
+    ///
+    /// The first word of a command is completed from builtins, plugin commands, functions,
+    /// aliases and executables found in `PATH`. Other words (and words containing a slash) are completed as paths
+    /// relative to the working directory.
+    pub fn complete(&self, line: &str) -> Completion {
+        let start = word_start(line);
+        let word = unescape(&line[start..]);
+        let before = line[..start].trim_end();
+        let is_command = before.is_empty()
+            || before.ends_with(['|', '&', ';', '('])
+            || COMMAND_KEYWORDS.contains(&before.rsplit(char::is_whitespace).next().unwrap());
+
+        let candidates = if is_command && !word.contains('/') {
+            self.complete_command(&word)
+        } else {
+            self.complete_path(&word)
+        };
+        Completion {
+            start,
+            candidates: candidates
+                .iter()
+                .map(|candidate| escape(candidate))
+                .collect(),
+        }
+    }
+
+    fn complete_command(&self, prefix: &str) -> Vec<String> {
+        let mut names: BTreeSet<String> = self
+            .commands
+            .iter()
+            .filter_map(|command| command.name())
+            .chain(SHELL_BUILTINS.iter().map(|(name, _)| *name))
+            .chain(
+                self.plugins
+                    .iter()
+                    .flat_map(|plugin| plugin.commands())
+                    .map(|command| command.name.as_str()),
+            )
+            .chain(self.functions.keys().map(|name| name.as_str()))
+            .chain(self.state.aliases.keys().map(|name| name.as_str()))
+            .filter(|name| name.starts_with(prefix))
+            .map(|name| format!("{name} "))
+            .collect();
+        if let Some(paths) = self.state.env.get("PATH") {
+            for dir in std::env::split_paths(paths) {
+                let Ok(entries) = std::fs::read_dir(self.state.resolve_path(dir)) else {
+                    continue;
+                };
+                names.extend(
+                    entries
+                        .filter_map(|entry| entry.ok())
+                        .filter(|entry| is_executable(&entry.path()))
+                        .filter_map(|entry| entry.file_name().into_string().ok())
+                        .filter(|name| name.starts_with(prefix))
+                        .map(|name| format!("{name} ")),
+                );
+            }
+        }
+        names.into_iter().collect()
+    }
+
+    fn complete_path(&self, word: &str) -> Vec<String> {
+        let (dir, prefix) = match word.rfind('/') {
+            Some(index) => word.split_at(index + 1),
+            None => ("", word),
+        };
+        let path = match (dir.strip_prefix("~/"), self.state.env.get("HOME")) {
+            (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+            _ => self.state.resolve_path(dir),
+        };
+        let Ok(entries) = std::fs::read_dir(path) else {
+            return vec![];
+        };
+        let mut candidates: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                // Hidden files are only completed if the prefix starts with a dot
+                if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.'))
+                {
+                    return None;
+                }
+                let suffix = if entry.path().is_dir() { "/" } else { " " };
+                Some(format!("{dir}{name}{suffix}"))
+            })
+            .collect();
+        candidates.sort();
+        candidates
+    }
+}
+
+/// Finds the start of the last word of `line`, respecting backslash escapes.
+fn word_start(line: &str) -> usize {
+    let mut start = 0;
+    let mut chars = line.char_indices();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            c if c.is_whitespace() || matches!(c, '|' | '&' | ';' | '<' | '>' | '(' | ')') => {
+                start = index + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    start
+}
+
+fn unescape(word: &str) -> String {
+    let mut result = String::new();
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => result.extend(chars.next()),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Escapes characters that have a special meaning for the shell.
+/// The trailing space that separates a completed word from the next one is kept.
+fn escape(candidate: &str) -> String {
+    let (text, suffix) = match candidate.strip_suffix(' ') {
+        Some(text) => (text, " "),
+        None => (candidate, ""),
+    };
+    let mut result = String::new();
+    for (index, c) in text.chars().enumerate() {
+        let home = index == 0 && c == '~' && text[1..].starts_with('/');
+        if !home && (c.is_whitespace() || "\\'\"$*?[]|&;<>()#~`".contains(c)) {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result.push_str(suffix);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ChangeWorkdir, PrintWorkdir, Shell};
+    use std::path::Path;
+
+    fn shell(dir: &Path) -> Shell {
+        let mut shell = Shell::with_workdir(dir.to_path_buf());
+        shell.add_command(PrintWorkdir);
+        shell.add_command(ChangeWorkdir);
+        shell
+    }
+
+    fn complete(shell: &Shell, line: &str) -> (usize, Vec<String>) {
+        let completion = shell.complete(line);
+        (completion.start, completion.candidates)
+    }
+
+    #[test]
+    fn complete_builtins_and_functions() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(dir.path());
+        shell.state.env.remove("PATH");
+        shell.execute_line("pwf() { pwd; }", &mut vec![]);
+        shell
+            .state
+            .aliases
+            .insert("pwa".to_string(), "pwd".to_string());
+        assert_eq!(
+            complete(&shell, "pw"),
+            (0, vec!["pwa ".into(), "pwd ".into(), "pwf ".into()])
+        );
+        assert_eq!(
+            complete(&shell, "ls | c"),
+            (5, vec!["cd ".into(), "continue ".into()])
+        );
+        assert_eq!(complete(&shell, "if cd"), (3, vec!["cd ".into()]));
+    }
+
+    #[test]
+    fn complete_executables_from_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let bin = dir.path().join("bin");
+        std::fs::create_dir(&bin).unwrap();
+        for (name, mode) in [("benzina-a", 0o755), ("benzina-b", 0o644)] {
+            let path = bin.join(name);
+            std::fs::write(&path, "").unwrap();
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+        }
+        let mut shell = shell(dir.path());
+        shell
+            .state
+            .env
+            .insert("PATH".to_string(), "bin".to_string());
+        assert_eq!(complete(&shell, "benz"), (0, vec!["benzina-a ".into()]));
+    }
+
+    #[test]
+    fn complete_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("src/commands")).unwrap();
+        std::fs::write(dir.path().join("src/lib.rs"), "").unwrap();
+        std::fs::write(dir.path().join("src/lexer.rs"), "").unwrap();
+        std::fs::write(dir.path().join("src/.hidden"), "").unwrap();
+        std::fs::write(dir.path().join("my file.txt"), "").unwrap();
+        let shell = shell(dir.path());
+
+        assert_eq!(complete(&shell, "cat s"), (4, vec!["src/".into()]));
+        assert_eq!(
+            complete(&shell, "cat src/l"),
+            (4, vec!["src/lexer.rs ".into(), "src/lib.rs ".into()])
+        );
+        assert_eq!(
+            complete(&shell, "cat src/"),
+            (
+                4,
+                vec![
+                    "src/commands/".into(),
+                    "src/lexer.rs ".into(),
+                    "src/lib.rs ".into()
+                ]
+            )
+        );
+        assert_eq!(
+            complete(&shell, "cat src/.h"),
+            (4, vec!["src/.hidden ".into()])
+        );
+        assert_eq!(
+            complete(&shell, "cat <my"),
+            (5, vec!["my\\ file.txt ".into()])
+        );
+        assert_eq!(
+            complete(&shell, "cat my\\ f"),
+            (4, vec!["my\\ file.txt ".into()])
+        );
+        assert_eq!(complete(&shell, "./s"), (0, vec!["./src/".into()]));
+        assert_eq!(complete(&shell, "cat x"), (4, vec![]));
+    }
+
+    #[test]
+    fn complete_home_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("projects")).unwrap();
+        let mut shell = shell(Path::new("/"));
+        shell
+            .state
+            .env
+            .insert("HOME".to_string(), dir.path().display().to_string());
+        assert_eq!(
+            complete(&shell, "cd ~/pro"),
+            (3, vec!["~/projects/".into()])
+        );
+    }
+}
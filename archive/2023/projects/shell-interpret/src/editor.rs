@@ -0,0 +1,536 @@
+use crate::history::History;
+use crate::signals::is_termination_pending;
+use crate::Shell;
+use std::io::Write;
+
+/// A key pressed by the user.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Key {
+    Char(char),
+    /// Ctrl + letter
+    Control(char),
+    Enter,
+    Tab,
+    Backspace,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    Home,
+    End,
+    Escape,
+    /// The terminal was closed
+    Eof,
+    Unknown,
+}
+
+/// Result of a reverse history search.
+enum Search {
+    /// The line should be executed
+    Accept(String),
+    /// The line should be edited further
+    Edit(String),
+    Cancel,
+}
+
+/// Line editor with cursor movement, history (Up/Down), reverse history search (Ctrl-R) and
+/// tab completion.
+///
+/// If stdin is not a terminal, lines are read as they are, without any editing.
+pub struct LineEditor {
+    history: History,
+}
+
+impl LineEditor {
+    pub fn new(history: History) -> Self {
+        Self { history }
+    }
+
+    /// Prints the prompt and reads a line from stdin.
+    /// Returns `None` at the end of input, or when a signal that ends the shell arrives. Ctrl-C
+    /// cancels the line and returns an error of kind `ErrorKind::Interrupted`.
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        shell: &Shell,
+        output: &mut dyn Write,
+    ) -> std::io::Result<Option<String>> {
+        if unsafe { libc::isatty(libc::STDIN_FILENO) } == 0 {
+            write!(output, "{prompt}")?;
+            output.flush()?;
+            // Bytes are read one by one, so that programs started by the following lines can read
+            // the rest of the input
+            let mut line = vec![];
+            loop {
+                match read_byte()? {
+                    Some(b'\n') => break,
+                    Some(byte) => line.push(byte),
+                    None if line.is_empty() => return Ok(None),
+                    None => break,
+                }
+            }
+            let line = String::from_utf8_lossy(&line);
+            return Ok(Some(line.trim_end_matches('\r').to_string()));
+        }
+
+        let raw_mode = RawMode::enable()?;
+        let line = self.edit(prompt, shell, output);
+        drop(raw_mode);
+        if let Ok(Some(line)) = &line {
+            // Failing to save the history should not prevent the line from being executed
+            let _ = self.history.add(line);
+        }
+        line
+    }
+
+    fn edit(
+        &mut self,
+        prompt: &str,
+        shell: &Shell,
+        output: &mut dyn Write,
+    ) -> std::io::Result<Option<String>> {
+        let mut buffer = LineBuffer::default();
+        // Index of the history entry that is being shown, `len()` for the edited line
+        let mut history_index = self.history.len();
+        let mut edited_line = String::new();
+        let mut last_key = Key::Unknown;
+
+        loop {
+            render(output, prompt, &buffer)?;
+            let key = read_key()?;
+            match key {
+                Key::Enter => {
+                    write!(output, "\r\n")?;
+                    return Ok(Some(buffer.text()));
+                }
+                Key::Eof => {
+                    write!(output, "\r\n")?;
+                    return Ok(None);
+                }
+                Key::Control('d') if buffer.is_empty() => {
+                    write!(output, "\r\n")?;
+                    return Ok(None);
+                }
+                Key::Control('c') => {
+                    write!(output, "^C\r\n")?;
+                    return Err(std::io::ErrorKind::Interrupted.into());
+                }
+                Key::Char(c) => buffer.insert(c),
+                Key::Backspace | Key::Control('h') => buffer.backspace(),
+                Key::Delete | Key::Control('d') => buffer.delete(),
+                Key::Left | Key::Control('b') => buffer.move_left(),
+                Key::Right | Key::Control('f') => buffer.move_right(),
+                Key::Home | Key::Control('a') => buffer.cursor = 0,
+                Key::End | Key::Control('e') => buffer.cursor = buffer.chars.len(),
+                Key::Control('u') => buffer.delete_to_start(),
+                Key::Control('k') => buffer.delete_to_end(),
+                Key::Control('w') => buffer.delete_word(),
+                Key::Control('l') => write!(output, "\x1b[H\x1b[2J")?,
+                Key::Up | Key::Control('p') if history_index > 0 => {
+                    if history_index == self.history.len() {
+                        edited_line = buffer.text();
+                    }
+                    history_index -= 1;
+                    buffer.set(self.history.get(history_index).unwrap_or_default());
+                }
+                Key::Down | Key::Control('n') if history_index < self.history.len() => {
+                    history_index += 1;
+                    match self.history.get(history_index) {
+                        Some(entry) => buffer.set(entry),
+                        None => buffer.set(&edited_line),
+                    }
+                }
+                Key::Tab => {
+                    // Pressing tab twice lists all possible completions
+                    let list = last_key == Key::Tab;
+                    complete(&mut buffer, shell, list, output)?;
+                }
+                Key::Control('r') => match self.reverse_search(&buffer.text(), output)? {
+                    Search::Accept(line) => {
+                        buffer.set(&line);
+                        render(output, prompt, &buffer)?;
+                        write!(output, "\r\n")?;
+                        return Ok(Some(line));
+                    }
+                    Search::Edit(line) => buffer.set(&line),
+                    Search::Cancel => {}
+                },
+                _ => {}
+            }
+            last_key = key;
+        }
+    }
+
+    /// Searches the history for lines containing the typed text, pressing Ctrl-R again finds
+    /// older matches. Enter executes the found line, Ctrl-G cancels the search and any other key
+    /// allows editing the found line.
+    fn reverse_search(&self, line: &str, output: &mut dyn Write) -> std::io::Result<Search> {
+        let mut query = String::new();
+        let mut found: Option<usize> = None;
+        loop {
+            let matched = found.and_then(|index| self.history.get(index));
+            write!(
+                output,
+                "\r(reverse-i-search)`{query}': {}\x1b[K",
+                matched.unwrap_or_default()
+            )?;
+            output.flush()?;
+
+            match read_key()? {
+                Key::Char(c) => {
+                    query.push(c);
+                    // The current match can still match the longer query
+                    let before = found.map(|index| index + 1).unwrap_or(self.history.len());
+                    found = self.history.search(&query, before);
+                }
+                Key::Backspace => {
+                    query.pop();
+                    found = self.history.search(&query, self.history.len());
+                }
+                Key::Control('r') => {
+                    let before = found.unwrap_or(self.history.len());
+                    if let Some(index) = self.history.search(&query, before) {
+                        found = Some(index);
+                    }
+                }
+                Key::Control('g' | 'c') | Key::Eof => return Ok(Search::Cancel),
+                Key::Enter => {
+                    return Ok(Search::Accept(matched.unwrap_or(line).to_string()));
+                }
+                _ => {
+                    return Ok(match matched {
+                        Some(matched) => Search::Edit(matched.to_string()),
+                        None => Search::Cancel,
+                    })
+                }
+            }
+        }
+    }
+}
+
+/// Text of the edited line and the position of the cursor.
+#[derive(Debug, Default)]
+struct LineBuffer {
+    chars: Vec<char>,
+    /// Index of the character before which the cursor is placed
+    cursor: usize,
+}
+
+impl LineBuffer {
+    fn text(&self) -> String {
+        self.chars.iter().collect()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.chars.is_empty()
+    }
+
+    /// Replaces the text and moves the cursor to its end.
+    fn set(&mut self, text: &str) {
+        self.chars = text.chars().collect();
+        self.cursor = self.chars.len();
+    }
+
+    fn insert(&mut self, c: char) {
+        self.chars.insert(self.cursor, c);
+        self.cursor += 1;
+    }
+
+    /// Replaces the characters between `start` and the cursor with `text`.
+    fn replace_before_cursor(&mut self, start: usize, text: &str) {
+        let end = self.cursor;
+        self.chars.splice(start..end, text.chars());
+        self.cursor = start + text.chars().count();
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.chars.remove(self.cursor);
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.chars.len() {
+            self.chars.remove(self.cursor);
+        }
+    }
+
+    fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    fn move_right(&mut self) {
+        self.cursor = (self.cursor + 1).min(self.chars.len());
+    }
+
+    fn delete_to_start(&mut self) {
+        self.chars.drain(..self.cursor);
+        self.cursor = 0;
+    }
+
+    fn delete_to_end(&mut self) {
+        self.chars.truncate(self.cursor);
+    }
+
+    /// Deletes the word before the cursor, together with the whitespace that follows it.
+    fn delete_word(&mut self) {
+        let mut start = self.cursor;
+        while start > 0 && self.chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        while start > 0 && !self.chars[start - 1].is_whitespace() {
+            start -= 1;
+        }
+        self.chars.drain(start..self.cursor);
+        self.cursor = start;
+    }
+}
+
+/// Completes the word before the cursor.
+/// If there are multiple candidates, their common prefix is inserted. If there is no common
+/// prefix to insert and `list` is `true`, all candidates are printed.
+fn complete(
+    buffer: &mut LineBuffer,
+    shell: &Shell,
+    list: bool,
+    output: &mut dyn Write,
+) -> std::io::Result<()> {
+    let before: String = buffer.chars[..buffer.cursor].iter().collect();
+    let completion = shell.complete(&before);
+    let start = before[..completion.start].chars().count();
+    let word = &before[completion.start..];
+
+    match completion.candidates.as_slice() {
+        [] => {}
+        [candidate] => buffer.replace_before_cursor(start, candidate),
+        candidates => {
+            let prefix = common_prefix(candidates);
+            if prefix.len() > word.len() {
+                buffer.replace_before_cursor(start, &prefix);
+            } else if list {
+                let candidates: Vec<&str> = candidates
+                    .iter()
+                    .map(|candidate| candidate.trim_end())
+                    .collect();
+                write!(output, "\r\n{}\r\n", candidates.join("  "))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn common_prefix(candidates: &[String]) -> String {
+    let mut prefix = candidates[0].as_str();
+    for candidate in &candidates[1..] {
+        let length = prefix
+            .char_indices()
+            .zip(candidate.chars())
+            .find(|((_, a), b)| a != b)
+            .map(|((index, _), _)| index)
+            .unwrap_or(prefix.len().min(candidate.len()));
+        prefix = &prefix[..length];
+    }
+    prefix.to_string()
+}
+
+/// Redraws the prompt and the edited line and places the cursor.
+fn render(output: &mut dyn Write, prompt: &str, buffer: &LineBuffer) -> std::io::Result<()> {
+    write!(output, "\r{prompt}{}\x1b[K\r", buffer.text())?;
+    let column = prompt.chars().count() + buffer.cursor;
+    if column > 0 {
+        write!(output, "\x1b[{column}C")?;
+    }
+    output.flush()
+}
+
+/// Switches the terminal to raw mode (input is not echoed and it is available byte by byte),
+/// the original mode is restored on drop.
+struct RawMode {
+    original: libc::termios,
+}
+
+impl RawMode {
+    fn enable() -> std::io::Result<Self> {
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        raw.c_iflag &= !(libc::IXON | libc::ICRNL | libc::BRKINT | libc::ISTRIP);
+        raw.c_cc[libc::VMIN] = 1;
+        raw.c_cc[libc::VTIME] = 0;
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &raw) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Self { original })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSADRAIN, &self.original) };
+    }
+}
+
+/// Reads a single byte directly from stdin, bypassing the buffer of `std::io::Stdin`.
+/// Returns `None` at the end of input, or if it was interrupted by a signal that ends the shell.
+fn read_byte() -> std::io::Result<Option<u8>> {
+    let mut byte = 0u8;
+    loop {
+        let res = unsafe { libc::read(libc::STDIN_FILENO, (&mut byte as *mut u8).cast(), 1) };
+        match res {
+            1 => return Ok(Some(byte)),
+            0 => return Ok(None),
+            _ => {
+                let error = std::io::Error::last_os_error();
+                if error.kind() != std::io::ErrorKind::Interrupted {
+                    return Err(error);
+                }
+                if is_termination_pending() {
+                    return Ok(None);
+                }
+            }
+        }
+    }
+}
+
+/// Returns `true` if more input arrives on stdin within a short time.
+/// Used to distinguish the Escape key from escape sequences.
+fn input_pending() -> bool {
+    let mut fd = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    unsafe { libc::poll(&mut fd, 1, 50) > 0 }
+}
+
+fn read_key() -> std::io::Result<Key> {
+    let Some(byte) = read_byte()? else {
+        return Ok(Key::Eof);
+    };
+    let key = match byte {
+        b'\r' | b'\n' => Key::Enter,
+        b'\t' => Key::Tab,
+        0x7f | 0x08 => Key::Backspace,
+        0x1b => read_escape_sequence()?,
+        1..=26 => Key::Control((b'a' + byte - 1) as char),
+        0..=0x1f => Key::Unknown,
+        _ => read_char(byte)?,
+    };
+    Ok(key)
+}
+
+fn read_escape_sequence() -> std::io::Result<Key> {
+    if !input_pending() {
+        return Ok(Key::Escape);
+    }
+    if !matches!(read_byte()?, Some(b'[' | b'O')) {
+        return Ok(Key::Unknown);
+    }
+    let mut parameter = String::new();
+    loop {
+        let Some(byte) = read_byte()? else {
+            return Ok(Key::Eof);
+        };
+        let key = match byte {
+            b'0'..=b'9' | b';' => {
+                parameter.push(byte as char);
+                continue;
+            }
+            b'A' => Key::Up,
+            b'B' => Key::Down,
+            b'C' => Key::Right,
+            b'D' => Key::Left,
+            b'H' => Key::Home,
+            b'F' => Key::End,
+            b'~' => match parameter.as_str() {
+                "1" | "7" => Key::Home,
+                "4" | "8" => Key::End,
+                "3" => Key::Delete,
+                _ => Key::Unknown,
+            },
+            _ => Key::Unknown,
+        };
+        return Ok(key);
+    }
+}
+
+/// Decodes an UTF-8 character that starts with `first`.
+fn read_char(first: u8) -> std::io::Result<Key> {
+    let length = first.leading_ones() as usize;
+    let mut bytes = vec![first];
+    for _ in 1..length.min(4) {
+        match read_byte()? {
+            Some(byte) => bytes.push(byte),
+            None => return Ok(Key::Eof),
+        }
+    }
+    Ok(match std::str::from_utf8(&bytes) {
+        Ok(text) => text.chars().next().map(Key::Char).unwrap_or(Key::Unknown),
+        Err(_) => Key::Unknown,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::editor::{common_prefix, LineBuffer};
+
+    fn buffer(text: &str, cursor: usize) -> LineBuffer {
+        let mut buffer = LineBuffer::default();
+        buffer.set(text);
+        buffer.cursor = cursor;
+        buffer
+    }
+
+    #[test]
+    fn edit_line() {
+        let mut buffer = LineBuffer::default();
+        for c in "ehlo".chars() {
+            buffer.insert(c);
+        }
+        buffer.move_left();
+        buffer.move_left();
+        buffer.insert('l');
+        buffer.cursor = 1;
+        buffer.backspace();
+        buffer.insert('h');
+        buffer.insert('e');
+        buffer.delete();
+        assert_eq!(buffer.text(), "hello");
+        assert_eq!(buffer.cursor, 2);
+    }
+
+    #[test]
+    fn delete_parts_of_line() {
+        let mut line = buffer("echo foo  bar", 10);
+        line.delete_word();
+        assert_eq!(line.text(), "echo bar");
+        assert_eq!(line.cursor, 5);
+        line.delete_to_end();
+        assert_eq!(line.text(), "echo ");
+        line.cursor = 2;
+        line.delete_to_start();
+        assert_eq!(line.text(), "ho ");
+        assert_eq!(line.cursor, 0);
+    }
+
+    #[test]
+    fn replace_word() {
+        let mut line = buffer("cat sr | wc", 6);
+        line.replace_before_cursor(4, "src/");
+        assert_eq!(line.text(), "cat src/ | wc");
+        assert_eq!(line.cursor, 8);
+    }
+
+    #[test]
+    fn find_common_prefix() {
+        let candidates = vec!["src/lexer.rs".to_string(), "src/lib.rs".to_string()];
+        assert_eq!(common_prefix(&candidates), "src/l");
+        let candidates = vec!["ab".to_string(), "abc".to_string()];
+        assert_eq!(common_prefix(&candidates), "ab");
+    }
+}
@@ -0,0 +1,252 @@
+//! Running scripts from other programs, e.g. test harnesses, with captured output.
+
+use crate::extensions::Extensions;
+use crate::state::ErrorOutput;
+use crate::{ExitStatus, Shell};
+use std::cell::RefCell;
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+
+/// Result of `Shell::run`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RunOutput {
+    /// Output of the commands
+    pub stdout: Vec<u8>,
+    /// Error messages of the shell and error output of the commands
+    pub stderr: Vec<u8>,
+    /// Exit status of the last executed command
+    pub status: ExitStatus,
+}
+
+impl RunOutput {
+    pub fn stdout_text(&self) -> String {
+        String::from_utf8_lossy(&self.stdout).into_owned()
+    }
+
+    pub fn stderr_text(&self) -> String {
+        String::from_utf8_lossy(&self.stderr).into_owned()
+    }
+}
+
+/// Writer whose data can still be read after it has been handed over to the state.
+#[derive(Clone, Default)]
+pub(crate) struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Removes and returns the data written so far.
+    pub(crate) fn take(&self) -> Vec<u8> {
+        self.0.take()
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Shell {
+    /// Executes `script` in `workdir` with exactly the environment variables in `env`, and
+    /// returns its captured output and exit status.
+    ///
+    /// The working directory and the environment replace those of previous runs, while functions,
+    /// aliases and extensions are kept. Like in a new script, options (e.g. `set -e`) are reset,
+    /// and `exit` or a signal in a previous run does not stop this one. The error output of
+    /// background jobs is discarded.
+    pub fn run<I, K, V>(&mut self, script: &str, workdir: &Path, env: I) -> RunOutput
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: Into<String>,
+        V: Into<String>,
+    {
+        self.state.workdir = workdir.to_path_buf();
+        self.state.env = env
+            .into_iter()
+            .map(|(name, value)| (name.into(), value.into()))
+            .collect();
+        self.state.exported = self.state.env.keys().cloned().collect();
+        self.state.last_status = 0;
+        self.state.errexit = false;
+        self.exited = false;
+        self.terminated = false;
+
+        let stderr = SharedBuffer::default();
+        let previous = std::mem::replace(
+            &mut self.state.stderr,
+            ErrorOutput::Capture(Box::new(stderr.clone())),
+        );
+        let mut stdout = vec![];
+        let status = self.execute_script(script, vec!["benzina".to_string()], &mut stdout);
+        // Every run behaves like a separate script
+        self.run_exit_trap(&mut stdout);
+        self.state.stderr = previous;
+
+        RunOutput {
+            stdout,
+            stderr: stderr.take(),
+            status,
+        }
+    }
+
+    /// Values available to commands through `InterpreterState::extensions`.
+    pub fn extensions(&self) -> &Extensions {
+        &self.state.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.state.extensions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::{Command, CommandError, CommandResponse};
+    use crate::state::InterpreterState;
+    use crate::{ExitStatus, ExternalCommand, Shell};
+    use std::io::{Read, Write};
+
+    /// Counts its invocations in an extension.
+    struct CountCalls;
+
+    #[derive(Default)]
+    struct Calls(usize);
+
+    impl Command for CountCalls {
+        fn execute(
+            &self,
+            args: &[String],
+            state: &mut InterpreterState,
+            _input: &mut dyn Read,
+            output: &mut dyn Write,
+        ) -> CommandResponse {
+            if args[0] != "count" {
+                return CommandResponse::Unhandled;
+            }
+            let calls = state.extensions.get_or_insert_with(Calls::default);
+            calls.0 += 1;
+            CommandResponse::Handled(writeln!(output, "{}", calls.0).map_err(CommandError::IO))
+        }
+    }
+
+    fn shell() -> Shell {
+        let mut shell = Shell::new().unwrap();
+        shell.add_builtins();
+        shell.add_command(CountCalls);
+        shell.add_command(ExternalCommand);
+        shell
+    }
+
+    #[test]
+    fn capture_output() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("file.txt"), "text\n").unwrap();
+        let mut shell = shell();
+        let output = shell.run(
+            "cat file.txt; echo $GREETING; cat missing.txt",
+            dir.path(),
+            [("GREETING", "hello")],
+        );
+        assert_eq!(output.stdout_text(), "text\nhello\n");
+        assert_eq!(
+            output.stderr_text(),
+            "Command `cat missing.txt` has failed: cat: missing.txt: No such file or directory (os error 2)\n"
+        );
+        assert_eq!(output.status, ExitStatus(1));
+
+        let output = shell.run("unknown-command", dir.path(), [("PATH", "")]);
+        assert!(output.stdout.is_empty());
+        assert_eq!(output.stderr_text(), "unknown-command: command not found\n");
+        assert_eq!(output.status, ExitStatus(127));
+    }
+
+    #[test]
+    fn capture_program_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell();
+        let output = shell.run(
+            "sh -c 'echo out; echo err >&2; exit 3'",
+            dir.path(),
+            [("PATH", std::env::var("PATH").unwrap())],
+        );
+        assert_eq!(output.stdout_text(), "out\n");
+        assert_eq!(output.stderr_text(), "err\n");
+        assert_eq!(output.status, ExitStatus(3));
+    }
+
+    #[test]
+    fn replace_workdir_and_environment() {
+        let first = tempfile::tempdir().unwrap();
+        let second = tempfile::tempdir().unwrap();
+        let mut shell = shell();
+        shell.run("greet() { echo hi $NAME; }", first.path(), [("NAME", "a")]);
+        let output = shell.run("pwd; greet; env", second.path(), [("NAME", "b")]);
+        assert_eq!(
+            output.stdout_text(),
+            format!("{}\nhi b\nNAME=b\n", second.path().display())
+        );
+    }
+
+    #[test]
+    fn run_scripts_back_to_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell();
+        let env = || [("PATH", std::env::var("PATH").unwrap())];
+        let output = shell.run("set -e; false; echo unreachable", dir.path(), env());
+        assert!(output.stdout.is_empty());
+        assert_eq!(output.status, ExitStatus(1));
+        // `set -e` and the status of the previous run do not apply to the next one
+        let output = shell.run("echo $?; false; echo next", dir.path(), env());
+        assert_eq!(output.stdout_text(), "0\nnext\n");
+        assert_eq!(output.status, ExitStatus::SUCCESS);
+
+        let output = shell.run("echo a; exit 4; echo b", dir.path(), env());
+        assert_eq!(output.stdout_text(), "a\n");
+        assert_eq!(output.status, ExitStatus(4));
+        assert!(shell.is_terminated());
+        let output = shell.run("echo c", dir.path(), env());
+        assert_eq!(output.stdout_text(), "c\n");
+        assert!(!shell.is_terminated());
+    }
+
+    #[test]
+    fn run_exit_trap() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell();
+        let env = std::iter::empty::<(String, String)>;
+        let output = shell.run(
+            "trap 'echo bye' EXIT; echo hi; cat missing.txt",
+            dir.path(),
+            env(),
+        );
+        assert_eq!(output.stdout_text(), "hi\nbye\n");
+        assert_eq!(output.status, ExitStatus(1));
+        // The trap is executed only once
+        assert_eq!(
+            shell.run("echo again", dir.path(), env()).stdout_text(),
+            "again\n"
+        );
+    }
+
+    #[test]
+    fn command_extensions() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell();
+        let output = shell.run(
+            "count; count",
+            dir.path(),
+            std::iter::empty::<(String, String)>(),
+        );
+        assert_eq!(output.stdout_text(), "1\n2\n");
+        assert_eq!(shell.extensions().get::<Calls>().unwrap().0, 2);
+
+        shell.extensions_mut().insert(Calls(10));
+        let output = shell.run("count", dir.path(), std::iter::empty::<(String, String)>());
+        assert_eq!(output.stdout_text(), "11\n");
+    }
+}
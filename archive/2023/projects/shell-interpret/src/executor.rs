@@ -0,0 +1,931 @@
+use crate::ast::Statement;
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::export::is_valid_name;
+use crate::commands::external::{exit_code, find_executable, program_command, spawn_error};
+use crate::help::SHELL_BUILTINS;
+use crate::lexer::{tokenize, Token, Word, WordPart};
+use crate::pipeline::{Pipeline, Redirect, RedirectKind, Stage};
+use crate::signals::{controls_terminal, Foreground};
+use crate::Shell;
+use std::borrow::Cow;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Child, ChildStdout, Stdio};
+use std::thread::JoinHandle;
+
+/// Maximum number of nested function calls, deeper recursion would overflow the stack.
+const MAX_FUNCTION_DEPTH: usize = 100;
+
+/// Input of the statements executed by the shell.
+pub(crate) enum Input<'a> {
+    /// Standard input of the shell, or nothing if the shell does not inherit it (see
+    /// `Shell::inherit_stdin`)
+    Shell,
+    /// Input of a function called in a pipeline
+    Reader(&'a mut dyn Read),
+}
+
+/// Input of a single stage of a pipeline.
+enum StageInput<'a, 'b> {
+    /// Input of the whole pipeline, which is read by its first stage
+    Pipeline(&'a mut Input<'b>),
+    /// Output of the previous stage, or a here-document
+    Piped(Piped),
+    /// A file redirected with `<`
+    File(File),
+}
+
+impl StageInput<'_, '_> {
+    /// Returns the input as a reader for a command executed by the shell itself.
+    fn reader(&mut self, stdin_inherited: bool) -> Box<dyn Read + '_> {
+        match self {
+            StageInput::Pipeline(Input::Shell) if stdin_inherited => Box::new(std::io::stdin()),
+            StageInput::Pipeline(Input::Shell) => Box::new(std::io::empty()),
+            StageInput::Pipeline(Input::Reader(reader)) => Box::new(&mut **reader),
+            StageInput::Piped(Piped::Buffer(data)) => Box::new(&data[..]),
+            StageInput::Piped(Piped::Program(stdout)) => Box::new(stdout),
+            StageInput::File(file) => Box::new(file),
+        }
+    }
+}
+
+/// Output passed from a stage of a pipeline to the next one.
+enum Piped {
+    /// Output of a command executed by the shell, which has already finished
+    Buffer(Vec<u8>),
+    /// Output of a program, which may still be running
+    Program(ChildStdout),
+}
+
+/// A program started by a pipeline, the shell waits for it when the pipeline ends.
+struct Process {
+    child: Child,
+    /// Thread that writes the input of the program
+    feeder: Option<JoinHandle<()>>,
+    /// Thread that collects the error output of the program, when it is captured
+    error_reader: Option<JoinHandle<Vec<u8>>>,
+    /// The program is the last stage, its exit code is the status of the pipeline
+    is_last: bool,
+}
+
+/// Result of `Shell::execute_builtin`.
+enum Executed<'a, 'b> {
+    Done(Flow),
+    /// The command is a program that should be started with the input
+    Program(Box<std::process::Command>, StageInput<'a, 'b>),
+}
+
+struct SpawnedProgram {
+    running: Process,
+    stdout: Piped,
+}
+
+/// Variable that is assigned only for a single command.
+struct PreviousVariable {
+    name: String,
+    /// Value before the assignment, `None` if the variable was not set
+    value: Option<String>,
+    /// Whether the variable was exported before the assignment
+    exported: bool,
+}
+
+/// Determines how the execution should continue after a statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Flow {
+    Normal,
+    /// `break` was executed, the innermost loop should end
+    Break,
+    /// `continue` was executed, the innermost loop should start its next iteration
+    Continue,
+    /// `return` was executed, the current function should end
+    Return,
+    /// `exit` was executed, a command has failed while `set -e` was active, or the shell was
+    /// interrupted by a signal, no more statements should be executed
+    Exit,
+}
+
+impl Shell {
+    /// Executes statements one after another.
+    /// `input` is passed to the first command of each pipeline that does not redirect its input.
+    pub(crate) fn execute_statements(
+        &mut self,
+        statements: &[Statement],
+        input: &mut Input,
+        output: &mut dyn Write,
+    ) -> Flow {
+        for statement in statements {
+            let flow = self.execute_statement(statement, input, output);
+            if flow != Flow::Normal {
+                return flow;
+            }
+            let flow = self.process_signals(output);
+            if flow != Flow::Normal {
+                return flow;
+            }
+        }
+        Flow::Normal
+    }
+
+    fn execute_statement(
+        &mut self,
+        statement: &Statement,
+        input: &mut Input,
+        output: &mut dyn Write,
+    ) -> Flow {
+        match statement {
+            Statement::Pipeline(pipeline) => self.execute_pipeline(pipeline, input, output),
+            Statement::And(left, right) => {
+                let flow = self.execute_condition(left, input, output);
+                if flow != Flow::Normal || self.state.last_status != 0 {
+                    return flow;
+                }
+                self.execute_statement(right, input, output)
+            }
+            Statement::Or(left, right) => {
+                let flow = self.execute_condition(left, input, output);
+                if flow != Flow::Normal || self.state.last_status == 0 {
+                    return flow;
+                }
+                self.execute_statement(right, input, output)
+            }
+            Statement::If {
+                branches,
+                otherwise,
+            } => {
+                for (condition, body) in branches {
+                    let flow = self.execute_conditions(condition, input, output);
+                    if flow != Flow::Normal {
+                        return flow;
+                    }
+                    if self.state.last_status == 0 {
+                        return self.execute_statements(body, input, output);
+                    }
+                }
+                match otherwise {
+                    Some(body) => self.execute_statements(body, input, output),
+                    None => {
+                        self.state.last_status = 0;
+                        Flow::Normal
+                    }
+                }
+            }
+            Statement::While { condition, body } => {
+                // Status of the last command of the body, or 0 if the body was not executed
+                let mut status = 0;
+                loop {
+                    match self.execute_conditions(condition, input, output) {
+                        Flow::Normal => {}
+                        Flow::Break => break,
+                        Flow::Continue => continue,
+                        flow @ (Flow::Return | Flow::Exit) => return flow,
+                    }
+                    if self.state.last_status != 0 {
+                        break;
+                    }
+                    let flow = self.execute_statements(body, input, output);
+                    status = self.state.last_status;
+                    match flow {
+                        Flow::Normal | Flow::Continue => {}
+                        Flow::Break => break,
+                        flow @ (Flow::Return | Flow::Exit) => return flow,
+                    }
+                }
+                self.state.last_status = status;
+                Flow::Normal
+            }
+            Statement::For {
+                variable,
+                items,
+                body,
+            } => {
+                let values = match self.expand_words(items) {
+                    Ok(values) => values,
+                    Err(error) => {
+                        self.state.last_status = error.exit_status().code();
+                        writeln!(self.state.stderr, "Cannot expand `for` items: {error}").unwrap();
+                        return Flow::Normal;
+                    }
+                };
+                self.state.last_status = 0;
+                for value in values {
+                    self.state.env.insert(variable.clone(), value);
+                    match self.execute_statements(body, input, output) {
+                        Flow::Normal | Flow::Continue => {}
+                        Flow::Break => break,
+                        flow @ (Flow::Return | Flow::Exit) => return flow,
+                    }
+                }
+                Flow::Normal
+            }
+            Statement::Group(body) => self.execute_statements(body, input, output),
+            Statement::Subshell(body) => {
+                let flow = self.execute_subshell(body, input, output);
+                self.exit_on_error(flow)
+            }
+            Statement::FunctionDefinition { name, body } => {
+                self.functions.insert(name.clone(), body.clone());
+                self.state.last_status = 0;
+                Flow::Normal
+            }
+        }
+    }
+
+    /// Executes statements in a subshell: changes of variables, the working directory, functions
+    /// and options are discarded afterwards, and `exit` only ends the subshell.
+    pub(crate) fn execute_subshell(
+        &mut self,
+        statements: &[Statement],
+        input: &mut Input,
+        output: &mut dyn Write,
+    ) -> Flow {
+        let workdir = self.state.workdir.clone();
+        let env = self.state.env.clone();
+        let exported = self.state.exported.clone();
+        let positional = self.state.positional.clone();
+        let aliases = self.state.aliases.clone();
+        let dir_stack = self.state.dir_stack.clone();
+        let errexit = self.state.errexit;
+        let functions = self.functions.clone();
+        let exited = self.exited;
+
+        self.execute_statements(statements, input, output);
+
+        self.state.workdir = workdir;
+        self.state.env = env;
+        self.state.exported = exported;
+        self.state.positional = positional;
+        self.state.aliases = aliases;
+        self.state.dir_stack = dir_stack;
+        self.state.errexit = errexit;
+        self.functions = functions;
+        self.exited = exited;
+        // Only a signal that ends the shell stops the statements after the subshell
+        if self.terminated {
+            Flow::Exit
+        } else {
+            Flow::Normal
+        }
+    }
+
+    /// Executes a statement whose exit status is tested (e.g. the condition of `if`), so its
+    /// failure does not stop the execution when `set -e` is active.
+    fn execute_condition(
+        &mut self,
+        statement: &Statement,
+        input: &mut Input,
+        output: &mut dyn Write,
+    ) -> Flow {
+        self.condition_depth += 1;
+        let flow = self.execute_statement(statement, input, output);
+        self.condition_depth -= 1;
+        flow
+    }
+
+    fn execute_conditions(
+        &mut self,
+        statements: &[Statement],
+        input: &mut Input,
+        output: &mut dyn Write,
+    ) -> Flow {
+        self.condition_depth += 1;
+        let flow = self.execute_statements(statements, input, output);
+        self.condition_depth -= 1;
+        flow
+    }
+
+    /// Executes a pipeline of commands separated by `|`, with optional `<`, `>` and `>>`
+    /// redirections.
+    ///
+    /// Programs in the pipeline run at the same time, connected with OS pipes, and the first
+    /// one reads the input of the pipeline directly. Builtins are executed by the shell one after
+    /// another, their output is buffered and then passed as the input of the next stage.
+    ///
+    /// If the pipeline ends with `&`, it is started as a background job instead.
+    ///
+    /// If the pipeline fails while `set -e` is active (and it is not a condition), `Flow::Exit`
+    /// is returned.
+    fn execute_pipeline(
+        &mut self,
+        pipeline: &Pipeline,
+        input: &mut Input,
+        output: &mut dyn Write,
+    ) -> Flow {
+        let flow = self.run_pipeline(pipeline, input, output);
+        self.exit_on_error(flow)
+    }
+
+    /// Returns `Flow::Exit` if the last command has failed while `set -e` is active, and it is
+    /// not a condition.
+    fn exit_on_error(&self, flow: Flow) -> Flow {
+        if flow == Flow::Normal
+            && self.state.last_status != 0
+            && self.state.errexit
+            && self.condition_depth == 0
+        {
+            return Flow::Exit;
+        }
+        flow
+    }
+
+    fn run_pipeline(
+        &mut self,
+        pipeline: &Pipeline,
+        input: &mut Input,
+        output: &mut dyn Write,
+    ) -> Flow {
+        if pipeline.background {
+            match self.start_job(pipeline) {
+                Ok(id) => {
+                    self.state.last_status = 0;
+                    let job = self.state.jobs.get_mut(id).unwrap();
+                    writeln!(output, "[{id}] {}", job.last_pid()).unwrap();
+                }
+                Err(error) => {
+                    self.state.last_status = error.exit_status().code();
+                    writeln!(
+                        self.state.stderr,
+                        "Cannot start `{}`: {error}",
+                        pipeline.line()
+                    )
+                    .unwrap();
+                }
+            }
+            return Flow::Normal;
+        }
+
+        let mut flow = Flow::Normal;
+        let exited = self.exited;
+        let mut processes: Vec<Process> = vec![];
+        let mut piped: Option<Piped> = None;
+        let stage_count = pipeline.stages.len();
+        for (index, stage) in pipeline.stages.iter().enumerate() {
+            let stage_input = match piped.take() {
+                Some(piped) => StageInput::Piped(piped),
+                None if index == 0 => StageInput::Pipeline(&mut *input),
+                None => StageInput::Piped(Piped::Buffer(vec![])),
+            };
+            let stage_output = if index + 1 == stage_count {
+                Some(&mut *output as &mut dyn Write)
+            } else {
+                None
+            };
+            match self.execute_stage(stage, stage_input, stage_output, &mut processes) {
+                Ok((stage_flow, stage_piped)) => {
+                    // Control flow only applies to commands that are not a part of a larger
+                    // pipeline
+                    if stage_count == 1 {
+                        flow = stage_flow;
+                    }
+                    piped = Some(stage_piped);
+                }
+                Err(error) => self.report_stage_error(stage, error),
+            }
+        }
+        self.finish_pipeline(pipeline, processes, piped, output);
+        if stage_count > 1 {
+            // Like in other shells, where each stage of a pipeline runs in a subshell
+            self.exited = exited;
+        }
+        flow
+    }
+
+    fn report_stage_error(&mut self, stage: &Stage, error: CommandError) {
+        self.state.last_status = error.exit_status().code();
+        match error {
+            CommandError::CommandNotFound(_) | CommandError::PermissionDenied(_) => {
+                writeln!(self.state.stderr, "{error}").unwrap()
+            }
+            error => writeln!(
+                self.state.stderr,
+                "Command `{}` has failed: {error}",
+                stage.line()
+            )
+            .unwrap(),
+        }
+    }
+
+    /// Copies the output of the last stage to `output` if it is a program, then waits for all
+    /// programs of the pipeline. The exit code of the last stage becomes the status of the
+    /// pipeline.
+    fn finish_pipeline(
+        &mut self,
+        pipeline: &Pipeline,
+        processes: Vec<Process>,
+        piped: Option<Piped>,
+        output: &mut dyn Write,
+    ) {
+        let foreground = processes
+            .last()
+            .map(|process| Foreground::set(process.child.id() as libc::pid_t));
+        let res = match piped {
+            Some(Piped::Program(mut stdout)) => std::io::copy(&mut stdout, output).map(|_| ()),
+            _ => Ok(()),
+        };
+        for mut process in processes {
+            let status = process.child.wait();
+            if let Some(feeder) = process.feeder {
+                feeder.join().unwrap();
+            }
+            if let Some(error_reader) = process.error_reader {
+                let _ = self.state.stderr.write_all(&error_reader.join().unwrap());
+            }
+            match status {
+                Ok(status) if process.is_last => self.state.last_status = exit_code(status),
+                Ok(_) => {}
+                Err(error) => {
+                    writeln!(self.state.stderr, "Cannot wait for a program: {error}").unwrap()
+                }
+            }
+        }
+        drop(foreground);
+        if let Err(error) = res {
+            let stage = pipeline.stages.last().unwrap();
+            self.report_stage_error(stage, CommandError::IO(error));
+        }
+    }
+
+    /// Executes a single stage of a pipeline. The stage is the last one if it has an `output`,
+    /// otherwise its output is returned, so that it can be passed to the next stage.
+    ///
+    /// Programs are started without waiting for them, they are added to `processes`.
+    fn execute_stage(
+        &mut self,
+        stage: &Stage,
+        mut input: StageInput,
+        output: Option<&mut dyn Write>,
+        processes: &mut Vec<Process>,
+    ) -> Result<(Flow, Piped), CommandError> {
+        let mut redirected_output: Option<File> = None;
+        for redirect in &stage.redirects {
+            match redirect.kind {
+                RedirectKind::Input => {
+                    input = StageInput::File(self.open_redirect(redirect)?);
+                }
+                RedirectKind::HereDocument => {
+                    let contents = self.expand_text(&redirect.target)?;
+                    input = StageInput::Piped(Piped::Buffer(contents.into_bytes()));
+                }
+                RedirectKind::Output | RedirectKind::Append => {
+                    redirected_output = Some(self.open_redirect(redirect)?);
+                }
+            }
+        }
+
+        if let Some(statement) = &stage.compound {
+            return Ok(self.execute_compound(statement, input, output, redirected_output));
+        }
+
+        let words = self.expand_aliases(&stage.words).into_owned();
+        let count = words
+            .iter()
+            .take_while(|word| assignment(word).is_some())
+            .count();
+        if count == words.len() {
+            // The status of the last command substitution in the values is kept
+            self.state.last_status = 0;
+            for word in &words {
+                let (name, value) = assignment(word).unwrap();
+                let value = self.expand_text(&value)?;
+                self.state.env.insert(name.to_string(), value);
+            }
+            return Ok((Flow::Normal, Piped::Buffer(vec![])));
+        }
+
+        // Assignments before a command only apply to the command
+        let mut previous = vec![];
+        let res = self
+            .assign_temporarily(&words[..count], &mut previous)
+            .and_then(|()| {
+                self.execute_command(&words[count..], input, output, redirected_output, processes)
+            });
+        self.restore_variables(previous);
+        res
+    }
+
+    /// Executes a compound statement that is a stage of a pipeline. Like for builtins, its output
+    /// is buffered if it is not the last stage.
+    fn execute_compound(
+        &mut self,
+        statement: &Statement,
+        input: StageInput,
+        output: Option<&mut dyn Write>,
+        mut redirected_output: Option<File>,
+    ) -> (Flow, Piped) {
+        let mut buffer = vec![];
+        let output: &mut dyn Write = match (redirected_output.as_mut(), output) {
+            (Some(file), _) => file,
+            (None, Some(output)) => output,
+            (None, None) => &mut buffer,
+        };
+        let flow = match input {
+            StageInput::Pipeline(input) => self.execute_statement(statement, input, output),
+            mut input => {
+                let mut reader = input.reader(self.stdin_inherited);
+                self.execute_statement(statement, &mut Input::Reader(&mut reader), output)
+            }
+        };
+        (flow, Piped::Buffer(buffer))
+    }
+
+    /// Sets and exports the variables of `assignments` for a single command. The previous values
+    /// are added to `previous`, so that `restore_variables` can bring them back.
+    fn assign_temporarily(
+        &mut self,
+        assignments: &[Word],
+        previous: &mut Vec<PreviousVariable>,
+    ) -> Result<(), CommandError> {
+        for word in assignments {
+            let (name, value) = assignment(word).unwrap();
+            let value = self.expand_text(&value)?;
+            previous.push(PreviousVariable {
+                name: name.to_string(),
+                value: self.state.env.insert(name.to_string(), value),
+                exported: !self.state.exported.insert(name.to_string()),
+            });
+        }
+        Ok(())
+    }
+
+    fn restore_variables(&mut self, previous: Vec<PreviousVariable>) {
+        // In reverse order, in case a variable was assigned more than once
+        for variable in previous.into_iter().rev() {
+            match variable.value {
+                Some(value) => self.state.env.insert(variable.name.clone(), value),
+                None => self.state.env.remove(&variable.name),
+            };
+            if !variable.exported {
+                self.state.exported.remove(&variable.name);
+            }
+        }
+    }
+
+    /// Executes the words of a stage that remain after the assignments.
+    fn execute_command(
+        &mut self,
+        words: &[Word],
+        input: StageInput,
+        output: Option<&mut dyn Write>,
+        mut redirected_output: Option<File>,
+        processes: &mut Vec<Process>,
+    ) -> Result<(Flow, Piped), CommandError> {
+        let args = self.expand_words(words)?;
+        if args.is_empty() {
+            self.state.last_status = 0;
+            return Ok((Flow::Normal, Piped::Buffer(vec![])));
+        }
+        let is_last = output.is_some();
+        let mut buffer = vec![];
+        let executed = match (redirected_output.as_mut(), output) {
+            (Some(file), _) => self.execute_builtin(args, input, file),
+            (None, Some(output)) => self.execute_builtin(args, input, output),
+            (None, None) => self.execute_builtin(args, input, &mut buffer),
+        }?;
+        match executed {
+            Executed::Done(flow) => Ok((flow, Piped::Buffer(buffer))),
+            Executed::Program(command, input) => {
+                let process = self.spawn_program(*command, input, redirected_output, is_last)?;
+                processes.push(process.running);
+                Ok((Flow::Normal, process.stdout))
+            }
+        }
+    }
+
+    /// Starts a program with the input of the stage. Its output goes to the redirected file, or
+    /// it is returned as `Piped::Program`.
+    fn spawn_program(
+        &mut self,
+        mut command: std::process::Command,
+        input: StageInput,
+        redirected_output: Option<File>,
+        is_last: bool,
+    ) -> Result<SpawnedProgram, CommandError> {
+        let (stdin, fed) = match input {
+            StageInput::Pipeline(Input::Shell) if self.stdin_inherited => (Stdio::inherit(), None),
+            StageInput::Pipeline(Input::Shell) => (Stdio::null(), None),
+            StageInput::Pipeline(Input::Reader(reader)) => {
+                let mut data = vec![];
+                reader.read_to_end(&mut data).map_err(CommandError::IO)?;
+                (Stdio::piped(), Some(data))
+            }
+            StageInput::Piped(Piped::Buffer(data)) => (Stdio::piped(), Some(data)),
+            StageInput::Piped(Piped::Program(stdout)) => (Stdio::from(stdout), None),
+            StageInput::File(file) => (Stdio::from(file), None),
+        };
+        command.stdin(stdin).stdout(match redirected_output {
+            Some(file) => Stdio::from(file),
+            None => Stdio::piped(),
+        });
+        if self.state.stderr.is_captured() {
+            command.stderr(Stdio::piped());
+        }
+        let mut child = command
+            .spawn()
+            .map_err(|error| spawn_error(Path::new(command.get_program()), error))?;
+
+        // The input is fed from a separate thread, otherwise the program could block on a full
+        // stdout pipe while the shell is still writing its stdin
+        let feeder = fed.zip(child.stdin.take()).map(|(data, mut stdin)| {
+            std::thread::spawn(move || {
+                // The program does not have to read all of its input
+                let _ = stdin.write_all(&data);
+            })
+        });
+        // The error output is collected by another thread for the same reason
+        let error_reader = child.stderr.take().map(|mut stderr| {
+            std::thread::spawn(move || {
+                let mut data = vec![];
+                let _ = stderr.read_to_end(&mut data);
+                data
+            })
+        });
+        let stdout = match child.stdout.take() {
+            Some(stdout) => Piped::Program(stdout),
+            None => Piped::Buffer(vec![]),
+        };
+        Ok(SpawnedProgram {
+            running: Process {
+                child,
+                feeder,
+                error_reader,
+                is_last,
+            },
+            stdout,
+        })
+    }
+
+    /// Executes a special builtin, a function, a command from a plugin or a builtin. If the
+    /// command is a program instead, it is returned with the unused input, so that it can be
+    /// started without waiting for it.
+    fn execute_builtin<'a, 'b>(
+        &mut self,
+        args: Vec<String>,
+        mut input: StageInput<'a, 'b>,
+        output: &mut dyn Write,
+    ) -> Result<Executed<'a, 'b>, CommandError> {
+        match args[0].as_str() {
+            "break" => return Ok(Executed::Done(Flow::Break)),
+            "continue" => return Ok(Executed::Done(Flow::Continue)),
+            "exit" => {
+                if let Some(status) = args.get(1) {
+                    self.state.last_status = status.parse().map_err(|_| {
+                        CommandError::Usage(format!("exit: {status}: numeric argument required"))
+                    })?;
+                }
+                self.exited = true;
+                return Ok(Executed::Done(Flow::Exit));
+            }
+            "help" => {
+                self.state.last_status = 0;
+                self.help(&args[1..], output)?;
+                return Ok(Executed::Done(Flow::Normal));
+            }
+            "return" => {
+                if let Some(status) = args.get(1) {
+                    self.state.last_status = status.parse().map_err(|_| {
+                        CommandError::Usage(format!("return: {status}: numeric argument required"))
+                    })?;
+                }
+                return Ok(Executed::Done(Flow::Return));
+            }
+            _ => {}
+        }
+
+        // External programs overwrite the status with their own exit code
+        self.state.last_status = 0;
+        if let Some(body) = self.functions.get(&args[0]).cloned() {
+            if self.function_depth >= MAX_FUNCTION_DEPTH {
+                return Err(CommandError::IO(std::io::Error::other(format!(
+                    "{}: maximum function nesting level exceeded",
+                    args[0]
+                ))));
+            }
+            let mut positional = vec![self.state.positional[0].clone()];
+            positional.extend(args.into_iter().skip(1));
+            let positional = std::mem::replace(&mut self.state.positional, positional);
+            self.function_depth += 1;
+            let flow = match input {
+                StageInput::Pipeline(input) => self.execute_statements(&body, input, output),
+                mut input => {
+                    let mut reader = input.reader(self.stdin_inherited);
+                    self.execute_statements(&body, &mut Input::Reader(&mut reader), output)
+                }
+            };
+            self.function_depth -= 1;
+            self.state.positional = positional;
+            return Ok(Executed::Done(if flow == Flow::Exit {
+                Flow::Exit
+            } else {
+                Flow::Normal
+            }));
+        }
+        // Plugins cannot override builtins, but they have to be checked before the catch-all
+        // external command
+        let plugins = self.plugins.iter().map(|plugin| plugin as &dyn Command);
+        for command in plugins.chain(self.commands.iter().map(|command| command.as_ref())) {
+            if let Some(program) = command.program(&args, &self.state) {
+                return Ok(Executed::Program(Box::new(program?), input));
+            }
+            let mut reader = input.reader(self.stdin_inherited);
+            match command.execute(&args, &mut self.state, &mut reader, output) {
+                CommandResponse::Handled(res) => return res.map(|_| Executed::Done(Flow::Normal)),
+                CommandResponse::Unhandled => {}
+            }
+        }
+        Err(CommandError::CommandNotFound(args[0].clone()))
+    }
+
+    /// Starts all stages of the pipeline as external programs connected with pipes, in a new
+    /// process group, and registers them as a job. Returns the ID of the job.
+    ///
+    /// Background jobs can only consist of external programs, builtins and compound commands are
+    /// reported as an error.
+    fn start_job(&mut self, pipeline: &Pipeline) -> Result<usize, CommandError> {
+        let mut pids: Vec<libc::pid_t> = vec![];
+        let res = self.spawn_stages(pipeline, &mut pids);
+        if let Err(error) = res {
+            // Do not leave half of the pipeline running
+            if let Some(group) = pids.first() {
+                unsafe { libc::kill(-group, libc::SIGKILL) };
+                for pid in pids {
+                    unsafe { libc::waitpid(pid, std::ptr::null_mut(), 0) };
+                }
+            }
+            return Err(error);
+        }
+        Ok(self.state.jobs.add(pipeline.line(), pids))
+    }
+
+    fn spawn_stages(
+        &mut self,
+        pipeline: &Pipeline,
+        pids: &mut Vec<libc::pid_t>,
+    ) -> Result<(), CommandError> {
+        let mut previous_stdout = None;
+        for (index, stage) in pipeline.stages.iter().enumerate() {
+            if stage.compound.is_some() {
+                return Err(CommandError::Usage(format!(
+                    "`{}`: compound commands cannot run in the background",
+                    stage.line()
+                )));
+            }
+            let words = self.expand_aliases(&stage.words).into_owned();
+            let count = words
+                .iter()
+                .take_while(|word| assignment(word).is_some())
+                .count();
+            let mut assignments = vec![];
+            for word in &words[..count] {
+                let (name, value) = assignment(word).unwrap();
+                assignments.push((name, self.expand_text(&value)?));
+            }
+            let args = self.expand_words(&words[count..])?;
+            let Some(program) = args.first() else {
+                return Err(CommandError::Usage(format!(
+                    "`{}` expands to an empty command",
+                    stage.line()
+                )));
+            };
+            if self.is_builtin(program) {
+                return Err(CommandError::Usage(format!(
+                    "{program}: builtins cannot run in the background"
+                )));
+            }
+            let path = find_executable(program, &self.state)?;
+            let mut command = program_command(&path, &args[1..], &self.state);
+            command.envs(assignments);
+            // Without job control, a job cannot be moved to the foreground, so it must not read
+            // the input of the shell. Otherwise it is stopped if it reads the terminal while it is
+            // in the background, until `fg` gives the terminal to it.
+            command.stdin(match previous_stdout.take() {
+                Some(stdout) => Stdio::from(stdout),
+                None if self.stdin_inherited && controls_terminal() => Stdio::inherit(),
+                None => Stdio::null(),
+            });
+            if index + 1 < pipeline.stages.len() {
+                command.stdout(Stdio::piped());
+            }
+            // The output of background jobs cannot be collected, when the error output is
+            // captured it is discarded instead of being printed
+            if self.state.stderr.is_captured() {
+                command.stderr(Stdio::null());
+            }
+            let mut document = None;
+            for redirect in &stage.redirects {
+                match redirect.kind {
+                    RedirectKind::Input => {
+                        command.stdin(self.open_redirect(redirect)?);
+                    }
+                    RedirectKind::HereDocument => {
+                        document = Some(self.expand_text(&redirect.target)?);
+                        command.stdin(Stdio::piped());
+                    }
+                    RedirectKind::Output | RedirectKind::Append => {
+                        command.stdout(self.open_redirect(redirect)?);
+                    }
+                }
+            }
+            // The first process becomes the leader of a new process group
+            command.process_group(pids.first().copied().unwrap_or(0));
+
+            let mut child = command.spawn().map_err(|error| spawn_error(&path, error))?;
+            previous_stdout = child.stdout.take();
+            pids.push(child.id() as libc::pid_t);
+            if let (Some(document), Some(mut stdin)) = (document, child.stdin.take()) {
+                // The job may not read everything, so the shell must not wait for it
+                std::thread::spawn(move || {
+                    let _ = stdin.write_all(document.as_bytes());
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if `name` is executed by the shell itself: a special builtin, a function, a
+    /// command from a plugin or a builtin.
+    fn is_builtin(&self, name: &str) -> bool {
+        SHELL_BUILTINS.iter().any(|(builtin, _)| *builtin == name)
+            || self.functions.contains_key(name)
+            || self
+                .plugins
+                .iter()
+                .flat_map(|plugin| plugin.commands())
+                .any(|command| command.name == name)
+            || self
+                .commands
+                .iter()
+                .any(|command| command.name() == Some(name))
+    }
+
+    /// Opens the file that is the target of the redirection.
+    fn open_redirect(&mut self, redirect: &Redirect) -> Result<File, CommandError> {
+        let Some(target) = self.expand_single_word(&redirect.target)? else {
+            return Err(CommandError::Usage(format!(
+                "ambiguous redirect `{}`",
+                redirect.target.text()
+            )));
+        };
+        let path = self.state.resolve_path(target);
+        let file = match redirect.kind {
+            RedirectKind::Input => File::open(path),
+            RedirectKind::Output => File::create(path),
+            RedirectKind::Append => OpenOptions::new().create(true).append(true).open(path),
+            RedirectKind::HereDocument => unreachable!("here-documents are not files"),
+        };
+        file.map_err(CommandError::IO)
+    }
+
+    /// Replaces the first word of a command by the words of its alias.
+    /// The expansion is repeated for the new first word, but every alias is expanded at most once,
+    /// so e.g. `alias ls='ls -F'` does not recurse.
+    fn expand_aliases<'a>(&self, words: &'a [Word]) -> Cow<'a, [Word]> {
+        let mut words = Cow::Borrowed(words);
+        let mut expanded: Vec<String> = vec![];
+        while let Some([WordPart::Unquoted(name)]) = words.first().map(|word| &word.parts[..]) {
+            let Some(value) = self.state.aliases.get(name) else {
+                break;
+            };
+            if expanded.contains(name) {
+                break;
+            }
+            // Aliases are checked when they are defined, so the value should be valid
+            let Ok(tokens) = tokenize(value) else {
+                break;
+            };
+            expanded.push(name.clone());
+            let mut replaced: Vec<Word> = tokens
+                .into_iter()
+                .filter_map(|token| match token {
+                    Token::Word(word) => Some(word),
+                    _ => None,
+                })
+                .collect();
+            replaced.extend(words[1..].iter().cloned());
+            words = Cow::Owned(replaced);
+        }
+        words
+    }
+
+    fn expand_words(&mut self, words: &[Word]) -> Result<Vec<String>, CommandError> {
+        let mut values = vec![];
+        for word in words {
+            values.extend(self.expand_word(word)?);
+        }
+        Ok(values)
+    }
+}
+
+/// Splits a `NAME=value` word into the name and the (unexpanded) value.
+fn assignment(word: &Word) -> Option<(&str, Word)> {
+    let Some(WordPart::Unquoted(text)) = word.parts.first() else {
+        return None;
+    };
+    let (name, value) = text.split_once('=')?;
+    if !is_valid_name(name) {
+        return None;
+    }
+    let mut parts = vec![WordPart::Unquoted(value.to_string())];
+    parts.extend(word.parts[1..].iter().cloned());
+    Some((name, Word { parts }))
+}
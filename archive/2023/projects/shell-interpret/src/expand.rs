@@ -0,0 +1,354 @@
+use crate::arithmetic;
+use crate::ast::Statement;
+use crate::command::CommandError;
+use crate::executor::Input;
+use crate::glob::expand_glob;
+use crate::lexer::{Word, WordPart};
+use crate::Shell;
+
+impl Shell {
+    /// Expands variables, `$?`, `~`, command substitutions and arithmetic in `word` and then
+    /// expands glob patterns.
+    ///
+    /// Expanded values are taken literally, they are not used as glob patterns. Unquoted variables
+    /// and command substitutions are split into multiple words, at spaces, tabs and newlines.
+    /// A word consisting only of unquoted expansions that are empty (e.g. `$UNSET`)
+    /// produces no value at all.
+    pub(crate) fn expand_word(&mut self, word: &Word) -> Result<Vec<String>, CommandError> {
+        // `"$@"` produces one word for each positional parameter
+        let parts: Vec<&WordPart> = word
+            .parts
+            .iter()
+            .filter(|part| !matches!(part, WordPart::Quoted(text) if text.is_empty()))
+            .collect();
+        if let [WordPart::Variable {
+            name,
+            default: None,
+            ..
+        }] = parts.as_slice()
+        {
+            if name == "@" {
+                return Ok(self.state.positional[1..].to_vec());
+            }
+        }
+        let fields = self.expand_fields(word, true)?;
+        Ok(fields
+            .iter()
+            .flat_map(|field| expand_glob(field, &self.state.workdir))
+            .collect())
+    }
+
+    /// Expands a word that must produce exactly one value (e.g. a redirection target).
+    pub(crate) fn expand_single_word(
+        &mut self,
+        word: &Word,
+    ) -> Result<Option<String>, CommandError> {
+        let mut values = self.expand_word(word)?;
+        Ok(if values.len() == 1 {
+            values.pop()
+        } else {
+            None
+        })
+    }
+
+    /// Expands `word` without splitting it and without expanding glob patterns (e.g. for the
+    /// value of an assignment).
+    pub(crate) fn expand_text(&mut self, word: &Word) -> Result<String, CommandError> {
+        let fields = self.expand_fields(word, false)?;
+        Ok(fields.first().map(Word::text).unwrap_or_default())
+    }
+
+    /// Replaces expansions in `word` by their values. If `split` is `true`, unquoted variables
+    /// and command substitutions can produce multiple words.
+    fn expand_fields(&mut self, word: &Word, split: bool) -> Result<Vec<Word>, CommandError> {
+        let mut fields = vec![];
+        let mut field = Field::default();
+        for part in &word.parts {
+            match part {
+                WordPart::Unquoted(_) | WordPart::Quoted(_) => field.push(part.clone(), true),
+                WordPart::Variable {
+                    name,
+                    default,
+                    quoted,
+                } => {
+                    let value = match (self.state.variable(name), default) {
+                        (Some(value), _) if !value.is_empty() => value,
+                        (_, Some(default)) if !*quoted && split => {
+                            self.split_default(default, &mut field, &mut fields)?;
+                            continue;
+                        }
+                        (_, Some(default)) => self.expand_text(default)?,
+                        (value, None) => value.unwrap_or_default(),
+                    };
+                    if *quoted || !split {
+                        field.push_value(value);
+                    } else {
+                        field.push_split(&value, WordPart::Quoted, &mut fields);
+                    }
+                }
+                WordPart::LastStatus => field.push_value(self.state.last_status.to_string()),
+                WordPart::Tilde => match self.state.env.get("HOME") {
+                    Some(home) => field.push(WordPart::Quoted(home.clone()), true),
+                    None => field.push(WordPart::Unquoted("~".to_string()), true),
+                },
+                WordPart::Arithmetic(expression) => {
+                    let expression = self.expand_text(expression)?;
+                    let value = arithmetic::evaluate(&expression, &mut self.state.env)?;
+                    field.push_value(value.to_string());
+                }
+                WordPart::CommandSubstitution {
+                    statements, quoted, ..
+                } => {
+                    let value = self.substitute_command(statements);
+                    if *quoted || !split {
+                        field.push_value(value);
+                    } else {
+                        field.push_split(&value, WordPart::Quoted, &mut fields);
+                    }
+                }
+            }
+        }
+        field.finish(&mut fields);
+        Ok(fields)
+    }
+
+    /// Expands the default value of an unquoted `${NAME:-default}` into `field`. Its unquoted
+    /// text and expansions are split into words, but quoted or escaped text is kept together.
+    fn split_default(
+        &mut self,
+        default: &Word,
+        field: &mut Field,
+        fields: &mut Vec<Word>,
+    ) -> Result<(), CommandError> {
+        for (index, word) in self.expand_fields(default, true)?.into_iter().enumerate() {
+            if index > 0 {
+                field.finish(fields);
+            }
+            for part in word.parts {
+                match part {
+                    WordPart::Unquoted(text) => field.push_split(&text, WordPart::Unquoted, fields),
+                    part => field.push(part, true),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Executes the commands of `$(...)` and returns their output without trailing newlines.
+    ///
+    /// The commands run in a subshell, so their changes of the state of the shell are not visible
+    /// after the substitution, only the exit status is kept.
+    fn substitute_command(&mut self, statements: &[Statement]) -> String {
+        let mut output: Vec<u8> = vec![];
+        self.execute_subshell(statements, &mut Input::Shell, &mut output);
+        let mut output = String::from_utf8_lossy(&output).into_owned();
+        output.truncate(output.trim_end_matches('\n').len());
+        output
+    }
+}
+
+/// A word that is being produced by expansions.
+#[derive(Default)]
+struct Field {
+    word: Word,
+    /// Whether the word should be kept even if it is empty, because it contains text from the
+    /// original word or a non-empty value
+    exists: bool,
+}
+
+impl Field {
+    fn push(&mut self, part: WordPart, exists: bool) {
+        self.word.parts.push(part);
+        self.exists |= exists;
+    }
+
+    /// Adds the value of an expansion, which is taken literally.
+    fn push_value(&mut self, value: String) {
+        let exists = !value.is_empty();
+        self.push(WordPart::Quoted(value), exists);
+    }
+
+    /// Adds the value of an unquoted expansion, which is split into words at spaces, tabs and
+    /// newlines. The first word continues the current one, the others are added to `fields`.
+    /// `part` makes the parts of the words, e.g. `WordPart::Quoted` for values that are taken
+    /// literally.
+    fn push_split(&mut self, value: &str, part: fn(String) -> WordPart, fields: &mut Vec<Word>) {
+        let is_separator = |c: char| matches!(c, ' ' | '\t' | '\n');
+        if value.starts_with(is_separator) {
+            self.finish(fields);
+        }
+        for (index, value) in value
+            .split(is_separator)
+            .filter(|value| !value.is_empty())
+            .enumerate()
+        {
+            if index > 0 {
+                self.finish(fields);
+            }
+            self.push(part(value.to_string()), true);
+        }
+        if value.ends_with(is_separator) {
+            self.finish(fields);
+        }
+    }
+
+    /// Ends the word, adding it to `fields` if it exists.
+    fn finish(&mut self, fields: &mut Vec<Word>) {
+        let field = std::mem::take(self);
+        if field.exists {
+            fields.push(field.word);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lexer::{tokenize, Token};
+    use crate::state::InterpreterState;
+    use crate::Shell;
+
+    fn shell(dir: &tempfile::TempDir) -> Shell {
+        let mut state = InterpreterState::new(dir.path().to_path_buf());
+        state.env.clear();
+        state
+            .env
+            .insert("HOME".to_string(), "/home/ferris".to_string());
+        state
+            .env
+            .insert("NAME".to_string(), "Ferris the crab".to_string());
+        state.env.insert("EMPTY".to_string(), String::new());
+        Shell {
+            state,
+            ..Shell::with_workdir(dir.path().to_path_buf())
+        }
+    }
+
+    fn expand(line: &str, shell: &mut Shell) -> Vec<String> {
+        tokenize(line)
+            .unwrap()
+            .into_iter()
+            .flat_map(|token| match token {
+                Token::Word(word) => shell.expand_word(&word).unwrap(),
+                token => panic!("Unexpected token {token:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn variables() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        assert_eq!(
+            expand(
+                "$NAME x${NAME}x $MISSING \"$MISSING\" $EMPTY$MISSING",
+                &mut shell
+            ),
+            vec!["Ferris", "the", "crab", "xFerris", "the", "crabx", ""]
+        );
+        assert_eq!(
+            expand("\"$NAME!\" '$NAME'", &mut shell),
+            vec!["Ferris the crab!", "$NAME"]
+        );
+        assert_eq!(expand("\\$NAME $ $-", &mut shell), vec!["$NAME", "$", "$-"]);
+    }
+
+    #[test]
+    fn default_value() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        assert_eq!(
+            expand(
+                "${NAME:-x} ${MISSING:-a b} ${EMPTY:-$NAME} ${MISSING:-'}'}",
+                &mut shell
+            ),
+            vec!["Ferris", "the", "crab", "a", "b", "Ferris", "the", "crab", "}"]
+        );
+        // Quoted and escaped text of the default value is not split
+        assert_eq!(
+            expand(
+                "${MISSING:-\"a  b\"} ${MISSING:-a\\ \\ b} ${MISSING:-'c  d' e}x",
+                &mut shell
+            ),
+            vec!["a  b", "a  b", "c  d", "ex"]
+        );
+        assert_eq!(
+            expand(
+                "${MISSING:-\"\"} ${MISSING:-} \"${MISSING:-a  b}\"",
+                &mut shell
+            ),
+            vec!["", "a  b"]
+        );
+    }
+
+    #[test]
+    fn field_splitting() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        shell
+            .state
+            .env
+            .insert("SPACES".to_string(), " a \t b\n".to_string());
+        assert_eq!(
+            expand("$SPACES x${SPACES}x \"$SPACES\"", &mut shell),
+            vec!["a", "b", "x", "a", "b", "x", " a \t b\n"]
+        );
+        // Assignments are not split
+        let word = match tokenize("$NAME").unwrap().pop() {
+            Some(Token::Word(word)) => word,
+            token => panic!("Unexpected token {token:?}"),
+        };
+        assert_eq!(shell.expand_text(&word).unwrap(), "Ferris the crab");
+    }
+
+    #[test]
+    fn tilde() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        assert_eq!(
+            expand("~ ~/foo a~ '~' ~bar", &mut shell),
+            vec!["/home/ferris", "/home/ferris/foo", "a~", "~", "~bar"]
+        );
+    }
+
+    #[test]
+    fn last_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        shell.state.last_status = 42;
+        assert_eq!(expand("$? \"[$?]\"", &mut shell), vec!["42", "[42]"]);
+    }
+
+    #[test]
+    fn positional_parameters() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        shell.state.positional = vec!["script.sh".to_string(), "a b".to_string(), "c".to_string()];
+        assert_eq!(
+            expand("$0 $2 ${1} $3 $# \"$*\"", &mut shell),
+            vec!["script.sh", "c", "a", "b", "2", "a b c"]
+        );
+        assert_eq!(
+            expand("\"$@\" x$@", &mut shell),
+            vec!["a b", "c", "xa", "b", "c"]
+        );
+    }
+
+    #[test]
+    fn expanded_values_are_not_globbed() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "").unwrap();
+        let mut shell = shell(&dir);
+        shell
+            .state
+            .env
+            .insert("PATTERN".to_string(), "*.txt".to_string());
+        assert_eq!(expand("$PATTERN *.txt", &mut shell), vec!["*.txt", "a.txt"]);
+    }
+
+    #[test]
+    fn bad_substitution() {
+        assert!(tokenize("${}").is_err());
+        assert!(tokenize("${A:=b}").is_err());
+        assert!(tokenize("${A:-b").is_err());
+    }
+}
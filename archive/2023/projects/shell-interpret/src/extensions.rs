@@ -0,0 +1,75 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+
+/// Values of arbitrary types stored in `InterpreterState`, at most one value of each type.
+///
+/// Custom commands can use them to keep their own state, and programs that embed the shell can
+/// use them to pass data to (and from) their commands.
+#[derive(Default)]
+pub struct Extensions {
+    values: HashMap<TypeId, Box<dyn Any>>,
+}
+
+impl Extensions {
+    /// Stores a value, returns the previous value of the same type.
+    pub fn insert<T: 'static>(&mut self, value: T) -> Option<T> {
+        self.values
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok())
+            .map(|previous| *previous)
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.values
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_mut())
+    }
+
+    /// Returns the value of type `T`, it is created with `create` if it does not exist yet.
+    pub fn get_or_insert_with<T: 'static, F: FnOnce() -> T>(&mut self, create: F) -> &mut T {
+        self.values
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::new(create()))
+            .downcast_mut()
+            .unwrap()
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.values
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok())
+            .map(|value| *value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::extensions::Extensions;
+
+    #[derive(Debug, PartialEq)]
+    struct Counter(u32);
+
+    #[test]
+    fn store_values_by_type() {
+        let mut extensions = Extensions::default();
+        assert_eq!(extensions.insert(Counter(1)), None);
+        assert_eq!(extensions.insert("text"), None);
+        assert_eq!(extensions.insert(Counter(2)), Some(Counter(1)));
+        extensions.get_mut::<Counter>().unwrap().0 += 1;
+        assert_eq!(extensions.get::<Counter>(), Some(&Counter(3)));
+        assert_eq!(extensions.get::<&str>(), Some(&"text"));
+        assert_eq!(extensions.get::<String>(), None);
+
+        assert_eq!(extensions.remove::<Counter>(), Some(Counter(3)));
+        assert_eq!(extensions.get::<Counter>(), None);
+        extensions.get_or_insert_with(|| Counter(10)).0 += 1;
+        extensions.get_or_insert_with(|| Counter(10)).0 += 1;
+        assert_eq!(extensions.get::<Counter>(), Some(&Counter(12)));
+    }
+}
@@ -0,0 +1,243 @@
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// When the sum of all ranks exceeds this value, the ranks are aged, so that old directories are
+/// eventually forgotten.
+const MAX_TOTAL_RANK: f64 = 9000.0;
+
+/// A visited directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DirectoryEntry {
+    pub path: PathBuf,
+    /// Incremented on every visit
+    pub rank: f64,
+    /// Time of the last visit, in seconds since the Unix epoch
+    pub last_visit: u64,
+}
+
+impl DirectoryEntry {
+    /// Combines the rank with the time of the last visit, recently visited directories are
+    /// preferred.
+    pub fn frecency(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last_visit);
+        match age {
+            age if age < 60 * 60 => self.rank * 4.0,
+            age if age < 24 * 60 * 60 => self.rank * 2.0,
+            age if age < 7 * 24 * 60 * 60 => self.rank / 2.0,
+            _ => self.rank / 4.0,
+        }
+    }
+}
+
+/// Database of visited directories used by `z`, ranked by "frecency" (frequency and recency).
+/// It is stored in a file with one `path|rank|last visit` line per directory.
+#[derive(Debug, Default)]
+pub struct DirectoryDatabase {
+    pub entries: Vec<DirectoryEntry>,
+}
+
+impl DirectoryDatabase {
+    /// Loads the database, a missing file is treated as an empty database and invalid lines
+    /// are skipped.
+    pub fn load(path: &Path) -> Self {
+        let entries = std::fs::read_to_string(path)
+            .unwrap_or_default()
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.rsplitn(3, '|');
+                let last_visit = parts.next()?.parse().ok()?;
+                let rank = parts.next()?.parse().ok()?;
+                let path = PathBuf::from(parts.next()?);
+                Some(DirectoryEntry {
+                    path,
+                    rank,
+                    last_visit,
+                })
+            })
+            .collect();
+        Self { entries }
+    }
+
+    /// Writes the database to a temporary file, which then replaces the original one, so that
+    /// a shell reading the file never sees it half-written. Use `record_visit` to update the file
+    /// without losing visits recorded by concurrent shells.
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut contents = String::new();
+        for entry in &self.entries {
+            contents.push_str(&format!(
+                "{}|{}|{}\n",
+                entry.path.display(),
+                entry.rank,
+                entry.last_visit
+            ));
+        }
+        let tmp_path = path.with_extension(format!("tmp{}", std::process::id()));
+        std::fs::write(&tmp_path, contents)?;
+        std::fs::rename(tmp_path, path)
+    }
+
+    /// Records a visit of `dir` at time `now`.
+    pub fn visit(&mut self, dir: &Path, now: u64) {
+        match self.entries.iter_mut().find(|entry| entry.path == dir) {
+            Some(entry) => {
+                entry.rank += 1.0;
+                entry.last_visit = now;
+            }
+            None => self.entries.push(DirectoryEntry {
+                path: dir.to_path_buf(),
+                rank: 1.0,
+                last_visit: now,
+            }),
+        }
+        let total: f64 = self.entries.iter().map(|entry| entry.rank).sum();
+        if total > MAX_TOTAL_RANK {
+            for entry in &mut self.entries {
+                entry.rank *= 0.99;
+            }
+            self.entries.retain(|entry| entry.rank >= 1.0);
+        }
+    }
+
+    /// Returns the entries whose path contains all `fragments` in the given order (ignoring
+    /// case), the best match comes first.
+    pub fn find(&self, fragments: &[&str], now: u64) -> Vec<&DirectoryEntry> {
+        let fragments: Vec<String> = fragments
+            .iter()
+            .map(|fragment| fragment.to_lowercase())
+            .collect();
+        let mut matches: Vec<&DirectoryEntry> = self
+            .entries
+            .iter()
+            .filter(|entry| {
+                let path = entry.path.to_string_lossy().to_lowercase();
+                let mut rest = path.as_str();
+                fragments
+                    .iter()
+                    .all(|fragment| match rest.find(fragment.as_str()) {
+                        Some(index) => {
+                            rest = &rest[index + fragment.len()..];
+                            true
+                        }
+                        None => false,
+                    })
+            })
+            .collect();
+        matches.sort_by(|a, b| b.frecency(now).total_cmp(&a.frecency(now)));
+        matches
+    }
+}
+
+/// Records a visit of `dir` in the database stored at `path`.
+/// The database is locked from loading until saving, so concurrent shells do not overwrite
+/// each other's visits.
+pub fn record_visit(path: &Path, dir: &Path) -> std::io::Result<()> {
+    // The database file itself is replaced when it is saved, so a separate file is locked
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(path.with_extension("lock"))?;
+    lock.lock()?;
+    let mut database = DirectoryDatabase::load(path);
+    database.visit(dir, now());
+    database.save(path)
+}
+
+/// Current time in seconds since the Unix epoch.
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frecency::{record_visit, DirectoryDatabase, MAX_TOTAL_RANK};
+    use std::path::{Path, PathBuf};
+
+    const NOW: u64 = 1_700_000_000;
+    const DAY: u64 = 24 * 60 * 60;
+
+    fn paths<'a>(entries: &[&'a crate::frecency::DirectoryEntry]) -> Vec<&'a Path> {
+        entries.iter().map(|entry| entry.path.as_path()).collect()
+    }
+
+    #[test]
+    fn rank_by_frequency_and_recency() {
+        let mut database = DirectoryDatabase::default();
+        for _ in 0..5 {
+            database.visit(Path::new("/src/old-project"), NOW - 10 * DAY);
+        }
+        database.visit(Path::new("/src/project"), NOW - 60);
+        database.visit(Path::new("/src/project"), NOW - 60);
+        database.visit(Path::new("/home/docs"), NOW);
+
+        assert_eq!(
+            paths(&database.find(&["proj"], NOW)),
+            [Path::new("/src/project"), Path::new("/src/old-project")]
+        );
+        // Fragments have to match in order
+        assert_eq!(
+            paths(&database.find(&["SRC", "old"], NOW)),
+            [Path::new("/src/old-project")]
+        );
+        assert!(database.find(&["old", "src"], NOW).is_empty());
+    }
+
+    #[test]
+    fn age_ranks() {
+        let mut database = DirectoryDatabase::default();
+        database.visit(Path::new("/rare"), NOW);
+        for _ in 0..MAX_TOTAL_RANK as usize {
+            database.visit(Path::new("/frequent"), NOW);
+        }
+        assert_eq!(database.entries.len(), 1);
+        assert_eq!(database.entries[0].path, PathBuf::from("/frequent"));
+        assert!(database.entries[0].rank < MAX_TOTAL_RANK);
+    }
+
+    #[test]
+    fn persist_database() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("z");
+        let mut database = DirectoryDatabase::load(&path);
+        database.visit(Path::new("/a|b"), NOW);
+        database.visit(Path::new("/c"), NOW);
+        database.visit(Path::new("/c"), NOW + 1);
+        database.save(&path).unwrap();
+        std::fs::write(
+            &path,
+            format!("{}invalid line\n", std::fs::read_to_string(&path).unwrap()),
+        )
+        .unwrap();
+
+        let database = DirectoryDatabase::load(&path);
+        assert_eq!(database.entries.len(), 2);
+        assert_eq!(database.entries[0].path, PathBuf::from("/a|b"));
+        assert_eq!(database.entries[1].rank, 2.0);
+        assert_eq!(database.entries[1].last_visit, NOW + 1);
+    }
+
+    #[test]
+    fn concurrent_visits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("z");
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let path = path.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..20 {
+                        record_visit(&path, Path::new("/a")).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let database = DirectoryDatabase::load(&path);
+        assert_eq!(database.entries[0].rank, 160.0);
+    }
+}
@@ -0,0 +1,273 @@
+use crate::lexer::{Word, WordPart};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PatternChar {
+    Literal(char),
+    /// `?`
+    AnyChar,
+    /// `*`
+    AnyString,
+    /// `[abc]`, `[a-z]` or `[!a-z]`
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+type Pattern = Vec<PatternChar>;
+
+/// Expands glob patterns (`*`, `?`, `[...]`) found in the unquoted parts of `word` into the list
+/// of matching paths, relative to `workdir`.
+///
+/// If the word does not contain any pattern, or nothing matches, the text of the word is
+/// returned unchanged.
+pub fn expand_glob(word: &Word, workdir: &Path) -> Vec<String> {
+    let pattern = parse_pattern(word);
+    if pattern.iter().all(|c| matches!(c, PatternChar::Literal(_))) {
+        return vec![word.text()];
+    }
+
+    let mut components = pattern
+        .split(|c| *c == PatternChar::Literal('/'))
+        .peekable();
+    // (path used for reading the filesystem, path that will be returned to the user)
+    let mut candidates: Vec<(PathBuf, String)> = vec![];
+    if components.next_if(|c| c.is_empty()).is_some() {
+        candidates.push((PathBuf::from("/"), "/".to_string()));
+    } else {
+        candidates.push((workdir.to_path_buf(), String::new()));
+    }
+
+    while let Some(component) = components.next() {
+        let is_last = components.peek().is_none();
+        if component.is_empty() {
+            // Repeated or trailing slash
+            for (_, display) in &mut candidates {
+                if !display.ends_with('/') {
+                    display.push('/');
+                }
+            }
+            continue;
+        }
+
+        let mut next = vec![];
+        for (path, display) in candidates {
+            let join = |name: &str| {
+                let mut display = display.clone();
+                if !display.is_empty() && !display.ends_with('/') {
+                    display.push('/');
+                }
+                display.push_str(name);
+                (path.join(name), display)
+            };
+
+            if let Some(literal) = as_literal(component) {
+                let candidate = join(&literal);
+                if candidate.0.exists() {
+                    next.push(candidate);
+                }
+                continue;
+            }
+
+            let Ok(entries) = std::fs::read_dir(&path) else {
+                continue;
+            };
+            let mut names: Vec<String> = entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| is_last || entry.path().is_dir())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| matches_pattern(component, name))
+                .collect();
+            names.sort();
+            next.extend(names.iter().map(|name| join(name)));
+        }
+        candidates = next;
+    }
+
+    if candidates.is_empty() {
+        return vec![word.text()];
+    }
+    candidates.into_iter().map(|(_, display)| display).collect()
+}
+
+fn as_literal(pattern: &[PatternChar]) -> Option<String> {
+    pattern
+        .iter()
+        .map(|c| match c {
+            PatternChar::Literal(c) => Some(*c),
+            _ => None,
+        })
+        .collect()
+}
+
+fn parse_pattern(word: &Word) -> Pattern {
+    let mut pattern = vec![];
+    for part in &word.parts {
+        match part {
+            WordPart::Unquoted(text) => {
+                let chars: Vec<char> = text.chars().collect();
+                let mut index = 0;
+                while index < chars.len() {
+                    match chars[index] {
+                        '*' => pattern.push(PatternChar::AnyString),
+                        '?' => pattern.push(PatternChar::AnyChar),
+                        '[' => match parse_class(&chars[index + 1..]) {
+                            Some((class, length)) => {
+                                pattern.push(class);
+                                index += length;
+                            }
+                            None => pattern.push(PatternChar::Literal('[')),
+                        },
+                        c => pattern.push(PatternChar::Literal(c)),
+                    }
+                    index += 1;
+                }
+            }
+            part => pattern.extend(part.text().chars().map(PatternChar::Literal)),
+        }
+    }
+    pattern
+}
+
+/// Parses a character class that follows `[`.
+/// Returns the class and the number of characters that it has consumed.
+fn parse_class(chars: &[char]) -> Option<(PatternChar, usize)> {
+    let mut index = 0;
+    let negated = matches!(chars.first(), Some('!' | '^'));
+    if negated {
+        index += 1;
+    }
+    let mut ranges = vec![];
+    // `]` right at the start is a literal character
+    let start = index;
+    while index < chars.len() {
+        let c = chars[index];
+        if c == ']' && index > start {
+            return Some((PatternChar::Class { negated, ranges }, index + 1));
+        }
+        if chars.get(index + 1) == Some(&'-') && chars.get(index + 2).is_some_and(|c| *c != ']') {
+            ranges.push((c, chars[index + 2]));
+            index += 3;
+        } else {
+            ranges.push((c, c));
+            index += 1;
+        }
+    }
+    None
+}
+
+fn matches_pattern(pattern: &[PatternChar], name: &str) -> bool {
+    // Hidden files have to be matched explicitly
+    if name.starts_with('.') && pattern.first() != Some(&PatternChar::Literal('.')) {
+        return false;
+    }
+    let name: Vec<char> = name.chars().collect();
+    matches_chars(pattern, &name)
+}
+
+fn matches_chars(pattern: &[PatternChar], name: &[char]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((PatternChar::AnyString, rest)) => {
+            (0..=name.len()).any(|skip| matches_chars(rest, &name[skip..]))
+        }
+        Some((c, rest)) => {
+            let Some((first, name_rest)) = name.split_first() else {
+                return false;
+            };
+            let matches = match c {
+                PatternChar::Literal(c) => c == first,
+                PatternChar::AnyChar => true,
+                PatternChar::Class { negated, ranges } => {
+                    ranges
+                        .iter()
+                        .any(|(start, end)| (start..=end).contains(&first))
+                        != *negated
+                }
+                PatternChar::AnyString => unreachable!(),
+            };
+            matches && matches_chars(rest, name_rest)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::glob::expand_glob;
+    use crate::lexer::{tokenize, Token};
+    use std::path::Path;
+
+    fn expand(word: &str, workdir: &Path) -> Vec<String> {
+        match tokenize(word).unwrap().pop().unwrap() {
+            Token::Word(word) => expand_glob(&word, workdir),
+            token => panic!("Unexpected token {token:?}"),
+        }
+    }
+
+    fn create_files(dir: &Path, files: &[&str]) {
+        for file in files {
+            let path = dir.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+    }
+
+    #[test]
+    fn no_pattern() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(expand("foo.txt", dir.path()), vec!["foo.txt"]);
+    }
+
+    #[test]
+    fn star() {
+        let dir = tempfile::tempdir().unwrap();
+        create_files(dir.path(), &["b.txt", "a.txt", "c.rs", ".hidden.txt"]);
+        assert_eq!(expand("*.txt", dir.path()), vec!["a.txt", "b.txt"]);
+        assert_eq!(expand(".*.txt", dir.path()), vec![".hidden.txt"]);
+    }
+
+    #[test]
+    fn question_mark_and_class() {
+        let dir = tempfile::tempdir().unwrap();
+        create_files(dir.path(), &["a1", "a2", "a3", "ab"]);
+        assert_eq!(expand("a?", dir.path()), vec!["a1", "a2", "a3", "ab"]);
+        assert_eq!(expand("a[12]", dir.path()), vec!["a1", "a2"]);
+        assert_eq!(expand("a[2-9]", dir.path()), vec!["a2", "a3"]);
+        assert_eq!(expand("a[!0-9]", dir.path()), vec!["ab"]);
+    }
+
+    #[test]
+    fn nested_directories() {
+        let dir = tempfile::tempdir().unwrap();
+        create_files(dir.path(), &["src/a.rs", "src/b.rs", "tests/c.rs", "d.rs"]);
+        assert_eq!(
+            expand("*/*.rs", dir.path()),
+            vec!["src/a.rs", "src/b.rs", "tests/c.rs"]
+        );
+        assert_eq!(expand("src/*", dir.path()), vec!["src/a.rs", "src/b.rs"]);
+    }
+
+    #[test]
+    fn absolute_path() {
+        let dir = tempfile::tempdir().unwrap();
+        create_files(dir.path(), &["a.txt"]);
+        let pattern = format!("{}/*.txt", dir.path().display());
+        let expected = format!("{}/a.txt", dir.path().display());
+        assert_eq!(expand(&pattern, Path::new("/")), vec![expected]);
+    }
+
+    #[test]
+    fn no_match() {
+        let dir = tempfile::tempdir().unwrap();
+        assert_eq!(expand("*.txt", dir.path()), vec!["*.txt"]);
+    }
+
+    #[test]
+    fn quoted_pattern() {
+        let dir = tempfile::tempdir().unwrap();
+        create_files(dir.path(), &["a.txt", "*.txt"]);
+        assert_eq!(expand("'*'.txt", dir.path()), vec!["*.txt"]);
+        assert_eq!(expand("\\*.txt", dir.path()), vec!["*.txt"]);
+    }
+}
@@ -0,0 +1,81 @@
+use crate::command::CommandError;
+use crate::Shell;
+use std::io::Write;
+
+/// Builtins that are implemented directly by the executor, with their descriptions.
+pub(crate) const SHELL_BUILTINS: &[(&str, &str)] = &[
+    ("break", "exit from a loop"),
+    ("continue", "start the next iteration of a loop"),
+    ("exit", "exit the shell"),
+    ("help", "describe builtins and plugin commands"),
+    ("return", "return from a function"),
+];
+
+impl Shell {
+    /// `help [NAME]...` prints the descriptions of the given commands, or of all builtins and
+    /// commands provided by plugins.
+    pub(crate) fn help(
+        &self,
+        names: &[String],
+        output: &mut dyn Write,
+    ) -> Result<(), CommandError> {
+        let mut builtins: Vec<(&str, &str)> = SHELL_BUILTINS.to_vec();
+        builtins.extend(self.commands.iter().filter_map(|command| {
+            Some((command.name()?, command.description().unwrap_or_default()))
+        }));
+        builtins.sort();
+        let plugins: Vec<(&str, String)> = self
+            .plugins
+            .iter()
+            .flat_map(|plugin| {
+                plugin.commands().iter().map(|command| {
+                    let description = format!(
+                        "{} (plugin `{}`)",
+                        command.description,
+                        plugin.path().display()
+                    );
+                    (command.name.as_str(), description.trim_start().to_string())
+                })
+            })
+            .collect();
+
+        let mut lines = vec![];
+        if names.is_empty() {
+            let width = builtins
+                .iter()
+                .map(|(name, _)| *name)
+                .chain(plugins.iter().map(|(name, _)| *name))
+                .map(|name| name.len())
+                .max()
+                .unwrap_or(0);
+            lines.push("Builtins:".to_string());
+            for (name, description) in &builtins {
+                lines.push(format!("  {name:width$}  {description}"));
+            }
+            if !plugins.is_empty() {
+                lines.push("\nPlugins:".to_string());
+                for (name, description) in &plugins {
+                    lines.push(format!("  {name:width$}  {description}"));
+                }
+            }
+        }
+        for name in names {
+            let description = builtins
+                .iter()
+                .find(|(builtin, _)| builtin == name)
+                .map(|(_, description)| *description)
+                .or_else(|| {
+                    plugins
+                        .iter()
+                        .find(|(command, _)| command == name)
+                        .map(|(_, description)| description.as_str())
+                })
+                .ok_or_else(|| CommandError::Usage(format!("help: no help for `{name}`")))?;
+            lines.push(format!("{name}: {description}"));
+        }
+        for line in lines {
+            writeln!(output, "{}", line.trim_end()).map_err(CommandError::IO)?;
+        }
+        Ok(())
+    }
+}
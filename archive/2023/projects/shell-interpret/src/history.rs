@@ -0,0 +1,197 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Maximum number of lines kept in memory and loaded from the history file.
+const MAX_ENTRIES: usize = 1000;
+
+/// History of entered lines, optionally persisted in a file (one line per entry, newlines and
+/// backslashes in entries are escaped).
+#[derive(Debug, Default)]
+pub struct History {
+    entries: Vec<String>,
+    path: Option<PathBuf>,
+    /// Number of entries in the file, it is compacted instead of growing past `MAX_ENTRIES`
+    file_entries: usize,
+}
+
+impl History {
+    /// Loads the history from `path`. New entries will be appended to the same file.
+    /// A missing file is treated as an empty history.
+    pub fn load(path: PathBuf) -> Self {
+        let mut entries: Vec<String> = std::fs::read_to_string(&path)
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(unescape)
+            .collect();
+        let file_entries = entries.len();
+        if entries.len() > MAX_ENTRIES {
+            entries.drain(..entries.len() - MAX_ENTRIES);
+        }
+        Self {
+            entries,
+            path: Some(path),
+            file_entries,
+        }
+    }
+
+    /// Adds a line to the history. Empty lines and repetitions of the last entry are ignored.
+    pub fn add(&mut self, line: &str) -> std::io::Result<()> {
+        if line.trim().is_empty() || self.entries.last().is_some_and(|last| last == line) {
+            return Ok(());
+        }
+        self.entries.push(line.to_string());
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.remove(0);
+        }
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if self.file_entries >= MAX_ENTRIES {
+            return self.compact();
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", escape(line))?;
+        self.file_entries += 1;
+        Ok(())
+    }
+
+    /// Rewrites the file with only the entries kept in memory. The new contents are written to a
+    /// temporary file first, so that the history is not lost if writing fails.
+    fn compact(&mut self) -> std::io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut contents = String::new();
+        for entry in &self.entries {
+            contents.push_str(&escape(entry));
+            contents.push('\n');
+        }
+        let temporary = path.with_extension("tmp");
+        std::fs::write(&temporary, contents)?;
+        std::fs::rename(&temporary, path)?;
+        self.file_entries = self.entries.len();
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.entries.get(index).map(|entry| entry.as_str())
+    }
+
+    /// Finds the most recent entry older than `before` that contains `query`.
+    /// Returns its index.
+    pub fn search(&self, query: &str, before: usize) -> Option<usize> {
+        self.entries[..before.min(self.entries.len())]
+            .iter()
+            .rposition(|entry| entry.contains(query))
+    }
+}
+
+/// Escapes an entry so that it fits on a single line of the file.
+fn escape(entry: &str) -> String {
+    entry.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(line: &str) -> String {
+    let mut entry = String::with_capacity(line.len());
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                entry.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                entry.push('\\');
+                chars.next();
+            }
+            _ => entry.push(c),
+        }
+    }
+    entry
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::{History, MAX_ENTRIES};
+
+    #[test]
+    fn add_entries() {
+        let mut history = History::default();
+        history.add("ls").unwrap();
+        history.add("ls").unwrap();
+        history.add("  ").unwrap();
+        history.add("cd foo").unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(0), Some("ls"));
+        assert_eq!(history.get(1), Some("cd foo"));
+    }
+
+    #[test]
+    fn search() {
+        let mut history = History::default();
+        for line in ["cd foo", "ls", "cd bar", "pwd"] {
+            history.add(line).unwrap();
+        }
+        assert_eq!(history.search("cd", history.len()), Some(2));
+        assert_eq!(history.search("cd", 2), Some(0));
+        assert_eq!(history.search("cd", 0), None);
+        assert_eq!(history.search("xyz", history.len()), None);
+    }
+
+    #[test]
+    fn persist_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+        let mut history = History::load(path.clone());
+        assert!(history.is_empty());
+        history.add("echo 1").unwrap();
+        history.add("echo 2").unwrap();
+
+        let history = History::load(path);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(1), Some("echo 2"));
+    }
+
+    #[test]
+    fn persist_multiline_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+        let mut history = History::load(path.clone());
+        history.add("for x in 1 2\ndo echo $x\ndone").unwrap();
+        history.add("echo a\\nb").unwrap();
+
+        let history = History::load(path);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.get(0), Some("for x in 1 2\ndo echo $x\ndone"));
+        assert_eq!(history.get(1), Some("echo a\\nb"));
+    }
+
+    #[test]
+    fn compact_history_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+        let mut history = History::load(path.clone());
+        for i in 0..MAX_ENTRIES + 10 {
+            history.add(&format!("echo {i}")).unwrap();
+        }
+        let lines = std::fs::read_to_string(&path).unwrap().lines().count();
+        assert!(lines <= MAX_ENTRIES);
+
+        let history = History::load(path);
+        assert_eq!(history.len(), MAX_ENTRIES);
+        assert_eq!(
+            history.get(MAX_ENTRIES - 1),
+            Some(format!("echo {}", MAX_ENTRIES + 9).as_str())
+        );
+    }
+}
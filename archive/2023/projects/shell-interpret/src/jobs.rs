@@ -0,0 +1,290 @@
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobStatus {
+    Running,
+    Stopped,
+    /// Exit code of the last process of the job
+    Done(i32),
+    /// The last process of the job was killed by a signal
+    Signaled(libc::c_int),
+}
+
+impl JobStatus {
+    /// Returns `true` if all processes of the job have ended.
+    pub fn is_finished(self) -> bool {
+        matches!(self, JobStatus::Done(_) | JobStatus::Signaled(_))
+    }
+
+    /// Exit status of a finished job, in the same way as for programs in the foreground.
+    pub fn exit_code(self) -> Option<i32> {
+        match self {
+            JobStatus::Done(code) => Some(code),
+            JobStatus::Signaled(signal) => Some(128 + signal),
+            JobStatus::Running | JobStatus::Stopped => None,
+        }
+    }
+}
+
+impl Display for JobStatus {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JobStatus::Running => f.write_str("Running"),
+            JobStatus::Stopped => f.write_str("Stopped"),
+            JobStatus::Done(0) => f.write_str("Done"),
+            JobStatus::Done(code) => write!(f, "Exit {code}"),
+            // e.g. "Terminated" or "Killed"
+            JobStatus::Signaled(signal) => {
+                let description = unsafe { std::ffi::CStr::from_ptr(libc::strsignal(*signal)) };
+                f.write_str(&description.to_string_lossy())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProcessStatus {
+    Running,
+    Stopped,
+    Exited(i32),
+    Signaled(libc::c_int),
+}
+
+impl ProcessStatus {
+    fn is_finished(self) -> bool {
+        matches!(self, ProcessStatus::Exited(_) | ProcessStatus::Signaled(_))
+    }
+}
+
+/// A pipeline running in the background.
+/// All its processes belong to a single process group, so that they can be signaled together.
+#[derive(Debug)]
+pub struct Job {
+    pub id: usize,
+    /// Command line that has started the job
+    pub command: String,
+    processes: Vec<(libc::pid_t, ProcessStatus)>,
+}
+
+impl Job {
+    pub fn status(&self) -> JobStatus {
+        if self
+            .processes
+            .iter()
+            .any(|(_, status)| *status == ProcessStatus::Stopped)
+        {
+            return JobStatus::Stopped;
+        }
+        let mut status = JobStatus::Done(0);
+        for (_, process_status) in &self.processes {
+            status = match process_status {
+                ProcessStatus::Exited(code) => JobStatus::Done(*code),
+                ProcessStatus::Signaled(signal) => JobStatus::Signaled(*signal),
+                _ => return JobStatus::Running,
+            }
+        }
+        status
+    }
+
+    pub fn process_group(&self) -> libc::pid_t {
+        self.processes[0].0
+    }
+
+    /// PID of the last process of the pipeline.
+    pub fn last_pid(&self) -> libc::pid_t {
+        self.processes[self.processes.len() - 1].0
+    }
+
+    /// Sends a signal to all processes of the job.
+    pub fn signal(&mut self, signal: libc::c_int) -> std::io::Result<()> {
+        if unsafe { libc::kill(-self.process_group(), signal) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        if signal == libc::SIGCONT {
+            for (_, status) in &mut self.processes {
+                if *status == ProcessStatus::Stopped {
+                    *status = ProcessStatus::Running;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Updates the status of the processes of the job.
+    /// If `block` is `true`, waits until all processes exit or some of them is stopped.
+    fn update(&mut self, block: bool) {
+        for (pid, process_status) in &mut self.processes {
+            while !process_status.is_finished() {
+                let flags =
+                    libc::WUNTRACED | libc::WCONTINUED | if block { 0 } else { libc::WNOHANG };
+                let mut status = 0;
+                let res = unsafe { libc::waitpid(*pid, &mut status, flags) };
+                if res == 0 {
+                    break;
+                }
+                if res < 0 {
+                    // A signal has arrived, the handler has already run
+                    if std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted {
+                        continue;
+                    }
+                    // The process does not exist anymore (it was already reaped)
+                    *process_status = ProcessStatus::Exited(1);
+                    break;
+                }
+                *process_status = if libc::WIFEXITED(status) {
+                    ProcessStatus::Exited(libc::WEXITSTATUS(status))
+                } else if libc::WIFSIGNALED(status) {
+                    ProcessStatus::Signaled(libc::WTERMSIG(status))
+                } else if libc::WIFSTOPPED(status) {
+                    ProcessStatus::Stopped
+                } else {
+                    ProcessStatus::Running
+                };
+                if !block || *process_status == ProcessStatus::Stopped {
+                    break;
+                }
+            }
+            if block && *process_status == ProcessStatus::Stopped {
+                return;
+            }
+        }
+    }
+}
+
+/// Table of background jobs.
+#[derive(Debug, Default)]
+pub struct Jobs {
+    jobs: Vec<Job>,
+}
+
+impl Jobs {
+    /// Registers a new job and returns its ID.
+    /// The first PID has to be the leader of the process group of the job.
+    pub fn add(&mut self, command: String, pids: Vec<libc::pid_t>) -> usize {
+        assert!(!pids.is_empty());
+        let id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        self.jobs.push(Job {
+            id,
+            command,
+            processes: pids
+                .into_iter()
+                .map(|pid| (pid, ProcessStatus::Running))
+                .collect(),
+        });
+        id
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Job> {
+        self.jobs.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut Job> {
+        self.jobs.iter_mut()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Job> {
+        self.jobs.iter_mut().find(|job| job.id == id)
+    }
+
+    /// Returns the ID of the most recently started job.
+    pub fn current(&self) -> Option<usize> {
+        self.jobs.last().map(|job| job.id)
+    }
+
+    /// Finds the job referenced by `spec` (`%n`, `n`, `%%` or `%+`).
+    pub fn find(&self, spec: &str) -> Option<usize> {
+        let id = match spec {
+            "%%" | "%+" => return self.current(),
+            spec => spec
+                .strip_prefix('%')
+                .unwrap_or(spec)
+                .parse::<usize>()
+                .ok()?,
+        };
+        self.jobs.iter().find(|job| job.id == id).map(|job| job.id)
+    }
+
+    /// Finds the job referenced by a job-control builtin.
+    /// Without a spec, the current job is used.
+    pub fn find_for_command(&self, command: &str, spec: Option<&String>) -> std::io::Result<usize> {
+        let id = match spec {
+            Some(spec) => self.find(spec),
+            None => self.current(),
+        };
+        id.ok_or_else(|| {
+            let spec = spec.map(|spec| spec.as_str()).unwrap_or("current");
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("{command}: {spec}: no such job"),
+            )
+        })
+    }
+
+    /// Updates the status of all jobs without blocking.
+    pub fn refresh(&mut self) {
+        for job in &mut self.jobs {
+            job.update(false);
+        }
+    }
+
+    /// Blocks until the job finishes or it is stopped.
+    /// Finished jobs are removed from the table.
+    pub fn wait(&mut self, id: usize) -> Option<JobStatus> {
+        let index = self.jobs.iter().position(|job| job.id == id)?;
+        let job = &mut self.jobs[index];
+        job.update(true);
+        let status = job.status();
+        if status.is_finished() {
+            self.jobs.remove(index);
+        }
+        Some(status)
+    }
+
+    /// Removes and returns jobs that have finished.
+    pub fn take_finished(&mut self) -> Vec<Job> {
+        self.refresh();
+        let (finished, running) = std::mem::take(&mut self.jobs)
+            .into_iter()
+            .partition(|job| job.status().is_finished());
+        self.jobs = running;
+        finished
+    }
+}
+
+/// Names of the signals known by `kill` and `trap`, without the `SIG` prefix.
+pub const SIGNALS: &[(&str, libc::c_int)] = &[
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("TERM", libc::SIGTERM),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+];
+
+/// Parses a signal given either as a number or a name (`KILL` or `SIGKILL`).
+pub fn parse_signal(signal: &str) -> Option<libc::c_int> {
+    if let Ok(number) = signal.parse() {
+        return Some(number);
+    }
+    let name = signal.strip_prefix("SIG").unwrap_or(signal);
+    SIGNALS
+        .iter()
+        .find(|(signal_name, _)| *signal_name == name)
+        .map(|(_, signal)| *signal)
+}
+
+/// Returns the name of the signal without the `SIG` prefix.
+pub fn signal_name(signal: libc::c_int) -> Option<&'static str> {
+    SIGNALS
+        .iter()
+        .find(|(_, number)| *number == signal)
+        .map(|(name, _)| *name)
+}
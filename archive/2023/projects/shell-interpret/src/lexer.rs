@@ -0,0 +1,895 @@
+use crate::ast::Statement;
+use crate::command::CommandError;
+use crate::parser::parse_tokens;
+use crate::pipeline::RedirectKind;
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WordPart {
+    /// Text that was not quoted, it can contain glob patterns.
+    Unquoted(String),
+    /// Text inside quotes or an escaped character, it is always taken literally.
+    Quoted(String),
+    /// `$NAME`, `${NAME}` or `${NAME:-default}`. If it is not `quoted` (inside double quotes or
+    /// a here-document), the value is split into words.
+    Variable {
+        name: String,
+        default: Option<Word>,
+        quoted: bool,
+    },
+    /// `$?`
+    LastStatus,
+    /// `~` at the start of a word
+    Tilde,
+    /// `$(commands)` or `` `commands` ``, whose output replaces it. If it is not `quoted` (inside
+    /// double quotes or a here-document), the output is split into words.
+    CommandSubstitution {
+        script: String,
+        statements: Rc<Vec<Statement>>,
+        quoted: bool,
+    },
+    /// `$((expression))`, the expression can contain other expansions
+    Arithmetic(Word),
+}
+
+impl WordPart {
+    /// Returns the text of the part as it was written, without quotes.
+    pub fn text(&self) -> String {
+        match self {
+            WordPart::Unquoted(text) | WordPart::Quoted(text) => text.clone(),
+            WordPart::Variable {
+                name,
+                default: None,
+                ..
+            } => format!("${{{name}}}"),
+            WordPart::Variable {
+                name,
+                default: Some(default),
+                ..
+            } => format!("${{{name}:-{}}}", default.text()),
+            WordPart::LastStatus => "$?".to_string(),
+            WordPart::Tilde => "~".to_string(),
+            WordPart::CommandSubstitution { script, .. } => format!("$({script})"),
+            WordPart::Arithmetic(expression) => format!("$(({}))", expression.text()),
+        }
+    }
+}
+
+/// A single shell word, e.g. `foo"bar baz"*` consists of three parts.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Word {
+    pub parts: Vec<WordPart>,
+}
+
+impl Word {
+    /// Returns the text of the word, with quotes removed and without any expansion.
+    pub fn text(&self) -> String {
+        self.parts.iter().map(|part| part.text()).collect()
+    }
+
+    fn push_unquoted(&mut self, c: char) {
+        match self.parts.last_mut() {
+            Some(WordPart::Unquoted(text)) => text.push(c),
+            _ => self.parts.push(WordPart::Unquoted(c.to_string())),
+        }
+    }
+
+    fn push_quoted(&mut self, c: char) {
+        match self.parts.last_mut() {
+            Some(WordPart::Quoted(text)) => text.push(c),
+            _ => self.parts.push(WordPart::Quoted(c.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Token {
+    Word(Word),
+    /// `|`
+    Pipe,
+    /// `<`, `>`, `>>` or `<<`, which is followed by a word with the contents of the
+    /// here-document
+    Redirect(RedirectKind),
+    /// `&`
+    Background,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// `;`
+    Semicolon,
+    /// End of line
+    Newline,
+    /// `(`
+    LeftParen,
+    /// `)`
+    RightParen,
+}
+
+/// Splits a line into words and operators.
+///
+/// Supports single quotes (everything is taken literally), double quotes (backslash can escape
+/// `"`, `\`, `$` and `` ` ``), backslash escapes outside of quotes and `$` expansions and
+/// backquotes (outside of quotes and inside double quotes).
+///
+/// The commands of `$(...)` are parsed right away, with their own quoting, so e.g.
+/// `"$(echo "a b")"` is a single word. Inside `` `...` `` a backslash only escapes `$`, `` ` ``
+/// and `\` (and `"` inside double quotes), the rest is parsed like the commands of `$(...)`.
+/// The lines of a here-document (`<<END`) follow the line with the redirection, up to a line
+/// with the delimiter.
+pub fn tokenize(line: &str) -> Result<Vec<Token>, CommandError> {
+    Lexer::new(line, true).tokenize()
+}
+
+/// Characters of the lexed text, together with the position of the next one.
+struct Source<'a> {
+    text: &'a str,
+    position: usize,
+}
+
+impl Source<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn next_if(&mut self, condition: impl FnOnce(&char) -> bool) -> Option<char> {
+        match self.peek() {
+            Some(c) if condition(&c) => self.next(),
+            _ => None,
+        }
+    }
+
+    fn next_if_eq(&mut self, expected: &char) -> Option<char> {
+        self.next_if(|c| c == expected)
+    }
+}
+
+/// A here-document whose lines have not been read yet.
+struct PendingDocument {
+    /// Index of the word token that receives the contents
+    token: usize,
+    delimiter: String,
+    /// The delimiter was quoted, so the contents are taken literally
+    quoted: bool,
+    /// `<<-` removes leading tabs from the lines
+    strip_tabs: bool,
+}
+
+struct Lexer<'a> {
+    chars: Source<'a>,
+    /// If `false`, whitespace and operators are treated as normal characters
+    operators: bool,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str, operators: bool) -> Self {
+        Self {
+            chars: Source { text, position: 0 },
+            operators,
+        }
+    }
+
+    fn tokenize(mut self) -> Result<Vec<Token>, CommandError> {
+        self.tokens(false)
+    }
+
+    /// Reads tokens until the end of the text, or if `substitution` is `true`, until the `)` that
+    /// ends a command substitution, which is consumed.
+    fn tokens(&mut self, substitution: bool) -> Result<Vec<Token>, CommandError> {
+        let mut tokens = vec![];
+        // `Some` if we are inside a word, even an empty one (e.g. `''`)
+        let mut word: Option<Word> = None;
+        // Number of `(` tokens without a matching `)`
+        let mut depth: usize = 0;
+        let mut documents: Vec<PendingDocument> = vec![];
+
+        while let Some(c) = self.chars.next() {
+            match c {
+                ' ' | '\t' | '\n' | '|' | '<' | '>' | '&' | ';' | '(' | ')' if self.operators => {
+                    if let Some(word) = word.take() {
+                        tokens.push(Token::Word(word));
+                    }
+                    match c {
+                        '\n' => {
+                            tokens.push(Token::Newline);
+                            for document in documents.drain(..) {
+                                tokens[document.token] =
+                                    Token::Word(self.here_document(&document)?);
+                            }
+                        }
+                        ';' => tokens.push(Token::Semicolon),
+                        '(' => {
+                            depth += 1;
+                            tokens.push(Token::LeftParen);
+                        }
+                        ')' if substitution && depth == 0 => {
+                            if let Some(document) = documents.first() {
+                                return Err(unterminated_document(&document.delimiter));
+                            }
+                            return Ok(tokens);
+                        }
+                        ')' => {
+                            depth = depth.saturating_sub(1);
+                            tokens.push(Token::RightParen);
+                        }
+                        '|' => {
+                            if self.chars.next_if_eq(&'|').is_some() {
+                                tokens.push(Token::Or);
+                            } else {
+                                tokens.push(Token::Pipe);
+                            }
+                        }
+                        '&' => {
+                            if self.chars.next_if_eq(&'&').is_some() {
+                                tokens.push(Token::And);
+                            } else {
+                                tokens.push(Token::Background);
+                            }
+                        }
+                        '<' if self.chars.next_if_eq(&'<').is_some() => {
+                            let strip_tabs = self.chars.next_if_eq(&'-').is_some();
+                            let (delimiter, quoted) = self.here_document_delimiter()?;
+                            tokens.push(Token::Redirect(RedirectKind::HereDocument));
+                            // Replaced by the contents at the end of the line
+                            tokens.push(Token::Word(Word::default()));
+                            documents.push(PendingDocument {
+                                token: tokens.len() - 1,
+                                delimiter,
+                                quoted,
+                                strip_tabs,
+                            });
+                        }
+                        '<' => tokens.push(Token::Redirect(RedirectKind::Input)),
+                        '>' => {
+                            if self.chars.next_if_eq(&'>').is_some() {
+                                tokens.push(Token::Redirect(RedirectKind::Append));
+                            } else {
+                                tokens.push(Token::Redirect(RedirectKind::Output));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+                '\'' => {
+                    let word = word.get_or_insert_with(Word::default);
+                    // Make sure that even an empty string creates a word part
+                    word.parts.push(WordPart::Quoted(String::new()));
+                    loop {
+                        match self.chars.next() {
+                            Some('\'') => break,
+                            Some(c) => word.push_quoted(c),
+                            None => {
+                                return Err(CommandError::InvalidSyntax(
+                                    "unterminated single quote".to_string(),
+                                ))
+                            }
+                        }
+                    }
+                }
+                '"' => {
+                    let word = word.get_or_insert_with(Word::default);
+                    word.parts.push(WordPart::Quoted(String::new()));
+                    loop {
+                        match self.chars.next() {
+                            Some('"') => break,
+                            Some('\\') => {
+                                match self.chars.next_if(|c| matches!(c, '"' | '\\' | '$' | '`')) {
+                                    Some(c) => word.push_quoted(c),
+                                    None => word.push_quoted('\\'),
+                                }
+                            }
+                            Some('$') => match self.dollar(true)? {
+                                Some(part) => word.parts.push(part),
+                                None => word.push_quoted('$'),
+                            },
+                            Some('`') => word.parts.push(self.backquote_substitution(true)?),
+                            Some(c) => word.push_quoted(c),
+                            None => {
+                                return Err(CommandError::InvalidSyntax(
+                                    "unterminated double quote".to_string(),
+                                ))
+                            }
+                        }
+                    }
+                }
+                '\\' => match self.chars.next() {
+                    // Line continuation
+                    Some('\n') if self.operators => {}
+                    Some(c) => word.get_or_insert_with(Word::default).push_quoted(c),
+                    None => {
+                        return Err(CommandError::InvalidSyntax(
+                            "unexpected end of line after `\\`".to_string(),
+                        ))
+                    }
+                },
+                '$' => {
+                    let word = word.get_or_insert_with(Word::default);
+                    match self.dollar(false)? {
+                        Some(part) => word.parts.push(part),
+                        None => word.push_unquoted('$'),
+                    }
+                }
+                '`' => {
+                    let part = self.backquote_substitution(false)?;
+                    word.get_or_insert_with(Word::default).parts.push(part);
+                }
+                '#' if word.is_none() && self.operators => {
+                    // Comment until the end of the line
+                    while self.chars.next_if(|c| *c != '\n').is_some() {}
+                }
+                '~' if word.is_none() && self.at_word_end(true) => {
+                    word = Some(Word {
+                        parts: vec![WordPart::Tilde],
+                    });
+                }
+                c => word.get_or_insert_with(Word::default).push_unquoted(c),
+            }
+        }
+        if let Some(word) = word {
+            tokens.push(Token::Word(word));
+        }
+        if substitution {
+            return Err(CommandError::IncompleteInput(
+                "unterminated `$(`".to_string(),
+            ));
+        }
+        if let Some(document) = documents.first() {
+            return Err(unterminated_document(&document.delimiter));
+        }
+
+        Ok(tokens)
+    }
+
+    /// Reads the delimiter that follows `<<`, quotes and backslashes are removed from it.
+    /// Returns the delimiter and whether any part of it was quoted.
+    fn here_document_delimiter(&mut self) -> Result<(String, bool), CommandError> {
+        while self.chars.next_if(|c| matches!(c, ' ' | '\t')).is_some() {}
+        let mut delimiter = String::new();
+        let mut quoted = false;
+        loop {
+            match self.chars.peek() {
+                Some(quote @ ('\'' | '"')) => {
+                    self.chars.next();
+                    quoted = true;
+                    loop {
+                        match self.chars.next() {
+                            Some(c) if c == quote => break,
+                            Some(c) => delimiter.push(c),
+                            None => {
+                                return Err(CommandError::InvalidSyntax(
+                                    "unterminated quote in here-document delimiter".to_string(),
+                                ))
+                            }
+                        }
+                    }
+                }
+                Some('\\') => {
+                    self.chars.next();
+                    quoted = true;
+                    delimiter.extend(self.chars.next());
+                }
+                Some(c) if !c.is_whitespace() && !"|&;<>()".contains(c) => {
+                    self.chars.next();
+                    delimiter.push(c);
+                }
+                _ => break,
+            }
+        }
+        if delimiter.is_empty() && !quoted {
+            return Err(CommandError::InvalidSyntax(
+                "missing here-document delimiter after `<<`".to_string(),
+            ));
+        }
+        Ok((delimiter, quoted))
+    }
+
+    /// Reads the lines of a here-document up to the line with its delimiter.
+    fn here_document(&mut self, document: &PendingDocument) -> Result<Word, CommandError> {
+        let mut contents = String::new();
+        loop {
+            if self.chars.peek().is_none() {
+                return Err(unterminated_document(&document.delimiter));
+            }
+            let mut line = String::new();
+            while let Some(c) = self.chars.next_if(|c| *c != '\n') {
+                line.push(c);
+            }
+            self.chars.next();
+            let line = if document.strip_tabs {
+                line.trim_start_matches('\t')
+            } else {
+                &line
+            };
+            if line == document.delimiter {
+                break;
+            }
+            contents.push_str(line);
+            contents.push('\n');
+        }
+        if document.quoted {
+            return Ok(Word {
+                parts: vec![WordPart::Quoted(contents)],
+            });
+        }
+        Lexer::new(&contents, false).here_document_contents()
+    }
+
+    /// Parses the contents of a here-document with an unquoted delimiter, which are treated like
+    /// text in double quotes, except that `"` is not special.
+    fn here_document_contents(mut self) -> Result<Word, CommandError> {
+        let mut word = Word {
+            parts: vec![WordPart::Quoted(String::new())],
+        };
+        while let Some(c) = self.chars.next() {
+            match c {
+                '\\' => match self.chars.next_if(|c| matches!(c, '$' | '\\' | '`' | '\n')) {
+                    // Line continuation
+                    Some('\n') => {}
+                    Some(c) => word.push_quoted(c),
+                    None => word.push_quoted('\\'),
+                },
+                '$' => match self.dollar(true)? {
+                    Some(part) => word.parts.push(part),
+                    None => word.push_quoted('$'),
+                },
+                '`' => word.parts.push(self.backquote_substitution(true)?),
+                c => word.push_quoted(c),
+            }
+        }
+        Ok(word)
+    }
+
+    /// Returns `true` if the next character ends the current word.
+    /// If `slash` is `true`, a `/` is also considered to be the end of a word.
+    fn at_word_end(&mut self, slash: bool) -> bool {
+        match self.chars.peek() {
+            None => true,
+            Some('/') => slash,
+            Some(' ' | '\t' | '\n' | '|' | '<' | '>' | '&' | ';' | '(' | ')') => self.operators,
+            Some(_) => false,
+        }
+    }
+
+    /// Parses an expansion that follows `$`, `quoted` if it is inside double quotes.
+    /// Returns `None` if the `$` should be taken literally.
+    fn dollar(&mut self, quoted: bool) -> Result<Option<WordPart>, CommandError> {
+        if self.chars.next_if_eq(&'?').is_some() {
+            return Ok(Some(WordPart::LastStatus));
+        }
+        if self.chars.next_if_eq(&'(').is_some() {
+            if self.chars.next_if_eq(&'(').is_some() {
+                return self.arithmetic().map(Some);
+            }
+            return self.command_substitution(quoted).map(Some);
+        }
+        // Positional parameters (`$1`) and `$#`, `$@`, `$*`
+        if let Some(c) = self
+            .chars
+            .next_if(|c| c.is_ascii_digit() || matches!(c, '#' | '@' | '*'))
+        {
+            return Ok(Some(WordPart::Variable {
+                name: c.to_string(),
+                default: None,
+                quoted,
+            }));
+        }
+        if self.chars.next_if_eq(&'{').is_some() {
+            return self.braced_variable(quoted).map(Some);
+        }
+        let name = self.variable_name();
+        if name.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(WordPart::Variable {
+            name,
+            default: None,
+            quoted,
+        }))
+    }
+
+    /// Parses `NAME}` or `NAME:-default}` that follows `${`.
+    fn braced_variable(&mut self, quoted: bool) -> Result<WordPart, CommandError> {
+        let mut name = self.variable_name();
+        if name.is_empty() {
+            while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit()) {
+                name.push(c);
+            }
+        }
+        if name.is_empty() {
+            return Err(CommandError::InvalidSyntax(
+                "bad substitution, expected a variable name after `${`".to_string(),
+            ));
+        }
+        if self.chars.next_if_eq(&'}').is_some() {
+            return Ok(WordPart::Variable {
+                name,
+                default: None,
+                quoted,
+            });
+        }
+        if self.chars.next_if_eq(&':').is_none() || self.chars.next_if_eq(&'-').is_none() {
+            return Err(CommandError::InvalidSyntax(format!(
+                "bad substitution of `{name}`, only `${{{name}:-default}}` is supported"
+            )));
+        }
+
+        // Find the matching closing brace, while respecting quotes and nested braces
+        let mut text = String::new();
+        let mut depth = 0;
+        let mut quote: Option<char> = None;
+        loop {
+            let Some(c) = self.chars.next() else {
+                return Err(CommandError::InvalidSyntax("unterminated `${`".to_string()));
+            };
+            match (c, quote) {
+                ('}', None) if depth == 0 => break,
+                ('}', None) => depth -= 1,
+                ('{', None) => depth += 1,
+                ('\'' | '"', None) => quote = Some(c),
+                (c, Some(q)) if c == q => quote = None,
+                ('\\', q) if q != Some('\'') => {
+                    text.push(c);
+                    if let Some(c) = self.chars.next() {
+                        text.push(c);
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            text.push(c);
+        }
+
+        let default = match Lexer::new(&text, false).tokenize()?.pop() {
+            Some(Token::Word(word)) => word,
+            _ => Word::default(),
+        };
+        Ok(WordPart::Variable {
+            name,
+            default: Some(default),
+            quoted,
+        })
+    }
+
+    /// Parses the commands that follow `$(`, up to the matching `)`.
+    fn command_substitution(&mut self, quoted: bool) -> Result<WordPart, CommandError> {
+        let start = self.chars.position;
+        // Inside the substitution, whitespace and operators separate words again
+        let operators = std::mem::replace(&mut self.operators, true);
+        let tokens = self.tokens(true);
+        self.operators = operators;
+        let tokens = tokens?;
+        let script = self.chars.text[start..self.chars.position - 1].to_string();
+        // The substitution is complete, so an unfinished statement inside it cannot continue
+        let statements = parse_tokens(tokens).map_err(|error| match error {
+            CommandError::IncompleteInput(message) => CommandError::InvalidSyntax(message),
+            error => error,
+        })?;
+        Ok(WordPart::CommandSubstitution {
+            script,
+            statements: Rc::new(statements),
+            quoted,
+        })
+    }
+
+    /// Parses the commands that follow `` ` ``, up to the next unescaped `` ` ``.
+    fn backquote_substitution(&mut self, quoted: bool) -> Result<WordPart, CommandError> {
+        let mut script = String::new();
+        loop {
+            match self.chars.next() {
+                Some('`') => break,
+                Some('\\') => match self.chars.next() {
+                    Some(c @ ('$' | '`' | '\\')) => script.push(c),
+                    Some('"') if quoted => script.push('"'),
+                    Some(c) => {
+                        script.push('\\');
+                        script.push(c);
+                    }
+                    None => script.push('\\'),
+                },
+                Some(c) => script.push(c),
+                None => {
+                    return Err(CommandError::InvalidSyntax(
+                        "unterminated backquote".to_string(),
+                    ))
+                }
+            }
+        }
+        let statements = tokenize(&script)
+            .and_then(parse_tokens)
+            .map_err(|error| match error {
+                CommandError::IncompleteInput(message) => CommandError::InvalidSyntax(message),
+                error => error,
+            })?;
+        Ok(WordPart::CommandSubstitution {
+            script,
+            statements: Rc::new(statements),
+            quoted,
+        })
+    }
+
+    /// Parses the expression that follows `$((`, up to the matching `))`.
+    fn arithmetic(&mut self) -> Result<WordPart, CommandError> {
+        let mut text = String::new();
+        let mut depth = 0;
+        loop {
+            match self.chars.next() {
+                None => {
+                    return Err(CommandError::IncompleteInput(
+                        "unterminated `$((`".to_string(),
+                    ))
+                }
+                Some(')') if depth == 0 => {
+                    if self.chars.next_if_eq(&')').is_some() {
+                        break;
+                    }
+                    return Err(CommandError::InvalidSyntax(
+                        "expected `))` at the end of `$((`".to_string(),
+                    ));
+                }
+                Some(c) => {
+                    match c {
+                        '(' => depth += 1,
+                        ')' => depth -= 1,
+                        _ => {}
+                    }
+                    text.push(c);
+                }
+            }
+        }
+        // Like in double quotes, expansions are performed and quotes are removed
+        let expression = match Lexer::new(&text, false).tokenize()?.pop() {
+            Some(Token::Word(word)) => word,
+            _ => Word::default(),
+        };
+        Ok(WordPart::Arithmetic(expression))
+    }
+
+    fn variable_name(&mut self) -> String {
+        let mut name = String::new();
+        while let Some(c) = self.chars.peek() {
+            if !(c.is_ascii_alphabetic() || c == '_' || (!name.is_empty() && c.is_ascii_digit())) {
+                break;
+            }
+            name.push(c);
+            self.chars.next();
+        }
+        name
+    }
+}
+
+fn unterminated_document(delimiter: &str) -> CommandError {
+    CommandError::IncompleteInput(format!(
+        "unterminated here-document, expected `{delimiter}`"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::CommandError;
+    use crate::lexer::{tokenize, Token, Word, WordPart};
+    use crate::pipeline::RedirectKind;
+
+    fn words(line: &str) -> Vec<String> {
+        tokenize(line)
+            .unwrap()
+            .into_iter()
+            .map(|token| match token {
+                Token::Word(word) => word.text(),
+                token => panic!("Unexpected token {token:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_whitespace() {
+        assert_eq!(words("  cd\t/foo   bar "), vec!["cd", "/foo", "bar"]);
+        assert!(words("   ").is_empty());
+    }
+
+    #[test]
+    fn single_quotes() {
+        assert_eq!(words("cd 'a b'"), vec!["cd", "a b"]);
+        assert_eq!(words(r#"echo 'a\b"c'"#), vec!["echo", r#"a\b"c"#]);
+        assert_eq!(words("echo ''"), vec!["echo", ""]);
+    }
+
+    #[test]
+    fn double_quotes() {
+        assert_eq!(words(r#"cd "a b""#), vec!["cd", "a b"]);
+        assert_eq!(
+            words(r#"echo "a\"b\\c\d 'e'""#),
+            vec!["echo", r#"a"b\c\d 'e'"#]
+        );
+        assert_eq!(words(r#"echo """#), vec!["echo", ""]);
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(words(r"cd a\ b"), vec!["cd", "a b"]);
+        assert_eq!(words(r"echo \|\>\\"), vec!["echo", r"|>\"]);
+    }
+
+    #[test]
+    fn concatenated_parts() {
+        let tokens = tokenize(r#"a"b c"'d'*"#).unwrap();
+        assert_eq!(
+            tokens,
+            vec![Token::Word(Word {
+                parts: vec![
+                    WordPart::Unquoted("a".to_string()),
+                    WordPart::Quoted("b c".to_string()),
+                    WordPart::Quoted("d".to_string()),
+                    WordPart::Unquoted("*".to_string()),
+                ]
+            })]
+        );
+    }
+
+    #[test]
+    fn operators() {
+        let tokens = tokenize("a<b|c>>d>e").unwrap();
+        assert_eq!(tokens.len(), 9);
+        assert_eq!(tokens[1], Token::Redirect(RedirectKind::Input));
+        assert_eq!(tokens[3], Token::Pipe);
+        assert_eq!(tokens[5], Token::Redirect(RedirectKind::Append));
+        assert_eq!(tokens[7], Token::Redirect(RedirectKind::Output));
+    }
+
+    #[test]
+    fn command_substitution() {
+        assert_eq!(
+            words(r#"echo "$(echo "a b" ')')" x$(echo $(pwd))"#),
+            vec!["echo", r#"$(echo "a b" ')')"#, "x$(echo $(pwd))"]
+        );
+        let tokens = tokenize("$(a | b; c) $((1 + (2)))").unwrap();
+        assert_eq!(tokens.len(), 2);
+        let Token::Word(word) = &tokens[0] else {
+            panic!("Unexpected token {:?}", tokens[0]);
+        };
+        assert!(matches!(
+            &word.parts[..],
+            [WordPart::CommandSubstitution { statements, quoted: false, .. }] if statements.len() == 2
+        ));
+        assert_eq!(
+            tokens[1],
+            Token::Word(Word {
+                parts: vec![WordPart::Arithmetic(Word {
+                    parts: vec![WordPart::Unquoted("1 + (2)".to_string())]
+                })]
+            })
+        );
+    }
+
+    #[test]
+    fn backquotes() {
+        assert_eq!(
+            words(r#"echo `echo "a b"` "x`pwd`" `echo \`pwd\``"#),
+            vec!["echo", r#"$(echo "a b")"#, "x$(pwd)", "$(echo `pwd`)"]
+        );
+        let tokens = tokenize("`a | b; c` \\`").unwrap();
+        let Token::Word(word) = &tokens[0] else {
+            panic!("Unexpected token {:?}", tokens[0]);
+        };
+        assert!(matches!(
+            &word.parts[..],
+            [WordPart::CommandSubstitution { statements, quoted: false, .. }] if statements.len() == 2
+        ));
+        assert_eq!(words("\\` \"\\`\""), vec!["`", "`"]);
+        assert!(matches!(
+            tokenize("echo `pwd"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+        assert!(matches!(
+            tokenize("echo `(pwd`"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn here_documents() {
+        let tokens =
+            tokenize("cat <<EOF; echo\n$x \\$y\nEOF\ncat <<-'A B'\n\t$x\n\tA B\n").unwrap();
+        assert_eq!(tokens.len(), 10);
+        assert_eq!(tokens[1], Token::Redirect(RedirectKind::HereDocument));
+        assert_eq!(
+            tokens[2],
+            Token::Word(Word {
+                parts: vec![
+                    WordPart::Quoted(String::new()),
+                    WordPart::Variable {
+                        name: "x".to_string(),
+                        default: None,
+                        quoted: true
+                    },
+                    WordPart::Quoted(" $y\n".to_string()),
+                ]
+            })
+        );
+        assert_eq!(tokens[3], Token::Semicolon);
+        assert_eq!(
+            tokens[8],
+            Token::Word(Word {
+                parts: vec![WordPart::Quoted("$x\n".to_string())]
+            })
+        );
+        assert!(matches!(
+            tokenize("cat <<EOF\ntext"),
+            Err(CommandError::IncompleteInput(_))
+        ));
+        assert!(matches!(
+            tokenize("cat <<"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn background() {
+        let tokens = tokenize("sleep 1&").unwrap();
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(tokens[2], Token::Background);
+    }
+
+    #[test]
+    fn control_operators() {
+        let tokens = tokenize("a&&b||c;d\ne(f)").unwrap();
+        assert_eq!(tokens.len(), 12);
+        assert_eq!(tokens[1], Token::And);
+        assert_eq!(tokens[3], Token::Or);
+        assert_eq!(tokens[5], Token::Semicolon);
+        assert_eq!(tokens[7], Token::Newline);
+        assert_eq!(tokens[9], Token::LeftParen);
+        assert_eq!(tokens[11], Token::RightParen);
+    }
+
+    #[test]
+    fn comments() {
+        let tokens = tokenize("#!/bin/benzina\na # b c\nd#e").unwrap();
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens[0], Token::Newline);
+        assert_eq!(tokens[2], Token::Newline);
+        assert!(matches!(&tokens[3], Token::Word(word) if word.text() == "d#e"));
+    }
+
+    #[test]
+    fn positional_parameters() {
+        let tokens = tokenize("$1 ${10} $# $@").unwrap();
+        let names: Vec<String> = tokens
+            .into_iter()
+            .map(|token| match token {
+                Token::Word(mut word) => match word.parts.pop() {
+                    Some(WordPart::Variable { name, .. }) => name,
+                    part => panic!("Unexpected part {part:?}"),
+                },
+                token => panic!("Unexpected token {token:?}"),
+            })
+            .collect();
+        assert_eq!(names, vec!["1", "10", "#", "@"]);
+    }
+
+    #[test]
+    fn quoted_operators() {
+        assert_eq!(words("echo '|' \">\" \\&"), vec!["echo", "|", ">", "&"]);
+    }
+
+    #[test]
+    fn unterminated_quotes() {
+        assert!(matches!(
+            tokenize("echo 'abc"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+        assert!(matches!(
+            tokenize("echo \"abc"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+        assert!(matches!(
+            tokenize("echo abc\\"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+    }
+}
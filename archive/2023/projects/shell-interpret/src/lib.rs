@@ -1,43 +1,1202 @@
+mod arithmetic;
+mod ast;
 mod command;
 mod commands;
+mod completion;
+mod editor;
+mod embed;
+mod executor;
+mod expand;
+mod extensions;
+mod frecency;
+mod glob;
+mod help;
+mod history;
+mod jobs;
+mod lexer;
+mod parser;
+mod pipeline;
+mod plugin;
+mod prompt;
+mod signals;
 mod state;
 
-use crate::command::{Command, CommandResponse};
-use state::InterpreterState;
+use crate::ast::Statement;
+use crate::executor::Input;
+use crate::help::SHELL_BUILTINS;
+use crate::parser::parse_script;
+use crate::plugin::Plugin;
+use crate::prompt::{format_prompt, DEFAULT_PROMPT};
+use std::collections::HashMap;
 use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
+pub use command::{Command, CommandError, CommandResponse, ExitStatus};
+pub use commands::alias::DefineAlias;
+pub use commands::background::BackgroundJob;
+pub use commands::cat::ConcatenateFiles;
 pub use commands::change_dir::ChangeWorkdir;
+pub use commands::cp::CopyFiles;
+pub use commands::dirs::PrintDirectoryStack;
+pub use commands::echo::PrintArguments;
+pub use commands::env::PrintEnvironment;
+pub use commands::export::ExportVariable;
+pub use commands::external::ExternalCommand;
+pub use commands::foreground::ForegroundJob;
+pub use commands::grep::SearchLines;
+pub use commands::head::PrintFirstLines;
+pub use commands::jobs::ListJobs;
+pub use commands::kill::KillJob;
+pub use commands::ls::ListDirectory;
+pub use commands::mkdir::MakeDirectory;
+pub use commands::mv::MoveFiles;
+pub use commands::popd::PopDirectory;
 pub use commands::print_workdir::PrintWorkdir;
+pub use commands::pushd::PushDirectory;
+pub use commands::read::ReadVariables;
+pub use commands::rm::RemoveFiles;
+pub use commands::set::SetOption;
+pub use commands::tail::PrintLastLines;
+pub use commands::touch::TouchFiles;
+pub use commands::trap::SetTrap;
+pub use commands::unalias::RemoveAlias;
+pub use commands::unset::UnsetVariable;
+pub use commands::wait::WaitForJobs;
+pub use commands::wc::WordCount;
+pub use commands::z::JumpToDirectory;
+pub use completion::Completion;
+pub use editor::LineEditor;
+pub use embed::RunOutput;
+pub use extensions::Extensions;
+pub use history::History;
+pub use plugin::abi as plugin_abi;
+pub use signals::install_handlers as install_signal_handlers;
+pub use state::{ErrorOutput, InterpreterState};
 
-#[derive(Default)]
 pub struct Shell {
     commands: Vec<Box<dyn Command>>,
     state: InterpreterState,
+    /// Commands loaded from plugins
+    plugins: Vec<Plugin>,
+    /// Functions defined by the user
+    functions: HashMap<String, Rc<Vec<Statement>>>,
+    /// Number of functions that are currently being executed
+    function_depth: usize,
+    /// Number of conditions (e.g. of `if` or `&&`) that are currently being executed, `set -e`
+    /// does not apply to them
+    condition_depth: usize,
+    /// A signal that ends the shell has arrived, see `is_terminated`
+    terminated: bool,
+    /// `exit` was executed, see `is_terminated`
+    exited: bool,
+    /// Commands without another input read the standard input of the shell, see `inherit_stdin`
+    stdin_inherited: bool,
 }
 
 impl Shell {
+    /// Creates a shell in the current working directory, with the environment variables of the
+    /// current process. Fails if the current directory cannot be determined, e.g. if it was
+    /// removed.
+    pub fn new() -> std::io::Result<Self> {
+        Ok(Self::with_workdir(std::env::current_dir()?))
+    }
+
+    /// Creates a shell in `workdir`, with the environment variables of the current process.
+    pub fn with_workdir(workdir: PathBuf) -> Self {
+        Shell {
+            commands: vec![],
+            state: InterpreterState::new(workdir),
+            plugins: vec![],
+            functions: HashMap::new(),
+            function_depth: 0,
+            condition_depth: 0,
+            terminated: false,
+            exited: false,
+            stdin_inherited: false,
+        }
+    }
+
     pub fn add_command<T: Command + 'static>(&mut self, command: T) {
         self.commands.push(Box::new(command));
     }
 
-    pub fn execute_line(&mut self, line: &str, output: &mut dyn Write) {
-        for command in &self.commands {
-            match command.execute(line, &mut self.state, output) {
-                CommandResponse::Handled(res) => {
-                    if let Err(error) = res {
-                        writeln!(output, "Command `{line}` has failed: {error:?}").unwrap();
-                    }
+    /// Registers all builtin commands except `ExternalCommand`, which should be added after any
+    /// other commands because it handles every command.
+    pub fn add_builtins(&mut self) {
+        self.add_command(PrintWorkdir);
+        self.add_command(ChangeWorkdir);
+        self.add_command(PushDirectory);
+        self.add_command(PopDirectory);
+        self.add_command(PrintDirectoryStack);
+        self.add_command(JumpToDirectory);
+        self.add_command(ExportVariable);
+        self.add_command(UnsetVariable);
+        self.add_command(PrintEnvironment);
+        self.add_command(ReadVariables);
+        self.add_command(SetOption);
+        self.add_command(SetTrap);
+        self.add_command(DefineAlias);
+        self.add_command(RemoveAlias);
+        self.add_command(ListJobs);
+        self.add_command(ForegroundJob);
+        self.add_command(BackgroundJob);
+        self.add_command(WaitForJobs);
+        self.add_command(KillJob);
+        self.add_command(PrintArguments);
+        self.add_command(ListDirectory);
+        self.add_command(ConcatenateFiles);
+        self.add_command(MakeDirectory);
+        self.add_command(RemoveFiles);
+        self.add_command(CopyFiles);
+        self.add_command(MoveFiles);
+        self.add_command(TouchFiles);
+        self.add_command(PrintFirstLines);
+        self.add_command(PrintLastLines);
+        self.add_command(WordCount);
+        self.add_command(SearchLines);
+    }
+
+    /// Lets commands without another input (e.g. the first program of a pipeline) read the
+    /// standard input of the shell. Otherwise their input is empty, which is what programs that
+    /// embed the shell usually want.
+    pub fn inherit_stdin(&mut self) {
+        self.stdin_inherited = true;
+    }
+
+    /// Enables recording of visited directories in the file at `path`, which is used by `z`.
+    pub fn set_directory_database(&mut self, path: PathBuf) {
+        self.state.directory_database = Some(path);
+    }
+
+    /// Loads a plugin (see `Plugin::load`) and registers its commands.
+    /// Fails if the plugin declares a command with the same name as a builtin or a command of
+    /// another plugin.
+    pub fn load_plugin(&mut self, path: &Path) -> Result<(), CommandError> {
+        let plugin = Plugin::load(path, &self.state)?;
+        for command in plugin.commands() {
+            let name = command.name.as_str();
+            let is_defined = SHELL_BUILTINS.iter().any(|(builtin, _)| *builtin == name)
+                || self
+                    .commands
+                    .iter()
+                    .any(|builtin| builtin.name() == Some(name))
+                || self
+                    .plugins
+                    .iter()
+                    .flat_map(|plugin| plugin.commands())
+                    .any(|command| command.name == name);
+            if is_defined {
+                return Err(CommandError::Usage(format!(
+                    "cannot load plugin `{}`: command `{name}` is already defined",
+                    path.display()
+                )));
+            }
+        }
+        self.plugins.push(plugin);
+        Ok(())
+    }
+
+    /// Executes one or more lines of statements. A statement can be a pipeline of commands, a
+    /// control flow construct (`if`, `while`, `for`), a function definition, or multiple
+    /// statements connected with `&&` and `||`. Statements are separated by newlines or `;`.
+    ///
+    /// Returns the exit status of the last executed command.
+    pub fn execute_line(&mut self, line: &str, output: &mut dyn Write) -> ExitStatus {
+        match parse_script(line) {
+            Ok(statements) => {
+                self.execute_statements(&statements, &mut Input::Shell, output);
+            }
+            Err(error) => {
+                self.state.last_status = error.exit_status().code();
+                writeln!(self.state.stderr, "Cannot parse `{line}`: {error}").unwrap();
+            }
+        }
+        self.last_status()
+    }
+
+    /// Executes a whole script.
+    /// `args` are the positional parameters of the script, the first one is its name.
+    ///
+    /// Returns the exit status of the last executed command.
+    pub fn execute_script(
+        &mut self,
+        script: &str,
+        args: Vec<String>,
+        output: &mut dyn Write,
+    ) -> ExitStatus {
+        assert!(!args.is_empty());
+        match parse_script(script) {
+            Ok(statements) => {
+                self.state.positional = args;
+                self.execute_statements(&statements, &mut Input::Shell, output);
+            }
+            Err(error) => {
+                self.state.last_status = error.exit_status().code();
+                writeln!(self.state.stderr, "Cannot parse `{}`: {error}", args[0]).unwrap();
+            }
+        }
+        self.last_status()
+    }
 
-                    break;
-                }
-                CommandResponse::Unhandled => {}
+    /// Executes the statements of a file in the current shell, e.g. the startup file
+    /// `~/.benzinarc`. Unlike `execute_script`, the positional parameters are not changed.
+    ///
+    /// Returns the exit status of the last executed command.
+    pub fn source_file(
+        &mut self,
+        path: &Path,
+        output: &mut dyn Write,
+    ) -> std::io::Result<ExitStatus> {
+        let script = std::fs::read_to_string(path)?;
+        match parse_script(&script) {
+            Ok(statements) => {
+                self.execute_statements(&statements, &mut Input::Shell, output);
+            }
+            Err(error) => {
+                self.state.last_status = error.exit_status().code();
+                writeln!(
+                    self.state.stderr,
+                    "Cannot parse `{}`: {error}",
+                    path.display()
+                )?;
             }
         }
+        Ok(self.last_status())
+    }
+
+    /// Returns `true` if `text` ends in the middle of a statement (e.g. an `if` without `fi`),
+    /// so more lines should be read before it is executed.
+    pub fn is_incomplete(text: &str) -> bool {
+        matches!(parse_script(text), Err(CommandError::IncompleteInput(_)))
+    }
+
+    /// Exit status of the last executed command.
+    pub fn last_status(&self) -> ExitStatus {
+        ExitStatus(self.state.last_status)
     }
 
-    pub fn print_prompt(&self, output: &mut dyn Write) -> std::io::Result<()> {
-        write!(output, "{}$ ", self.state.workdir.display())?;
+    /// Prints the prompt, preceded by notifications about background jobs that have finished.
+    pub fn print_prompt(&mut self, output: &mut dyn Write) -> std::io::Result<()> {
+        self.print_job_notifications(output)?;
+        write!(output, "{}", self.prompt())?;
         output.flush()?;
         Ok(())
     }
+
+    /// Prints notifications about background jobs that have finished.
+    pub fn print_job_notifications(&mut self, output: &mut dyn Write) -> std::io::Result<()> {
+        for job in self.state.jobs.take_finished() {
+            writeln!(output, "[{}] {}\t{}", job.id, job.status(), job.command)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the prompt, formatted according to the `PS1` variable (see `format_prompt` for
+    /// the supported escapes).
+    pub fn prompt(&self) -> String {
+        let format = self
+            .state
+            .env
+            .get("PS1")
+            .map(|format| format.as_str())
+            .unwrap_or(DEFAULT_PROMPT);
+        format_prompt(format, &self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command::{Command, CommandResponse};
+    use crate::embed::SharedBuffer;
+    use crate::state::{ErrorOutput, InterpreterState};
+    use crate::{
+        BackgroundJob, DefineAlias, ExitStatus, ExportVariable, ExternalCommand, ForegroundJob,
+        KillJob, ListJobs, PrintEnvironment, PrintWorkdir, RemoveAlias, SetOption, Shell,
+        UnsetVariable, WaitForJobs,
+    };
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    /// Counts the bytes of its input.
+    struct CountBytes;
+
+    impl Command for CountBytes {
+        fn execute(
+            &self,
+            args: &[String],
+            _state: &mut InterpreterState,
+            input: &mut dyn Read,
+            output: &mut dyn Write,
+        ) -> CommandResponse {
+            if args[0] != "count" {
+                return CommandResponse::Unhandled;
+            }
+            let mut buffer = vec![];
+            input.read_to_end(&mut buffer).unwrap();
+            writeln!(output, "{}", buffer.len()).unwrap();
+            CommandResponse::Handled(Ok(()))
+        }
+    }
+
+    fn shell(dir: &tempfile::TempDir) -> Shell {
+        let mut shell = Shell::with_workdir(dir.path().to_path_buf());
+        shell.state.stderr = ErrorOutput::Capture(Box::new(std::io::sink()));
+        shell.add_command(PrintWorkdir);
+        shell.add_command(CountBytes);
+        shell
+    }
+
+    fn run(shell: &mut Shell, line: &str) -> String {
+        let mut output = vec![];
+        shell.execute_line(line, &mut output);
+        String::from_utf8(output).unwrap()
+    }
+
+    /// Executes `line` and returns its error messages.
+    fn run_errors(shell: &mut Shell, line: &str) -> String {
+        let errors = SharedBuffer::default();
+        let previous = std::mem::replace(
+            &mut shell.state.stderr,
+            ErrorOutput::Capture(Box::new(errors.clone())),
+        );
+        shell.execute_line(line, &mut vec![]);
+        shell.state.stderr = previous;
+        String::from_utf8(errors.take()).unwrap()
+    }
+
+    #[test]
+    fn pipe_output_into_next_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        let expected = format!("{}\n", dir.path().display().to_string().len() + 1);
+        assert_eq!(run(&mut shell, "pwd | count"), expected);
+        assert_eq!(run(&mut shell, "pwd | count | count"), "3\n");
+    }
+
+    #[test]
+    fn redirect_output_relative_to_workdir() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        assert_eq!(run(&mut shell, "pwd > out.txt"), "");
+        let expected = format!("{}\n", dir.path().display());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("out.txt")).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn redirect_append() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        run(&mut shell, "pwd >> out.txt");
+        run(&mut shell, "pwd>>out.txt");
+        let expected = format!("{0}\n{0}\n", dir.path().display());
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("out.txt")).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn redirect_input() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("in.txt"), "hello").unwrap();
+        let mut shell = shell(&dir);
+        assert_eq!(run(&mut shell, "count < in.txt"), "5\n");
+    }
+
+    #[test]
+    fn redirect_output_inside_pipeline() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        assert_eq!(run(&mut shell, "pwd > out.txt | count"), "0\n");
+        assert!(dir.path().join("out.txt").is_file());
+    }
+
+    #[test]
+    fn unknown_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        assert_eq!(run_errors(&mut shell, "foo"), "foo: command not found\n");
+        assert_eq!(
+            run_errors(&mut shell, "pwd | foo"),
+            "foo: command not found\n"
+        );
+    }
+
+    #[test]
+    fn pipe_into_external_command() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        shell.add_command(ExternalCommand);
+        let expected = format!("{}\n", dir.path().display());
+        assert_eq!(run(&mut shell, "pwd | cat"), expected);
+        assert_eq!(run(&mut shell, "echo hello > out.txt"), "");
+        assert_eq!(run(&mut shell, "cat < out.txt | count"), "6\n");
+    }
+
+    #[test]
+    fn programs_in_pipeline_run_concurrently() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        shell.add_builtins();
+        shell.add_command(ExternalCommand);
+        // `yes` never ends by itself, it is stopped when the next stage closes the pipe
+        assert_eq!(run(&mut shell, "yes | env head -n 2"), "y\ny\n");
+        assert_eq!(run(&mut shell, "yes | head -n 1"), "y\n");
+        assert_eq!(run(&mut shell, "yes | tr y n | env head -n 1"), "n\n");
+        assert_eq!(shell.last_status(), ExitStatus::SUCCESS);
+        // The status of the pipeline is the status of its last stage
+        run(&mut shell, "true | false");
+        assert_eq!(shell.last_status(), ExitStatus(1));
+        run(&mut shell, "false | true");
+        assert_eq!(shell.last_status(), ExitStatus::SUCCESS);
+    }
+
+    #[test]
+    fn quoted_arguments() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        shell.add_command(ExternalCommand);
+        assert_eq!(
+            run(&mut shell, r#"echo 'a  b' "c | d" e\ \>f"#),
+            "a  b c | d e >f\n"
+        );
+        run(&mut shell, "echo x > 'out file.txt'");
+        assert!(dir.path().join("out file.txt").is_file());
+    }
+
+    #[test]
+    fn expand_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("b.txt"), "").unwrap();
+        std::fs::write(dir.path().join("a.txt"), "").unwrap();
+        let mut shell = shell(&dir);
+        shell.add_command(ExternalCommand);
+        assert_eq!(run(&mut shell, "echo *.txt '*.txt'"), "a.txt b.txt *.txt\n");
+    }
+
+    #[test]
+    fn ambiguous_redirect() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("b.txt"), "").unwrap();
+        std::fs::write(dir.path().join("a.txt"), "").unwrap();
+        let mut shell = shell(&dir);
+        assert!(run_errors(&mut shell, "pwd > *.txt").starts_with("Command `pwd` has failed"));
+    }
+
+    #[test]
+    fn parse_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        assert!(run_errors(&mut shell, "pwd 'foo").starts_with("Cannot parse `pwd 'foo`"));
+    }
+
+    #[test]
+    fn expand_variables() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        shell.add_command(ExportVariable);
+        shell.add_command(UnsetVariable);
+        shell.add_command(ExternalCommand);
+        run(&mut shell, "export GREETING='hello world' FILE=out.txt");
+        assert_eq!(run(&mut shell, "echo \"$GREETING\" > $FILE"), "");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("out.txt")).unwrap(),
+            "hello world\n"
+        );
+        assert_eq!(run(&mut shell, "sh -c 'echo $GREETING'"), "hello world\n");
+        run(&mut shell, "unset GREETING");
+        assert_eq!(run(&mut shell, "echo ${GREETING:-bye}"), "bye\n");
+    }
+
+    #[test]
+    fn export_variables() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        shell.add_command(ExportVariable);
+        shell.add_command(ExternalCommand);
+        let print = "sh -c 'echo \"[$LOCAL] [$SHARED] [$LATER]\"'";
+        run(&mut shell, "LOCAL=1; export SHARED=2");
+        assert_eq!(run(&mut shell, "echo $LOCAL $SHARED"), "1 2\n");
+        assert_eq!(run(&mut shell, print), "[] [2] []\n");
+        run(&mut shell, "export LOCAL LATER; LATER=3");
+        assert_eq!(run(&mut shell, print), "[1] [2] [3]\n");
+        assert_eq!(run(&mut shell, "export | grep LATER"), "export LATER=3\n");
+    }
+
+    #[test]
+    fn prefix_assignments() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = job_shell(&dir);
+        shell.add_command(PrintEnvironment);
+        let print = "sh -c 'echo \"[$X] [$Y]\"'";
+        assert_eq!(run(&mut shell, &format!("X=1 Y=$X {print}")), "[1] [1]\n");
+        assert_eq!(run(&mut shell, "echo \"[$X]\""), "[]\n");
+        // The previous value is restored, and the variable is not exported after the command
+        run(&mut shell, "X=outer");
+        assert_eq!(run(&mut shell, &format!("X=inner {print}")), "[inner] []\n");
+        assert_eq!(
+            run(&mut shell, &format!("echo $X; {print}")),
+            "outer\n[] []\n"
+        );
+        // Builtins, functions and background jobs see the variables too
+        assert_eq!(run(&mut shell, "X=1 env | grep ^X="), "X=1\n");
+        assert_eq!(run(&mut shell, "f() { echo $X; }; X=2 f; f"), "2\nouter\n");
+        run(&mut shell, "X=3 sh -c 'echo $X > job.txt' &");
+        run(&mut shell, "wait");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("job.txt")).unwrap(),
+            "3\n"
+        );
+    }
+
+    #[test]
+    fn expand_last_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        shell.add_command(ExternalCommand);
+        run(&mut shell, "false");
+        assert_eq!(run(&mut shell, "echo $?"), "1\n");
+        assert_eq!(run(&mut shell, "echo $?"), "0\n");
+        run(&mut shell, "benzina-does-not-exist");
+        assert_eq!(run(&mut shell, "echo $?"), "127\n");
+    }
+
+    fn job_shell(dir: &tempfile::TempDir) -> Shell {
+        let mut shell = shell(dir);
+        shell.add_command(ListJobs);
+        shell.add_command(ForegroundJob);
+        shell.add_command(BackgroundJob);
+        shell.add_command(WaitForJobs);
+        shell.add_command(KillJob);
+        shell.add_command(ExternalCommand);
+        shell
+    }
+
+    fn prompt(shell: &mut Shell) -> String {
+        let mut output = vec![];
+        shell.print_prompt(&mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn background_job_notification() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = job_shell(&dir);
+        let output = run(&mut shell, "sh -c 'exit 3' &");
+        assert!(output.starts_with("[1] "));
+        let notification = (0..100)
+            .map(|_| {
+                std::thread::sleep(Duration::from_millis(20));
+                prompt(&mut shell)
+            })
+            .find(|prompt| prompt.starts_with('['))
+            .expect("job did not finish");
+        assert!(notification.starts_with("[1] Exit 3\tsh -c exit 3\n"));
+        // The notification is printed only once
+        assert!(!prompt(&mut shell).starts_with('['));
+    }
+
+    #[test]
+    fn list_and_wait_for_jobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = job_shell(&dir);
+        run(&mut shell, "sleep 0.2 &");
+        run(&mut shell, "sh -c 'sleep 0.1; exit 4' &");
+        assert_eq!(
+            run(&mut shell, "jobs"),
+            "[1] Running\tsleep 0.2\n[2] Running\tsh -c sleep 0.1; exit 4\n"
+        );
+        run(&mut shell, "wait %2");
+        assert_eq!(run(&mut shell, "echo $?"), "4\n");
+        run(&mut shell, "wait");
+        assert_eq!(run(&mut shell, "echo $?"), "0\n");
+        assert_eq!(run(&mut shell, "jobs"), "");
+    }
+
+    #[test]
+    fn background_pipeline() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = job_shell(&dir);
+        run(&mut shell, "echo hello | tr a-z A-Z > out.txt &");
+        run(&mut shell, "wait");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("out.txt")).unwrap(),
+            "HELLO\n"
+        );
+    }
+
+    #[test]
+    fn kill_job() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = job_shell(&dir);
+        run(&mut shell, "sleep 10 &");
+        assert_eq!(run(&mut shell, "kill %1"), "");
+        run(&mut shell, "wait %1");
+        assert_eq!(run(&mut shell, "echo $?"), "143\n");
+
+        run(&mut shell, "sleep 10 &");
+        run(&mut shell, "kill %1");
+        let notification = (0..100)
+            .map(|_| {
+                std::thread::sleep(Duration::from_millis(20));
+                prompt(&mut shell)
+            })
+            .find(|prompt| prompt.starts_with('['))
+            .expect("job did not finish");
+        assert!(notification.starts_with("[1] Terminated\tsleep 10\n"));
+    }
+
+    #[test]
+    fn background_builtin() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = job_shell(&dir);
+        assert_eq!(
+            run_errors(&mut shell, "pwd &"),
+            "Cannot start `pwd`: pwd: builtins cannot run in the background\n"
+        );
+        assert_eq!(shell.last_status(), ExitStatus(2));
+        assert_eq!(run(&mut shell, "jobs"), "");
+    }
+
+    #[test]
+    fn stop_and_resume_job() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = job_shell(&dir);
+        run(&mut shell, "sleep 0.3 &");
+        run(&mut shell, "kill -STOP %1");
+        let stopped = (0..100).any(|_| {
+            std::thread::sleep(Duration::from_millis(10));
+            run(&mut shell, "jobs") == "[1] Stopped\tsleep 0.3\n"
+        });
+        assert!(stopped);
+        assert_eq!(run(&mut shell, "bg"), "[1] sleep 0.3 &\n");
+        assert_eq!(run(&mut shell, "fg %1"), "sleep 0.3\n");
+        assert_eq!(run(&mut shell, "echo $?"), "0\n");
+        assert_eq!(run(&mut shell, "jobs"), "");
+    }
+
+    #[test]
+    fn missing_job() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = job_shell(&dir);
+        assert!(run_errors(&mut shell, "fg").starts_with("Command `fg` has failed"));
+        assert!(run_errors(&mut shell, "kill %3").starts_with("Command `kill %3` has failed"));
+    }
+
+    #[test]
+    fn background_unknown_program() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = job_shell(&dir);
+        assert!(run_errors(&mut shell, "sleep 1 | benzina-does-not-exist &")
+            .starts_with("Cannot start"));
+        assert_eq!(run(&mut shell, "jobs"), "");
+    }
+
+    #[test]
+    fn redirect_missing_input() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        assert!(
+            run_errors(&mut shell, "count < missing.txt").starts_with("Command `count` has failed")
+        );
+    }
+
+    fn script_shell(dir: &tempfile::TempDir) -> Shell {
+        let mut shell = shell(dir);
+        shell.add_command(ExportVariable);
+        shell.add_command(ExternalCommand);
+        shell
+    }
+
+    /// A shell with all builtins, unlike programs they do not consume the input of a loop that
+    /// reads lines with `read`.
+    fn builtin_shell(dir: &tempfile::TempDir) -> Shell {
+        let mut shell = shell(dir);
+        shell.add_builtins();
+        shell.add_command(ExternalCommand);
+        shell
+    }
+
+    #[test]
+    fn sequence_and_or() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = script_shell(&dir);
+        assert_eq!(run(&mut shell, "echo a; echo b"), "a\nb\n");
+        assert_eq!(
+            run(
+                &mut shell,
+                "true && echo a || echo b; false && echo c || echo d"
+            ),
+            "a\nd\n"
+        );
+        run(&mut shell, "false || false");
+        assert_eq!(run(&mut shell, "echo $?"), "1\n");
+        assert_eq!(
+            run(&mut shell, "sleep 0.1 & echo started").lines().last(),
+            Some("started")
+        );
+    }
+
+    #[test]
+    fn if_statement() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = script_shell(&dir);
+        let script = "for x in 1 2 3; do
+    if test $x = 1; then
+        echo one
+    elif [ $x = 2 ]; then echo two
+    else
+        echo other $x
+    fi
+done";
+        assert_eq!(run(&mut shell, script), "one\ntwo\nother 3\n");
+        assert_eq!(run(&mut shell, "if false; then echo a; fi; echo $?"), "0\n");
+    }
+
+    #[test]
+    fn while_loop() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = script_shell(&dir);
+        let script = "X=
+while test \"$X\" != aaaa; do
+    X=${X}a
+    if test $X = aa; then continue; fi
+    echo $X
+done
+while true; do break; done";
+        assert_eq!(run(&mut shell, script), "a\naaa\naaaa\n");
+    }
+
+    #[test]
+    fn for_loop_with_globs() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("b.txt"), "").unwrap();
+        std::fs::write(dir.path().join("a.txt"), "").unwrap();
+        let mut shell = script_shell(&dir);
+        assert_eq!(
+            run(&mut shell, "for f in *.txt 'c d'; do echo \"[$f]\"; done"),
+            "[a.txt]\n[b.txt]\n[c d]\n"
+        );
+    }
+
+    #[test]
+    fn for_loop_splits_variables() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = script_shell(&dir);
+        assert_eq!(
+            run(&mut shell, "X='a b'; for i in $X; do echo \"[$i]\"; done"),
+            "[a]\n[b]\n"
+        );
+        assert_eq!(
+            run(&mut shell, "for i in \"$X\"; do echo \"[$i]\"; done"),
+            "[a b]\n"
+        );
+    }
+
+    #[test]
+    fn pipe_compound_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = builtin_shell(&dir);
+        assert_eq!(
+            run(&mut shell, "for i in 1 2; do echo $i; done | tr 12 ab"),
+            "a\nb\n"
+        );
+        assert_eq!(run(&mut shell, "if true; then echo x; fi | cat"), "x\n");
+        assert_eq!(run(&mut shell, "{ echo a; echo b; } | count"), "4\n");
+        assert_eq!(
+            run(&mut shell, "(echo sub; echo shell) | tr a-z A-Z"),
+            "SUB\nSHELL\n"
+        );
+        assert_eq!(
+            run(
+                &mut shell,
+                "printf 'a b\\nc d\\n' | while read x y; do echo \"$y-$x\"; done"
+            ),
+            "b-a\nd-c\n"
+        );
+        // A compound command reads the whole input of the stage
+        assert_eq!(
+            run(&mut shell, "printf '1\\n2\\n' | { read x; cat; echo $x; }"),
+            "2\n1\n"
+        );
+        assert_eq!(
+            run(
+                &mut shell,
+                "echo a | while read l; do echo \"<$l>\"; done | cat"
+            ),
+            "<a>\n"
+        );
+    }
+
+    #[test]
+    fn redirect_compound_commands() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = builtin_shell(&dir);
+        let script = "for i in 1 2; do echo $i; done > out.txt
+if true; then echo x; fi >> out.txt
+{ echo a; } >> out.txt
+(echo sub) >> out.txt
+while read l; do echo \"[$l]\"; done < out.txt";
+        assert_eq!(run(&mut shell, script), "[1]\n[2]\n[x]\n[a]\n[sub]\n");
+        assert_eq!(
+            run(
+                &mut shell,
+                "while read l; do echo $l; done <<EOF\nhere\nEOF"
+            ),
+            "here\n"
+        );
+        assert!(run_errors(&mut shell, "{ echo a; } &")
+            .contains("compound commands cannot run in the background"));
+    }
+
+    #[test]
+    fn groups_and_subshells() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = builtin_shell(&dir);
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        assert_eq!(run(&mut shell, "(echo sub)"), "sub\n");
+        assert_eq!(run(&mut shell, "{ X=1; }; echo $X"), "1\n");
+        // Changes made in a subshell are discarded
+        assert_eq!(
+            run(&mut shell, "(X=2; cd sub; pwd); echo $X"),
+            format!("{}\n1\n", dir.path().join("sub").display())
+        );
+        assert_eq!(
+            run(&mut shell, "pwd"),
+            format!("{}\n", dir.path().display())
+        );
+        // `exit` only ends the subshell
+        assert_eq!(run(&mut shell, "(exit 3; echo a); echo $?"), "3\n");
+        assert!(!shell.is_terminated());
+        assert_eq!(run(&mut shell, "(false) || echo failed"), "failed\n");
+    }
+
+    #[test]
+    fn functions() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = script_shell(&dir);
+        let script = "greet() {
+    echo \"hello $1 ($#)\"
+    return 3
+    echo unreachable
+}
+function shout { tr a-z A-Z; }
+greet 'big world' x";
+        assert_eq!(run(&mut shell, script), "hello big world (2)\n");
+        assert_eq!(run(&mut shell, "echo $?"), "3\n");
+        // Functions compose with builtins and external programs in pipelines
+        assert_eq!(run(&mut shell, "greet crab | shout | count"), "15\n");
+        assert_eq!(run(&mut shell, "greet crab | shout"), "HELLO CRAB (1)\n");
+        assert_eq!(
+            run(&mut shell, "pwd | shout > out.txt; cat out.txt"),
+            format!("{}\n", dir.path().display().to_string().to_uppercase())
+        );
+    }
+
+    #[test]
+    fn recursive_function() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = script_shell(&dir);
+        let script = "reverse() {
+    if test $# -gt 1; then
+        reverse $2 $3
+    fi
+    echo $1
+}
+reverse a b c";
+        assert_eq!(run(&mut shell, script), "c\nb\na\n");
+        // Infinite recursion is stopped
+        assert!(run_errors(&mut shell, "f() { f; }; f")
+            .contains("f: maximum function nesting level exceeded"));
+        let script = "each() { for x; do echo \"<$x>\"; done; }; each a 'b c'";
+        assert_eq!(run(&mut shell, script), "<a>\n<b c>\n");
+    }
+
+    #[test]
+    fn execute_script() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = script_shell(&dir);
+        let script = "#!/usr/bin/env benzina
+# Prints its arguments
+echo $0: \"$@\" \\
+  done
+";
+        let mut output = vec![];
+        shell.execute_script(
+            script,
+            vec!["test.sh".to_string(), "a".to_string(), "b".to_string()],
+            &mut output,
+        );
+        assert_eq!(String::from_utf8(output).unwrap(), "test.sh: a b done\n");
+        assert_eq!(shell.last_status(), ExitStatus::SUCCESS);
+    }
+
+    #[test]
+    fn report_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("script.sh"), "echo hello").unwrap();
+        let mut shell = script_shell(&dir);
+        let mut execute = |line: &str| {
+            let errors = SharedBuffer::default();
+            shell.state.stderr = ErrorOutput::Capture(Box::new(errors.clone()));
+            let status = shell.execute_line(line, &mut vec![]);
+            (status, String::from_utf8(errors.take()).unwrap())
+        };
+        assert_eq!(execute("true"), (ExitStatus::SUCCESS, "".to_string()));
+        assert_eq!(
+            execute("benzina-does-not-exist"),
+            (
+                ExitStatus(127),
+                "benzina-does-not-exist: command not found\n".to_string()
+            )
+        );
+        assert_eq!(
+            execute("./script.sh"),
+            (
+                ExitStatus(126),
+                "./script.sh: permission denied\n".to_string()
+            )
+        );
+        assert_eq!(
+            execute("return x"),
+            (
+                ExitStatus(2),
+                "Command `return x` has failed: return: x: numeric argument required\n".to_string()
+            )
+        );
+        assert_eq!(
+            execute("fi"),
+            (
+                ExitStatus(2),
+                "Cannot parse `fi`: syntax error: unexpected keyword `fi`\n".to_string()
+            )
+        );
+        assert_eq!(execute("sh -c 'exit 5'"), (ExitStatus(5), "".to_string()));
+    }
+
+    #[test]
+    fn errexit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        shell.add_command(SetOption);
+        shell.add_command(ExternalCommand);
+        let script = "set -e
+if false; then echo a; fi
+while false; do echo b; done
+false || echo c
+false && echo d
+f() {
+    false
+    echo e
+}
+f
+echo f";
+        let mut output = vec![];
+        let status = shell.execute_script(script, vec!["test.sh".to_string()], &mut output);
+        assert_eq!(String::from_utf8(output).unwrap(), "c\n");
+        assert_eq!(status, ExitStatus(1));
+
+        run(&mut shell, "set +e");
+        assert_eq!(run(&mut shell, "false; echo a"), "a\n");
+    }
+
+    #[test]
+    fn exit() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = script_shell(&dir);
+        let script = "f() {
+    echo a
+    exit 3
+    echo b
+}
+echo $(exit 4; echo c) $?
+f
+echo d";
+        let mut output = vec![];
+        let status = shell.execute_script(script, vec!["test.sh".to_string()], &mut output);
+        assert_eq!(String::from_utf8(output).unwrap(), "4\na\n");
+        assert_eq!(status, ExitStatus(3));
+        assert!(shell.is_terminated());
+
+        let mut shell = script_shell(&dir);
+        assert_eq!(run(&mut shell, "exit | echo a; echo b"), "a\nb\n");
+        assert!(!shell.is_terminated());
+        run(&mut shell, "false; exit");
+        assert_eq!(shell.last_status(), ExitStatus(1));
+        assert!(shell.is_terminated());
+        assert_eq!(
+            run_errors(&mut script_shell(&dir), "exit x"),
+            "Command `exit x` has failed: exit: x: numeric argument required\n"
+        );
+    }
+
+    #[test]
+    fn expand_aliases() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = shell(&dir);
+        shell.add_command(DefineAlias);
+        shell.add_command(RemoveAlias);
+        shell.add_command(ExternalCommand);
+        run(&mut shell, "alias say='echo \"hello  world\"'");
+        // Aliases can only expand to a simple command, not to a pipeline
+        assert_eq!(
+            run_errors(&mut shell, "alias shout='say | tr a-z A-Z'"),
+            "Command `alias shout=say | tr a-z A-Z` has failed: alias: `say | tr a-z A-Z`: an \
+             alias must expand to a simple command\n"
+        );
+        assert_eq!(
+            run_errors(&mut shell, "shout"),
+            "shout: command not found\n"
+        );
+        run(&mut shell, "alias shout='say'");
+        assert_eq!(run(&mut shell, "say crab"), "hello  world crab\n");
+        assert_eq!(run(&mut shell, "shout | count"), "13\n");
+        // Quoted words are not expanded, aliases do not expand recursively
+        assert_eq!(
+            run_errors(&mut shell, "'say' x"),
+            "say: command not found\n"
+        );
+        run(&mut shell, "alias echo='echo -n' greet=echo");
+        assert_eq!(run(&mut shell, "greet a; echo b"), "ab");
+        run(&mut shell, "unalias echo greet");
+        assert_eq!(run(&mut shell, "echo b"), "b\n");
+    }
+
+    #[test]
+    fn source_startup_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(".benzinarc");
+        std::fs::write(&path, "# Settings\nexport PS1='\\W [\\?]> '\nfalse\n").unwrap();
+        let mut shell = script_shell(&dir);
+        let mut output = vec![];
+        let status = shell.source_file(&path, &mut output).unwrap();
+        assert_eq!(status, ExitStatus(1));
+        assert_eq!(shell.state.positional, vec!["benzina"]);
+        let name = dir.path().file_name().unwrap().to_str().unwrap();
+        assert_eq!(shell.prompt(), format!("{name} [1]> "));
+        assert!(shell
+            .source_file(&dir.path().join("missing"), &mut output)
+            .is_err());
+    }
+
+    #[test]
+    fn command_substitution() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = script_shell(&dir);
+        assert_eq!(
+            run(
+                &mut shell,
+                r#"echo "$(echo "a  b")" $(echo "c  d") x$(echo)y"#
+            ),
+            "a  b c d xy\n"
+        );
+        assert_eq!(
+            run(&mut shell, r#"echo "[$(echo "$(echo ')' "in  ner")")]""#),
+            "[) in  ner]\n"
+        );
+        // The output of the commands can be piped and redirected inside the substitution
+        std::fs::write(dir.path().join("names.txt"), "b\na\n").unwrap();
+        assert_eq!(
+            run(
+                &mut shell,
+                "for name in $(sort < names.txt); do echo $name; done"
+            ),
+            "a\nb\n"
+        );
+        // Unquoted empty output does not produce a word
+        assert_eq!(run(&mut shell, "printf '[%s]' $(true) \"$(true)\""), "[]");
+    }
+
+    #[test]
+    fn backquote_substitution() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = script_shell(&dir);
+        assert_eq!(
+            run(&mut shell, r#"echo "`echo "a  b"`" `echo "c  d"` x`echo`y"#),
+            "a  b c d xy\n"
+        );
+        assert_eq!(
+            run(&mut shell, r"echo `echo \`echo in\` \\\$HOME`"),
+            "in $HOME\n"
+        );
+        assert!(run_errors(&mut shell, "echo `echo").contains("unterminated backquote"));
+    }
+
+    #[test]
+    fn command_substitution_status_and_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = script_shell(&dir);
+        assert_eq!(run(&mut shell, "x=$(echo a; false); echo $x $?"), "a 1\n");
+        // Changes of the state inside the substitution are not kept
+        assert_eq!(run(&mut shell, "x=1; echo $(x=2; echo $x) $x"), "2 1\n");
+    }
+
+    #[test]
+    fn arithmetic_expansion() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = script_shell(&dir);
+        assert_eq!(
+            run(
+                &mut shell,
+                "echo $((1 + 2 * 3)) $(( (1 + 2) * 3 )) $((-7 / 2)) $((2 ** 10))"
+            ),
+            "7 9 -3 1024\n"
+        );
+        assert_eq!(
+            run(
+                &mut shell,
+                "i=0; while [ $i -lt 3 ]; do i=$((i + 1)); echo $i; done"
+            ),
+            "1\n2\n3\n"
+        );
+        assert_eq!(
+            run(&mut shell, "n=4; echo $((n * $(echo 5))) $((x = n + 1)) $x"),
+            "20 5 5\n"
+        );
+        assert_eq!(
+            run_errors(&mut shell, "echo $((1 / 0))"),
+            "Command `echo $((1 / 0))` has failed: arithmetic: division by zero\n"
+        );
+        assert_eq!(run(&mut shell, "echo $?"), "1\n");
+    }
+
+    #[test]
+    fn here_documents() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut shell = script_shell(&dir);
+        let script = "name=world
+cat <<EOF
+hello $name
+$((6 * 7)) \\$name
+EOF
+cat <<'EOF'
+$name
+EOF
+cat <<-END | count
+\t\tindented
+\tEND
+echo $(cat <<EOF
+inside
+EOF
+)";
+        assert_eq!(
+            run(&mut shell, script),
+            "hello world\n42 $name\n$name\n9\ninside\n"
+        );
+    }
+
+    #[test]
+    fn incomplete_input() {
+        assert!(Shell::is_incomplete("if true; then\n echo a"));
+        assert!(Shell::is_incomplete("f() {"));
+        assert!(!Shell::is_incomplete("if true; then echo a; fi"));
+        assert!(!Shell::is_incomplete("echo 'a"));
+        assert!(Shell::is_incomplete("echo $(echo a"));
+        assert!(Shell::is_incomplete("cat <<EOF\na"));
+        assert!(!Shell::is_incomplete("cat <<EOF\na\nEOF"));
+    }
 }
@@ -0,0 +1,687 @@
+use crate::ast::Statement;
+use crate::command::CommandError;
+use crate::lexer::{tokenize, Token, Word, WordPart};
+use crate::pipeline::{Pipeline, Redirect, Stage};
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::vec::IntoIter;
+
+/// Parses a script (one or more lines) into a list of statements.
+///
+/// Statements are separated by newlines, `;` or `&`. Control flow keywords (`if`, `while`,
+/// `for`, ...) are only recognized at the start of a command and only if they are not quoted.
+///
+/// If the script ends in the middle of a statement (e.g. `if` without `fi`),
+/// `CommandError::IncompleteInput` is returned, so that the caller can ask for more lines.
+pub fn parse_script(script: &str) -> Result<Vec<Statement>, CommandError> {
+    parse_tokens(tokenize(script)?)
+}
+
+/// Parses the tokens of a script, e.g. of the commands of `$(...)`.
+pub fn parse_tokens(tokens: Vec<Token>) -> Result<Vec<Statement>, CommandError> {
+    let mut parser = Parser {
+        tokens: tokens.into_iter().peekable(),
+    };
+    let statements = parser.list(&[])?;
+    match parser.tokens.next() {
+        None => Ok(statements),
+        Some(token) => Err(unexpected(&token)),
+    }
+}
+
+struct Parser {
+    tokens: Peekable<IntoIter<Token>>,
+}
+
+impl Parser {
+    /// Parses statements until the end of input or one of the `terminators` keywords, which is
+    /// not consumed.
+    fn list(&mut self, terminators: &[&str]) -> Result<Vec<Statement>, CommandError> {
+        let mut statements = vec![];
+        loop {
+            self.skip_newlines(true);
+            match self.tokens.peek() {
+                // The end of a subshell
+                None | Some(Token::RightParen) => break,
+                Some(token) if keyword(token).is_some_and(|kw| terminators.contains(&kw)) => break,
+                Some(_) => {}
+            }
+            let statement = self.and_or()?;
+            // `&` also separates statements
+            let background = is_background(&statement);
+            statements.push(statement);
+            match self.tokens.peek() {
+                None | Some(Token::RightParen) => break,
+                Some(Token::Semicolon | Token::Newline) => {
+                    self.tokens.next();
+                }
+                Some(_) if background => {}
+                Some(token) => return Err(unexpected(token)),
+            }
+        }
+        Ok(statements)
+    }
+
+    /// Parses a list that has to be terminated by `terminator`, and consumes the terminator.
+    fn block(&mut self, terminator: &str) -> Result<Vec<Statement>, CommandError> {
+        let statements = self.list(&[terminator])?;
+        self.expect_keyword(terminator)?;
+        Ok(statements)
+    }
+
+    /// Parses pipelines connected by `&&` and `||`.
+    fn and_or(&mut self) -> Result<Statement, CommandError> {
+        let mut statement = self.command()?;
+        loop {
+            let and = match self.tokens.peek() {
+                Some(Token::And) => true,
+                Some(Token::Or) => false,
+                _ => break,
+            };
+            if is_background(&statement) {
+                return Err(background_error());
+            }
+            self.tokens.next();
+            self.skip_newlines(false);
+            if self.tokens.peek().is_none() {
+                return Err(incomplete(if and { "&&" } else { "||" }));
+            }
+            let right = Box::new(self.command()?);
+            if is_background(&right) {
+                return Err(background_error());
+            }
+            let left = Box::new(statement);
+            statement = if and {
+                Statement::And(left, right)
+            } else {
+                Statement::Or(left, right)
+            };
+        }
+        Ok(statement)
+    }
+
+    fn command(&mut self) -> Result<Statement, CommandError> {
+        match self.tokens.peek().and_then(keyword) {
+            Some("function") => {
+                self.tokens.next();
+                let name = self.name("function")?;
+                self.function_body(name)
+            }
+            Some(kw @ ("then" | "elif" | "else" | "fi" | "do" | "done" | "}")) => Err(
+                CommandError::InvalidSyntax(format!("unexpected keyword `{kw}`")),
+            ),
+            _ => {
+                let mut pipeline = self.pipeline()?;
+                if matches!(self.tokens.peek(), Some(Token::LeftParen)) {
+                    return self.function_definition(pipeline);
+                }
+                // A compound statement that is not a part of a larger pipeline
+                if let [stage] = pipeline.stages.as_mut_slice() {
+                    if stage.redirects.is_empty() && !pipeline.background {
+                        if let Some(statement) = stage.compound.take() {
+                            return Ok(*statement);
+                        }
+                    }
+                }
+                Ok(Statement::Pipeline(pipeline))
+            }
+        }
+    }
+
+    /// Parses a compound statement, which can be a stage of a pipeline.
+    fn compound(&mut self) -> Result<Statement, CommandError> {
+        if self.tokens.next_if_eq(&Token::LeftParen).is_some() {
+            let body = self.list(&[])?;
+            return match self.tokens.next() {
+                Some(Token::RightParen) => Ok(Statement::Subshell(body)),
+                Some(token) => Err(unexpected(&token)),
+                None => Err(incomplete("(")),
+            };
+        }
+        match self.tokens.peek().and_then(keyword) {
+            Some("if") => self.if_statement(),
+            Some("while") => self.while_statement(),
+            Some("for") => self.for_statement(),
+            _ => {
+                self.expect_keyword("{")?;
+                Ok(Statement::Group(self.block("}")?))
+            }
+        }
+    }
+
+    fn if_statement(&mut self) -> Result<Statement, CommandError> {
+        self.expect_keyword("if")?;
+        let mut branches = vec![];
+        let mut otherwise = None;
+        loop {
+            let condition = self.block("then")?;
+            let body = self.list(&["elif", "else", "fi"])?;
+            branches.push((condition, body));
+            let token = self.tokens.next().ok_or_else(|| incomplete("if"))?;
+            match keyword(&token) {
+                Some("elif") => {}
+                Some("else") => {
+                    otherwise = Some(self.block("fi")?);
+                    break;
+                }
+                Some("fi") => break,
+                _ => return Err(unexpected(&token)),
+            }
+        }
+        Ok(Statement::If {
+            branches,
+            otherwise,
+        })
+    }
+
+    fn while_statement(&mut self) -> Result<Statement, CommandError> {
+        self.expect_keyword("while")?;
+        let condition = self.block("do")?;
+        let body = self.block("done")?;
+        Ok(Statement::While { condition, body })
+    }
+
+    fn for_statement(&mut self) -> Result<Statement, CommandError> {
+        self.expect_keyword("for")?;
+        let variable = self.name("for")?;
+        self.skip_newlines(false);
+        let items = if self.tokens.peek().and_then(keyword) == Some("in") {
+            self.tokens.next();
+            let mut items = vec![];
+            while let Some(Token::Word(word)) =
+                self.tokens.next_if(|token| matches!(token, Token::Word(_)))
+            {
+                items.push(word);
+            }
+            items
+        } else {
+            // `for x; do` iterates over the positional parameters
+            vec![Word {
+                parts: vec![WordPart::Variable {
+                    name: "@".to_string(),
+                    default: None,
+                    quoted: true,
+                }],
+            }]
+        };
+        self.skip_newlines(true);
+        self.expect_keyword("do")?;
+        let body = self.block("done")?;
+        Ok(Statement::For {
+            variable,
+            items,
+            body,
+        })
+    }
+
+    /// Parses `() { body; }` that follows the name of a function, which was parsed as the first
+    /// stage of `pipeline`.
+    fn function_definition(&mut self, pipeline: Pipeline) -> Result<Statement, CommandError> {
+        let name = match pipeline.stages.as_slice() {
+            [stage] if stage.words.len() == 1 && stage.redirects.is_empty() => {
+                keyword_text(&stage.words[0])
+            }
+            _ => None,
+        };
+        let Some(name) = name else {
+            return Err(CommandError::InvalidSyntax(format!(
+                "invalid function name `{}`",
+                pipeline.line()
+            )));
+        };
+        self.tokens.next();
+        match self.tokens.next() {
+            Some(Token::RightParen) => {}
+            None => return Err(incomplete("(")),
+            Some(token) => return Err(unexpected(&token)),
+        }
+        self.function_body(name.to_string())
+    }
+
+    fn function_body(&mut self, name: String) -> Result<Statement, CommandError> {
+        self.skip_newlines(false);
+        self.expect_keyword("{")?;
+        let body = self.block("}")?;
+        Ok(Statement::FunctionDefinition {
+            name,
+            body: Rc::new(body),
+        })
+    }
+
+    /// Parses commands separated by `|`, with their redirections, optionally followed by `&`.
+    fn pipeline(&mut self) -> Result<Pipeline, CommandError> {
+        let mut stages = vec![];
+        let mut stage = Stage::default();
+        let mut background = false;
+
+        loop {
+            match self.tokens.peek() {
+                Some(token) if is_empty(&stage) && starts_compound(token) => {
+                    stage.compound = Some(Box::new(self.compound()?));
+                }
+                // Only redirections can follow a compound statement
+                Some(Token::Word(_)) if stage.compound.is_some() => {
+                    return Err(unexpected(&self.tokens.next().unwrap()));
+                }
+                Some(Token::Word(_)) => {
+                    if let Some(Token::Word(word)) = self.tokens.next() {
+                        stage.words.push(word);
+                    }
+                }
+                Some(Token::Redirect(kind)) => {
+                    let kind = *kind;
+                    self.tokens.next();
+                    match self.tokens.next() {
+                        Some(Token::Word(target)) => {
+                            stage.redirects.push(Redirect { kind, target })
+                        }
+                        _ => {
+                            return Err(CommandError::InvalidSyntax(
+                                "missing redirection target".to_string(),
+                            ))
+                        }
+                    }
+                }
+                Some(Token::Pipe) => {
+                    if is_empty(&stage) {
+                        return Err(CommandError::InvalidSyntax(
+                            "empty command in pipeline".to_string(),
+                        ));
+                    }
+                    self.tokens.next();
+                    stages.push(std::mem::take(&mut stage));
+                }
+                Some(Token::Background) => {
+                    self.tokens.next();
+                    background = true;
+                    break;
+                }
+                _ => break,
+            }
+        }
+
+        if is_empty(&stage) {
+            return Err(CommandError::InvalidSyntax(
+                "empty command in pipeline".to_string(),
+            ));
+        }
+        stages.push(stage);
+        Ok(Pipeline { stages, background })
+    }
+
+    /// Skips newlines (and also `;` if `semicolons` is `true`).
+    fn skip_newlines(&mut self, semicolons: bool) {
+        while self
+            .tokens
+            .next_if(|token| *token == Token::Newline || (semicolons && *token == Token::Semicolon))
+            .is_some()
+        {}
+    }
+
+    fn expect_keyword(&mut self, expected: &str) -> Result<(), CommandError> {
+        match self.tokens.next() {
+            Some(token) if keyword(&token) == Some(expected) => Ok(()),
+            Some(token) => Err(CommandError::InvalidSyntax(format!(
+                "expected `{expected}`, found {}",
+                describe(&token)
+            ))),
+            None => Err(incomplete(expected)),
+        }
+    }
+
+    /// Parses the name of a variable or a function following `keyword`.
+    fn name(&mut self, keyword: &str) -> Result<String, CommandError> {
+        match self.tokens.next() {
+            Some(Token::Word(word)) => match keyword_text(&word) {
+                Some(name) => Ok(name.to_string()),
+                None => Err(CommandError::InvalidSyntax(format!(
+                    "invalid name `{}` after `{keyword}`",
+                    word.text()
+                ))),
+            },
+            Some(token) => Err(unexpected(&token)),
+            None => Err(incomplete(keyword)),
+        }
+    }
+}
+
+fn is_empty(stage: &Stage) -> bool {
+    stage.words.is_empty() && stage.compound.is_none()
+}
+
+/// Returns `true` if the token starts a compound statement at the start of a command.
+fn starts_compound(token: &Token) -> bool {
+    *token == Token::LeftParen || matches!(keyword(token), Some("if" | "while" | "for" | "{"))
+}
+
+fn is_background(statement: &Statement) -> bool {
+    matches!(statement, Statement::Pipeline(pipeline) if pipeline.background)
+}
+
+fn background_error() -> CommandError {
+    CommandError::InvalidSyntax("only whole pipelines can run in the background".to_string())
+}
+
+/// Returns the text of the token if it is an unquoted word, which could be a keyword.
+fn keyword(token: &Token) -> Option<&str> {
+    match token {
+        Token::Word(word) => keyword_text(word),
+        _ => None,
+    }
+}
+
+fn keyword_text(word: &Word) -> Option<&str> {
+    match word.parts.as_slice() {
+        [WordPart::Unquoted(text)] => Some(text.as_str()),
+        _ => None,
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Word(word) => format!("`{}`", word.text()),
+        Token::Pipe => "`|`".to_string(),
+        Token::Redirect(_) => "redirection".to_string(),
+        Token::Background => "`&`".to_string(),
+        Token::And => "`&&`".to_string(),
+        Token::Or => "`||`".to_string(),
+        Token::Semicolon => "`;`".to_string(),
+        Token::Newline => "end of line".to_string(),
+        Token::LeftParen => "`(`".to_string(),
+        Token::RightParen => "`)`".to_string(),
+    }
+}
+
+fn unexpected(token: &Token) -> CommandError {
+    CommandError::InvalidSyntax(format!("unexpected {}", describe(token)))
+}
+
+fn incomplete(construct: &str) -> CommandError {
+    CommandError::IncompleteInput(format!("unexpected end of input after `{construct}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ast::Statement;
+    use crate::command::CommandError;
+    use crate::parser::parse_script;
+    use crate::pipeline::{Pipeline, RedirectKind, Stage};
+
+    fn words(stage: &Stage) -> Vec<String> {
+        stage.words.iter().map(|word| word.text()).collect()
+    }
+
+    fn parse_pipeline(line: &str) -> Result<Pipeline, CommandError> {
+        let mut statements = parse_script(line)?;
+        assert_eq!(statements.len(), 1);
+        match statements.pop().unwrap() {
+            Statement::Pipeline(pipeline) => Ok(pipeline),
+            statement => panic!("Unexpected statement {statement:?}"),
+        }
+    }
+
+    fn commands(statements: &[Statement]) -> Vec<String> {
+        statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::Pipeline(pipeline) => pipeline.line(),
+                statement => panic!("Unexpected statement {statement:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_single_command() {
+        let pipeline = parse_pipeline("  cd  '/foo bar' ").unwrap();
+        assert_eq!(pipeline.stages.len(), 1);
+        assert_eq!(words(&pipeline.stages[0]), vec!["cd", "/foo bar"]);
+        assert!(pipeline.stages[0].redirects.is_empty());
+    }
+
+    #[test]
+    fn parse_empty_line() {
+        assert!(parse_script("   ").unwrap().is_empty());
+        assert!(parse_script("\n ; \n").unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_pipe() {
+        let pipeline = parse_pipeline("a 1|b 2 | c").unwrap();
+        let stages: Vec<Vec<String>> = pipeline.stages.iter().map(words).collect();
+        assert_eq!(stages, vec![vec!["a", "1"], vec!["b", "2"], vec!["c"]]);
+    }
+
+    #[test]
+    fn parse_redirects() {
+        let pipeline = parse_pipeline("a <in.txt | b x >> log.txt >'out file.txt'").unwrap();
+        assert_eq!(pipeline.stages.len(), 2);
+
+        let first = &pipeline.stages[0];
+        assert_eq!(words(first), vec!["a"]);
+        assert_eq!(first.redirects.len(), 1);
+        assert_eq!(first.redirects[0].kind, RedirectKind::Input);
+        assert_eq!(first.redirects[0].target.text(), "in.txt");
+
+        let second = &pipeline.stages[1];
+        assert_eq!(words(second), vec!["b", "x"]);
+        let redirects: Vec<(RedirectKind, String)> = second
+            .redirects
+            .iter()
+            .map(|redirect| (redirect.kind, redirect.target.text()))
+            .collect();
+        assert_eq!(
+            redirects,
+            vec![
+                (RedirectKind::Append, "log.txt".to_string()),
+                (RedirectKind::Output, "out file.txt".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_missing_target() {
+        assert!(matches!(
+            parse_script("a >"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+        assert!(matches!(
+            parse_script("a > | b"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn parse_empty_stage() {
+        assert!(matches!(
+            parse_script("a | | b"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+        assert!(matches!(
+            parse_script("a |"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn parse_background() {
+        let pipeline = parse_pipeline("a | b > out.txt &").unwrap();
+        assert_eq!(pipeline.stages.len(), 2);
+        assert!(pipeline.background);
+        assert!(!parse_pipeline("a").unwrap().background);
+        assert!(matches!(
+            parse_script("&"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+
+        assert!(matches!(
+            parse_script("a & && b"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+
+        let statements = parse_script("a & b").unwrap();
+        assert_eq!(commands(&statements), vec!["a", "b"]);
+        assert!(matches!(&statements[0], Statement::Pipeline(pipeline) if pipeline.background));
+        assert!(matches!(&statements[1], Statement::Pipeline(pipeline) if !pipeline.background));
+    }
+
+    #[test]
+    fn parse_unterminated_quote() {
+        assert!(matches!(
+            parse_script("cd 'foo"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn parse_sequence() {
+        let statements = parse_script("a; b\n\nc 1 ;").unwrap();
+        assert_eq!(commands(&statements), vec!["a", "b", "c 1"]);
+    }
+
+    #[test]
+    fn parse_and_or() {
+        let statements = parse_script("a && b || c").unwrap();
+        let [Statement::Or(left, right)] = statements.as_slice() else {
+            panic!("Unexpected statements {statements:?}");
+        };
+        assert!(matches!(left.as_ref(), Statement::And(_, _)));
+        assert!(matches!(right.as_ref(), Statement::Pipeline(_)));
+    }
+
+    #[test]
+    fn parse_if() {
+        let statements =
+            parse_script("if a; then b; elif c\nthen d; e\nelse f; fi; echo if then").unwrap();
+        assert_eq!(statements.len(), 2);
+        let Statement::If {
+            branches,
+            otherwise,
+        } = &statements[0]
+        else {
+            panic!("Unexpected statement {:?}", statements[0]);
+        };
+        assert_eq!(branches.len(), 2);
+        assert_eq!(commands(&branches[0].0), vec!["a"]);
+        assert_eq!(commands(&branches[1].1), vec!["d", "e"]);
+        assert_eq!(commands(otherwise.as_ref().unwrap()), vec!["f"]);
+        assert_eq!(commands(&statements[1..]), vec!["echo if then"]);
+    }
+
+    #[test]
+    fn parse_loops() {
+        let statements =
+            parse_script("while a; do b; done\nfor x in 1 \"2 3\"\ndo\n  c $x\ndone").unwrap();
+        assert!(
+            matches!(&statements[0], Statement::While { condition, body }
+            if commands(condition) == vec!["a"] && commands(body) == vec!["b"])
+        );
+        let Statement::For {
+            variable, items, ..
+        } = &statements[1]
+        else {
+            panic!("Unexpected statement {:?}", statements[1]);
+        };
+        assert_eq!(variable, "x");
+        let items: Vec<String> = items.iter().map(|item| item.text()).collect();
+        assert_eq!(items, vec!["1", "2 3"]);
+    }
+
+    #[test]
+    fn parse_compound_stages() {
+        let pipeline = parse_pipeline("for x in a; do b; done | c > out.txt").unwrap();
+        assert!(matches!(
+            pipeline.stages[0].compound.as_deref(),
+            Some(Statement::For { .. })
+        ));
+        assert_eq!(words(&pipeline.stages[1]), vec!["c"]);
+        assert_eq!(pipeline.line(), "for x in a; do b; done | c");
+
+        let pipeline = parse_pipeline("a | while b; do c; done >> log.txt").unwrap();
+        assert!(matches!(
+            pipeline.stages[1].compound.as_deref(),
+            Some(Statement::While { .. })
+        ));
+        assert_eq!(pipeline.stages[1].redirects[0].kind, RedirectKind::Append);
+
+        let pipeline = parse_pipeline("if a; then b; fi < in.txt").unwrap();
+        assert_eq!(pipeline.stages[0].redirects[0].kind, RedirectKind::Input);
+        let pipeline = parse_pipeline("{ a; b | c; } | (d; e)").unwrap();
+        assert_eq!(pipeline.line(), "{ a; b | c; } | (d; e)");
+        assert!(matches!(
+            pipeline.stages[1].compound.as_deref(),
+            Some(Statement::Subshell(body)) if commands(body) == vec!["d", "e"]
+        ));
+
+        // Without a pipe or redirections, the statement is not wrapped in a pipeline
+        let statements = parse_script("(a)\n{ b; }").unwrap();
+        assert!(matches!(&statements[0], Statement::Subshell(body) if commands(body) == vec!["a"]));
+        assert!(matches!(&statements[1], Statement::Group(body) if commands(body) == vec!["b"]));
+
+        assert!(matches!(
+            parse_script("if a; then b; fi c"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+        assert!(matches!(
+            parse_script("(a; b"),
+            Err(CommandError::IncompleteInput(_))
+        ));
+        assert!(matches!(
+            parse_script("{ a; ) }"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn parse_functions() {
+        let statements =
+            parse_script("greet() {\n echo hi; }\nfunction bye { echo bye; }").unwrap();
+        let names: Vec<&str> = statements
+            .iter()
+            .map(|statement| match statement {
+                Statement::FunctionDefinition { name, body } => {
+                    assert_eq!(body.len(), 1);
+                    name.as_str()
+                }
+                statement => panic!("Unexpected statement {statement:?}"),
+            })
+            .collect();
+        assert_eq!(names, vec!["greet", "bye"]);
+        assert!(matches!(
+            parse_script("a b() { c; }"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+    }
+
+    #[test]
+    fn parse_incomplete() {
+        for script in [
+            "if a; then b",
+            "while a; do",
+            "for x in a b",
+            "f() {",
+            "{ a;",
+            "a &&",
+            "echo $(a",
+            "echo \"$(echo ')'",
+            "echo $((1 +",
+            "cat <<EOF\ntext",
+        ] {
+            assert!(matches!(
+                parse_script(script),
+                Err(CommandError::IncompleteInput(_))
+            ));
+        }
+        assert!(matches!(
+            parse_script("if a; then b; done"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+        assert!(matches!(
+            parse_script("fi"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+        assert!(matches!(
+            parse_script("echo $(if a)"),
+            Err(CommandError::InvalidSyntax(_))
+        ));
+    }
+}
@@ -0,0 +1,56 @@
+use crate::ast::Statement;
+use crate::lexer::Word;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectKind {
+    /// `< file`
+    Input,
+    /// `> file`
+    Output,
+    /// `>> file`
+    Append,
+    /// `<< DELIMITER`, the target is the contents of the here-document
+    HereDocument,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Redirect {
+    pub kind: RedirectKind,
+    pub target: Word,
+}
+
+/// A single command of a pipeline, together with its redirections.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Stage {
+    pub words: Vec<Word>,
+    /// A compound statement (e.g. `while` or `{ ...; }`) that is executed instead of the words
+    pub compound: Option<Box<Statement>>,
+    pub redirects: Vec<Redirect>,
+}
+
+impl Stage {
+    /// Command line of this stage, without quotes and redirections.
+    pub fn line(&self) -> String {
+        if let Some(statement) = &self.compound {
+            return statement.line();
+        }
+        let words: Vec<String> = self.words.iter().map(|word| word.text()).collect();
+        words.join(" ")
+    }
+}
+
+/// Commands separated by `|`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Pipeline {
+    pub stages: Vec<Stage>,
+    /// The pipeline should be executed in the background (it ends with `&`)
+    pub background: bool,
+}
+
+impl Pipeline {
+    /// Command line of this pipeline, without quotes and redirections.
+    pub fn line(&self) -> String {
+        let stages: Vec<String> = self.stages.iter().map(|stage| stage.line()).collect();
+        stages.join(" | ")
+    }
+}
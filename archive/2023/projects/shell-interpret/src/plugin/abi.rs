@@ -0,0 +1,67 @@
+//! C ABI of plugins that are loaded from dynamic libraries.
+//!
+//! A plugin library exports a function called `benzina_plugin_v1` (see `PluginEntryPoint`),
+//! which returns a pointer to a `PluginDescriptor`. The descriptor (and the strings it points to)
+//! must stay valid until the library is unloaded. All strings are NUL-terminated UTF-8.
+
+use std::ffi::{c_char, c_void};
+
+/// Version of the ABI described by this module, it is stored in `PluginDescriptor::abi_version`.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+/// Name of the function that is looked up in plugin libraries.
+pub const PLUGIN_ENTRY_POINT: &str = "benzina_plugin_v1";
+
+/// Signature of the `benzina_plugin_v1` function.
+pub type PluginEntryPoint = unsafe extern "C" fn() -> *const PluginDescriptor;
+
+/// Writes `len` bytes from `data` to the output of the command.
+/// `context` must be the `CommandInvocation::context` of the current invocation.
+pub type WriteCallback = unsafe extern "C" fn(context: *mut c_void, data: *const u8, len: usize);
+
+/// Description of a plugin.
+#[repr(C)]
+pub struct PluginDescriptor {
+    /// Must be equal to `PLUGIN_ABI_VERSION`.
+    pub abi_version: u32,
+    /// Number of items in `commands`.
+    pub command_count: usize,
+    /// Commands handled by the plugin.
+    pub commands: *const PluginCommandInfo,
+    /// Executes a command and returns its exit status.
+    pub execute: unsafe extern "C" fn(invocation: *const CommandInvocation) -> i32,
+}
+
+/// Name and description of a command handled by a plugin.
+#[repr(C)]
+pub struct PluginCommandInfo {
+    pub name: *const c_char,
+    pub description: *const c_char,
+}
+
+/// Arguments of a single command execution. The pointers are only valid during the call.
+#[repr(C)]
+pub struct CommandInvocation {
+    /// Number of items in `argv`.
+    pub argc: usize,
+    /// Expanded arguments, the first one is the name of the command.
+    pub argv: *const *const c_char,
+    /// Working directory of the shell.
+    pub workdir: *const c_char,
+    /// Number of items in `envp`.
+    pub envc: usize,
+    /// Variables of the shell in the `NAME=value` form.
+    pub envp: *const *const c_char,
+    /// Input of the command (e.g. the output of the previous command in a pipeline).
+    pub input: *const u8,
+    /// Length of `input` in bytes.
+    pub input_len: usize,
+    /// Writes to the output of the command.
+    pub write: WriteCallback,
+    /// Opaque pointer that has to be passed to `write`.
+    pub context: *mut c_void,
+}
+
+// Descriptors are immutable, so plugins can store them in statics.
+unsafe impl Sync for PluginDescriptor {}
+unsafe impl Sync for PluginCommandInfo {}
@@ -0,0 +1,126 @@
+//! Protocol of plugins that are separate executables.
+//!
+//! The executable is started for every request with the working directory and environment of
+//! the shell. The shell writes a single JSON request to its stdin (followed by a newline) and
+//! closes it, the plugin writes a single JSON response to its stdout and exits.
+//!
+//! When the plugin is loaded, it receives `{"type": "describe"}` and responds with the commands
+//! that it handles:
+//! `{"commands": [{"name": "shout", "description": "print the input in upper case"}]}`
+//!
+//! To execute a command, it receives
+//! `{"type": "execute", "args": ["shout", "a"], "workdir": "/home", "env": {...}, "input": "..."}`
+//! (`args` start with the name of the command) and responds with
+//! `{"status": 0, "output": "..."}`, or with `{"error": "message"}` if the command has failed.
+//!
+//! JSON strings cannot contain arbitrary bytes, so the input has to be valid UTF-8, otherwise
+//! the command fails without starting the plugin.
+
+use crate::command::CommandError;
+use crate::commands::external::{program_command, spawn_error};
+use crate::plugin::PluginCommand;
+use crate::state::InterpreterState;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::Stdio;
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+enum Request<'a> {
+    Describe,
+    Execute {
+        args: &'a [String],
+        workdir: &'a Path,
+        env: BTreeMap<&'a String, &'a String>,
+        input: String,
+    },
+}
+
+#[derive(Deserialize)]
+struct Description {
+    commands: Vec<PluginCommand>,
+}
+
+#[derive(Deserialize)]
+struct Response {
+    #[serde(default)]
+    status: i32,
+    #[serde(default)]
+    output: String,
+    error: Option<String>,
+}
+
+/// Asks the plugin for the commands that it handles.
+pub fn describe(path: &Path, state: &InterpreterState) -> Result<Vec<PluginCommand>, CommandError> {
+    let description: Description = send_request(path, &Request::Describe, state)?;
+    Ok(description.commands)
+}
+
+/// Executes a command of the plugin and returns its exit status.
+pub fn execute(
+    path: &Path,
+    args: &[String],
+    state: &InterpreterState,
+    input: &[u8],
+    output: &mut dyn Write,
+) -> Result<i32, CommandError> {
+    let input = String::from_utf8(input.to_vec()).map_err(|_| {
+        CommandError::IO(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("{}: plugin commands only accept UTF-8 input", args[0]),
+        ))
+    })?;
+    let request = Request::Execute {
+        args,
+        workdir: &state.workdir,
+        env: state.exported_env().collect(),
+        input,
+    };
+    let response: Response = send_request(path, &request, state)?;
+    if let Some(error) = response.error {
+        return Err(CommandError::IO(std::io::Error::other(format!(
+            "{}: {error}",
+            args[0]
+        ))));
+    }
+    output
+        .write_all(response.output.as_bytes())
+        .map_err(CommandError::IO)?;
+    Ok(response.status)
+}
+
+fn send_request<T: DeserializeOwned>(
+    path: &Path,
+    request: &Request,
+    state: &InterpreterState,
+) -> Result<T, CommandError> {
+    let mut child = program_command(path, &[], state)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|error| spawn_error(path, error))?;
+
+    let mut message = serde_json::to_vec(request).unwrap();
+    message.push(b'\n');
+    let mut stdin = child.stdin.take().unwrap();
+    // Write from a separate thread, the plugin could start responding before it reads everything
+    let writer = std::thread::spawn(move || {
+        // The plugin does not have to read the whole request
+        let _ = stdin.write_all(&message);
+    });
+    let mut response = vec![];
+    let res = child.stdout.take().unwrap().read_to_end(&mut response);
+    let status = child.wait().map_err(CommandError::IO)?;
+    writer.join().unwrap();
+    res.map_err(CommandError::IO)?;
+
+    serde_json::from_slice(&response).map_err(|error| {
+        CommandError::IO(std::io::Error::other(format!(
+            "plugin `{}` has sent an invalid response ({status}): {error}",
+            path.display()
+        )))
+    })
+}
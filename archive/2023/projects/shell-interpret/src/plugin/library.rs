@@ -0,0 +1,177 @@
+use crate::command::CommandError;
+use crate::plugin::abi::{
+    CommandInvocation, PluginDescriptor, PluginEntryPoint, PLUGIN_ABI_VERSION, PLUGIN_ENTRY_POINT,
+};
+use crate::plugin::PluginCommand;
+use crate::state::InterpreterState;
+use std::ffi::{c_void, CStr, CString};
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+/// A plugin loaded from a dynamic library, it is unloaded when dropped.
+pub struct Library {
+    handle: *mut c_void,
+    descriptor: *const PluginDescriptor,
+}
+
+impl Library {
+    /// Loads the library and returns it together with the commands that it declares.
+    pub fn open(path: &Path) -> Result<(Self, Vec<PluginCommand>), CommandError> {
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| load_error(path, "invalid path".to_string()))?;
+        let handle = unsafe { libc::dlopen(c_path.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL) };
+        if handle.is_null() {
+            return Err(load_error(path, dl_error()));
+        }
+        // The library is closed on errors by dropping it
+        let mut library = Library {
+            handle,
+            descriptor: std::ptr::null(),
+        };
+
+        let symbol = CString::new(PLUGIN_ENTRY_POINT).unwrap();
+        let entry_point = unsafe { libc::dlsym(handle, symbol.as_ptr()) };
+        if entry_point.is_null() {
+            return Err(load_error(path, dl_error()));
+        }
+        let entry_point: PluginEntryPoint = unsafe { std::mem::transmute(entry_point) };
+        library.descriptor = unsafe { entry_point() };
+        let Some(descriptor) = (unsafe { library.descriptor.as_ref() }) else {
+            return Err(load_error(
+                path,
+                "the plugin has returned no descriptor".to_string(),
+            ));
+        };
+        if descriptor.abi_version != PLUGIN_ABI_VERSION {
+            return Err(load_error(
+                path,
+                format!(
+                    "unsupported ABI version {} (expected {PLUGIN_ABI_VERSION})",
+                    descriptor.abi_version
+                ),
+            ));
+        }
+
+        let infos = if descriptor.command_count == 0 {
+            &[][..]
+        } else {
+            unsafe { std::slice::from_raw_parts(descriptor.commands, descriptor.command_count) }
+        };
+        let mut commands = vec![];
+        for info in infos {
+            if info.name.is_null() {
+                return Err(load_error(path, "a command has no name".to_string()));
+            }
+            let name = unsafe { CStr::from_ptr(info.name) };
+            let description = if info.description.is_null() {
+                String::new()
+            } else {
+                unsafe { CStr::from_ptr(info.description) }
+                    .to_string_lossy()
+                    .into_owned()
+            };
+            commands.push(PluginCommand {
+                name: name.to_string_lossy().into_owned(),
+                description,
+            });
+        }
+        Ok((library, commands))
+    }
+
+    /// Executes a command of the plugin and returns its exit status.
+    pub fn execute(
+        &self,
+        args: &[String],
+        state: &InterpreterState,
+        input: &[u8],
+        output: &mut dyn Write,
+    ) -> Result<i32, CommandError> {
+        let args = c_strings(args.iter().map(|arg| arg.as_bytes()))?;
+        let env = c_strings(
+            state
+                .exported_env()
+                .map(|(name, value)| format!("{name}={value}").into_bytes()),
+        )?;
+        let workdir = c_string(state.workdir.as_os_str().as_bytes())?;
+        let argv: Vec<*const libc::c_char> = args.iter().map(|arg| arg.as_ptr()).collect();
+        let envp: Vec<*const libc::c_char> = env.iter().map(|var| var.as_ptr()).collect();
+
+        let mut context = WriteContext {
+            output,
+            error: None,
+        };
+        let invocation = CommandInvocation {
+            argc: argv.len(),
+            argv: argv.as_ptr(),
+            workdir: workdir.as_ptr(),
+            envc: envp.len(),
+            envp: envp.as_ptr(),
+            input: input.as_ptr(),
+            input_len: input.len(),
+            write: write_output,
+            context: &mut context as *mut WriteContext as *mut c_void,
+        };
+        let status = unsafe { ((*self.descriptor).execute)(&invocation) };
+        match context.error {
+            Some(error) => Err(CommandError::IO(error)),
+            None => Ok(status),
+        }
+    }
+}
+
+impl Drop for Library {
+    fn drop(&mut self) {
+        unsafe { libc::dlclose(self.handle) };
+    }
+}
+
+/// State of the `write` callback passed to the plugin.
+struct WriteContext<'a> {
+    output: &'a mut dyn Write,
+    /// The first error that has happened while writing, further output is ignored
+    error: Option<std::io::Error>,
+}
+
+unsafe extern "C" fn write_output(context: *mut c_void, data: *const u8, len: usize) {
+    let context = unsafe { &mut *(context as *mut WriteContext) };
+    if context.error.is_some() || len == 0 {
+        return;
+    }
+    let data = unsafe { std::slice::from_raw_parts(data, len) };
+    if let Err(error) = context.output.write_all(data) {
+        context.error = Some(error);
+    }
+}
+
+fn c_string(bytes: &[u8]) -> Result<CString, CommandError> {
+    CString::new(bytes).map_err(|_| {
+        CommandError::Usage("plugin arguments cannot contain NUL characters".to_string())
+    })
+}
+
+fn c_strings<I: IntoIterator<Item = T>, T: AsRef<[u8]>>(
+    items: I,
+) -> Result<Vec<CString>, CommandError> {
+    items
+        .into_iter()
+        .map(|item| c_string(item.as_ref()))
+        .collect()
+}
+
+fn dl_error() -> String {
+    let error = unsafe { libc::dlerror() };
+    if error.is_null() {
+        return "unknown error".to_string();
+    }
+    unsafe { CStr::from_ptr(error) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn load_error(path: &Path, message: String) -> CommandError {
+    CommandError::IO(std::io::Error::other(format!(
+        "cannot load plugin `{}`: {message}",
+        path.display()
+    )))
+}
@@ -0,0 +1,95 @@
+//! Commands loaded at runtime from plugins.
+//!
+//! A plugin is either a dynamic library that implements the C ABI described in `abi`, or an
+//! executable that communicates with the shell using JSON messages (see `executable`).
+//! Each plugin declares the names of the commands that it handles.
+
+pub mod abi;
+mod executable;
+mod library;
+
+use crate::command::{Command, CommandError, CommandResponse};
+use crate::commands::external::is_executable;
+use crate::plugin::library::Library;
+use crate::state::InterpreterState;
+use serde::Deserialize;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// A command declared by a plugin.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct PluginCommand {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+enum Backend {
+    Library(Library),
+    Executable,
+}
+
+pub struct Plugin {
+    path: PathBuf,
+    commands: Vec<PluginCommand>,
+    backend: Backend,
+}
+
+impl Plugin {
+    /// Loads a plugin. Files with the `so` or `dylib` extension are loaded as dynamic libraries,
+    /// other files have to be executables.
+    pub fn load(path: &Path, state: &InterpreterState) -> Result<Self, CommandError> {
+        let path = state.resolve_path(path);
+        let is_library = matches!(
+            path.extension().and_then(|extension| extension.to_str()),
+            Some("so" | "dylib")
+        );
+        let (backend, commands) = if is_library {
+            let (library, commands) = Library::open(&path)?;
+            (Backend::Library(library), commands)
+        } else if is_executable(&path) {
+            (Backend::Executable, executable::describe(&path, state)?)
+        } else {
+            return Err(CommandError::IO(std::io::Error::other(format!(
+                "cannot load plugin `{}`: it is neither a library nor an executable",
+                path.display()
+            ))));
+        };
+        Ok(Self {
+            path,
+            commands,
+            backend,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn commands(&self) -> &[PluginCommand] {
+        &self.commands
+    }
+}
+
+impl Command for Plugin {
+    fn execute(
+        &self,
+        args: &[String],
+        state: &mut InterpreterState,
+        input: &mut dyn Read,
+        output: &mut dyn Write,
+    ) -> CommandResponse {
+        if !self.commands.iter().any(|command| command.name == args[0]) {
+            return CommandResponse::Unhandled;
+        }
+        let mut buffer = vec![];
+        if let Err(error) = input.read_to_end(&mut buffer) {
+            return CommandResponse::Handled(Err(CommandError::IO(error)));
+        }
+        let res = match &self.backend {
+            Backend::Library(library) => library.execute(args, state, &buffer, output),
+            Backend::Executable => executable::execute(&self.path, args, state, &buffer, output),
+        };
+        CommandResponse::Handled(res.map(|status| state.last_status = status))
+    }
+}
@@ -0,0 +1,125 @@
+use crate::state::InterpreterState;
+use std::ffi::CStr;
+
+/// Prompt used when the `PS1` variable is not set.
+pub const DEFAULT_PROMPT: &str = "\\w\\$ ";
+
+/// Expands the escapes of a prompt format string (a subset of the escapes supported by bash):
+///
+/// - `\u`: name of the current user
+/// - `\h`: host name up to the first dot, `\H`: full host name
+/// - `\w`: working directory (with the home directory abbreviated as `~`), `\W`: its last
+///   component
+/// - `\?`: exit status of the last command
+/// - `\$`: `#` for the root user, `$` otherwise
+/// - `\n`: newline, `\e`: escape character (for colors), `\\`: backslash
+///
+/// `\[` and `\]` (used by bash to mark non-printable characters) are ignored, other escapes are
+/// kept as they are.
+pub fn format_prompt(format: &str, state: &InterpreterState) -> String {
+    let mut prompt = String::new();
+    let mut chars = format.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            prompt.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('u') => prompt.push_str(&user_name(state)),
+            Some('h') => prompt.push_str(host_name().split('.').next().unwrap()),
+            Some('H') => prompt.push_str(&host_name()),
+            Some('w') => prompt.push_str(&state.abbreviate_home(&state.workdir)),
+            Some('W') => {
+                let name = state
+                    .workdir
+                    .file_name()
+                    .unwrap_or(state.workdir.as_os_str());
+                prompt.push_str(&name.to_string_lossy());
+            }
+            Some('?') => prompt.push_str(&state.last_status.to_string()),
+            Some('$') => prompt.push(if unsafe { libc::geteuid() } == 0 {
+                '#'
+            } else {
+                '$'
+            }),
+            Some('n') => prompt.push('\n'),
+            Some('e') => prompt.push('\x1b'),
+            Some('\\') => prompt.push('\\'),
+            Some('[' | ']') => {}
+            Some(c) => {
+                prompt.push('\\');
+                prompt.push(c);
+            }
+            None => prompt.push('\\'),
+        }
+    }
+    prompt
+}
+
+fn user_name(state: &InterpreterState) -> String {
+    if let Some(user) = state.env.get("USER") {
+        return user.clone();
+    }
+    let passwd = unsafe { libc::getpwuid(libc::geteuid()) };
+    if passwd.is_null() {
+        return String::new();
+    }
+    unsafe { CStr::from_ptr((*passwd).pw_name) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+fn host_name() -> String {
+    let mut buffer = [0u8; 256];
+    if unsafe { libc::gethostname(buffer.as_mut_ptr().cast(), buffer.len()) } != 0 {
+        return String::new();
+    }
+    CStr::from_bytes_until_nul(&buffer)
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prompt::format_prompt;
+    use crate::state::InterpreterState;
+    use std::path::PathBuf;
+
+    #[test]
+    fn expand_escapes() {
+        let mut state = InterpreterState::new(PathBuf::from("/home/ferris/projects/benzina"));
+        state.env.insert("USER".to_string(), "ferris".to_string());
+        state
+            .env
+            .insert("HOME".to_string(), "/home/ferris".to_string());
+        state.last_status = 3;
+        assert_eq!(
+            format_prompt("\\u:\\w [\\?] \\W> ", &state),
+            "ferris:~/projects/benzina [3] benzina> "
+        );
+        assert_eq!(
+            format_prompt("\\[\\e[32m\\]x\\\\\\n\\q\\", &state),
+            "\x1b[32mx\\\n\\q\\"
+        );
+    }
+
+    #[test]
+    fn abbreviate_home() {
+        let mut state = InterpreterState::new(PathBuf::from("/home/ferris"));
+        state
+            .env
+            .insert("HOME".to_string(), "/home/ferris".to_string());
+        assert_eq!(format_prompt("\\w", &state), "~");
+        state.workdir = PathBuf::from("/home/ferrisx");
+        assert_eq!(format_prompt("\\w", &state), "/home/ferrisx");
+        state.workdir = PathBuf::from("/");
+        assert_eq!(format_prompt("\\W", &state), "/");
+    }
+
+    #[test]
+    fn host_name() {
+        let state = InterpreterState::new(PathBuf::from("/"));
+        let full = format_prompt("\\H", &state);
+        assert!(full.starts_with(&format_prompt("\\h", &state)));
+    }
+}
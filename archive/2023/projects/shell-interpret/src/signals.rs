@@ -0,0 +1,244 @@
+//! Handling of signals received by the shell.
+//!
+//! The signal handler only records the signal and forwards it to the foreground program. The
+//! shell reacts to it between statements: it runs the handler registered with `trap`, or without
+//! one, `SIGINT` cancels the current line and `SIGTERM` and `SIGHUP` are forwarded to all jobs
+//! and end the shell.
+
+use crate::executor::Flow;
+use crate::jobs::JobStatus;
+use crate::Shell;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, Ordering};
+
+/// Pseudo-signal of `trap` whose handler is executed when the shell exits.
+pub const EXIT: libc::c_int = 0;
+
+/// Signals that can be trapped. The first `ALWAYS_HANDLED` of them are handled by the shell even
+/// without a trap, the others keep their default effect until a trap is set.
+pub const TRAPPABLE_SIGNALS: &[libc::c_int] = &[
+    libc::SIGINT,
+    libc::SIGTERM,
+    libc::SIGHUP,
+    libc::SIGQUIT,
+    libc::SIGUSR1,
+    libc::SIGUSR2,
+];
+const ALWAYS_HANDLED: usize = 3;
+
+/// Whether `install_handlers` was called.
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+/// Bit set of the signals received since the last call of `take_pending`.
+static PENDING: AtomicU64 = AtomicU64::new(0);
+/// PID of the program running in the foreground, or the negated process group of a job brought
+/// to the foreground with `fg`, 0 if there is none.
+static FOREGROUND: AtomicI32 = AtomicI32::new(0);
+
+extern "C" fn handle_signal(
+    signal: libc::c_int,
+    info: *mut libc::siginfo_t,
+    _context: *mut libc::c_void,
+) {
+    let errno = unsafe { *errno_location() };
+    PENDING.fetch_or(1 << signal, Ordering::SeqCst);
+    // Signals from the terminal already reach programs in the process group of the shell, so
+    // only signals sent by other processes (`si_code <= 0`) are forwarded to them. Jobs have
+    // their own process group, they always get the signal.
+    let target = FOREGROUND.load(Ordering::SeqCst);
+    if target < 0 || (target > 0 && unsafe { (*info).si_code } <= 0) {
+        unsafe { libc::kill(target, signal) };
+    }
+    unsafe { *errno_location() = errno };
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+unsafe fn errno_location() -> *mut libc::c_int {
+    libc::__errno_location()
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+unsafe fn errno_location() -> *mut libc::c_int {
+    libc::__error()
+}
+
+/// Installs the signal handlers of the shell.
+///
+/// Until it is called, signals have their default effect, which is what programs that embed the
+/// shell usually want. Traps are still recorded, but only the `EXIT` trap is executed.
+pub fn install_handlers() -> std::io::Result<()> {
+    INSTALLED.store(true, Ordering::SeqCst);
+    for signal in &TRAPPABLE_SIGNALS[..ALWAYS_HANDLED] {
+        set_handler(*signal, true)?;
+    }
+    Ok(())
+}
+
+/// Installs the handler for a signal that is not always handled, when a trap for it is set
+/// (`trapped`) or removed.
+pub fn update_handler(signal: libc::c_int, trapped: bool) -> std::io::Result<()> {
+    if INSTALLED.load(Ordering::SeqCst) && TRAPPABLE_SIGNALS[ALWAYS_HANDLED..].contains(&signal) {
+        set_handler(signal, trapped)?;
+    }
+    Ok(())
+}
+
+fn set_handler(signal: libc::c_int, handle: bool) -> std::io::Result<()> {
+    let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+    if handle {
+        action.sa_sigaction = handle_signal as *const () as libc::sighandler_t;
+        action.sa_flags = libc::SA_SIGINFO;
+        // Signals that end the shell have to interrupt reading of the next line
+        if signal != libc::SIGTERM && signal != libc::SIGHUP {
+            action.sa_flags |= libc::SA_RESTART;
+        }
+    } else {
+        action.sa_sigaction = libc::SIG_DFL;
+    }
+    unsafe { libc::sigemptyset(&mut action.sa_mask) };
+    if unsafe { libc::sigaction(signal, &action, std::ptr::null_mut()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Returns `true` if a signal that ends the shell has arrived and it was not processed yet.
+pub fn is_termination_pending() -> bool {
+    let mask = (1 << libc::SIGTERM) | (1 << libc::SIGHUP);
+    PENDING.load(Ordering::SeqCst) & mask != 0
+}
+
+fn take_pending() -> u64 {
+    PENDING.swap(0, Ordering::SeqCst)
+}
+
+/// Forwards signals received by the shell to a program (or the negated process group of a job)
+/// until it is dropped.
+pub struct Foreground {
+    previous: libc::pid_t,
+}
+
+impl Foreground {
+    pub fn set(target: libc::pid_t) -> Self {
+        Self {
+            previous: FOREGROUND.swap(target, Ordering::SeqCst),
+        }
+    }
+}
+
+impl Drop for Foreground {
+    fn drop(&mut self) {
+        FOREGROUND.store(self.previous, Ordering::SeqCst);
+    }
+}
+
+/// Returns `true` if the shell controls the terminal, i.e. its standard input is a terminal and
+/// the shell is in its foreground process group. Only then jobs can be moved to the foreground.
+pub(crate) fn controls_terminal() -> bool {
+    unsafe {
+        libc::isatty(libc::STDIN_FILENO) == 1
+            && libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp()
+    }
+}
+
+/// Gives the terminal to the process group of a job until it is dropped, so that the job can
+/// read from it and it receives the signals typed on it (e.g. Ctrl+C). Does nothing if the shell
+/// does not control the terminal.
+pub struct TerminalOwner {
+    /// Process group of the shell, which gets the terminal back
+    shell_group: Option<libc::pid_t>,
+}
+
+impl TerminalOwner {
+    pub fn give(group: libc::pid_t) -> Self {
+        let shell_group = controls_terminal()
+            .then(|| unsafe { libc::getpgrp() })
+            .filter(|_| set_terminal_group(group));
+        Self { shell_group }
+    }
+}
+
+impl Drop for TerminalOwner {
+    fn drop(&mut self) {
+        if let Some(group) = self.shell_group {
+            set_terminal_group(group);
+        }
+    }
+}
+
+/// Makes `group` the foreground process group of the terminal.
+fn set_terminal_group(group: libc::pid_t) -> bool {
+    // A shell that is not in the foreground process group would be stopped by `SIGTTOU` when it
+    // takes the terminal back, unless the signal is blocked
+    unsafe {
+        let mut blocked: libc::sigset_t = std::mem::zeroed();
+        let mut previous: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut blocked);
+        libc::sigaddset(&mut blocked, libc::SIGTTOU);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &blocked, &mut previous);
+        let res = libc::tcsetpgrp(libc::STDIN_FILENO, group);
+        libc::pthread_sigmask(libc::SIG_SETMASK, &previous, std::ptr::null_mut());
+        res == 0
+    }
+}
+
+impl Shell {
+    /// Reacts to the signals that have arrived since the last call.
+    ///
+    /// Returns `Flow::Exit` if the execution of the current line should stop.
+    pub(crate) fn process_signals(&mut self, output: &mut dyn Write) -> Flow {
+        let pending = take_pending();
+        if pending == 0 {
+            return Flow::Normal;
+        }
+        let mut flow = Flow::Normal;
+        for signal in 1..64 {
+            if pending & (1 << signal) == 0 {
+                continue;
+            }
+            if let Some(action) = self.state.traps.get(&signal).cloned() {
+                self.run_trap(&action, output);
+                continue;
+            }
+            self.state.last_status = 128 + signal;
+            flow = Flow::Exit;
+            if signal != libc::SIGINT {
+                self.terminated = true;
+                for job in self.state.jobs.iter_mut() {
+                    let _ = job.signal(signal);
+                    // Stopped jobs only receive the signal once they continue
+                    if job.status() == JobStatus::Stopped {
+                        let _ = job.signal(libc::SIGCONT);
+                    }
+                }
+            }
+        }
+        flow
+    }
+
+    /// Reacts to signals that have arrived while the shell was not executing commands, e.g. when
+    /// it was reading the next line.
+    pub fn handle_signals(&mut self, output: &mut dyn Write) {
+        self.process_signals(output);
+    }
+
+    /// Returns `true` if the shell should end: `exit` was executed, or the shell has received a
+    /// signal that ends it (`SIGTERM` or `SIGHUP` without a trap).
+    pub fn is_terminated(&self) -> bool {
+        self.terminated || self.exited
+    }
+
+    /// Executes the handler of the `EXIT` trap, should be called before the shell exits.
+    /// The handler is executed at most once.
+    pub fn run_exit_trap(&mut self, output: &mut dyn Write) {
+        if let Some(action) = self.state.traps.remove(&EXIT) {
+            self.run_trap(&action, output);
+        }
+    }
+
+    /// Executes the handler of a trap, it does not change the exit status.
+    fn run_trap(&mut self, action: &str, output: &mut dyn Write) {
+        let status = self.state.last_status;
+        self.execute_line(action, output);
+        self.state.last_status = status;
+    }
+}
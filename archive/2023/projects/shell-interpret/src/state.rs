@@ -1,20 +1,126 @@
+use crate::extensions::Extensions;
+use crate::jobs::Jobs;
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::Write;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 pub struct InterpreterState {
     pub workdir: PathBuf,
+    /// Variables of the shell.
+    pub env: BTreeMap<String, String>,
+    /// Names of the variables that are passed to external programs, marked with `export`.
+    pub exported: BTreeSet<String>,
+    /// Exit status of the last executed command.
+    pub last_status: i32,
+    /// Jobs running in the background.
+    pub jobs: Jobs,
+    /// Positional parameters (`$0`, `$1`, ...), the first one is the name of the shell or of
+    /// the executed script.
+    pub positional: Vec<String>,
+    /// `set -e`: stop executing statements after a command fails.
+    pub errexit: bool,
+    /// Aliases defined with `alias`, mapped to their (unexpanded) values.
+    pub aliases: BTreeMap<String, String>,
+    /// Directories saved by `pushd`, the last one is the top of the stack.
+    pub dir_stack: Vec<PathBuf>,
+    /// File in which visited directories are recorded for `z`, `None` disables the recording.
+    pub directory_database: Option<PathBuf>,
+    /// Destination of error messages.
+    pub stderr: ErrorOutput,
+    /// Custom state of commands, see `Extensions`.
+    pub extensions: Extensions,
+    /// Commands executed when a signal arrives, set with `trap`. `signals::EXIT` (0) is the
+    /// handler executed when the shell exits, an empty command ignores the signal.
+    pub traps: BTreeMap<libc::c_int, String>,
 }
 
-impl Default for InterpreterState {
-    fn default() -> Self {
-        Self {
-            workdir: std::env::current_dir().unwrap(),
+/// Destination of the error messages of the shell and of the error output of programs.
+pub enum ErrorOutput {
+    /// The error output of the shell process, which is inherited by programs.
+    Inherit,
+    /// Messages are written to the given writer and the error output of programs is copied
+    /// into it.
+    Capture(Box<dyn Write>),
+}
+
+impl ErrorOutput {
+    pub fn is_captured(&self) -> bool {
+        matches!(self, ErrorOutput::Capture(_))
+    }
+}
+
+impl Write for ErrorOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            ErrorOutput::Inherit => std::io::stderr().write(buf),
+            ErrorOutput::Capture(output) => output.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            ErrorOutput::Inherit => std::io::stderr().flush(),
+            ErrorOutput::Capture(output) => output.flush(),
         }
     }
 }
 
 impl InterpreterState {
+    /// Creates a new state, with environment variables inherited from the current process.
     pub fn new(workdir: PathBuf) -> Self {
-        Self { workdir }
+        let env: BTreeMap<String, String> = std::env::vars().collect();
+        Self {
+            workdir,
+            exported: env.keys().cloned().collect(),
+            env,
+            last_status: 0,
+            jobs: Jobs::default(),
+            positional: vec!["benzina".to_string()],
+            errexit: false,
+            aliases: BTreeMap::new(),
+            dir_stack: vec![],
+            directory_database: None,
+            stderr: ErrorOutput::Inherit,
+            extensions: Extensions::default(),
+            traps: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the value of a variable, a positional parameter (`1`) or a special parameter
+    /// (`#`, `@`, `*`).
+    pub fn variable(&self, name: &str) -> Option<String> {
+        match name {
+            "#" => Some((self.positional.len() - 1).to_string()),
+            "@" | "*" => Some(self.positional[1..].join(" ")),
+            name if name.starts_with(|c: char| c.is_ascii_digit()) => {
+                self.positional.get(name.parse::<usize>().ok()?).cloned()
+            }
+            name => self.env.get(name).cloned(),
+        }
+    }
+
+    /// Returns the variables that are passed to external programs.
+    pub fn exported_env(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.env
+            .iter()
+            .filter(|(name, _)| self.exported.contains(name.as_str()))
+    }
+
+    /// Formats `path` for the user, with the home directory abbreviated as `~`.
+    pub fn abbreviate_home(&self, path: &Path) -> String {
+        let path = path.display().to_string();
+        match self.env.get("HOME") {
+            Some(home) if !home.is_empty() && home != "/" => match path.strip_prefix(home.as_str())
+            {
+                Some(rest) if rest.is_empty() || rest.starts_with('/') => format!("~{rest}"),
+                _ => path,
+            },
+            _ => path,
+        }
+    }
+
+    /// Resolves `path` relative to the working directory of the shell.
+    pub fn resolve_path<P: AsRef<Path>>(&self, path: P) -> PathBuf {
+        self.workdir.join(path)
     }
 }
@@ -0,0 +1,171 @@
+use benzina::{ChangeWorkdir, ExitStatus, ExternalCommand, PrintArguments, Shell};
+use std::path::{Path, PathBuf};
+
+fn shell() -> Shell {
+    let mut shell = Shell::new().unwrap();
+    shell.add_command(PrintArguments);
+    shell.add_command(ChangeWorkdir);
+    shell
+}
+
+fn run(shell: &mut Shell, line: &str) -> (ExitStatus, String) {
+    let mut output = vec![];
+    let status = shell.execute_line(line, &mut output);
+    (status, String::from_utf8(output).unwrap())
+}
+
+/// Path of the `hello-plugin` example, which is built by `cargo test`.
+fn library_plugin() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let path = exe
+        .parent()
+        .and_then(|deps| deps.parent())
+        .unwrap()
+        .join("examples")
+        .join(format!(
+            "{}hello_plugin{}",
+            std::env::consts::DLL_PREFIX,
+            std::env::consts::DLL_SUFFIX
+        ));
+    assert!(
+        path.is_file(),
+        "{} does not exist, build it with `cargo build --examples`",
+        path.display()
+    );
+    path
+}
+
+fn write_executable(path: &Path, contents: &str) {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::write(path, contents).unwrap();
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[test]
+fn library_plugin_commands() {
+    let dir = tempfile::tempdir().unwrap();
+    let workdir = dir.path().canonicalize().unwrap();
+    let mut shell = shell();
+    shell.load_plugin(&library_plugin()).unwrap();
+    run(&mut shell, &format!("cd {}", workdir.display()));
+
+    assert_eq!(
+        run(&mut shell, "hello"),
+        (
+            ExitStatus::SUCCESS,
+            format!("Hello, world from {}!\n", workdir.display())
+        )
+    );
+    assert_eq!(
+        run(&mut shell, "hello crab"),
+        (ExitStatus::SUCCESS, "Hello, crab!\n".to_string())
+    );
+    assert_eq!(run(&mut shell, "echo abc | rev | rev | rev").1, "cba\n");
+}
+
+#[test]
+fn executable_plugin_commands() {
+    let dir = tempfile::tempdir().unwrap();
+    let workdir = dir.path().canonicalize().unwrap();
+    let plugin = workdir.join("plugin.sh");
+    write_executable(
+        &plugin,
+        r#"#!/bin/sh
+read -r request
+case "$request" in
+*'"type":"describe"'*)
+    echo '{"commands": [{"name": "greet", "description": "print a greeting"}, {"name": "fail"}]}' ;;
+*'"args":["greet"'*)
+    printf '{"status": 3, "output": "hi from %s\\n"}\n' "$(pwd)" ;;
+*)
+    echo '{"error": "something went wrong"}' ;;
+esac
+"#,
+    );
+    let mut shell = shell();
+    shell.load_plugin(&plugin).unwrap();
+    run(&mut shell, &format!("cd {}", workdir.display()));
+
+    assert_eq!(
+        run(&mut shell, "greet"),
+        (ExitStatus(3), format!("hi from {}\n", workdir.display()))
+    );
+    let output = shell.run("fail", &workdir, std::env::vars());
+    assert_eq!(output.status, ExitStatus(1));
+    assert_eq!(
+        output.stderr_text(),
+        "Command `fail` has failed: fail: something went wrong\n"
+    );
+
+    // Binary input cannot be passed in JSON
+    shell.add_command(ExternalCommand);
+    let output = shell.run("printf '\\377' | greet", &workdir, std::env::vars());
+    assert_eq!(output.status, ExitStatus(1));
+    assert_eq!(
+        output.stderr_text(),
+        "Command `greet` has failed: greet: plugin commands only accept UTF-8 input\n"
+    );
+}
+
+#[test]
+fn invalid_plugins() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut shell = shell();
+    std::fs::write(dir.path().join("notes.txt"), "").unwrap();
+    assert!(shell.load_plugin(&dir.path().join("notes.txt")).is_err());
+    assert!(shell.load_plugin(&dir.path().join("missing.so")).is_err());
+
+    let plugin = dir.path().join("plugin.sh");
+    write_executable(&plugin, "#!/bin/sh\necho 'not json'\n");
+    assert!(shell.load_plugin(&plugin).is_err());
+
+    // Plugins cannot redefine builtins or commands of other plugins
+    write_executable(
+        &plugin,
+        "#!/bin/sh\necho '{\"commands\": [{\"name\": \"echo\"}]}'\n",
+    );
+    assert!(shell.load_plugin(&plugin).is_err());
+    shell.load_plugin(&library_plugin()).unwrap();
+    assert!(shell.load_plugin(&library_plugin()).is_err());
+    assert_eq!(run(&mut shell, "echo ok").1, "ok\n");
+}
+
+#[test]
+fn help() {
+    let mut shell = shell();
+    let plugin = library_plugin();
+    shell.load_plugin(&plugin).unwrap();
+
+    let (_, output) = run(&mut shell, "help");
+    let expected = format!(
+        "Builtins:
+  break     exit from a loop
+  cd        change the working directory
+  continue  start the next iteration of a loop
+  echo      print arguments
+  exit      exit the shell
+  help      describe builtins and plugin commands
+  return    return from a function
+
+Plugins:
+  hello     greet someone (plugin `{0}`)
+  rev       reverse the characters of each line of the input (plugin `{0}`)
+",
+        plugin.display()
+    );
+    assert_eq!(output, expected);
+    assert_eq!(
+        run(&mut shell, "help cd echo"),
+        (
+            ExitStatus::SUCCESS,
+            "cd: change the working directory\necho: print arguments\n".to_string()
+        )
+    );
+    let output = shell.run("help nothing", Path::new("/"), std::env::vars());
+    assert_eq!(output.status, ExitStatus(2));
+    assert_eq!(
+        output.stderr_text(),
+        "Command `help nothing` has failed: help: no help for `nothing`\n"
+    );
+}
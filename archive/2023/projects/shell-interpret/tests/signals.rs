@@ -0,0 +1,117 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::process::{Child, ChildStdout, Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// Starts the shell with `args`, with an empty home directory so that no startup file or
+/// plugins are loaded.
+fn start_shell(home: &tempfile::TempDir, args: &[&str]) -> (Child, BufReader<ChildStdout>) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_benzina"))
+        .args(args)
+        .env("HOME", home.path())
+        .env("PS1", "")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    (child, stdout)
+}
+
+fn read_line(stdout: &mut BufReader<ChildStdout>) -> String {
+    let mut line = String::new();
+    stdout.read_line(&mut line).unwrap();
+    line
+}
+
+fn send_signal(child: &Child, signal: libc::c_int) {
+    // Give the shell some time to start the program after printing the line
+    std::thread::sleep(Duration::from_millis(200));
+    assert_eq!(unsafe { libc::kill(child.id() as libc::pid_t, signal) }, 0);
+}
+
+fn is_running(pid: libc::pid_t) -> bool {
+    unsafe { libc::kill(pid, 0) == 0 }
+}
+
+#[test]
+fn interrupt_script() {
+    let home = tempfile::tempdir().unwrap();
+    let start = Instant::now();
+    let (mut child, mut stdout) = start_shell(&home, &["-c", "echo started; sleep 10; echo after"]);
+    assert_eq!(read_line(&mut stdout), "started\n");
+    send_signal(&child, libc::SIGINT);
+
+    let status = child.wait().unwrap();
+    assert_eq!(status.code(), Some(130));
+    assert!(start.elapsed() < Duration::from_secs(5));
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    assert_eq!(rest, "");
+}
+
+#[test]
+fn interrupt_line_of_interactive_shell() {
+    let home = tempfile::tempdir().unwrap();
+    let (mut child, mut stdout) = start_shell(&home, &[]);
+    let mut stdin = child.stdin.take().unwrap();
+    writeln!(stdin, "echo started; sleep 10; echo skipped").unwrap();
+    assert_eq!(read_line(&mut stdout), "started\n");
+    send_signal(&child, libc::SIGINT);
+
+    // The shell keeps reading lines
+    writeln!(stdin, "echo alive").unwrap();
+    drop(stdin);
+    let status = child.wait().unwrap();
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    assert_eq!(rest, "alive\n");
+    assert_eq!(status.code(), Some(0));
+}
+
+#[test]
+fn trap_signals() {
+    let home = tempfile::tempdir().unwrap();
+    let script = "trap 'echo caught $?' INT
+trap 'echo bye' EXIT
+echo started
+sleep 10
+echo after";
+    let (mut child, mut stdout) = start_shell(&home, &["-c", script]);
+    assert_eq!(read_line(&mut stdout), "started\n");
+    send_signal(&child, libc::SIGINT);
+
+    let status = child.wait().unwrap();
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    assert_eq!(rest, "caught 130\nafter\nbye\n");
+    assert_eq!(status.code(), Some(0));
+}
+
+#[test]
+fn forward_termination_to_jobs() {
+    let home = tempfile::tempdir().unwrap();
+    let (mut child, mut stdout) = start_shell(&home, &[]);
+    let mut stdin = child.stdin.take().unwrap();
+    writeln!(stdin, "trap 'echo bye' EXIT").unwrap();
+    writeln!(stdin, "sleep 30 &").unwrap();
+    // `[1] <pid>`
+    let line = read_line(&mut stdout);
+    let pid: libc::pid_t = line.trim().split(' ').nth(1).unwrap().parse().unwrap();
+    assert!(is_running(pid));
+
+    // The shell is waiting for the next line
+    send_signal(&child, libc::SIGTERM);
+    let status = child.wait().unwrap();
+    assert_eq!(status.code(), Some(128 + libc::SIGTERM));
+    let mut rest = String::new();
+    stdout.read_to_string(&mut rest).unwrap();
+    assert_eq!(rest, "bye\n");
+
+    // The job was a child of the shell, so it is reaped by init once it ends
+    let start = Instant::now();
+    while is_running(pid) && start.elapsed() < Duration::from_secs(5) {
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    assert!(!is_running(pid));
+    drop(stdin);
+}
@@ -0,0 +1,131 @@
+//! Compares the throughput of the codecs by sending many small broadcasts through the server.
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use std::time::{Duration, Instant};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::task::LocalSet;
+use week10::codec::Codec;
+use week10::messages::{ClientToServerMsg, Protocol, ServerToClientMsg, PROTOCOL_VERSION};
+use week10::reader::{MessageReader, DEFAULT_MAX_MESSAGE_SIZE};
+use week10::writer::MessageWriter;
+use week10::{run_server, RunningServer, ServerOpts};
+
+/// Number of broadcasts sent in each iteration.
+const BROADCASTS: usize = 1000;
+/// Number of clients that receive each broadcast.
+const RECEIVERS: usize = 4;
+
+type Reader = MessageReader<ServerToClientMsg, OwnedReadHalf>;
+type Writer = MessageWriter<ClientToServerMsg, OwnedWriteHalf>;
+
+async fn join(port: u16, name: String, codec: Codec) -> (Reader, Writer) {
+    let stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let (rx, tx) = stream.into_split();
+    let mut reader = Reader::new(rx);
+    let mut writer = Writer::new(tx);
+    let protocol = Protocol {
+        version: PROTOCOL_VERSION,
+        codec,
+    };
+    writer
+        .send(ClientToServerMsg::Join {
+            name,
+            password: None,
+            protocol: Some(protocol),
+        })
+        .await
+        .unwrap();
+    assert!(matches!(
+        reader.recv().await,
+        Some(Ok(ServerToClientMsg::Welcome))
+    ));
+    reader.set_codec(codec);
+    writer.set_codec(codec);
+    (reader, writer)
+}
+
+/// Clients that take part in the broadcasts. The writers of the receivers are kept, so that
+/// they stay connected.
+struct Clients {
+    receivers: Vec<(Reader, Writer)>,
+    sender: Writer,
+}
+
+async fn connect_clients(port: u16, codec: Codec) -> Clients {
+    let mut receivers = vec![];
+    for receiver in 0..RECEIVERS {
+        let name = format!("Receiver {codec:?}/{receiver}");
+        receivers.push(join(port, name, codec).await);
+    }
+    let (_, sender) = join(port, format!("Sender {codec:?}"), codec).await;
+    Clients { receivers, sender }
+}
+
+/// Sends the broadcasts from a single client and waits until all receivers get them.
+async fn broadcasts(clients: &mut Clients) {
+    let sender = &mut clients.sender;
+    let send = async {
+        for i in 0..BROADCASTS {
+            let message = format!("Message {i}");
+            sender
+                .send(ClientToServerMsg::Broadcast { message })
+                .await
+                .unwrap();
+        }
+    };
+    let receive = clients.receivers.iter_mut().map(|(reader, _)| async move {
+        for _ in 0..BROADCASTS {
+            let msg = reader.recv().await.unwrap().unwrap();
+            assert!(matches!(msg, ServerToClientMsg::Message { .. }));
+        }
+    });
+    tokio::join!(send, futures_util::future::join_all(receive));
+}
+
+fn codecs(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let localset = LocalSet::new();
+    let opts = ServerOpts {
+        max_clients: 100,
+        max_room_members: 10,
+        account_database: None,
+        max_login_failures: 3,
+        login_lockout: Duration::from_secs(60),
+        data_directory: None,
+        tls: None,
+        websocket: false,
+        max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        rate_limit: None,
+    };
+    let RunningServer {
+        port, future, tx, ..
+    } = localset.block_on(&runtime, run_server(opts)).unwrap();
+    let server = localset.spawn_local(future);
+
+    let mut group = c.benchmark_group("broadcasts");
+    group.throughput(Throughput::Elements((BROADCASTS * RECEIVERS) as u64));
+    for codec in [Codec::Json, Codec::MessagePack] {
+        // Connecting and joining is not measured, only the broadcasts
+        let mut clients = localset.block_on(&runtime, connect_clients(port, codec));
+        group.bench_function(format!("{codec:?}"), |b| {
+            b.iter_custom(|iterations| {
+                let start = Instant::now();
+                for _ in 0..iterations {
+                    localset.block_on(&runtime, broadcasts(&mut clients));
+                }
+                start.elapsed()
+            })
+        });
+    }
+    group.finish();
+
+    tx.send(()).unwrap();
+    localset.block_on(&runtime, server).unwrap().unwrap();
+}
+
+criterion_group!(benches, codecs);
+criterion_main!(benches);
@@ -0,0 +1,193 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::Argon2;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::time::Instant;
+
+/// A registered account, stored as a single JSON line in the account database.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Account {
+    name: String,
+    /// Argon2 hash of the password in the PHC string format, which also contains the salt
+    password_hash: String,
+}
+
+/// Failed logins of a single username.
+#[derive(Default)]
+struct Failures {
+    count: usize,
+    locked_until: Option<Instant>,
+}
+
+/// Registered accounts, loaded from and appended to a file.
+pub struct Accounts {
+    password_hashes: HashMap<String, String>,
+    /// File with the accounts, `None` if they are only kept in memory
+    path: Option<PathBuf>,
+    failures: HashMap<String, Failures>,
+    max_failures: usize,
+    lockout: Duration,
+}
+
+impl Accounts {
+    /// Loads the accounts from `path`, a missing file is treated as an empty database.
+    ///
+    /// After `max_failures` failed logins, logging in as the same user is refused for `lockout`.
+    pub async fn load(
+        path: Option<PathBuf>,
+        max_failures: usize,
+        lockout: Duration,
+    ) -> anyhow::Result<Self> {
+        let mut password_hashes = HashMap::new();
+        if let Some(path) = &path {
+            let data = match tokio::fs::read_to_string(path).await {
+                Ok(data) => data,
+                Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+                Err(error) => return Err(error.into()),
+            };
+            for line in data.lines().filter(|line| !line.is_empty()) {
+                let account: Account = serde_json::from_str(line)?;
+                password_hashes.insert(account.name, account.password_hash);
+            }
+        }
+        Ok(Self {
+            password_hashes,
+            path,
+            failures: Default::default(),
+            max_failures,
+            lockout,
+        })
+    }
+
+    fn is_locked(&self, name: &str) -> bool {
+        match self
+            .failures
+            .get(name)
+            .and_then(|failures| failures.locked_until)
+        {
+            Some(until) => Instant::now() < until,
+            None => false,
+        }
+    }
+
+    fn record_failure(&mut self, name: &str) {
+        let failures = self.failures.entry(name.to_string()).or_default();
+        failures.count += 1;
+        if failures.count >= self.max_failures {
+            failures.count = 0;
+            failures.locked_until = Some(Instant::now() + self.lockout);
+        }
+    }
+}
+
+/// Checks the credentials of a user that wants to join.
+/// Users without an account can join without a password.
+pub async fn login(
+    accounts: &RefCell<Accounts>,
+    name: &str,
+    password: Option<String>,
+) -> Result<(), String> {
+    let password_hash = {
+        let accounts = accounts.borrow();
+        if accounts.is_locked(name) {
+            return Err("Too many failed login attempts".to_string());
+        }
+        accounts.password_hashes.get(name).cloned()
+    };
+    let (password_hash, password) = match (password_hash, password) {
+        (None, None) => return Ok(()),
+        (None, Some(_)) => return Err(format!("User {name} is not registered")),
+        (Some(_), None) => return Err("Password required".to_string()),
+        (Some(password_hash), Some(password)) => (password_hash, password),
+    };
+
+    // Hashing is slow on purpose, so it must not block the other clients
+    let valid =
+        tokio::task::spawn_blocking(move || verify_password(&password_hash, &password)).await;
+    let mut accounts = accounts.borrow_mut();
+    if matches!(valid, Ok(true)) {
+        accounts.failures.remove(name);
+        Ok(())
+    } else {
+        accounts.record_failure(name);
+        Err("Invalid password".to_string())
+    }
+}
+
+/// Creates an account and stores it in the database.
+pub async fn register(
+    accounts: &RefCell<Accounts>,
+    name: &str,
+    password: String,
+) -> Result<(), String> {
+    if password.is_empty() {
+        return Err("Password cannot be empty".to_string());
+    }
+    if accounts.borrow().password_hashes.contains_key(name) {
+        return Err("Username already registered".to_string());
+    }
+    let password_hash = match tokio::task::spawn_blocking(move || hash_password(&password)).await {
+        Ok(Ok(password_hash)) => password_hash,
+        result => {
+            log::error!("Cannot hash password: {result:?}");
+            return Err("Registration failed".to_string());
+        }
+    };
+
+    let path = {
+        let mut accounts = accounts.borrow_mut();
+        // Someone could have registered the same name while the password was being hashed
+        if accounts.password_hashes.contains_key(name) {
+            return Err("Username already registered".to_string());
+        }
+        accounts
+            .password_hashes
+            .insert(name.to_string(), password_hash.clone());
+        accounts.path.clone()
+    };
+    if let Some(path) = path {
+        let account = Account {
+            name: name.to_string(),
+            password_hash,
+        };
+        if let Err(error) = append_account(&path, &account).await {
+            log::error!("Cannot store account {name}: {error}");
+            accounts.borrow_mut().password_hashes.remove(name);
+            return Err("Registration failed".to_string());
+        }
+    }
+    Ok(())
+}
+
+async fn append_account(path: &Path, account: &Account) -> anyhow::Result<()> {
+    let mut line = serde_json::to_vec(account)?;
+    line.push(b'\n');
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await?;
+    file.write_all(&line).await?;
+    file.sync_all().await?;
+    Ok(())
+}
+
+fn hash_password(password: &str) -> Result<String, argon2::password_hash::Error> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string())
+}
+
+fn verify_password(password_hash: &str, password: &str) -> bool {
+    match PasswordHash::new(password_hash) {
+        Ok(hash) => Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,146 @@
+//! Interactive terminal chat client.
+//!
+//! Usage: `client [--tls <certificate.pem>] <port> <name> [password]`
+//!
+//! With `--tls`, the connection is encrypted and the server has to present a certificate for
+//! `localhost` signed by (or equal to) the given certificate.
+//!
+//! Incoming messages are shown above the line that is being typed. Lines starting with `/` are
+//! commands (`/dm <user> <message>`, `/list`, `/ping`, `/quit`), other lines are broadcasted.
+//! Press `Esc` or `Ctrl+C` to quit.
+
+use crossterm::event::{Event, KeyCode, KeyEventKind, KeyModifiers};
+use crossterm::{cursor, execute, queue, style, terminal};
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::Path;
+use tokio::sync::mpsc;
+use week10::tls::ClientTls;
+use week10::tui::{run_client, ClientOpts};
+
+/// How many incoming lines are kept for redrawing the screen.
+const MAX_LINES: usize = 1000;
+
+#[derive(Default)]
+struct Screen {
+    lines: Vec<String>,
+    input: String,
+}
+
+impl Screen {
+    fn push(&mut self, line: String) {
+        self.lines.push(line);
+        if self.lines.len() > MAX_LINES {
+            self.lines.drain(..self.lines.len() - MAX_LINES);
+        }
+    }
+
+    /// Draws the incoming lines and, below them, the line that is being typed.
+    fn draw(&self, out: &mut impl Write) -> std::io::Result<()> {
+        let (width, height) = terminal::size()?;
+        let width = width as usize;
+        let pane = height.saturating_sub(1) as usize;
+        queue!(out, terminal::Clear(terminal::ClearType::All))?;
+        let visible = &self.lines[self.lines.len().saturating_sub(pane)..];
+        for (row, line) in visible.iter().enumerate() {
+            let line: String = line.chars().take(width).collect();
+            queue!(out, cursor::MoveTo(0, row as u16), style::Print(line))?;
+        }
+        // Show the end of the input if it is too long
+        let input_len = self.input.chars().count();
+        let input: String = self
+            .input
+            .chars()
+            .skip((input_len + 2).saturating_sub(width))
+            .collect();
+        queue!(
+            out,
+            cursor::MoveTo(0, height.saturating_sub(1)),
+            style::Print(format!("> {input}"))
+        )?;
+        out.flush()
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    let (tls, args) = match &args[1..] {
+        [flag, certificate, args @ ..] if flag == "--tls" => (
+            Some(ClientTls::load(Path::new(certificate), "localhost")?),
+            args,
+        ),
+        args => (None, args),
+    };
+    let (port, name, password) = match args {
+        [port, name] => (port, name, None),
+        [port, name, password] => (port, name, Some(password.clone())),
+        _ => anyhow::bail!("Usage: client [--tls <certificate.pem>] <port> <name> [password]"),
+    };
+    let address = SocketAddr::from(([127, 0, 0, 1], port.parse()?));
+    let opts = ClientOpts {
+        password,
+        tls,
+        ..ClientOpts::new(address, name)
+    };
+
+    let (input_tx, input_rx) = mpsc::unbounded_channel();
+    let (events_tx, mut events_rx) = mpsc::unbounded_channel();
+    // Reading from the terminal is blocking, so it runs in a separate thread
+    let (keys_tx, mut keys_rx) = mpsc::unbounded_channel();
+    std::thread::spawn(move || {
+        while let Ok(event) = crossterm::event::read() {
+            if keys_tx.send(event).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut out = std::io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(out, terminal::EnterAlternateScreen)?;
+
+    let mut screen = Screen::default();
+    // Dropping the sender tells the client to quit
+    let mut input_tx = Some(input_tx);
+    let client = run_client(opts, input_rx, events_tx);
+    tokio::pin!(client);
+    let result = loop {
+        if let Err(error) = screen.draw(&mut out) {
+            break Err(error.into());
+        }
+        tokio::select! {
+            result = &mut client => break result,
+            Some(event) = events_rx.recv() => screen.push(event.to_string()),
+            Some(event) = keys_rx.recv() => {
+                let Event::Key(key) = event else {
+                    continue;
+                };
+                if key.kind == KeyEventKind::Release {
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Esc => input_tx = None,
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        input_tx = None;
+                    }
+                    KeyCode::Char(c) => screen.input.push(c),
+                    KeyCode::Backspace => {
+                        screen.input.pop();
+                    }
+                    KeyCode::Enter if !screen.input.is_empty() => {
+                        let line = std::mem::take(&mut screen.input);
+                        if let Some(input_tx) = &input_tx {
+                            let _ = input_tx.send(line);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    };
+
+    execute!(out, terminal::LeaveAlternateScreen)?;
+    terminal::disable_raw_mode()?;
+    result
+}
@@ -1,5 +1,101 @@
-//! You can use this file for experiments.
+//! Chat server listening on a port assigned by the operating system, which is printed at start.
+//!
+//! Usage: `server [options]`
+//!
+//! - `--max-clients <n>`, `--max-room-members <n>`: limits of connected clients and room members
+//! - `--accounts <file>`: file in which registered accounts are stored
+//! - `--data <directory>`: directory in which the log of messages is stored
+//! - `--tls <certificate.pem> <key.pem>`: only accept encrypted connections
+//! - `--websocket`: also listen for WebSocket clients on a second port
+//! - `--max-message-size <bytes>`: largest message that a client can send
+//! - `--rate-limit <messages per second>`: limit the traffic of each client
+//!
+//! Press `Ctrl+C` to disconnect all clients and stop the server.
 
-fn main() {
-    println!("Hello Rust!");
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::task::LocalSet;
+use week10::rate_limit::RateLimit;
+use week10::reader::DEFAULT_MAX_MESSAGE_SIZE;
+use week10::tls::TlsOpts;
+use week10::{run_server, ServerOpts};
+
+const USAGE: &str = "Usage: server [--max-clients <n>] [--max-room-members <n>] \
+[--accounts <file>] [--data <directory>] [--tls <certificate.pem> <key.pem>] [--websocket] \
+[--max-message-size <bytes>] [--rate-limit <messages per second>]";
+
+fn parse_args(args: &[String]) -> anyhow::Result<ServerOpts> {
+    let mut opts = ServerOpts {
+        max_clients: 100,
+        max_room_members: 50,
+        account_database: None,
+        max_login_failures: 5,
+        login_lockout: Duration::from_secs(60),
+        data_directory: None,
+        tls: None,
+        websocket: false,
+        max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+        rate_limit: None,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || match args.next() {
+            Some(value) => Ok(value.clone()),
+            None => Err(anyhow::anyhow!("Missing value of `{arg}`\n{USAGE}")),
+        };
+        match arg.as_str() {
+            "--max-clients" => opts.max_clients = value()?.parse()?,
+            "--max-room-members" => opts.max_room_members = value()?.parse()?,
+            "--accounts" => opts.account_database = Some(PathBuf::from(value()?)),
+            "--data" => opts.data_directory = Some(PathBuf::from(value()?)),
+            "--tls" => {
+                opts.tls = Some(TlsOpts {
+                    certificate: PathBuf::from(value()?),
+                    private_key: PathBuf::from(value()?),
+                })
+            }
+            "--websocket" => opts.websocket = true,
+            "--max-message-size" => opts.max_message_size = value()?.parse()?,
+            "--rate-limit" => {
+                let messages_per_second: u32 = value()?.parse()?;
+                // Allow short bursts of messages that are as large as possible
+                let bytes_per_second =
+                    u32::try_from(opts.max_message_size)?.saturating_mul(messages_per_second);
+                opts.rate_limit = Some(RateLimit {
+                    messages_per_second,
+                    message_burst: messages_per_second.saturating_mul(2),
+                    bytes_per_second,
+                    byte_burst: bytes_per_second.saturating_mul(2),
+                    max_violations: messages_per_second.saturating_mul(10),
+                });
+            }
+            _ => anyhow::bail!("Unknown argument `{arg}`\n{USAGE}"),
+        }
+    }
+    Ok(opts)
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let opts = parse_args(&args)?;
+
+    LocalSet::new()
+        .run_until(async move {
+            let server = run_server(opts).await?;
+            println!("Listening on port {}", server.port);
+            if let Some(port) = server.websocket_port {
+                println!("Listening for WebSocket clients on port {port}");
+            }
+            let future = server.future;
+            tokio::pin!(future);
+            tokio::select! {
+                result = &mut future => return result,
+                result = tokio::signal::ctrl_c() => result?,
+            }
+            println!("Shutting down");
+            let _ = server.tx.send(());
+            future.await
+        })
+        .await
 }
@@ -0,0 +1,349 @@
+use crate::history::{History, LogEntry};
+use crate::messages::{
+    HistoryMessage, Presence, ReceiptStatus, ServerToClientMsg, UserPresence, RECEIPTS_VERSION,
+};
+use std::collections::{BTreeSet, HashMap};
+use tokio::sync::mpsc::UnboundedSender;
+
+/// Queue of messages that should be delivered to a connected user.
+/// The task that handles the connection of the user writes them to its socket.
+pub type Outbox = UnboundedSender<ServerToClientMsg>;
+
+/// A connected user.
+struct User {
+    outbox: Outbox,
+    /// Protocol version negotiated in `Join`
+    version: u32,
+    presence: Presence,
+    status: Option<String>,
+    /// Set when the server has marked the user away because it was idle
+    idle: bool,
+    /// Whether the user receives changes of presence of other users
+    watching: bool,
+}
+
+impl User {
+    fn send(&self, msg: ServerToClientMsg) {
+        // The receiver is only dropped when the user is disconnecting, so the message can be lost
+        let _ = self.outbox.send(msg);
+    }
+
+    fn has_receipts(&self) -> bool {
+        self.version >= RECEIPTS_VERSION
+    }
+}
+
+/// State shared by all connections of the server: the users that have joined, the rooms and
+/// the history of messages.
+pub struct Chat {
+    users: HashMap<String, User>,
+    rooms: HashMap<String, BTreeSet<String>>,
+    history: History,
+    max_room_members: usize,
+}
+
+impl Chat {
+    pub fn new(max_room_members: usize, history: History) -> Self {
+        Self {
+            users: Default::default(),
+            rooms: Default::default(),
+            history,
+            max_room_members,
+        }
+    }
+
+    /// Sends all future changes of the history to the log writer.
+    pub fn set_log(&mut self, log: UnboundedSender<LogEntry>) {
+        self.history.set_log(log);
+    }
+
+    /// Adds a user that has joined with protocol `version`, returns `false` if the name is
+    /// already taken.
+    pub fn add_user(&mut self, name: &str, outbox: Outbox, version: u32) -> bool {
+        if self.users.contains_key(name) {
+            return false;
+        }
+        let user = User {
+            outbox,
+            version,
+            presence: Presence::Online,
+            status: None,
+            idle: false,
+            watching: false,
+        };
+        self.users.insert(name.to_string(), user);
+        self.history.add_user(name);
+        self.notify_presence(name);
+        true
+    }
+
+    /// Removes a disconnected user, together with its membership in rooms.
+    pub fn remove_user(&mut self, name: &str) {
+        if self.users.remove(name).is_none() {
+            return;
+        }
+        self.rooms.retain(|_, members| {
+            members.remove(name);
+            !members.is_empty()
+        });
+        self.notify(UserPresence {
+            name: name.to_string(),
+            presence: Presence::Offline,
+            status: None,
+        });
+    }
+
+    pub fn is_connected(&self, name: &str) -> bool {
+        self.users.contains_key(name)
+    }
+
+    pub fn users(&self) -> Vec<String> {
+        self.users.keys().cloned().collect()
+    }
+
+    /// Sends a DM, or stores it until the recipient joins if it is offline.
+    /// Returns the response for the sender, if it uses receipts.
+    pub fn send_dm(
+        &mut self,
+        from: &str,
+        to: &str,
+        message: &str,
+    ) -> Result<Option<ServerToClientMsg>, String> {
+        if from == to {
+            return Err("Cannot send a DM to yourself".to_string());
+        }
+        let id = match self.users.get(to) {
+            Some(recipient) => {
+                let id = self.history.add_message(from, Some(to), message, false);
+                recipient.send(dm(recipient, id, from, message));
+                self.send_receipt(from, id, ReceiptStatus::Delivered);
+                id
+            }
+            None if self.history.is_known(to) => {
+                self.history.add_message(from, Some(to), message, true)
+            }
+            None => return Err(format!("User {to} does not exist")),
+        };
+        Ok(self
+            .users
+            .get(from)
+            .filter(|sender| sender.has_receipts())
+            .map(|_| ServerToClientMsg::DMSent { id }))
+    }
+
+    pub fn broadcast(&mut self, from: &str, message: &str) {
+        for (name, user) in &self.users {
+            if name != from {
+                user.send(ServerToClientMsg::Message {
+                    from: from.to_string(),
+                    message: message.to_string(),
+                });
+            }
+        }
+        self.history.add_message(from, None, message, false);
+    }
+
+    /// Returns the DMs that were sent to `name` while it was offline, from the oldest one.
+    /// Their senders are told that they were delivered.
+    pub fn take_queued(&mut self, name: &str) -> Vec<ServerToClientMsg> {
+        let queued = self.history.take_queued(name);
+        for message in &queued {
+            self.send_receipt(&message.from, message.id, ReceiptStatus::Delivered);
+        }
+        let Some(recipient) = self.users.get(name) else {
+            return vec![];
+        };
+        queued
+            .into_iter()
+            .map(|message| dm(recipient, message.id, &message.from, &message.message))
+            .collect()
+    }
+
+    /// Marks a DM sent to `name` as read and tells its sender.
+    pub fn mark_read(&mut self, name: &str, id: u64) -> Result<(), String> {
+        let from = match self.history.find(id) {
+            Some(message) if message.to.as_deref() == Some(name) => message.from.clone(),
+            _ => return Err(format!("Message {id} does not exist")),
+        };
+        self.send_receipt(&from, id, ReceiptStatus::Read);
+        Ok(())
+    }
+
+    fn send_receipt(&self, sender: &str, id: u64, status: ReceiptStatus) {
+        if let Some(sender) = self
+            .users
+            .get(sender)
+            .filter(|sender| sender.has_receipts())
+        {
+            sender.send(ServerToClientMsg::Receipt { id, status });
+        }
+    }
+
+    /// Tells `to` that `from` is typing, if it is connected.
+    pub fn typing(&self, from: &str, to: &str) {
+        if let Some(recipient) = self.users.get(to).filter(|_| from != to) {
+            recipient.send(ServerToClientMsg::Typing {
+                from: from.to_string(),
+            });
+        }
+    }
+
+    pub fn set_presence(
+        &mut self,
+        name: &str,
+        presence: Presence,
+        status: Option<String>,
+    ) -> Result<(), String> {
+        if presence == Presence::Offline {
+            return Err("Cannot set presence Offline".to_string());
+        }
+        if let Some(user) = self.users.get_mut(name) {
+            user.presence = presence;
+            user.status = status;
+            user.idle = false;
+        }
+        self.notify_presence(name);
+        Ok(())
+    }
+
+    /// Makes `name` receive changes of presence, returns the presence of all connected users.
+    pub fn watch_presence(&mut self, name: &str) -> Vec<UserPresence> {
+        if let Some(user) = self.users.get_mut(name) {
+            user.watching = true;
+        }
+        self.users
+            .keys()
+            .filter_map(|name| self.presence(name))
+            .collect()
+    }
+
+    /// Marks an online user that has not sent anything for a while as away.
+    pub fn mark_idle(&mut self, name: &str) {
+        match self.users.get_mut(name) {
+            Some(user) if user.presence == Presence::Online => {
+                user.presence = Presence::Away;
+                user.idle = true;
+            }
+            _ => return,
+        }
+        self.notify_presence(name);
+    }
+
+    /// Marks a user that was marked away by [Chat::mark_idle] as online again.
+    pub fn mark_active(&mut self, name: &str) {
+        match self.users.get_mut(name) {
+            Some(user) if user.idle => {
+                user.presence = Presence::Online;
+                user.idle = false;
+            }
+            _ => return,
+        }
+        self.notify_presence(name);
+    }
+
+    fn presence(&self, name: &str) -> Option<UserPresence> {
+        self.users.get(name).map(|user| UserPresence {
+            name: name.to_string(),
+            presence: user.presence,
+            status: user.status.clone(),
+        })
+    }
+
+    fn notify_presence(&self, name: &str) {
+        if let Some(presence) = self.presence(name) {
+            self.notify(presence);
+        }
+    }
+
+    /// Sends the presence of a user to all other users watching presence.
+    fn notify(&self, presence: UserPresence) {
+        for (name, user) in &self.users {
+            if user.watching && *name != presence.name {
+                user.send(ServerToClientMsg::PresenceChanged(presence.clone()));
+            }
+        }
+    }
+
+    /// See [History::query].
+    pub fn history(
+        &self,
+        name: &str,
+        with: Option<&str>,
+        limit: usize,
+        before: Option<u64>,
+    ) -> Vec<HistoryMessage> {
+        self.history.query(name, with, limit, before)
+    }
+
+    /// Creates a room whose only member is `name`.
+    pub fn create_room(&mut self, name: &str, room: &str) -> Result<(), String> {
+        if self.rooms.contains_key(room) {
+            return Err(format!("Room {room} already exists"));
+        }
+        self.rooms
+            .insert(room.to_string(), BTreeSet::from([name.to_string()]));
+        Ok(())
+    }
+
+    pub fn join_room(&mut self, name: &str, room: &str) -> Result<(), String> {
+        let Some(members) = self.rooms.get_mut(room) else {
+            return Err(format!("Room {room} does not exist"));
+        };
+        if members.contains(name) {
+            return Err(format!("You are already in room {room}"));
+        }
+        if members.len() >= self.max_room_members {
+            return Err(format!("Room {room} is full"));
+        }
+        members.insert(name.to_string());
+        Ok(())
+    }
+
+    /// Removes `name` from the room, the room is removed when it becomes empty.
+    pub fn leave_room(&mut self, name: &str, room: &str) -> Result<(), String> {
+        let members = match self.rooms.get_mut(room) {
+            Some(members) if members.contains(name) => members,
+            _ => return Err(not_member(room)),
+        };
+        members.remove(name);
+        if members.is_empty() {
+            self.rooms.remove(room);
+        }
+        Ok(())
+    }
+
+    pub fn rooms(&self) -> Vec<String> {
+        self.rooms.keys().cloned().collect()
+    }
+
+    pub fn send_to_room(&self, from: &str, room: &str, message: &str) -> Result<(), String> {
+        let members = match self.rooms.get(room) {
+            Some(members) if members.contains(from) => members,
+            _ => return Err(not_member(room)),
+        };
+        for member in members.iter().filter(|member| *member != from) {
+            if let Some(user) = self.users.get(member) {
+                user.send(ServerToClientMsg::RoomMessage {
+                    room: room.to_string(),
+                    from: from.to_string(),
+                    message: message.to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+fn not_member(room: &str) -> String {
+    format!("You are not in room {room}")
+}
+
+/// A DM in the form understood by `recipient`.
+fn dm(recipient: &User, id: u64, from: &str, message: &str) -> ServerToClientMsg {
+    let from = from.to_string();
+    let message = message.to_string();
+    match recipient.has_receipts() {
+        true => ServerToClientMsg::DirectMessage { id, from, message },
+        false => ServerToClientMsg::Message { from, message },
+    }
+}
@@ -0,0 +1,285 @@
+use crate::accounts::{login, register, Accounts};
+use crate::chat::Chat;
+use crate::codec::Codec;
+use crate::messages::{ClientToServerMsg, Protocol, ServerToClientMsg, PROTOCOL_VERSION};
+use crate::rate_limit::{RateLimit, RateLimiter, Verdict};
+use crate::reader::{MessageReader, MessageTooLarge};
+use crate::tls::{ReadHalf, WriteHalf};
+use crate::writer::MessageWriter;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::sync::{mpsc, watch};
+use tokio::time::Instant;
+
+/// How long the server waits for the `Join` message.
+const JOIN_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long a user can stay connected without sending or receiving any message.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(3);
+/// How long a user can stay without sending anything before it is marked away.
+const AWAY_TIMEOUT: Duration = Duration::from_secs(2);
+/// Protocol of clients that do not request any in `Join`.
+const DEFAULT_PROTOCOL: Protocol = Protocol {
+    version: 1,
+    codec: Codec::Json,
+};
+/// Error sent for each message over the rate limit of the client.
+const RATE_LIMIT_EXCEEDED: &str = "Rate limit exceeded";
+
+type Reader = MessageReader<ClientToServerMsg, ReadHalf>;
+type Writer = MessageWriter<ServerToClientMsg, WriteHalf>;
+
+/// Settings of a connection, which depend on the server and on the listener of the client.
+#[derive(Copy, Clone)]
+pub struct ConnectionOpts {
+    /// Codecs that the client can choose from
+    pub codecs: &'static [Codec],
+    /// Largest message that the client can send
+    pub max_message_size: usize,
+    /// Limits of the traffic of the client after it has joined
+    pub rate_limit: Option<RateLimit>,
+}
+
+/// What should happen after a message of the client was handled.
+enum Response {
+    Nothing,
+    Reply(ServerToClientMsg),
+    /// Send the message and disconnect the client
+    Disconnect(ServerToClientMsg),
+}
+
+/// Handles a connected client until it disconnects or until `shutdown` is signalled.
+pub async fn handle_client(
+    (rx, tx): (ReadHalf, WriteHalf),
+    opts: ConnectionOpts,
+    chat: Rc<RefCell<Chat>>,
+    accounts: Rc<RefCell<Accounts>>,
+    mut shutdown: watch::Receiver<()>,
+) -> anyhow::Result<()> {
+    let mut reader = Reader::new(rx).with_max_message_size(opts.max_message_size);
+    let mut writer = Writer::new(tx);
+    let mut protocol = DEFAULT_PROTOCOL;
+
+    let msg = tokio::select! {
+        msg = tokio::time::timeout(JOIN_TIMEOUT, reader.recv()) => msg,
+        _ = shutdown.changed() => return Ok(()),
+    };
+    let (name, result) = match msg {
+        Ok(Some(Ok(ClientToServerMsg::Join {
+            name,
+            password,
+            protocol: requested,
+        }))) => {
+            let result = match negotiate(requested, opts.codecs) {
+                Ok(negotiated) => {
+                    protocol = negotiated;
+                    login(&accounts, &name, password).await
+                }
+                Err(error) => Err(error),
+            };
+            (name, result)
+        }
+        Ok(Some(Ok(ClientToServerMsg::Register { name, password }))) => {
+            // Do not create an account for a name that cannot be used right now
+            if chat.borrow().is_connected(&name) {
+                return send_error(&mut writer, "Username already taken").await;
+            }
+            let result = register(&accounts, &name, password).await;
+            (name, result)
+        }
+        Ok(Some(Ok(_))) => return send_error(&mut writer, "Unexpected message received").await,
+        Ok(Some(Err(error))) if is_too_large(&error) => {
+            return send_error(&mut writer, &MessageTooLarge.to_string()).await;
+        }
+        Ok(Some(Err(error))) => return Err(error.into()),
+        Ok(None) => return Ok(()),
+        Err(_) => return send_error(&mut writer, "Timed out waiting for Join").await,
+    };
+    if let Err(error) = result {
+        return send_error(&mut writer, &error).await;
+    }
+
+    let (outbox, inbox) = mpsc::unbounded_channel();
+    let queued = {
+        let mut chat = chat.borrow_mut();
+        chat.add_user(&name, outbox, protocol.version)
+            .then(|| chat.take_queued(&name))
+    };
+    let Some(queued) = queued else {
+        return send_error(&mut writer, "Username already taken").await;
+    };
+    let result = async {
+        writer.send(ServerToClientMsg::Welcome).await?;
+        reader.set_codec(protocol.codec);
+        writer.set_codec(protocol.codec);
+        // Messages sent while the user was offline are delivered first
+        for msg in queued {
+            writer.send(msg).await?;
+        }
+        let limiter = opts.rate_limit.map(RateLimiter::new);
+        run_session(&name, &chat, reader, writer, limiter, inbox, shutdown).await
+    }
+    .await;
+    chat.borrow_mut().remove_user(&name);
+    result
+}
+
+/// Checks the protocol requested in `Join`.
+fn negotiate(protocol: Option<Protocol>, codecs: &[Codec]) -> Result<Protocol, String> {
+    let Some(protocol) = protocol else {
+        return Ok(DEFAULT_PROTOCOL);
+    };
+    if protocol.version > PROTOCOL_VERSION {
+        return Err(format!("Unsupported protocol version {}", protocol.version));
+    }
+    if !codecs.contains(&protocol.codec) {
+        return Err(format!("Unsupported codec {:?}", protocol.codec));
+    }
+    Ok(protocol)
+}
+
+/// Exchanges messages with a user that has joined the chat and received the `Welcome` message.
+/// Messages over the limits of `limiter` are dropped, they are not even read by the chat.
+/// A user that is idle for a while is marked away, and disconnected later.
+async fn run_session(
+    name: &str,
+    chat: &RefCell<Chat>,
+    mut reader: Reader,
+    mut writer: Writer,
+    mut limiter: Option<RateLimiter>,
+    mut inbox: mpsc::UnboundedReceiver<ServerToClientMsg>,
+    mut shutdown: watch::Receiver<()>,
+) -> anyhow::Result<()> {
+    let deadline = tokio::time::sleep(IDLE_TIMEOUT);
+    tokio::pin!(deadline);
+    let away = tokio::time::sleep(AWAY_TIMEOUT);
+    tokio::pin!(away);
+    let mut idle = false;
+    loop {
+        tokio::select! {
+            msg = reader.recv() => {
+                let msg = match msg {
+                    Some(Ok(msg)) => msg,
+                    Some(Err(error)) if is_too_large(&error) => {
+                        return send_error(&mut writer, &MessageTooLarge.to_string()).await;
+                    }
+                    Some(Err(error)) => return Err(error.into()),
+                    None => return Ok(()),
+                };
+                deadline.as_mut().reset(Instant::now() + IDLE_TIMEOUT);
+                away.as_mut().reset(Instant::now() + AWAY_TIMEOUT);
+                if idle {
+                    idle = false;
+                    chat.borrow_mut().mark_active(name);
+                }
+                let verdict = match &mut limiter {
+                    Some(limiter) => limiter.check(reader.last_message_size()),
+                    None => Verdict::Allowed,
+                };
+                match verdict {
+                    Verdict::Allowed => {}
+                    Verdict::Exceeded => {
+                        send_error(&mut writer, RATE_LIMIT_EXCEEDED).await?;
+                        continue;
+                    }
+                    Verdict::Dropped => continue,
+                    Verdict::Disconnect => return send_error(&mut writer, RATE_LIMIT_EXCEEDED).await,
+                }
+                match handle_message(name, chat, msg) {
+                    Response::Nothing => {}
+                    Response::Reply(msg) => writer.send(msg).await?,
+                    Response::Disconnect(msg) => return writer.send(msg).await,
+                }
+            }
+            Some(msg) = inbox.recv() => {
+                // Receiving messages keeps the connection alive, but it does not make the user
+                // active
+                deadline.as_mut().reset(Instant::now() + IDLE_TIMEOUT);
+                writer.send(msg).await?;
+            }
+            _ = &mut away, if !idle => {
+                idle = true;
+                chat.borrow_mut().mark_idle(name);
+            }
+            _ = &mut deadline => return send_error(&mut writer, "Timeouted").await,
+            _ = shutdown.changed() => return Ok(()),
+        }
+    }
+}
+
+fn is_too_large(error: &std::io::Error) -> bool {
+    error
+        .get_ref()
+        .is_some_and(|error| error.is::<MessageTooLarge>())
+}
+
+fn handle_message(name: &str, chat: &RefCell<Chat>, msg: ClientToServerMsg) -> Response {
+    let mut chat = chat.borrow_mut();
+    let result = match msg {
+        ClientToServerMsg::Join { .. } | ClientToServerMsg::Register { .. } => {
+            return Response::Disconnect(ServerToClientMsg::Error(
+                "Unexpected message received".to_string(),
+            ))
+        }
+        ClientToServerMsg::Ping => Ok(Some(ServerToClientMsg::Pong)),
+        ClientToServerMsg::ListUsers => Ok(Some(ServerToClientMsg::UserList {
+            users: chat.users(),
+        })),
+        ClientToServerMsg::SendDM { to, message } => chat.send_dm(name, &to, &message),
+        ClientToServerMsg::Broadcast { message } => {
+            chat.broadcast(name, &message);
+            Ok(None)
+        }
+        ClientToServerMsg::CreateRoom { room } => chat
+            .create_room(name, &room)
+            .map(|_| Some(ServerToClientMsg::RoomJoined { room })),
+        ClientToServerMsg::JoinRoom { room } => chat
+            .join_room(name, &room)
+            .map(|_| Some(ServerToClientMsg::RoomJoined { room })),
+        ClientToServerMsg::LeaveRoom { room } => chat
+            .leave_room(name, &room)
+            .map(|_| Some(ServerToClientMsg::RoomLeft { room })),
+        ClientToServerMsg::ListRooms => Ok(Some(ServerToClientMsg::RoomList {
+            rooms: chat.rooms(),
+        })),
+        ClientToServerMsg::SendToRoom { room, message } => {
+            chat.send_to_room(name, &room, &message).map(|_| None)
+        }
+        ClientToServerMsg::History {
+            with,
+            limit,
+            before,
+        } => Ok(Some(ServerToClientMsg::History {
+            messages: chat.history(name, with.as_deref(), limit, before),
+        })),
+        ClientToServerMsg::SetPresence { presence, status } => {
+            chat.set_presence(name, presence, status).map(|_| None)
+        }
+        ClientToServerMsg::WatchPresence => Ok(Some(ServerToClientMsg::PresenceList {
+            users: chat.watch_presence(name),
+        })),
+        ClientToServerMsg::Typing { to } => {
+            chat.typing(name, &to);
+            Ok(None)
+        }
+        ClientToServerMsg::MarkRead { id } => chat.mark_read(name, id).map(|_| None),
+    };
+    match result {
+        Ok(Some(msg)) => Response::Reply(msg),
+        Ok(None) => Response::Nothing,
+        Err(error) => Response::Reply(ServerToClientMsg::Error(error)),
+    }
+}
+
+/// Sends an error to a client that is being disconnected.
+pub async fn send_error<W>(
+    writer: &mut MessageWriter<ServerToClientMsg, W>,
+    error: &str,
+) -> anyhow::Result<()>
+where
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    writer
+        .send(ServerToClientMsg::Error(error.to_string()))
+        .await
+}
@@ -0,0 +1,121 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::io::{Error, ErrorKind};
+use std::ops::Range;
+
+/// Size of the header that precedes each MessagePack message.
+const SIZE_HEADER: usize = 4;
+
+/// Format in which messages are encoded on the wire.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Codec {
+    /// Each message is a JSON document followed by a newline
+    #[default]
+    Json,
+    /// Each message is encoded with MessagePack and preceded by its size (big-endian `u32`)
+    MessagePack,
+}
+
+impl Codec {
+    /// Encodes a message, including the framing.
+    pub fn encode<T: Serialize>(self, msg: &T) -> anyhow::Result<Vec<u8>> {
+        match self {
+            Codec::Json => {
+                let mut data = serde_json::to_vec(msg)?;
+                data.push(b'\n');
+                Ok(data)
+            }
+            Codec::MessagePack => {
+                let mut data = vec![0; SIZE_HEADER];
+                rmp_serde::encode::write(&mut data, msg)?;
+                let size = u32::try_from(data.len() - SIZE_HEADER)?;
+                data[..SIZE_HEADER].copy_from_slice(&size.to_be_bytes());
+                Ok(data)
+            }
+        }
+    }
+
+    /// Finds the first complete message in `buffer`.
+    /// Returns the range of its encoded content and the size of the whole frame.
+    pub fn frame(self, buffer: &[u8]) -> Option<(Range<usize>, usize)> {
+        match self {
+            Codec::Json => {
+                let position = buffer.iter().position(|c| *c == b'\n')?;
+                Some((0..position, position + 1))
+            }
+            Codec::MessagePack => {
+                let header = buffer.get(..SIZE_HEADER)?;
+                let size = u32::from_be_bytes(header.try_into().unwrap()) as usize;
+                let end = SIZE_HEADER.checked_add(size)?;
+                (buffer.len() >= end).then_some((SIZE_HEADER..end, end))
+            }
+        }
+    }
+
+    /// Number of bytes that the framing adds to each message.
+    pub fn overhead(self) -> usize {
+        match self {
+            Codec::Json => 1,
+            Codec::MessagePack => SIZE_HEADER,
+        }
+    }
+
+    /// Decodes the content of a message returned by [Codec::frame].
+    pub fn decode<T: DeserializeOwned>(self, data: &[u8]) -> std::io::Result<T> {
+        match self {
+            Codec::Json => Ok(serde_json::from_slice(data)?),
+            Codec::MessagePack => rmp_serde::from_slice(data)
+                .map_err(|error| Error::new(ErrorKind::InvalidData, error)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Codec;
+    use crate::messages::ClientToServerMsg;
+
+    fn roundtrip(codec: Codec) {
+        let msg = ClientToServerMsg::SendDM {
+            to: "Ferris".to_string(),
+            message: "Hello\nthere".to_string(),
+        };
+        let data = codec.encode(&msg).unwrap();
+        for end in 0..data.len() {
+            assert_eq!(codec.frame(&data[..end]), None);
+        }
+
+        let mut buffer = data.clone();
+        buffer.extend_from_slice(&data[..3]);
+        let (content, size) = codec.frame(&buffer).unwrap();
+        assert_eq!(size, data.len());
+        assert_eq!(content.len(), data.len() - codec.overhead());
+        let decoded: ClientToServerMsg = codec.decode(&buffer[content]).unwrap();
+        assert_eq!(decoded, msg);
+    }
+
+    #[test]
+    fn json() {
+        roundtrip(Codec::Json);
+    }
+
+    #[test]
+    fn message_pack() {
+        roundtrip(Codec::MessagePack);
+        // MessagePack is the compact one
+        let msg = ClientToServerMsg::Broadcast {
+            message: "Hi".to_string(),
+        };
+        assert!(
+            Codec::MessagePack.encode(&msg).unwrap().len()
+                < Codec::Json.encode(&msg).unwrap().len()
+        );
+    }
+
+    #[test]
+    fn invalid_message() {
+        for codec in [Codec::Json, Codec::MessagePack] {
+            assert!(codec.decode::<ClientToServerMsg>(b"\xff\x00").is_err());
+        }
+    }
+}
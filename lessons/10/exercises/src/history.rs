@@ -0,0 +1,206 @@
+use crate::messages::HistoryMessage;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+
+/// Name of the message log inside the data directory of the server.
+const LOG_FILE: &str = "messages.log";
+/// Maximum number of messages returned by a single history request.
+pub const MAX_HISTORY_LIMIT: usize = 100;
+
+/// A record of the message log, stored as a single JSON line.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+pub enum LogEntry {
+    /// A user has joined the chat for the first time
+    User { name: String },
+    /// A DM or a broadcast was sent. `queued` DMs were sent to an offline user and wait until the
+    /// user joins again.
+    Message {
+        message: HistoryMessage,
+        queued: bool,
+    },
+    /// A queued DM was delivered
+    Delivered { id: u64 },
+}
+
+/// All DMs and broadcasts sent through the server, together with the users that have ever joined.
+///
+/// Changes are sent to a task that appends them to the log file (see [write_log]), the history
+/// is restored from it when the server starts.
+#[derive(Default)]
+pub struct History {
+    messages: Vec<HistoryMessage>,
+    /// IDs of queued DMs of each user
+    queued: HashMap<String, Vec<u64>>,
+    known_users: HashSet<String>,
+    log: Option<UnboundedSender<LogEntry>>,
+}
+
+impl History {
+    /// Loads the history from the log in `directory`.
+    /// Returns the history and the opened log file, to which new entries should be appended.
+    pub async fn load(directory: &Path) -> anyhow::Result<(Self, File)> {
+        tokio::fs::create_dir_all(directory).await?;
+        let path = directory.join(LOG_FILE);
+        let data = match tokio::fs::read_to_string(&path).await {
+            Ok(data) => data,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(error) => return Err(error.into()),
+        };
+        let mut history = Self::default();
+        for line in data.lines().filter(|line| !line.is_empty()) {
+            history.apply(serde_json::from_str(line)?);
+        }
+        let file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        Ok((history, file))
+    }
+
+    /// Sends all future changes to `log`.
+    pub fn set_log(&mut self, log: UnboundedSender<LogEntry>) {
+        self.log = Some(log);
+    }
+
+    fn apply(&mut self, entry: LogEntry) {
+        match entry {
+            LogEntry::User { name } => {
+                self.known_users.insert(name);
+            }
+            LogEntry::Message { message, queued } => {
+                if let (Some(to), true) = (&message.to, queued) {
+                    self.queued.entry(to.clone()).or_default().push(message.id);
+                }
+                self.messages.push(message);
+            }
+            LogEntry::Delivered { id } => {
+                for ids in self.queued.values_mut() {
+                    ids.retain(|queued| *queued != id);
+                }
+            }
+        }
+    }
+
+    fn record(&mut self, entry: LogEntry) {
+        if let Some(log) = &self.log {
+            // The writer only stops when the server shuts down
+            let _ = log.send(entry.clone());
+        }
+        self.apply(entry);
+    }
+
+    pub fn is_known(&self, name: &str) -> bool {
+        self.known_users.contains(name)
+    }
+
+    pub fn add_user(&mut self, name: &str) {
+        if !self.is_known(name) {
+            self.record(LogEntry::User {
+                name: name.to_string(),
+            });
+        }
+    }
+
+    /// Stores a DM (if `to` is `Some`) or a broadcast and returns its ID. A `queued` DM is
+    /// returned by [History::take_queued] when its recipient joins.
+    pub fn add_message(
+        &mut self,
+        from: &str,
+        to: Option<&str>,
+        message: &str,
+        queued: bool,
+    ) -> u64 {
+        let id = self.messages.last().map_or(1, |last| last.id + 1);
+        let message = HistoryMessage {
+            id,
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_millis() as u64),
+            from: from.to_string(),
+            to: to.map(|to| to.to_string()),
+            message: message.to_string(),
+        };
+        self.record(LogEntry::Message { message, queued });
+        id
+    }
+
+    /// Returns the DMs queued for `name` (from the oldest one) and marks them as delivered.
+    pub fn take_queued(&mut self, name: &str) -> Vec<HistoryMessage> {
+        let ids = self.queued.remove(name).unwrap_or_default();
+        let messages = ids
+            .iter()
+            .filter_map(|id| self.find(*id).cloned())
+            .collect();
+        for id in ids {
+            self.record(LogEntry::Delivered { id });
+        }
+        messages
+    }
+
+    /// Returns at most `limit` of the newest messages older than `before`, from the oldest one.
+    /// If `with` is `Some`, returns DMs between `name` and `with`, otherwise broadcasts.
+    pub fn query(
+        &self,
+        name: &str,
+        with: Option<&str>,
+        limit: usize,
+        before: Option<u64>,
+    ) -> Vec<HistoryMessage> {
+        let is_match = |message: &HistoryMessage| match (with, message.to.as_deref()) {
+            (None, None) => true,
+            (Some(with), Some(to)) => {
+                (message.from == name && to == with) || (message.from == with && to == name)
+            }
+            _ => false,
+        };
+        let mut messages: Vec<HistoryMessage> = self
+            .messages
+            .iter()
+            .rev()
+            .filter(|message| before.is_none_or(|before| message.id < before))
+            .filter(|message| is_match(message))
+            .take(limit.min(MAX_HISTORY_LIMIT))
+            .cloned()
+            .collect();
+        messages.reverse();
+        messages
+    }
+
+    pub fn find(&self, id: u64) -> Option<&HistoryMessage> {
+        // IDs are increasing
+        self.messages
+            .binary_search_by_key(&id, |message| message.id)
+            .ok()
+            .map(|index| &self.messages[index])
+    }
+}
+
+/// Appends entries to the log until all senders are dropped.
+/// Entries that arrive together are written at once, each batch is synced to the disk.
+pub async fn write_log(
+    mut file: File,
+    mut entries: UnboundedReceiver<LogEntry>,
+) -> anyhow::Result<()> {
+    let mut buffer = vec![];
+    while let Some(entry) = entries.recv().await {
+        buffer.clear();
+        serialize(&mut buffer, &entry)?;
+        while let Ok(entry) = entries.try_recv() {
+            serialize(&mut buffer, &entry)?;
+        }
+        file.write_all(&buffer).await?;
+        file.sync_data().await?;
+    }
+    Ok(())
+}
+
+fn serialize(buffer: &mut Vec<u8>, entry: &LogEntry) -> anyhow::Result<()> {
+    serde_json::to_writer(&mut *buffer, entry)?;
+    buffer.push(b'\n');
+    Ok(())
+}
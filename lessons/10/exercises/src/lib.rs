@@ -1,6 +1,6 @@
 #![warn(clippy::await_holding_refcell_ref)]
 
-//! TODO: implement a simple chat server using async/await and tokio (still using non-blocking I/O)
+//! A simple chat server using async/await and tokio (still using non-blocking I/O)
 //!
 //! The chat server should behave identically as the one from last week, with one new feature.
 //! However, it should be implemented using async/await (with non-blocking I/O) and run on a
@@ -13,34 +13,96 @@
 //! Your code will run inside [`tokio::task::LocalSet`], so you can use [`tokio::task::spawn_local`]
 //! to spawn new asynchronous tasks.
 
+/// Encoding of messages
+pub mod codec;
 /// The following modules were prepared for you. You should not need to modify them.
 ///
 /// Take a look at this file to see how should the individual messages be handled
-mod messages;
+pub mod messages;
 /// Message reading
-mod reader;
+pub mod reader;
 /// Message writing
-mod writer;
-
-#[derive(Copy, Clone)]
-struct ServerOpts {
+pub mod writer;
+
+/// Registered accounts
+mod accounts;
+/// State shared by all connected clients
+mod chat;
+/// Handling of a single connection
+mod client;
+/// Stored messages
+mod history;
+/// Limits of the traffic of clients
+pub mod rate_limit;
+/// Encrypted connections
+pub mod tls;
+/// Terminal chat client
+pub mod tui;
+/// Connections of browser clients
+mod websocket;
+
+use crate::accounts::Accounts;
+use crate::chat::Chat;
+use crate::client::{handle_client, send_error, ConnectionOpts};
+use crate::codec::Codec;
+use crate::history::{write_log, History};
+use crate::messages::ServerToClientMsg;
+use crate::rate_limit::RateLimit;
+use crate::tls::{ReadHalf, TlsOpts, WriteHalf};
+use crate::writer::MessageWriter;
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::time::Duration;
+use tokio::fs::File;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinSet;
+use tokio_rustls::TlsAcceptor;
+
+#[derive(Clone)]
+pub struct ServerOpts {
     /// Maximum number of clients that can be connected to the server at once.
-    max_clients: usize,
+    pub max_clients: usize,
+    /// Maximum number of members of a single room.
+    pub max_room_members: usize,
+    /// File in which registered accounts are stored, `None` keeps them only in memory.
+    pub account_database: Option<PathBuf>,
+    /// Number of failed logins after which logging in as the same user is refused.
+    pub max_login_failures: usize,
+    /// How long logins are refused after too many failures.
+    pub login_lockout: Duration,
+    /// Directory in which the log of messages is stored, `None` keeps them only in memory.
+    pub data_directory: Option<PathBuf>,
+    /// If set, clients have to connect using TLS with this certificate, plaintext clients are
+    /// refused.
+    pub tls: Option<TlsOpts>,
+    /// If set, the server also listens for WebSocket clients on a second port.
+    pub websocket: bool,
+    /// Largest message (in bytes, without framing) that a client can send. Clients sending larger
+    /// messages are disconnected.
+    pub max_message_size: usize,
+    /// Limits of the number of messages and bytes that each client can send, `None` disables
+    /// rate limiting.
+    pub rate_limit: Option<RateLimit>,
 }
 
 /// Representation of a running server
-struct RunningServer {
+pub struct RunningServer {
     /// Port on which the server is running
-    port: u16,
+    pub port: u16,
+    /// Port on which the server accepts WebSocket clients, if it was enabled
+    pub websocket_port: Option<u16>,
     /// Main future of the server
-    future: Pin<Box<dyn Future<Output = anyhow::Result<()>>>>,
+    pub future: Pin<Box<dyn Future<Output = anyhow::Result<()>>>>,
     /// Channel that can be used to tell the server to stop
-    tx: tokio::sync::oneshot::Sender<()>,
+    pub tx: tokio::sync::oneshot::Sender<()>,
 }
 
-/// TODO: implement the following asynchronous function called `run_server`
-/// It should start a chat server on a TCP/IP port assigned to it by the operating system and
-/// return a [`RunningServer`]. Note that the function is asynchronous, but it should create a
+/// Starts a chat server on a TCP/IP port assigned to it by the operating system and
+/// returns a [`RunningServer`]. Note that the function is asynchronous, but it should create a
 /// separate future that will run the server loop, and return that future inside the returned
 /// [`RunningServer`].
 ///
@@ -55,19 +117,19 @@ struct RunningServer {
 /// # Client connection
 /// When a client connects to the server, it should send a `Join` message.
 /// - If the client does not send a `Join` message within two seconds, the server should
-/// send an error "Timed out waiting for Join" and disconnect the client immediately.
+///   send an error "Timed out waiting for Join" and disconnect the client immediately.
 /// - If it sends anything else, the server should respond with an error "Unexpected message received"
-/// and disconnect the client immediately.
+///   and disconnect the client immediately.
 /// - If the user sends a Join message (with a unique username), the server should respond with
-/// the `Welcome` message.
+///   the `Welcome` message.
 ///
 /// Then it should start receiving requests from the client.
 /// - If the client ever sends the `Join` message again, the server should respond with an error
-/// "Unexpected message received" and disconnect the client immediately.
+///   "Unexpected message received" and disconnect the client immediately.
 /// - **(NEW)** If the client does not send any message in three seconds AND it does not receive
-/// any message (through a DM or a broadcast) within that duration, the server should respond with
-/// an error "Timeouted" and disconnect the client immediately. This three second timer is refreshed
-/// everytime the client sends something or receives a DM/broadcast.
+///   any message (through a DM or a broadcast) within that duration, the server should respond with
+///   an error "Timeouted" and disconnect the client immediately. This three second timer is refreshed
+///   everytime the client sends something or receives a DM/broadcast.
 ///
 /// # Maximum number of clients
 /// When a client connects and there are already `opts.max_clients` other clients connected, the
@@ -75,32 +137,260 @@ struct RunningServer {
 /// Note that if the server is full, the client should be disconnected even before it sends the
 /// `Join` message.
 ///
+/// # Rooms
+/// Users can create named rooms, join and leave them and send messages to all their members (see
+/// `messages.rs`). A room can have at most `opts.max_room_members` members. When its last member
+/// leaves it or disconnects, the room is removed.
+///
+/// # Accounts
+/// Users can register an account with a password, then nobody can join with their name without
+/// the password. Passwords are stored as salted Argon2 hashes in `opts.account_database`. After
+/// `opts.max_login_failures` failed logins, logging in as the same user is refused for
+/// `opts.login_lockout`.
+///
+/// # Message history
+/// DMs and broadcasts are appended to a log in `opts.data_directory`, from which they are loaded
+/// when the server starts again. DMs sent to a user that is offline, but has joined before, are
+/// delivered right after the `Welcome` message when the user joins. Clients can also request
+/// past messages (see `messages.rs`).
+///
+/// # TLS
+/// If `opts.tls` is set, the server only accepts clients that perform a TLS handshake with the
+/// configured certificate, plaintext clients are disconnected.
+///
+/// # WebSocket
+/// If `opts.websocket` is set, the server also accepts WebSocket clients on
+/// [`RunningServer::websocket_port`]. Each text frame sent by them contains a single
+/// `ClientToServerMsg` and each `ServerToClientMsg` is sent to them in a single text frame,
+/// otherwise they behave like TCP clients (and they share the limit of connected clients).
+/// If TLS is enabled, it is also required from WebSocket clients.
+///
+/// # Codecs
+/// Clients can choose a binary codec (see [`codec::Codec`]) in the `Join` message, which is then
+/// used for all following messages. If a client sends a message larger than
+/// `opts.max_message_size`, the server responds with an error "Message is too large" and
+/// disconnects the client.
+///
+/// # Rate limiting
+/// If `opts.rate_limit` is set, each client can only send a limited number of messages and bytes
+/// per second (with short bursts allowed). Messages over the limit are dropped and the server
+/// responds to the first one of each burst with an error "Rate limit exceeded". After
+/// `RateLimit::max_violations` such messages in a short time, the client is also disconnected.
+///
+/// # Presence and receipts
+/// Users have a presence with an optional status message, which can be watched by other clients,
+/// and they can tell others that they are typing (see `messages.rs`). A user that has not sent
+/// anything for two seconds is marked away (unless it has set a different presence), and it is
+/// online again when it sends something. Clients using a newer protocol version receive IDs of
+/// DMs and receipts when their DMs are delivered and read.
+///
 /// # Graceful shutdown
 /// Your server should react to a message sent through the oneshot channel that you should create
 /// in `RunningServer`. When a message is received on this channel, the server should:
 /// 1) Stop receiving new TCP/IP connections
 /// 2) Correctly disconnect all connected users (bonus, see [`tests::drop_clients_on_shutdown`])
 /// 3) Wait until all async tasks that it has created has completed executing (bonus)
+///
 /// The rest is handled by the test infrastructure.
 ///
 /// See tests for more details.
-async fn run_server(opts: ServerOpts) -> anyhow::Result<RunningServer> { todo!() }
+pub async fn run_server(opts: ServerOpts) -> anyhow::Result<RunningServer> {
+    let listener = TcpListener::bind(("127.0.0.1", 0)).await?;
+    let port = listener.local_addr()?.port();
+    let websocket_listener = match opts.websocket {
+        true => Some(TcpListener::bind(("127.0.0.1", 0)).await?),
+        false => None,
+    };
+    let websocket_port = match &websocket_listener {
+        Some(listener) => Some(listener.local_addr()?.port()),
+        None => None,
+    };
+    let accounts = Accounts::load(
+        opts.account_database.clone(),
+        opts.max_login_failures,
+        opts.login_lockout,
+    )
+    .await?;
+    let (history, log) = match &opts.data_directory {
+        Some(directory) => {
+            let (history, log) = History::load(directory).await?;
+            (history, Some(log))
+        }
+        None => (History::default(), None),
+    };
+    let chat = Chat::new(opts.max_room_members, history);
+    let tls = match &opts.tls {
+        Some(tls) => Some(tls::load_acceptor(tls).await?),
+        None => None,
+    };
+    let (tx, rx) = oneshot::channel();
+    let listeners = (listener, websocket_listener);
+    Ok(RunningServer {
+        port,
+        websocket_port,
+        future: Box::pin(serve(listeners, opts, chat, accounts, log, tls, rx)),
+        tx,
+    })
+}
+
+/// Accepts clients until a message arrives through `shutdown`, then disconnects all clients and
+/// waits for their tasks.
+async fn serve(
+    (listener, websocket_listener): (TcpListener, Option<TcpListener>),
+    opts: ServerOpts,
+    mut chat: Chat,
+    accounts: Accounts,
+    log: Option<File>,
+    tls: Option<TlsAcceptor>,
+    mut shutdown: oneshot::Receiver<()>,
+) -> anyhow::Result<()> {
+    let log_writer = log.map(|file| {
+        let (log, entries) = tokio::sync::mpsc::unbounded_channel();
+        chat.set_log(log);
+        tokio::task::spawn_local(write_log(file, entries))
+    });
+    let chat = Rc::new(RefCell::new(chat));
+    let accounts = Rc::new(RefCell::new(accounts));
+    let connected = Rc::new(Cell::new(0));
+    // Dropping the sender tells the clients to disconnect
+    let (stop_clients, stopped) = watch::channel(());
+    let mut tasks = JoinSet::new();
+
+    loop {
+        let (accepted, transport) = tokio::select! {
+            _ = &mut shutdown => break,
+            // Clean up tasks of clients that have already disconnected
+            Some(_) = tasks.join_next(), if !tasks.is_empty() => continue,
+            accepted = listener.accept() => (accepted, Transport::Tcp),
+            accepted = accept_optional(websocket_listener.as_ref()) => {
+                (accepted, Transport::WebSocket)
+            }
+        };
+        let stream = match accepted {
+            Ok((stream, _)) => stream,
+            Err(error) => {
+                log::warn!("Cannot accept a connection: {error}");
+                continue;
+            }
+        };
+        let tls = tls.clone();
+        let max_message_size = opts.max_message_size;
+        let connection_opts = ConnectionOpts {
+            codecs: match transport {
+                Transport::Tcp => &[Codec::Json, Codec::MessagePack],
+                // Text frames can only contain JSON
+                Transport::WebSocket => &[Codec::Json],
+            },
+            max_message_size,
+            rate_limit: opts.rate_limit,
+        };
+        if connected.get() >= opts.max_clients {
+            tasks.spawn_local(async move {
+                let _ = connect(stream, tls, transport, max_message_size, reject_client).await;
+            });
+            continue;
+        }
+        connected.set(connected.get() + 1);
+        let chat = chat.clone();
+        let accounts = accounts.clone();
+        let connected = connected.clone();
+        let stopped = stopped.clone();
+        tasks.spawn_local(async move {
+            let result = connect(stream, tls, transport, max_message_size, |connection| {
+                handle_client(connection, connection_opts, chat, accounts, stopped)
+            })
+            .await;
+            if let Err(error) = result {
+                log::warn!("Client has failed: {error}");
+            }
+            connected.set(connected.get() - 1);
+        });
+    }
+
+    drop(listener);
+    drop(websocket_listener);
+    drop(stop_clients);
+    while tasks.join_next().await.is_some() {}
+    // Dropping the chat stops the log writer once it writes all entries
+    drop(chat);
+    if let Some(log_writer) = log_writer {
+        log_writer.await??;
+    }
+    Ok(())
+}
+
+/// Listener on which a client has connected.
+#[derive(Copy, Clone)]
+enum Transport {
+    Tcp,
+    WebSocket,
+}
+
+/// Accepts a connection from `listener`, or waits forever if there is no listener.
+async fn accept_optional(
+    listener: Option<&TcpListener>,
+) -> std::io::Result<(TcpStream, std::net::SocketAddr)> {
+    match listener {
+        Some(listener) => listener.accept().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Performs the TLS and WebSocket handshakes (if they are needed) and runs `handler` with
+/// a connection that speaks the newline-delimited JSON protocol. WebSocket messages are limited
+/// to `max_message_size` bytes.
+async fn connect<F, Fut>(
+    stream: TcpStream,
+    tls: Option<TlsAcceptor>,
+    transport: Transport,
+    max_message_size: usize,
+    handler: F,
+) -> anyhow::Result<()>
+where
+    F: FnOnce((ReadHalf, WriteHalf)) -> Fut,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let stream = tls::accept(stream, tls.as_ref()).await?;
+    match transport {
+        Transport::Tcp => handler(tls::split(stream)).await,
+        Transport::WebSocket => {
+            let (connection, forward) = websocket::accept(stream, max_message_size).await?;
+            let (handled, forwarded) = tokio::join!(handler(connection), forward);
+            handled.and(forwarded)
+        }
+    }
+}
 
+/// Tells a client that the server is full and disconnects it.
+async fn reject_client((_, tx): (ReadHalf, WriteHalf)) -> anyhow::Result<()> {
+    let mut writer = MessageWriter::<ServerToClientMsg, _>::new(tx);
+    send_error(&mut writer, "Server is full").await
+}
 
 #[cfg(test)]
 mod tests {
-    use crate::messages::{ClientToServerMsg, ServerToClientMsg};
+    use crate::codec::Codec;
+    use crate::messages::{
+        ClientToServerMsg, HistoryMessage, Presence, Protocol, ReceiptStatus, ServerToClientMsg,
+        UserPresence, PROTOCOL_VERSION,
+    };
+    use crate::rate_limit::RateLimit;
     use crate::reader::MessageReader;
+    use crate::tls::{ClientTls, ReadHalf, TlsOpts, WriteHalf};
+    use crate::tui::{run_client, ClientOpts, Event};
     use crate::writer::MessageWriter;
     use crate::{run_server, ServerOpts};
+    use futures_util::{SinkExt, StreamExt};
     use std::cell::{Cell, RefCell};
     use std::future::Future;
     use std::rc::Rc;
     use std::time::Duration;
     use tokio::io::AsyncWriteExt;
-    use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
     use tokio::net::TcpStream;
-    use tokio::task::LocalSet;
+    use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+    use tokio::task::{JoinHandle, LocalSet};
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::WebSocketStream;
 
     // If you're struggling with this test, comment it and implement the rest of the
     // functionality first.
@@ -162,6 +452,8 @@ mod tests {
                     let _ = client
                         .try_send(ClientToServerMsg::Join {
                             name: format!("Client {client_id}"),
+                            password: None,
+                            protocol: None,
                         })
                         .await;
                     match client.recv().await {
@@ -221,16 +513,15 @@ mod tests {
         run_test(opts(2), |spawner| async move {
             let mut client = spawner.client().await;
             sleep(3000).await;
-            match client
+            if let Ok(()) = client
                 .try_send(ClientToServerMsg::Join {
                     name: "Bilbo".to_string(),
+                    password: None,
+                    protocol: None,
                 })
                 .await
             {
-                Ok(_) => {
-                    client.expect_error("Timed out waiting for Join").await;
-                }
-                Err(_) => {}
+                client.expect_error("Timed out waiting for Join").await;
             }
 
             Ok(())
@@ -246,6 +537,8 @@ mod tests {
             client
                 .send(ClientToServerMsg::Join {
                     name: "Bar".to_string(),
+                    password: None,
+                    protocol: None,
                 })
                 .await;
             client.expect_error("Unexpected message received").await;
@@ -263,6 +556,8 @@ mod tests {
             client
                 .send(ClientToServerMsg::Join {
                     name: "Bar".to_string(),
+                    password: None,
+                    protocol: None,
                 })
                 .await;
             client.close().await;
@@ -285,6 +580,8 @@ mod tests {
             client2
                 .send(ClientToServerMsg::Join {
                     name: "Foo".to_string(),
+                    password: None,
+                    protocol: None,
                 })
                 .await;
             client2.expect_error("Username already taken").await;
@@ -371,6 +668,8 @@ mod tests {
             client
                 .send(ClientToServerMsg::Join {
                     name: "Barbara".to_string(),
+                    password: None,
+                    protocol: None,
                 })
                 .await;
 
@@ -680,176 +979,1838 @@ mod tests {
         .await;
     }
 
-    // TODO(bonus): The server should correctly close client sockets when it shuts down,
-    // to avoid a situation where the clients would be stuck waiting for a message
-    // for some indeterminate amount of time.
-    /*
     #[tokio::test]
-    async fn drop_clients_on_shutdown() {
-        let (mut client, mut client2) = run_test(opts(10), |spawner| async move {
-            let mut client = spawner.client().await;
-            client.join("Bar").await;
-            let mut client2 = spawner.client().await;
-            client2.join("Foo").await;
-            Ok((client, client2))
+    async fn room_create_and_list() {
+        run_test(opts(3), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            assert_eq!(ferris.list_rooms().await, Vec::<String>::new());
+            ferris.create_room("rust").await;
+            ferris.create_room("crabs").await;
+
+            let mut corro = spawner.client().await;
+            corro.join("Corro").await;
+            assert_eq!(
+                corro.list_rooms().await,
+                vec!["crabs".to_string(), "rust".to_string()]
+            );
+            corro
+                .send(ClientToServerMsg::CreateRoom {
+                    room: "rust".to_string(),
+                })
+                .await;
+            corro.expect_error("Room rust already exists").await;
+
+            Ok(())
         })
         .await;
-
-        assert!(client.reader.recv().await.is_none());
-        assert!(client2.reader.recv().await.is_none());
     }
-    */
-    async fn run_test<C, F, R>(opts: ServerOpts, func: C) -> R
-    where
-        C: FnOnce(ClientSpawner) -> F,
-        F: Future<Output = anyhow::Result<R>>,
-    {
-        let localset = LocalSet::new();
-        let (port, ret) = localset
-            .run_until(async {
-                // Start the server
-                let server = run_server(opts).await.expect("creating server failed");
-                let port = server.port;
 
-                let spawner = ClientSpawner { port };
+    #[tokio::test]
+    async fn room_join_errors() {
+        run_test(opts(2), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            ferris
+                .send(ClientToServerMsg::JoinRoom {
+                    room: "rust".to_string(),
+                })
+                .await;
+            ferris.expect_error("Room rust does not exist").await;
 
-                // Spawn the server future
-                let server_fut = tokio::task::spawn_local(server.future);
+            ferris.create_room("rust").await;
+            ferris
+                .send(ClientToServerMsg::JoinRoom {
+                    room: "rust".to_string(),
+                })
+                .await;
+            ferris.expect_error("You are already in room rust").await;
 
-                // Run the test
-                let ret = func(spawner).await.expect("test failed");
+            Ok(())
+        })
+        .await;
+    }
 
-                // Tell the server to shut down
-                server.tx.send(()).unwrap();
+    #[tokio::test]
+    async fn room_before_join() {
+        run_test(opts(2), |spawner| async move {
+            let mut client = spawner.client().await;
+            client.send(ClientToServerMsg::ListRooms).await;
+            client.expect_error("Unexpected message received").await;
+            client.check_closed().await;
 
-                // Wait until it shuts down
-                server_fut.await.unwrap().unwrap();
+            Ok(())
+        })
+        .await;
+    }
 
-                (port, ret)
-            })
-            .await;
+    #[tokio::test]
+    async fn room_messages() {
+        run_test(opts(3), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            ferris.create_room("rust").await;
+
+            let mut corro = spawner.client().await;
+            corro.join("Corro").await;
+            corro.join_room("rust").await;
+
+            let mut outsider = spawner.client().await;
+            outsider.join("Outsider").await;
+
+            ferris.send_to_room("rust", "Hello crabs").await;
+            corro
+                .expect_room_message("rust", "Ferris", "Hello crabs")
+                .await;
+            corro.send_to_room("rust", "Hi!").await;
+            ferris.expect_room_message("rust", "Corro", "Hi!").await;
 
-        TcpStream::connect(("127.0.0.1", port))
-            .await
-            .expect_err("server is still alive");
-        ret
+            // Neither the sender nor users outside of the room receive the message
+            corro.ping().await;
+            outsider.ping().await;
+
+            outsider.send_to_room("rust", "Let me in").await;
+            outsider.expect_error("You are not in room rust").await;
+
+            Ok(())
+        })
+        .await;
     }
 
-    struct Client {
-        writer: MessageWriter<ClientToServerMsg, OwnedWriteHalf>,
-        reader: MessageReader<ServerToClientMsg, OwnedReadHalf>,
+    #[tokio::test]
+    async fn room_leave() {
+        run_test(opts(2), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            ferris.create_room("rust").await;
+
+            let mut corro = spawner.client().await;
+            corro.join("Corro").await;
+            corro.join_room("rust").await;
+            corro.leave_room("rust").await;
+            corro
+                .send(ClientToServerMsg::LeaveRoom {
+                    room: "rust".to_string(),
+                })
+                .await;
+            corro.expect_error("You are not in room rust").await;
+
+            ferris.send_to_room("rust", "Anyone?").await;
+            corro.ping().await;
+
+            Ok(())
+        })
+        .await;
     }
 
-    impl Client {
-        async fn join(&mut self, name: &str) {
-            self.send(ClientToServerMsg::Join {
-                name: name.to_string(),
+    #[tokio::test]
+    async fn room_member_limit() {
+        let opts = ServerOpts {
+            max_room_members: 2,
+            ..opts(5)
+        };
+        run_test(opts, |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            ferris.create_room("rust").await;
+
+            let mut corro = spawner.client().await;
+            corro.join("Corro").await;
+            corro.join_room("rust").await;
+
+            let mut bob = spawner.client().await;
+            bob.join("Bob").await;
+            bob.send(ClientToServerMsg::JoinRoom {
+                room: "rust".to_string(),
             })
             .await;
-            let msg = self.recv().await;
-            assert!(matches!(msg, ServerToClientMsg::Welcome));
-        }
+            bob.expect_error("Room rust is full").await;
 
-        async fn ping(&mut self) {
-            self.send(ClientToServerMsg::Ping).await;
-            let msg = self.recv().await;
-            assert!(matches!(msg, ServerToClientMsg::Pong));
-        }
+            corro.leave_room("rust").await;
+            bob.join_room("rust").await;
 
-        async fn list_users(&mut self) -> Vec<String> {
-            self.send(ClientToServerMsg::ListUsers).await;
-            let msg = self.recv().await;
-            match msg {
-                ServerToClientMsg::UserList { mut users } => {
-                    users.sort();
-                    users
-                }
-                msg => {
-                    panic!("Unexpected response {msg:?}");
-                }
-            }
-        }
+            Ok(())
+        })
+        .await;
+    }
 
-        async fn dm(&mut self, to: &str, message: &str) {
-            self.send(ClientToServerMsg::SendDM {
-                to: to.to_string(),
-                message: message.to_string(),
-            })
-            .await;
-        }
+    #[tokio::test]
+    async fn room_removed_when_empty() {
+        run_test(opts(2), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            ferris.create_room("rust").await;
+            ferris.create_room("crabs").await;
+            ferris.leave_room("crabs").await;
+            assert_eq!(ferris.list_rooms().await, vec!["rust".to_string()]);
+            ferris.close().await;
 
-        async fn expect_message(&mut self, expected_from: &str, expected_message: &str) {
-            let msg = self.recv().await;
-            match msg {
-                ServerToClientMsg::Message { from, message } => {
-                    assert_eq!(from, expected_from);
-                    assert_eq!(message, expected_message);
-                }
-                msg => panic!("Unexpected message {msg:?}"),
-            }
-        }
+            sleep(500).await;
 
-        async fn send(&mut self, msg: ClientToServerMsg) {
-            self.writer.send(msg).await.expect("cannot send message");
-        }
+            let mut corro = spawner.client().await;
+            corro.join("Corro").await;
+            assert_eq!(corro.list_rooms().await, Vec::<String>::new());
+            // The name can be used again
+            corro.create_room("rust").await;
 
-        async fn try_send(&mut self, msg: ClientToServerMsg) -> anyhow::Result<()> {
-            self.writer.send(msg).await
-        }
+            Ok(())
+        })
+        .await;
+    }
 
-        async fn expect_error(&mut self, expected_error: &str) {
-            let msg = self.recv().await;
-            match msg {
-                ServerToClientMsg::Error(error) => {
-                    assert_eq!(error, expected_error);
-                }
-                msg => {
-                    panic!("Unexpected response {msg:?}");
-                }
-            }
-        }
+    #[tokio::test]
+    async fn register_and_join() {
+        run_test(opts(2), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.register("Ferris", "crab123").await;
+            ferris.close().await;
+            sleep(500).await;
 
-        async fn recv(&mut self) -> ServerToClientMsg {
-            self.reader
-                .recv()
-                .await
-                .expect("connection was closed")
-                .expect("did not receive welcome message")
-        }
+            let mut impostor = spawner.client().await;
+            impostor.join_with("Ferris", None).await;
+            impostor.expect_error("Password required").await;
+            impostor.check_closed().await;
 
-        async fn close(self) {
-            self.writer.into_inner().shutdown().await.unwrap();
-        }
+            let mut impostor = spawner.client().await;
+            impostor.join_with("Ferris", Some("crab")).await;
+            impostor.expect_error("Invalid password").await;
+            impostor.check_closed().await;
 
-        async fn check_closed(mut self) {
-            assert!(matches!(self.reader.recv().await, None | Some(Err(_))));
-        }
-    }
+            let mut ferris = spawner.client().await;
+            ferris.join_with("Ferris", Some("crab123")).await;
+            ferris.expect_welcome().await;
+            assert_eq!(ferris.list_users().await, vec!["Ferris".to_string()]);
 
-    #[derive(Copy, Clone)]
-    struct ClientSpawner {
-        port: u16,
+            Ok(())
+        })
+        .await;
     }
 
-    impl ClientSpawner {
-        async fn client(&self) -> Client {
-            let client = TcpStream::connect(("127.0.0.1", self.port))
-                .await
-                .expect("cannot connect to server");
+    #[tokio::test]
+    async fn register_errors() {
+        run_test(opts(3), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.register("Ferris", "crab123").await;
 
-            let (rx, tx) = client.into_split();
+            let mut client = spawner.client().await;
+            client
+                .send(ClientToServerMsg::Register {
+                    name: "Ferris".to_string(),
+                    password: "other".to_string(),
+                })
+                .await;
+            client.expect_error("Username already taken").await;
 
-            let reader = MessageReader::<ServerToClientMsg, _>::new(rx);
-            let writer = MessageWriter::<ClientToServerMsg, _>::new(tx);
-            Client { reader, writer }
-        }
+            ferris.close().await;
+            sleep(500).await;
+            let mut client = spawner.client().await;
+            client
+                .send(ClientToServerMsg::Register {
+                    name: "Ferris".to_string(),
+                    password: "other".to_string(),
+                })
+                .await;
+            client.expect_error("Username already registered").await;
+
+            let mut client = spawner.client().await;
+            client
+                .send(ClientToServerMsg::Register {
+                    name: "Corro".to_string(),
+                    password: String::new(),
+                })
+                .await;
+            client.expect_error("Password cannot be empty").await;
+
+            Ok(())
+        })
+        .await;
     }
 
-    async fn sleep(duration_ms: u64) {
-        tokio::time::sleep(Duration::from_millis(duration_ms)).await;
+    #[tokio::test]
+    async fn join_unregistered_with_password() {
+        run_test(opts(2), |spawner| async move {
+            let mut client = spawner.client().await;
+            client.join_with("Corro", Some("secret")).await;
+            client.expect_error("User Corro is not registered").await;
+
+            Ok(())
+        })
+        .await;
     }
 
-    fn opts(max_clients: usize) -> ServerOpts {
-        ServerOpts { max_clients }
+    #[tokio::test]
+    async fn register_after_join() {
+        run_test(opts(2), |spawner| async move {
+            let mut client = spawner.client().await;
+            client.join("Ferris").await;
+            client
+                .send(ClientToServerMsg::Register {
+                    name: "Ferris".to_string(),
+                    password: "crab123".to_string(),
+                })
+                .await;
+            client.expect_error("Unexpected message received").await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn login_rate_limit() {
+        let opts = ServerOpts {
+            max_login_failures: 2,
+            login_lockout: Duration::from_millis(1000),
+            ..opts(3)
+        };
+        run_test(opts, |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.register("Ferris", "crab123").await;
+            ferris.close().await;
+            sleep(500).await;
+
+            for _ in 0..2 {
+                let mut impostor = spawner.client().await;
+                impostor.join_with("Ferris", Some("guess")).await;
+                impostor.expect_error("Invalid password").await;
+            }
+            // Even the correct password is refused during the lockout
+            let mut ferris = spawner.client().await;
+            ferris.join_with("Ferris", Some("crab123")).await;
+            ferris.expect_error("Too many failed login attempts").await;
+
+            sleep(1100).await;
+            let mut ferris = spawner.client().await;
+            ferris.join_with("Ferris", Some("crab123")).await;
+            ferris.expect_welcome().await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn accounts_are_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = ServerOpts {
+            account_database: Some(dir.path().join("accounts.json")),
+            ..opts(2)
+        };
+        run_test(opts.clone(), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.register("Ferris", "crab123").await;
+
+            Ok(())
+        })
+        .await;
+
+        let database = std::fs::read_to_string(dir.path().join("accounts.json")).unwrap();
+        assert!(database.contains("Ferris"));
+        assert!(!database.contains("crab123"));
+
+        run_test(opts, |spawner| async move {
+            let mut impostor = spawner.client().await;
+            impostor.join_with("Ferris", None).await;
+            impostor.expect_error("Password required").await;
+
+            let mut ferris = spawner.client().await;
+            ferris.join_with("Ferris", Some("crab123")).await;
+            ferris.expect_welcome().await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn offline_dm() {
+        run_test(opts(3), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            ferris.close().await;
+            sleep(100).await;
+
+            let mut mark = spawner.client().await;
+            mark.join("Mark").await;
+            mark.dm("Ferris", "Are you there?").await;
+            mark.dm("Ferris", "Hello?").await;
+            mark.dm("Fiona", "Hi").await;
+            mark.expect_error("User Fiona does not exist").await;
+
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            ferris.expect_message("Mark", "Are you there?").await;
+            ferris.expect_message("Mark", "Hello?").await;
+            ferris.close().await;
+            sleep(100).await;
+
+            // Queued messages are delivered only once
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            ferris.ping().await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn history() {
+        run_test(opts(3), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            let mut mark = spawner.client().await;
+            mark.join("Mark").await;
+            let mut fiona = spawner.client().await;
+            fiona.join("Fiona").await;
+
+            for message in ["1", "2", "3"] {
+                mark.dm("Ferris", message).await;
+                ferris.expect_message("Mark", message).await;
+            }
+            fiona.dm("Ferris", "Secret").await;
+            ferris.expect_message("Fiona", "Secret").await;
+            ferris
+                .send(ClientToServerMsg::Broadcast {
+                    message: "Hello everyone".to_string(),
+                })
+                .await;
+            mark.expect_message("Ferris", "Hello everyone").await;
+            fiona.expect_message("Ferris", "Hello everyone").await;
+
+            let messages = ferris.history(Some("Mark"), 2, None).await;
+            assert_eq!(
+                messages
+                    .iter()
+                    .map(|message| message.message.as_str())
+                    .collect::<Vec<_>>(),
+                vec!["2", "3"]
+            );
+            assert!(messages
+                .iter()
+                .all(|message| message.from == "Mark" && message.to.as_deref() == Some("Ferris")));
+            let older = ferris.history(Some("Mark"), 10, Some(messages[0].id)).await;
+            assert_eq!(older.len(), 1);
+            assert_eq!(older[0].message, "1");
+            assert!(older[0].id < messages[0].id);
+            assert!(ferris
+                .history(Some("Mark"), 10, Some(older[0].id))
+                .await
+                .is_empty());
+
+            // The same conversation seen by the other side
+            assert_eq!(mark.history(Some("Ferris"), 10, None).await.len(), 3);
+            // DMs of other users are not visible
+            assert!(mark.history(Some("Fiona"), 10, None).await.is_empty());
+
+            let broadcasts = mark.history(None, 10, None).await;
+            assert_eq!(broadcasts.len(), 1);
+            assert_eq!(broadcasts[0].from, "Ferris");
+            assert_eq!(broadcasts[0].to, None);
+            assert_eq!(broadcasts[0].message, "Hello everyone");
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn history_is_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let opts = ServerOpts {
+            data_directory: Some(dir.path().join("data")),
+            ..opts(2)
+        };
+        run_test(opts.clone(), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            ferris.close().await;
+            sleep(100).await;
+
+            let mut mark = spawner.client().await;
+            mark.join("Mark").await;
+            mark.dm("Ferris", "See you tomorrow").await;
+            mark.send(ClientToServerMsg::Broadcast {
+                message: "Bye".to_string(),
+            })
+            .await;
+            mark.ping().await;
+
+            Ok(())
+        })
+        .await;
+
+        run_test(opts.clone(), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            ferris.expect_message("Mark", "See you tomorrow").await;
+
+            let broadcasts = ferris.history(None, 10, None).await;
+            assert_eq!(broadcasts.len(), 1);
+            assert_eq!(broadcasts[0].message, "Bye");
+
+            // Users that have never joined still do not exist
+            ferris.dm("Fiona", "Hi").await;
+            ferris.expect_error("User Fiona does not exist").await;
+            ferris.dm("Mark", "Thanks").await;
+            ferris.ping().await;
+
+            Ok(())
+        })
+        .await;
+
+        run_test(opts, |spawner| async move {
+            // The DM was already delivered before the restart
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            ferris.ping().await;
+
+            let mut mark = spawner.client().await;
+            mark.join("Mark").await;
+            mark.expect_message("Ferris", "Thanks").await;
+            let messages = mark.history(Some("Ferris"), 10, None).await;
+            assert_eq!(
+                messages
+                    .iter()
+                    .map(|message| message.message.as_str())
+                    .collect::<Vec<_>>(),
+                vec!["See you tomorrow", "Thanks"]
+            );
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn tui_client_commands() {
+        run_test(opts(3), |spawner| async move {
+            let mut mark = spawner.client().await;
+            mark.join("Mark").await;
+
+            let (input, mut events, client) =
+                spawner.tui_client("Ferris", Duration::from_millis(500), None);
+            assert_eq!(next_event(&mut events).await, Event::Connected);
+
+            input.send("/list".to_string())?;
+            assert_eq!(
+                next_event(&mut events).await,
+                Event::UserList(vec!["Ferris".to_string(), "Mark".to_string()])
+            );
+            input.send("/dm Mark Hi Mark".to_string())?;
+            mark.expect_message("Ferris", "Hi Mark").await;
+            input.send("Hello everyone".to_string())?;
+            mark.expect_message("Ferris", "Hello everyone").await;
+
+            mark.dm("Ferris", "Hi Ferris").await;
+            assert_eq!(
+                next_event(&mut events).await,
+                Event::Message {
+                    from: "Mark".to_string(),
+                    message: "Hi Ferris".to_string()
+                }
+            );
+
+            input.send("/dm Fiona Hi".to_string())?;
+            assert_eq!(
+                next_event(&mut events).await,
+                Event::Error("User Fiona does not exist".to_string())
+            );
+            input.send("/foo".to_string())?;
+            assert_eq!(
+                next_event(&mut events).await,
+                Event::Error("Unknown command /foo".to_string())
+            );
+
+            mark.close().await;
+
+            // Keepalive pings prevent the server from disconnecting the client, their responses
+            // are not shown
+            sleep(3500).await;
+            input.send("/ping".to_string())?;
+            assert!(matches!(next_event(&mut events).await, Event::Pong(_)));
+
+            input.send("/quit".to_string())?;
+            client.await??;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn tui_client_reconnects() {
+        run_test(opts(2), |spawner| async move {
+            let (input, mut events, client) =
+                spawner.tui_client("Ferris", Duration::from_secs(60), None);
+            assert_eq!(next_event(&mut events).await, Event::Connected);
+
+            // Without keepalive pings, the server disconnects the idle client
+            assert_eq!(
+                next_event(&mut events).await,
+                Event::Error("Timeouted".to_string())
+            );
+            assert_eq!(
+                next_event(&mut events).await,
+                Event::Reconnecting {
+                    delay: Duration::from_millis(50)
+                }
+            );
+            assert_eq!(next_event(&mut events).await, Event::Connected);
+
+            input.send("/list".to_string())?;
+            assert_eq!(
+                next_event(&mut events).await,
+                Event::UserList(vec!["Ferris".to_string()])
+            );
+
+            drop(input);
+            client.await??;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn tui_client_reconnects_while_name_is_taken() {
+        run_test(opts(2), |spawner| async move {
+            let (input, mut events, client) =
+                spawner.tui_client("Ferris", Duration::from_secs(60), None);
+            assert_eq!(next_event(&mut events).await, Event::Connected);
+            assert_eq!(
+                next_event(&mut events).await,
+                Event::Error("Timeouted".to_string())
+            );
+            assert_eq!(
+                next_event(&mut events).await,
+                Event::Reconnecting {
+                    delay: Duration::from_millis(50)
+                }
+            );
+
+            // The name is still taken when the client reconnects
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            assert_eq!(
+                next_event(&mut events).await,
+                Event::Error("Username already taken".to_string())
+            );
+            assert_eq!(
+                next_event(&mut events).await,
+                Event::Reconnecting {
+                    delay: Duration::from_millis(100)
+                }
+            );
+            drop(ferris);
+            assert_eq!(next_event(&mut events).await, Event::Connected);
+
+            drop(input);
+            client.await??;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn tui_client_rejected() {
+        run_test(opts(2), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+
+            let (_input, _events, client) =
+                spawner.tui_client("Ferris", Duration::from_secs(1), None);
+            let error = client.await?.expect_err("client should fail");
+            assert_eq!(error.to_string(), "Username already taken");
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn tls() {
+        let dir = tempfile::tempdir().unwrap();
+        let (tls, client_tls) = tls_certificate(dir.path());
+        let opts = ServerOpts {
+            tls: Some(tls),
+            ..opts(2)
+        };
+        run_test(opts, |spawner| async move {
+            let mut ferris = spawner.tls_client(&client_tls).await;
+            ferris.join("Ferris").await;
+            let mut mark = spawner.tls_client(&client_tls).await;
+            mark.join("Mark").await;
+
+            ferris.dm("Mark", "This is secret").await;
+            mark.expect_message("Ferris", "This is secret").await;
+            ferris.ping().await;
+
+            let mut client3 = spawner.tls_client(&client_tls).await;
+            client3.expect_error("Server is full").await;
+            client3.check_closed().await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn tls_refuses_plaintext() {
+        let dir = tempfile::tempdir().unwrap();
+        let (tls, client_tls) = tls_certificate(dir.path());
+        let opts = ServerOpts {
+            tls: Some(tls),
+            ..opts(2)
+        };
+        run_test(opts, |spawner| async move {
+            let mut client = spawner.client().await;
+            client.join_with("Ferris", None).await;
+            assert!(!matches!(client.reader.recv().await, Some(Ok(_))));
+
+            let mut ferris = spawner.tls_client(&client_tls).await;
+            ferris.join("Ferris").await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn tls_unknown_certificate() {
+        let dir = tempfile::tempdir().unwrap();
+        let (tls, _) = tls_certificate(dir.path());
+        let other_dir = tempfile::tempdir().unwrap();
+        let (_, other_tls) = tls_certificate(other_dir.path());
+        let opts = ServerOpts {
+            tls: Some(tls),
+            ..opts(2)
+        };
+        run_test(opts, |spawner| async move {
+            let stream = TcpStream::connect(("127.0.0.1", spawner.port)).await?;
+            assert!(other_tls.connect(stream).await.is_err());
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn tls_tui_client() {
+        let dir = tempfile::tempdir().unwrap();
+        let (tls, client_tls) = tls_certificate(dir.path());
+        let opts = ServerOpts {
+            tls: Some(tls),
+            ..opts(2)
+        };
+        run_test(opts, |spawner| async move {
+            let (input, mut events, client) =
+                spawner.tui_client("Ferris", Duration::from_secs(1), Some(client_tls));
+            assert_eq!(next_event(&mut events).await, Event::Connected);
+            input.send("/list".to_string())?;
+            assert_eq!(
+                next_event(&mut events).await,
+                Event::UserList(vec!["Ferris".to_string()])
+            );
+            drop(input);
+            client.await??;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn websocket() {
+        let opts = ServerOpts {
+            websocket: true,
+            ..opts(3)
+        };
+        run_test(opts, |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            let mut web = spawner.websocket_client().await;
+            web.join("Web").await;
+            assert_eq!(ferris.list_users().await, vec!["Ferris", "Web"]);
+
+            ferris.dm("Web", "Hi from TCP").await;
+            match web.recv().await {
+                ServerToClientMsg::Message { from, message } => {
+                    assert_eq!(from, "Ferris");
+                    assert_eq!(message, "Hi from TCP");
+                }
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+            web.send(ClientToServerMsg::SendDM {
+                to: "Ferris".to_string(),
+                message: "Hi from the browser".to_string(),
+            })
+            .await;
+            ferris.expect_message("Web", "Hi from the browser").await;
+
+            // Frames can contain pretty-printed JSON
+            let msg = ClientToServerMsg::Broadcast {
+                message: "Line\nbreak".to_string(),
+            };
+            web.send_text(serde_json::to_string_pretty(&msg)?).await;
+            ferris.expect_message("Web", "Line\nbreak").await;
+
+            web.send(ClientToServerMsg::Join {
+                name: "Web".to_string(),
+                password: None,
+                protocol: None,
+            })
+            .await;
+            assert!(matches!(
+                web.recv().await,
+                ServerToClientMsg::Error(error) if error == "Unexpected message received"
+            ));
+            web.check_closed().await;
+            sleep(100).await;
+            assert_eq!(ferris.list_users().await, vec!["Ferris"]);
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn websocket_max_clients() {
+        let opts = ServerOpts {
+            websocket: true,
+            ..opts(1)
+        };
+        run_test(opts, |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+
+            let mut web = spawner.websocket_client().await;
+            assert!(matches!(
+                web.recv().await,
+                ServerToClientMsg::Error(error) if error == "Server is full"
+            ));
+            web.check_closed().await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn websocket_disabled() {
+        run_test(opts(1), |spawner| async move {
+            assert!(spawner.websocket_port.is_none());
+
+            Ok(())
+        })
+        .await;
+    }
+
+    /// Without receipts, so that DMs are received as `Message`.
+    const MESSAGE_PACK: Protocol = Protocol {
+        version: 1,
+        codec: Codec::MessagePack,
+    };
+
+    #[tokio::test]
+    async fn message_pack_codec() {
+        run_test(opts(3), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join_with_protocol("Ferris", MESSAGE_PACK).await;
+            let mut mark = spawner.client().await;
+            mark.join("Mark").await;
+            let mut fiona = spawner.client().await;
+            fiona
+                .join_with_protocol(
+                    "Fiona",
+                    Protocol {
+                        version: PROTOCOL_VERSION,
+                        codec: Codec::Json,
+                    },
+                )
+                .await;
+
+            assert_eq!(ferris.list_users().await, vec!["Ferris", "Fiona", "Mark"]);
+            ferris.dm("Mark", "Hi Mark").await;
+            mark.expect_message("Ferris", "Hi Mark").await;
+            mark.dm("Ferris", "Hi Ferris").await;
+            ferris.expect_message("Mark", "Hi Ferris").await;
+            fiona.dm("Ferris", "Hello").await;
+            ferris.expect_message("Fiona", "Hello").await;
+            ferris.ping().await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn unsupported_protocol_version() {
+        run_test(opts(2), |spawner| async move {
+            let mut client = spawner.client().await;
+            client
+                .send(ClientToServerMsg::Join {
+                    name: "Ferris".to_string(),
+                    password: None,
+                    protocol: Some(Protocol {
+                        version: PROTOCOL_VERSION + 1,
+                        codec: Codec::Json,
+                    }),
+                })
+                .await;
+            client
+                .expect_error(&format!(
+                    "Unsupported protocol version {}",
+                    PROTOCOL_VERSION + 1
+                ))
+                .await;
+            client.check_closed().await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn websocket_only_json() {
+        let opts = ServerOpts {
+            websocket: true,
+            ..opts(2)
+        };
+        run_test(opts, |spawner| async move {
+            let mut web = spawner.websocket_client().await;
+            web.send(ClientToServerMsg::Join {
+                name: "Web".to_string(),
+                password: None,
+                protocol: Some(MESSAGE_PACK),
+            })
+            .await;
+            assert!(matches!(
+                web.recv().await,
+                ServerToClientMsg::Error(error) if error == "Unsupported codec MessagePack"
+            ));
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn max_message_size() {
+        let opts = ServerOpts {
+            max_message_size: 100,
+            ..opts(2)
+        };
+        run_test(opts, |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            ferris.dm("Mark", &"a".repeat(50)).await;
+            ferris.expect_error("User Mark does not exist").await;
+            ferris.dm("Mark", &"a".repeat(200)).await;
+            ferris.expect_error("Message is too large").await;
+            ferris.check_closed().await;
+
+            let mut mark = spawner.client().await;
+            mark.join_with_protocol("Mark", MESSAGE_PACK).await;
+            mark.dm("Fiona", &"a".repeat(50)).await;
+            mark.expect_error("User Fiona does not exist").await;
+            mark.dm("Fiona", &"a".repeat(200)).await;
+            mark.expect_error("Message is too large").await;
+            mark.check_closed().await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn websocket_max_message_size() {
+        let opts = ServerOpts {
+            websocket: true,
+            max_message_size: 100,
+            ..opts(2)
+        };
+        run_test(opts, |spawner| async move {
+            let mut web = spawner.websocket_client().await;
+            web.join("Web").await;
+            web.send(ClientToServerMsg::SendDM {
+                to: "Mark".to_string(),
+                message: "a".repeat(50),
+            })
+            .await;
+            assert!(matches!(
+                web.recv().await,
+                ServerToClientMsg::Error(error) if error == "User Mark does not exist"
+            ));
+            // The frame is refused before it is buffered
+            web.send(ClientToServerMsg::SendDM {
+                to: "Mark".to_string(),
+                message: "a".repeat(200),
+            })
+            .await;
+            assert!(matches!(
+                web.recv().await,
+                ServerToClientMsg::Error(error) if error == "Message is too large"
+            ));
+            web.check_closed().await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn rate_limit_burst() {
+        let opts = ServerOpts {
+            rate_limit: Some(RateLimit {
+                messages_per_second: 1,
+                message_burst: 50,
+                bytes_per_second: 1_000_000,
+                byte_burst: 1_000_000,
+                max_violations: 1000,
+            }),
+            ..opts(2)
+        };
+        run_test(opts, |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            let mut mark = spawner.client().await;
+            mark.join("Mark").await;
+
+            for _ in 0..300 {
+                ferris.send(ClientToServerMsg::Ping).await;
+            }
+            // Other clients are not affected by the flood
+            mark.ping().await;
+
+            // The bucket is refilled over time
+            sleep(1100).await;
+            ferris.send(ClientToServerMsg::ListUsers).await;
+
+            let (mut pongs, mut errors) = (0, 0);
+            loop {
+                match ferris.recv().await {
+                    ServerToClientMsg::Pong => pongs += 1,
+                    ServerToClientMsg::Error(error) if error == "Rate limit exceeded" => {
+                        errors += 1
+                    }
+                    ServerToClientMsg::UserList { .. } => break,
+                    msg => panic!("Unexpected response {msg:?}"),
+                }
+            }
+            assert!((50..=52).contains(&pongs), "{pongs} messages were allowed");
+            // Only the first dropped message of a burst is answered
+            assert!(
+                (1..=pongs - 49).contains(&errors),
+                "{errors} errors were sent"
+            );
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn rate_limit_bytes() {
+        let opts = ServerOpts {
+            rate_limit: Some(RateLimit {
+                messages_per_second: 1000,
+                message_burst: 1000,
+                bytes_per_second: 1,
+                byte_burst: 500,
+                max_violations: 100,
+            }),
+            ..opts(2)
+        };
+        run_test(opts, |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+
+            // Each DM has 140 bytes
+            for _ in 0..10 {
+                ferris.dm("Fiona", &"a".repeat(100)).await;
+            }
+            for _ in 0..3 {
+                ferris.expect_error("User Fiona does not exist").await;
+            }
+            ferris.expect_error("Rate limit exceeded").await;
+            // Small messages still fit into the bucket
+            ferris.ping().await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn rate_limit_disconnect() {
+        let opts = ServerOpts {
+            rate_limit: Some(RateLimit {
+                messages_per_second: 1,
+                message_burst: 20,
+                bytes_per_second: 1_000_000,
+                byte_burst: 1_000_000,
+                max_violations: 5,
+            }),
+            ..opts(2)
+        };
+        run_test(opts, |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            let mut mark = spawner.client().await;
+            mark.join("Mark").await;
+
+            for i in 0..25 {
+                ferris
+                    .send(ClientToServerMsg::Broadcast {
+                        message: format!("Spam {i}"),
+                    })
+                    .await;
+            }
+            // The first dropped message is answered, then the reason of the disconnection
+            for _ in 0..2 {
+                ferris.expect_error("Rate limit exceeded").await;
+            }
+            ferris.check_closed().await;
+
+            for i in 0..20 {
+                mark.expect_message("Ferris", &format!("Spam {i}")).await;
+            }
+            assert_eq!(mark.list_users().await, vec!["Mark".to_string()]);
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn presence() {
+        run_test(opts(3), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            assert_eq!(
+                ferris.watch_presence().await,
+                vec![user_presence("Ferris", Presence::Online, None)]
+            );
+
+            let mut mark = spawner.client().await;
+            mark.join("Mark").await;
+            ferris.expect_presence("Mark", Presence::Online, None).await;
+            mark.set_presence(Presence::DoNotDisturb, Some("Busy"))
+                .await;
+            ferris
+                .expect_presence("Mark", Presence::DoNotDisturb, Some("Busy"))
+                .await;
+            mark.set_presence(Presence::Offline, None).await;
+            mark.expect_error("Cannot set presence Offline").await;
+
+            // Changes of the watching client itself are not sent to it
+            ferris.set_presence(Presence::Away, Some("Lunch")).await;
+            let mut watched = mark.watch_presence().await;
+            watched.sort_by(|a, b| a.name.cmp(&b.name));
+            assert_eq!(
+                watched,
+                vec![
+                    user_presence("Ferris", Presence::Away, Some("Lunch")),
+                    user_presence("Mark", Presence::DoNotDisturb, Some("Busy"))
+                ]
+            );
+
+            mark.close().await;
+            ferris
+                .expect_presence("Mark", Presence::Offline, None)
+                .await;
+            ferris.ping().await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn presence_idle() {
+        run_test(opts(3), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            ferris.watch_presence().await;
+            let mut mark = spawner.client().await;
+            mark.join("Mark").await;
+            ferris.expect_presence("Mark", Presence::Online, None).await;
+
+            sleep(1000).await;
+            ferris.ping().await;
+            ferris.expect_presence("Mark", Presence::Away, None).await;
+
+            // Sending anything makes the user online again
+            mark.ping().await;
+            ferris.expect_presence("Mark", Presence::Online, None).await;
+            ferris.ping().await;
+
+            sleep(1000).await;
+            ferris.ping().await;
+            ferris.expect_presence("Mark", Presence::Away, None).await;
+            ferris.ping().await;
+            mark.expect_error("Timeouted").await;
+            ferris
+                .expect_presence("Mark", Presence::Offline, None)
+                .await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn presence_idle_while_receiving() {
+        run_test(opts(3), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            ferris.watch_presence().await;
+            let mut mark = spawner.client().await;
+            mark.join("Mark").await;
+            ferris.expect_presence("Mark", Presence::Online, None).await;
+
+            // Mark only receives messages, which keep the connection alive
+            for i in 0..5 {
+                sleep(700).await;
+                ferris.dm("Mark", &format!("Hi {i}")).await;
+                mark.expect_message("Ferris", &format!("Hi {i}")).await;
+            }
+            mark.ping().await;
+            // But they do not make Mark active
+            ferris.expect_presence("Mark", Presence::Away, None).await;
+            ferris.expect_presence("Mark", Presence::Online, None).await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn presence_idle_keeps_do_not_disturb() {
+        run_test(opts(3), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            ferris.watch_presence().await;
+            let mut mark = spawner.client().await;
+            mark.join("Mark").await;
+            ferris.expect_presence("Mark", Presence::Online, None).await;
+            mark.set_presence(Presence::DoNotDisturb, None).await;
+            ferris
+                .expect_presence("Mark", Presence::DoNotDisturb, None)
+                .await;
+
+            // Mark is not marked away
+            sleep(2500).await;
+            ferris.ping().await;
+            mark.expect_error("Timeouted").await;
+            ferris
+                .expect_presence("Mark", Presence::Offline, None)
+                .await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn typing() {
+        run_test(opts(3), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join("Ferris").await;
+            let mut mark = spawner.client().await;
+            mark.join("Mark").await;
+
+            ferris.typing("Mark").await;
+            assert!(matches!(
+                mark.recv().await,
+                ServerToClientMsg::Typing { from } if from == "Ferris"
+            ));
+            // Notifications for users that are not connected are dropped
+            ferris.typing("Fiona").await;
+            ferris.typing("Ferris").await;
+            ferris.ping().await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn receipts() {
+        run_test(opts(3), |spawner| async move {
+            let mut ferris = spawner.client().await;
+            ferris.join_with_receipts("Ferris").await;
+            let mut mark = spawner.client().await;
+            mark.join_with_receipts("Mark").await;
+
+            ferris.dm("Mark", "Hi").await;
+            let id = ferris.expect_dm_sent().await;
+            ferris.expect_receipt(id, ReceiptStatus::Delivered).await;
+            mark.expect_direct_message(id, "Ferris", "Hi").await;
+
+            mark.mark_read(id).await;
+            ferris.expect_receipt(id, ReceiptStatus::Read).await;
+
+            // Only the recipient can read the DM
+            ferris.mark_read(id).await;
+            ferris
+                .expect_error(&format!("Message {id} does not exist"))
+                .await;
+            mark.mark_read(id + 1).await;
+            mark.expect_error(&format!("Message {} does not exist", id + 1))
+                .await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn receipts_offline() {
+        run_test(opts(3), |spawner| async move {
+            let mut fiona = spawner.client().await;
+            fiona.join_with_receipts("Fiona").await;
+            fiona.close().await;
+
+            let mut ferris = spawner.client().await;
+            ferris.join_with_receipts("Ferris").await;
+            ferris.dm("Fiona", "Are you there?").await;
+            let id = ferris.expect_dm_sent().await;
+            ferris.ping().await;
+
+            let mut fiona = spawner.client().await;
+            fiona.join_with_receipts("Fiona").await;
+            fiona
+                .expect_direct_message(id, "Ferris", "Are you there?")
+                .await;
+            ferris.expect_receipt(id, ReceiptStatus::Delivered).await;
+
+            // Clients using an older protocol do not receive receipts
+            let mut mark = spawner.client().await;
+            mark.join("Mark").await;
+            mark.dm("Fiona", "Hello").await;
+            assert!(matches!(
+                fiona.recv().await,
+                ServerToClientMsg::DirectMessage { from, .. } if from == "Mark"
+            ));
+            mark.ping().await;
+
+            Ok(())
+        })
+        .await;
+    }
+
+    // The server should correctly close client sockets when it shuts down,
+    // to avoid a situation where the clients would be stuck waiting for a message
+    // for some indeterminate amount of time.
+    #[tokio::test]
+    async fn drop_clients_on_shutdown() {
+        let (mut client, mut client2) = run_test(opts(10), |spawner| async move {
+            let mut client = spawner.client().await;
+            client.join("Bar").await;
+            let mut client2 = spawner.client().await;
+            client2.join("Foo").await;
+            Ok((client, client2))
+        })
+        .await;
+
+        assert!(client.reader.recv().await.is_none());
+        assert!(client2.reader.recv().await.is_none());
+    }
+
+    async fn run_test<C, F, R>(opts: ServerOpts, func: C) -> R
+    where
+        C: FnOnce(ClientSpawner) -> F,
+        F: Future<Output = anyhow::Result<R>>,
+    {
+        let localset = LocalSet::new();
+        let (port, ret) = localset
+            .run_until(async {
+                // Start the server
+                let server = run_server(opts).await.expect("creating server failed");
+                let port = server.port;
+
+                let spawner = ClientSpawner {
+                    port,
+                    websocket_port: server.websocket_port,
+                };
+
+                // Spawn the server future
+                let server_fut = tokio::task::spawn_local(server.future);
+
+                // Run the test
+                let ret = func(spawner).await.expect("test failed");
+
+                // Tell the server to shut down
+                server.tx.send(()).unwrap();
+
+                // Wait until it shuts down
+                server_fut.await.unwrap().unwrap();
+
+                (port, ret)
+            })
+            .await;
+
+        TcpStream::connect(("127.0.0.1", port))
+            .await
+            .expect_err("server is still alive");
+        ret
+    }
+
+    struct Client {
+        writer: MessageWriter<ClientToServerMsg, WriteHalf>,
+        reader: MessageReader<ServerToClientMsg, ReadHalf>,
+    }
+
+    impl Client {
+        async fn join(&mut self, name: &str) {
+            self.join_with(name, None).await;
+            self.expect_welcome().await;
+        }
+
+        async fn join_with(&mut self, name: &str, password: Option<&str>) {
+            self.send(ClientToServerMsg::Join {
+                name: name.to_string(),
+                password: password.map(|password| password.to_string()),
+                protocol: None,
+            })
+            .await;
+        }
+
+        /// Joins with the given protocol and switches to its codec.
+        async fn join_with_protocol(&mut self, name: &str, protocol: Protocol) {
+            self.send(ClientToServerMsg::Join {
+                name: name.to_string(),
+                password: None,
+                protocol: Some(protocol),
+            })
+            .await;
+            self.expect_welcome().await;
+            self.reader.set_codec(protocol.codec);
+            self.writer.set_codec(protocol.codec);
+        }
+
+        async fn register(&mut self, name: &str, password: &str) {
+            self.send(ClientToServerMsg::Register {
+                name: name.to_string(),
+                password: password.to_string(),
+            })
+            .await;
+            self.expect_welcome().await;
+        }
+
+        async fn expect_welcome(&mut self) {
+            let msg = self.recv().await;
+            assert!(matches!(msg, ServerToClientMsg::Welcome), "{msg:?}");
+        }
+
+        async fn ping(&mut self) {
+            self.send(ClientToServerMsg::Ping).await;
+            let msg = self.recv().await;
+            assert!(matches!(msg, ServerToClientMsg::Pong));
+        }
+
+        async fn list_users(&mut self) -> Vec<String> {
+            self.send(ClientToServerMsg::ListUsers).await;
+            let msg = self.recv().await;
+            match msg {
+                ServerToClientMsg::UserList { mut users } => {
+                    users.sort();
+                    users
+                }
+                msg => {
+                    panic!("Unexpected response {msg:?}");
+                }
+            }
+        }
+
+        async fn dm(&mut self, to: &str, message: &str) {
+            self.send(ClientToServerMsg::SendDM {
+                to: to.to_string(),
+                message: message.to_string(),
+            })
+            .await;
+        }
+
+        async fn create_room(&mut self, room: &str) {
+            self.send(ClientToServerMsg::CreateRoom {
+                room: room.to_string(),
+            })
+            .await;
+            self.expect_room_joined(room).await;
+        }
+
+        async fn join_room(&mut self, room: &str) {
+            self.send(ClientToServerMsg::JoinRoom {
+                room: room.to_string(),
+            })
+            .await;
+            self.expect_room_joined(room).await;
+        }
+
+        async fn expect_room_joined(&mut self, expected_room: &str) {
+            match self.recv().await {
+                ServerToClientMsg::RoomJoined { room } => assert_eq!(room, expected_room),
+                msg => panic!("Unexpected response {msg:?}"),
+            }
+        }
+
+        async fn leave_room(&mut self, room: &str) {
+            self.send(ClientToServerMsg::LeaveRoom {
+                room: room.to_string(),
+            })
+            .await;
+            match self.recv().await {
+                ServerToClientMsg::RoomLeft { room: left } => assert_eq!(left, room),
+                msg => panic!("Unexpected response {msg:?}"),
+            }
+        }
+
+        async fn list_rooms(&mut self) -> Vec<String> {
+            self.send(ClientToServerMsg::ListRooms).await;
+            match self.recv().await {
+                ServerToClientMsg::RoomList { mut rooms } => {
+                    rooms.sort();
+                    rooms
+                }
+                msg => panic!("Unexpected response {msg:?}"),
+            }
+        }
+
+        async fn send_to_room(&mut self, room: &str, message: &str) {
+            self.send(ClientToServerMsg::SendToRoom {
+                room: room.to_string(),
+                message: message.to_string(),
+            })
+            .await;
+        }
+
+        async fn expect_room_message(
+            &mut self,
+            expected_room: &str,
+            expected_from: &str,
+            expected_message: &str,
+        ) {
+            match self.recv().await {
+                ServerToClientMsg::RoomMessage {
+                    room,
+                    from,
+                    message,
+                } => {
+                    assert_eq!(room, expected_room);
+                    assert_eq!(from, expected_from);
+                    assert_eq!(message, expected_message);
+                }
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+        }
+
+        async fn expect_message(&mut self, expected_from: &str, expected_message: &str) {
+            let msg = self.recv().await;
+            match msg {
+                ServerToClientMsg::Message { from, message } => {
+                    assert_eq!(from, expected_from);
+                    assert_eq!(message, expected_message);
+                }
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+        }
+
+        async fn history(
+            &mut self,
+            with: Option<&str>,
+            limit: usize,
+            before: Option<u64>,
+        ) -> Vec<HistoryMessage> {
+            self.send(ClientToServerMsg::History {
+                with: with.map(|with| with.to_string()),
+                limit,
+                before,
+            })
+            .await;
+            match self.recv().await {
+                ServerToClientMsg::History { messages } => messages,
+                msg => panic!("Unexpected response {msg:?}"),
+            }
+        }
+
+        async fn join_with_receipts(&mut self, name: &str) {
+            let protocol = Protocol {
+                version: PROTOCOL_VERSION,
+                codec: Codec::Json,
+            };
+            self.join_with_protocol(name, protocol).await;
+        }
+
+        async fn expect_dm_sent(&mut self) -> u64 {
+            match self.recv().await {
+                ServerToClientMsg::DMSent { id } => id,
+                msg => panic!("Unexpected response {msg:?}"),
+            }
+        }
+
+        async fn expect_direct_message(
+            &mut self,
+            expected_id: u64,
+            expected_from: &str,
+            expected_message: &str,
+        ) {
+            match self.recv().await {
+                ServerToClientMsg::DirectMessage { id, from, message } => {
+                    assert_eq!(id, expected_id);
+                    assert_eq!(from, expected_from);
+                    assert_eq!(message, expected_message);
+                }
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+        }
+
+        async fn mark_read(&mut self, id: u64) {
+            self.send(ClientToServerMsg::MarkRead { id }).await;
+        }
+
+        async fn expect_receipt(&mut self, expected_id: u64, expected_status: ReceiptStatus) {
+            match self.recv().await {
+                ServerToClientMsg::Receipt { id, status } => {
+                    assert_eq!(id, expected_id);
+                    assert_eq!(status, expected_status);
+                }
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+        }
+
+        async fn typing(&mut self, to: &str) {
+            self.send(ClientToServerMsg::Typing { to: to.to_string() })
+                .await;
+        }
+
+        async fn set_presence(&mut self, presence: Presence, status: Option<&str>) {
+            self.send(ClientToServerMsg::SetPresence {
+                presence,
+                status: status.map(|status| status.to_string()),
+            })
+            .await;
+        }
+
+        async fn watch_presence(&mut self) -> Vec<UserPresence> {
+            self.send(ClientToServerMsg::WatchPresence).await;
+            match self.recv().await {
+                ServerToClientMsg::PresenceList { users } => users,
+                msg => panic!("Unexpected response {msg:?}"),
+            }
+        }
+
+        async fn expect_presence(&mut self, name: &str, presence: Presence, status: Option<&str>) {
+            match self.recv().await {
+                ServerToClientMsg::PresenceChanged(changed) => {
+                    assert_eq!(changed, user_presence(name, presence, status));
+                }
+                msg => panic!("Unexpected message {msg:?}"),
+            }
+        }
+
+        async fn send(&mut self, msg: ClientToServerMsg) {
+            self.writer.send(msg).await.expect("cannot send message");
+        }
+
+        async fn try_send(&mut self, msg: ClientToServerMsg) -> anyhow::Result<()> {
+            self.writer.send(msg).await
+        }
+
+        async fn expect_error(&mut self, expected_error: &str) {
+            let msg = self.recv().await;
+            match msg {
+                ServerToClientMsg::Error(error) => {
+                    assert_eq!(error, expected_error);
+                }
+                msg => {
+                    panic!("Unexpected response {msg:?}");
+                }
+            }
+        }
+
+        async fn recv(&mut self) -> ServerToClientMsg {
+            self.reader
+                .recv()
+                .await
+                .expect("connection was closed")
+                .expect("did not receive welcome message")
+        }
+
+        async fn close(self) {
+            self.writer.into_inner().shutdown().await.unwrap();
+        }
+
+        async fn check_closed(mut self) {
+            assert!(matches!(self.reader.recv().await, None | Some(Err(_))));
+        }
+    }
+
+    #[derive(Copy, Clone)]
+    struct ClientSpawner {
+        port: u16,
+        websocket_port: Option<u16>,
+    }
+
+    /// A client that speaks to the server over a WebSocket, like a browser.
+    struct WebClient {
+        websocket: WebSocketStream<TcpStream>,
+    }
+
+    impl WebClient {
+        async fn send(&mut self, msg: ClientToServerMsg) {
+            let text = serde_json::to_string(&msg).unwrap();
+            self.send_text(text).await;
+        }
+
+        async fn send_text(&mut self, text: String) {
+            self.websocket
+                .send(Message::Text(text))
+                .await
+                .expect("cannot send frame");
+        }
+
+        async fn recv(&mut self) -> ServerToClientMsg {
+            loop {
+                let frame = tokio::time::timeout(Duration::from_secs(5), self.websocket.next())
+                    .await
+                    .expect("no frame received")
+                    .expect("connection closed")
+                    .expect("cannot receive frame");
+                if let Message::Text(text) = frame {
+                    return serde_json::from_str(&text).expect("invalid message");
+                }
+            }
+        }
+
+        async fn join(&mut self, name: &str) {
+            self.send(ClientToServerMsg::Join {
+                name: name.to_string(),
+                password: None,
+                protocol: None,
+            })
+            .await;
+            assert!(matches!(self.recv().await, ServerToClientMsg::Welcome));
+        }
+
+        async fn check_closed(mut self) {
+            match self.websocket.next().await {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => {}
+                Some(Ok(frame)) => panic!("Unexpected frame {frame:?}"),
+            }
+        }
+    }
+
+    impl ClientSpawner {
+        async fn client(&self) -> Client {
+            self.connect(None).await
+        }
+
+        async fn websocket_client(&self) -> WebClient {
+            let port = self.websocket_port.expect("WebSocket is not enabled");
+            let stream = TcpStream::connect(("127.0.0.1", port))
+                .await
+                .expect("cannot connect to server");
+            let (websocket, _) =
+                tokio_tungstenite::client_async(format!("ws://127.0.0.1:{port}"), stream)
+                    .await
+                    .expect("WebSocket handshake failed");
+            WebClient { websocket }
+        }
+
+        async fn tls_client(&self, tls: &ClientTls) -> Client {
+            self.connect(Some(tls)).await
+        }
+
+        async fn connect(&self, tls: Option<&ClientTls>) -> Client {
+            let client = TcpStream::connect(("127.0.0.1", self.port))
+                .await
+                .expect("cannot connect to server");
+
+            let (rx, tx): (ReadHalf, WriteHalf) = match tls {
+                Some(tls) => tls.connect(client).await.expect("TLS handshake failed"),
+                None => {
+                    let (rx, tx) = client.into_split();
+                    (Box::new(rx), Box::new(tx))
+                }
+            };
+
+            let reader = MessageReader::<ServerToClientMsg, _>::new(rx);
+            let writer = MessageWriter::<ClientToServerMsg, _>::new(tx);
+            Client { reader, writer }
+        }
+
+        /// Starts the terminal client logic, returns a channel for typing lines and a channel of
+        /// the events that it shows.
+        fn tui_client(
+            &self,
+            name: &str,
+            keepalive: Duration,
+            tls: Option<ClientTls>,
+        ) -> (
+            UnboundedSender<String>,
+            UnboundedReceiver<Event>,
+            JoinHandle<anyhow::Result<()>>,
+        ) {
+            let opts = ClientOpts {
+                tls,
+                keepalive,
+                min_backoff: Duration::from_millis(50),
+                ..ClientOpts::new(([127, 0, 0, 1], self.port).into(), name)
+            };
+            let (input, input_rx) = tokio::sync::mpsc::unbounded_channel();
+            let (events_tx, events) = tokio::sync::mpsc::unbounded_channel();
+            let client = tokio::task::spawn_local(run_client(opts, input_rx, events_tx));
+            (input, events, client)
+        }
+    }
+
+    async fn next_event(events: &mut UnboundedReceiver<Event>) -> Event {
+        tokio::time::timeout(Duration::from_secs(5), events.recv())
+            .await
+            .expect("no event received")
+            .expect("client has stopped")
+    }
+
+    fn user_presence(name: &str, presence: Presence, status: Option<&str>) -> UserPresence {
+        UserPresence {
+            name: name.to_string(),
+            presence,
+            status: status.map(|status| status.to_string()),
+        }
+    }
+
+    async fn sleep(duration_ms: u64) {
+        tokio::time::sleep(Duration::from_millis(duration_ms)).await;
+    }
+
+    fn opts(max_clients: usize) -> ServerOpts {
+        ServerOpts {
+            max_clients,
+            max_room_members: 10,
+            account_database: None,
+            max_login_failures: 3,
+            login_lockout: Duration::from_secs(60),
+            data_directory: None,
+            tls: None,
+            websocket: false,
+            max_message_size: 1024,
+            rate_limit: None,
+        }
+    }
+
+    /// Generates a self-signed certificate for `localhost` in `dir`.
+    fn tls_certificate(dir: &std::path::Path) -> (TlsOpts, ClientTls) {
+        let certified = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let opts = TlsOpts {
+            certificate: dir.join("cert.pem"),
+            private_key: dir.join("key.pem"),
+        };
+        std::fs::write(&opts.certificate, certified.cert.pem()).unwrap();
+        std::fs::write(&opts.private_key, certified.key_pair.serialize_pem()).unwrap();
+        let client_tls = ClientTls::load(&opts.certificate, "localhost").unwrap();
+        (opts, client_tls)
     }
 }
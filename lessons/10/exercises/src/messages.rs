@@ -1,9 +1,48 @@
-#[derive(serde::Serialize, serde::Deserialize, Debug)]
+use crate::codec::Codec;
+
+/// Version of the protocol implemented by the server.
+pub const PROTOCOL_VERSION: u32 = 2;
+/// First protocol version in which DMs carry their IDs and their senders receive receipts
+/// (see [ClientToServerMsg::SendDM]).
+pub const RECEIPTS_VERSION: u32 = 2;
+
+/// Protocol requested by a client in [ClientToServerMsg::Join].
+#[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone, PartialEq)]
+pub struct Protocol {
+    pub version: u32,
+    /// Codec of all messages after [ServerToClientMsg::Welcome]
+    pub codec: Codec,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
 pub enum ClientToServerMsg {
     /// This is the first message in the communication, which should be sent by the client.
     /// When some other client with the same name already exists, the server should respond
     /// with an error "Username already taken" and disconnect the new client.
-    Join { name: String },
+    /// If the name belongs to a registered account (see [ClientToServerMsg::Register]), the
+    /// password of the account has to be provided, otherwise the server responds with an error
+    /// "Password required" or "Invalid password" and disconnects the client. After too many failed
+    /// attempts, the server responds with an error "Too many failed login attempts" for a while.
+    /// If a password is provided for a name without an account, the server responds with an error
+    /// "User <name> is not registered".
+    /// The `Join` message and the response to it are always encoded as JSON. If `protocol` is set,
+    /// all following messages use its codec, otherwise they use JSON. If the version is newer
+    /// than [PROTOCOL_VERSION], the server responds with an error
+    /// "Unsupported protocol version <version>". If the codec cannot be used on the connection
+    /// (WebSocket clients can only use JSON), the server responds with an error
+    /// "Unsupported codec <codec>".
+    Join {
+        name: String,
+        #[serde(default)]
+        password: Option<String>,
+        #[serde(default)]
+        protocol: Option<Protocol>,
+    },
+    /// Can be sent instead of [ClientToServerMsg::Join] to create an account protected by
+    /// a password and join with it. The server responds with [ServerToClientMsg::Welcome].
+    /// If the name already has an account, the server responds with an error
+    /// "Username already registered" and disconnects the client.
+    Register { name: String, password: String },
     /// This message checks that the connection is OK.
     /// The server should respond with [ServerToClientMsg::Pong].
     Ping,
@@ -11,17 +50,120 @@ pub enum ClientToServerMsg {
     /// The order of the usernames is not important.
     ListUsers,
     /// Sends a direct message to the user with the given name (`to`).
+    /// If the user is not connected, but it has joined the chat before, the message is stored and
+    /// delivered when the user joins again.
     /// If the user does not exist, the server responds with an error "User <to> does not exist".
     /// If the client tries to send a message to themselves, the server responds with an error
     /// "Cannot send a DM to yourself".
+    /// Clients that have joined with protocol version [RECEIPTS_VERSION] or newer receive DMs as
+    /// [ServerToClientMsg::DirectMessage]. When they send a DM, the server responds with
+    /// [ServerToClientMsg::DMSent] and later sends them a [ServerToClientMsg::Receipt] when the DM
+    /// is delivered to the recipient and when the recipient reads it (if they are still connected).
     SendDM { to: String, message: String },
     /// Sends a message to all currently connected users (except for the sender of the broadcast).
     Broadcast { message: String },
+    /// Creates a new room with the given name and makes the client its first member.
+    /// The server responds with [ServerToClientMsg::RoomJoined].
+    /// If the room already exists, the server responds with an error "Room <room> already exists".
+    CreateRoom { room: String },
+    /// Makes the client a member of an existing room.
+    /// The server responds with [ServerToClientMsg::RoomJoined].
+    /// If the room does not exist, the server responds with an error "Room <room> does not exist".
+    /// If the room already has the maximum number of members, the server responds with an error
+    /// "Room <room> is full".
+    /// If the client is already a member, the server responds with an error
+    /// "You are already in room <room>".
+    JoinRoom { room: String },
+    /// Removes the client from a room. The server responds with [ServerToClientMsg::RoomLeft].
+    /// When the last member leaves the room, the room is removed.
+    /// If the client is not a member of the room, the server responds with an error
+    /// "You are not in room <room>".
+    LeaveRoom { room: String },
+    /// Send a request to list the names of all existing rooms.
+    /// The order of the names is not important.
+    ListRooms,
+    /// Sends a message to all members of a room (except for the sender).
+    /// If the client is not a member of the room, the server responds with an error
+    /// "You are not in room <room>".
+    SendToRoom { room: String, message: String },
+    /// Send a request for past messages, the server responds with [ServerToClientMsg::History].
+    /// If `with` is set, the response contains DMs between the client and the user `with`,
+    /// otherwise it contains broadcasts. It contains at most `limit` (but no more than 100)
+    /// of the newest messages whose ID is smaller than `before` (if it is set), ordered from the
+    /// oldest one. Older messages can be requested by setting `before` to the smallest returned
+    /// ID.
+    History {
+        with: Option<String>,
+        limit: usize,
+        before: Option<u64>,
+    },
+    /// Changes the presence of the client, which is [Presence::Online] after joining, and its
+    /// status message. Clients watching presence receive [ServerToClientMsg::PresenceChanged].
+    /// A client cannot set itself [Presence::Offline], the server responds with an error
+    /// "Cannot set presence Offline".
+    SetPresence {
+        presence: Presence,
+        #[serde(default)]
+        status: Option<String>,
+    },
+    /// Send a request to watch the presence of users. The server responds with
+    /// [ServerToClientMsg::PresenceList] containing the presence of all connected users, then
+    /// it sends [ServerToClientMsg::PresenceChanged] whenever another user joins, leaves or
+    /// changes its presence.
+    WatchPresence,
+    /// Tells the user `to` that the client is typing a DM for it, with
+    /// [ServerToClientMsg::Typing]. If the user is not connected, the notification is dropped.
+    Typing { to: String },
+    /// Marks the DM with the given ID, which was sent to the client, as read. Its sender receives
+    /// a [ServerToClientMsg::Receipt] (see [ClientToServerMsg::SendDM]).
+    /// If there is no such DM, the server responds with an error "Message <id> does not exist".
+    MarkRead { id: u64 },
+}
+
+/// Availability of a user.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum Presence {
+    Online,
+    /// Set by the user, or by the server when the user has not sent anything for a while
+    Away,
+    DoNotDisturb,
+    /// The user has disconnected
+    Offline,
+}
+
+/// Presence of a single user.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct UserPresence {
+    pub name: String,
+    pub presence: Presence,
+    pub status: Option<String>,
+}
+
+/// What has happened with a DM, reported to its sender.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Copy, Clone, PartialEq)]
+pub enum ReceiptStatus {
+    /// The DM was sent to the recipient (right away, or when it joined after being offline)
+    Delivered,
+    /// The recipient has marked the DM as read
+    Read,
+}
+
+/// A DM or a broadcast stored by the server.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct HistoryMessage {
+    /// Increasing ID assigned by the server
+    pub id: u64,
+    /// When the message was sent, in milliseconds since the Unix epoch
+    pub timestamp: u64,
+    pub from: String,
+    /// Recipient of a DM, `None` for broadcasts
+    pub to: Option<String>,
+    pub message: String,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
 pub enum ServerToClientMsg {
-    /// Response to [ClientToServerMsg::Join].
+    /// Response to [ClientToServerMsg::Join] and [ClientToServerMsg::Register].
     Welcome,
     /// Response to [ClientToServerMsg::Ping].
     Pong,
@@ -30,6 +172,39 @@ pub enum ServerToClientMsg {
     /// This message is sent by the server to a client that should receive a message
     /// (that was sent either by [ClientToServerMsg::SendDM] or [ClientToServerMsg::Broadcast]).
     Message { from: String, message: String },
+    /// Response to [ClientToServerMsg::CreateRoom] and [ClientToServerMsg::JoinRoom].
+    RoomJoined { room: String },
+    /// Response to [ClientToServerMsg::LeaveRoom].
+    RoomLeft { room: String },
+    /// Response to [ClientToServerMsg::ListRooms].
+    RoomList { rooms: Vec<String> },
+    /// This message is sent by the server to all members of a room (except for the sender) when
+    /// a message is sent to it with [ClientToServerMsg::SendToRoom].
+    RoomMessage {
+        room: String,
+        from: String,
+        message: String,
+    },
+    /// Response to [ClientToServerMsg::History].
+    History { messages: Vec<HistoryMessage> },
+    /// A DM sent to a client using protocol version [RECEIPTS_VERSION] or newer, instead of
+    /// [ServerToClientMsg::Message]. The ID can be used in [ClientToServerMsg::MarkRead].
+    DirectMessage {
+        id: u64,
+        from: String,
+        message: String,
+    },
+    /// Response to [ClientToServerMsg::SendDM] for clients using protocol version
+    /// [RECEIPTS_VERSION] or newer, with the ID assigned to the DM.
+    DMSent { id: u64 },
+    /// Tells the sender of the DM with the given ID that it was delivered or read.
+    Receipt { id: u64, status: ReceiptStatus },
+    /// Response to [ClientToServerMsg::WatchPresence].
+    PresenceList { users: Vec<UserPresence> },
+    /// Sent to clients watching presence when a user joins, leaves or changes its presence.
+    PresenceChanged(UserPresence),
+    /// Sent to a client when the user `from` is typing a DM for it.
+    Typing { from: String },
     /// This message is returned by the server when an error occurs.
     Error(String),
 }
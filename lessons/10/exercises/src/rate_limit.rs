@@ -0,0 +1,216 @@
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// How long it takes until one message over the limit is forgotten.
+const VIOLATION_DECAY: Duration = Duration::from_secs(10);
+
+/// Limits of the traffic of a single client.
+///
+/// Both limits are enforced by token buckets, so a client can send a burst of messages after
+/// being quiet for a while, but not more than the given rate in the long run.
+#[derive(Copy, Clone, Debug)]
+pub struct RateLimit {
+    /// Number of messages per second that a client can send
+    pub messages_per_second: u32,
+    /// Number of messages that a client can send at once
+    pub message_burst: u32,
+    /// Number of bytes (including framing) per second that a client can send
+    pub bytes_per_second: u32,
+    /// Number of bytes that a client can send at once
+    pub byte_burst: u32,
+    /// After this many messages over the limit, the client is disconnected. They are forgotten
+    /// over time, so only clients that keep sending too much are disconnected.
+    pub max_violations: u32,
+}
+
+/// Outcome of [RateLimiter::check].
+#[derive(Debug, PartialEq)]
+pub enum Verdict {
+    Allowed,
+    /// The message should be dropped and the client told that it is over the limit
+    Exceeded,
+    /// The message should be dropped, the client has already been told about the current burst
+    Dropped,
+    /// The message should be dropped and the client disconnected
+    Disconnect,
+}
+
+/// Tracks the traffic of a single client.
+pub struct RateLimiter {
+    messages: TokenBucket,
+    bytes: TokenBucket,
+    /// Number of recent messages over the limit, it decreases by one every [VIOLATION_DECAY]
+    violations: u32,
+    max_violations: u32,
+    /// When the last violation was forgotten (or the first one happened)
+    forgotten: Instant,
+    /// The last message was over the limit
+    limited: bool,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        let now = Instant::now();
+        Self {
+            messages: TokenBucket::new(limit.messages_per_second, limit.message_burst, now),
+            bytes: TokenBucket::new(limit.bytes_per_second, limit.byte_burst, now),
+            violations: 0,
+            max_violations: limit.max_violations,
+            forgotten: now,
+            limited: false,
+        }
+    }
+
+    /// Checks whether the client can send a message of `size` bytes now.
+    pub fn check(&mut self, size: usize) -> Verdict {
+        self.check_at(size, Instant::now())
+    }
+
+    fn check_at(&mut self, size: usize, now: Instant) -> Verdict {
+        self.messages.refill(now);
+        self.bytes.refill(now);
+        self.forget_violations(now);
+        // Take the tokens only if both buckets have enough, a dropped message costs nothing
+        if self.messages.has(1.0) && self.bytes.has(size as f64) {
+            self.messages.take(1.0);
+            self.bytes.take(size as f64);
+            self.limited = false;
+            return Verdict::Allowed;
+        }
+        self.violations += 1;
+        if self.violations >= self.max_violations {
+            return Verdict::Disconnect;
+        }
+        match std::mem::replace(&mut self.limited, true) {
+            true => Verdict::Dropped,
+            false => Verdict::Exceeded,
+        }
+    }
+
+    fn forget_violations(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.forgotten);
+        let forgotten = (elapsed.as_nanos() / VIOLATION_DECAY.as_nanos()) as u32;
+        self.violations = self.violations.saturating_sub(forgotten);
+        self.forgotten += VIOLATION_DECAY * forgotten;
+        if self.violations == 0 {
+            self.forgotten = now;
+        }
+    }
+}
+
+/// Holds up to `capacity` tokens, which are refilled at `rate` tokens per second.
+struct TokenBucket {
+    capacity: f64,
+    rate: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u32, capacity: u32, now: Instant) -> Self {
+        Self {
+            capacity: capacity as f64,
+            rate: rate as f64,
+            tokens: capacity as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.updated = now;
+    }
+
+    fn has(&self, amount: f64) -> bool {
+        self.tokens >= amount
+    }
+
+    fn take(&mut self, amount: f64) {
+        self.tokens -= amount;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RateLimit, RateLimiter, Verdict};
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    fn limiter(max_violations: u32) -> RateLimiter {
+        RateLimiter::new(RateLimit {
+            messages_per_second: 2,
+            message_burst: 3,
+            bytes_per_second: 100,
+            byte_burst: 200,
+            max_violations,
+        })
+    }
+
+    #[test]
+    fn message_burst() {
+        let mut limiter = limiter(10);
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at(10, now), Verdict::Allowed);
+        }
+        assert_eq!(limiter.check_at(10, now), Verdict::Exceeded);
+        assert_eq!(limiter.check_at(10, now), Verdict::Dropped);
+
+        // Two messages per second
+        let now = now + Duration::from_millis(500);
+        assert_eq!(limiter.check_at(10, now), Verdict::Allowed);
+        assert_eq!(limiter.check_at(10, now), Verdict::Exceeded);
+
+        // The burst is not exceeded after a long pause
+        let now = now + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at(10, now), Verdict::Allowed);
+        }
+        assert_eq!(limiter.check_at(10, now), Verdict::Exceeded);
+    }
+
+    #[test]
+    fn byte_burst() {
+        let mut limiter = limiter(10);
+        let now = Instant::now();
+        assert_eq!(limiter.check_at(150, now), Verdict::Allowed);
+        assert_eq!(limiter.check_at(100, now), Verdict::Exceeded);
+        // A dropped message does not use up the message tokens
+        assert_eq!(limiter.check_at(50, now), Verdict::Allowed);
+
+        let now = now + Duration::from_secs(1);
+        assert_eq!(limiter.check_at(100, now), Verdict::Allowed);
+    }
+
+    #[test]
+    fn disconnect_repeat_offenders() {
+        let mut limiter = limiter(2);
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at(10, now), Verdict::Allowed);
+        }
+        assert_eq!(limiter.check_at(10, now), Verdict::Exceeded);
+        assert_eq!(limiter.check_at(10, now), Verdict::Disconnect);
+    }
+
+    #[test]
+    fn violations_decay() {
+        let mut limiter = limiter(3);
+        let now = Instant::now();
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at(10, now), Verdict::Allowed);
+        }
+        assert_eq!(limiter.check_at(10, now), Verdict::Exceeded);
+        assert_eq!(limiter.check_at(10, now), Verdict::Dropped);
+
+        // The old violations are forgotten, so two more do not disconnect the client
+        let now = now + Duration::from_secs(60);
+        for _ in 0..3 {
+            assert_eq!(limiter.check_at(10, now), Verdict::Allowed);
+        }
+        assert_eq!(limiter.check_at(10, now), Verdict::Exceeded);
+        assert_eq!(limiter.check_at(10, now), Verdict::Dropped);
+        assert_eq!(limiter.check_at(10, now), Verdict::Disconnect);
+    }
+}
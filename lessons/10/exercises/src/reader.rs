@@ -1,42 +1,94 @@
+use crate::codec::Codec;
 use serde::de::DeserializeOwned;
+use std::fmt::{Display, Formatter};
+use std::io::{Error, ErrorKind};
 use std::marker::PhantomData;
 use tokio::io::{AsyncRead, AsyncReadExt};
 
+/// Largest message (without framing) accepted by a reader unless configured otherwise.
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024;
+/// Initial size of the buffer, it grows when a larger message arrives.
+const INITIAL_BUFFER_SIZE: usize = 1024;
+
+/// Error returned by [MessageReader::recv] when a message exceeds the maximum size.
+#[derive(Debug)]
+pub struct MessageTooLarge;
+
+impl Display for MessageTooLarge {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Message is too large")
+    }
+}
+
+impl std::error::Error for MessageTooLarge {}
+
 pub struct MessageReader<T, R> {
     buffer: Vec<u8>,
     loaded: usize,
     client: R,
+    codec: Codec,
+    max_message_size: usize,
+    last_message_size: usize,
     _phantom: PhantomData<T>,
 }
 
 impl<T: DeserializeOwned, R: AsyncRead + Unpin> MessageReader<T, R> {
     pub fn new(client: R) -> Self {
         Self {
-            buffer: vec![0; 1024],
+            buffer: vec![0; INITIAL_BUFFER_SIZE],
             loaded: 0,
             client,
+            codec: Codec::Json,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            last_message_size: 0,
             _phantom: Default::default(),
         }
     }
 
+    /// Makes [MessageReader::recv] fail with [MessageTooLarge] when a message has more than
+    /// `size` bytes.
+    pub fn with_max_message_size(self, size: usize) -> Self {
+        Self {
+            max_message_size: size,
+            ..self
+        }
+    }
+
+    /// Decodes the following messages with `codec`.
+    pub fn set_codec(&mut self, codec: Codec) {
+        self.codec = codec;
+    }
+
+    /// Size (including framing) of the last message returned by [MessageReader::recv].
+    pub fn last_message_size(&self) -> usize {
+        self.last_message_size
+    }
+
     pub async fn recv(&mut self) -> Option<std::io::Result<T>> {
         loop {
-            if let Some(position) = self.buffer[..self.loaded].iter().position(|c| *c == b'\n') {
-                let msg = &self.buffer[..position];
-                let msg: T = match serde_json::from_slice(msg) {
-                    Ok(msg) => msg,
-                    Err(error) => return Some(Err(error.into())),
-                };
-                self.buffer.copy_within(position + 1.., 0);
-
-                self.loaded -= position + 1;
-                return Some(Ok(msg));
+            if let Some((content, size)) = self.codec.frame(&self.buffer[..self.loaded]) {
+                if content.len() > self.max_message_size {
+                    return Some(Err(too_large()));
+                }
+                let msg = self.codec.decode(&self.buffer[content]);
+                self.buffer.copy_within(size..self.loaded, 0);
+
+                self.loaded -= size;
+                self.last_message_size = size;
+                return Some(msg);
             }
 
-            assert!(self.loaded < self.buffer.len());
+            let limit = self.max_message_size + self.codec.overhead();
+            if self.loaded >= limit {
+                return Some(Err(too_large()));
+            }
+            if self.loaded == self.buffer.len() {
+                let size = (self.buffer.len() * 2).min(limit);
+                self.buffer.resize(size, 0);
+            }
             let read_bytes = match self.client.read(&mut self.buffer[self.loaded..]).await {
                 Ok(b) => b,
-                Err(err) => return Some(Err(err.into())),
+                Err(err) => return Some(Err(err)),
             };
             if read_bytes == 0 {
                 break;
@@ -46,3 +98,7 @@ impl<T: DeserializeOwned, R: AsyncRead + Unpin> MessageReader<T, R> {
         None
     }
 }
+
+fn too_large() -> Error {
+    Error::new(ErrorKind::InvalidData, MessageTooLarge)
+}
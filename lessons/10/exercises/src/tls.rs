@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+/// How long the server waits for a client to finish the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// A connection, which can be either plaintext or encrypted.
+pub trait Stream: AsyncRead + AsyncWrite + Unpin {}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> Stream for S {}
+
+/// Reading half of a connection, which can be either plaintext or encrypted.
+pub type ReadHalf = Box<dyn AsyncRead + Unpin>;
+/// Writing half of a connection, which can be either plaintext or encrypted.
+pub type WriteHalf = Box<dyn AsyncWrite + Unpin>;
+
+/// Certificate (chain) and private key of the server, both stored in PEM files.
+#[derive(Clone)]
+pub struct TlsOpts {
+    pub certificate: PathBuf,
+    pub private_key: PathBuf,
+}
+
+/// Splits a stream into halves that can be passed to `MessageReader` and `MessageWriter`.
+pub fn split<S>(stream: S) -> (ReadHalf, WriteHalf)
+where
+    S: AsyncRead + AsyncWrite + Unpin + 'static,
+{
+    let (rx, tx) = tokio::io::split(stream);
+    (Box::new(rx), Box::new(tx))
+}
+
+/// Creates an acceptor of TLS connections from the certificate and key in `opts`.
+pub async fn load_acceptor(opts: &TlsOpts) -> anyhow::Result<TlsAcceptor> {
+    let certificate = tokio::fs::read(&opts.certificate).await?;
+    let certificates = CertificateDer::pem_slice_iter(&certificate).collect::<Result<_, _>>()?;
+    let private_key = PrivateKeyDer::from_pem_slice(&tokio::fs::read(&opts.private_key).await?)?;
+    let config = ServerConfig::builder()
+        .with_no_client_auth()
+        .with_single_cert(certificates, private_key)?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Performs the TLS handshake if `acceptor` is set, otherwise the connection stays plaintext.
+/// Clients that do not speak TLS fail the handshake, so they are refused.
+pub async fn accept(
+    stream: TcpStream,
+    acceptor: Option<&TlsAcceptor>,
+) -> anyhow::Result<Box<dyn Stream>> {
+    match acceptor {
+        Some(acceptor) => {
+            let stream = tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await??;
+            Ok(Box::new(stream))
+        }
+        None => Ok(Box::new(stream)),
+    }
+}
+
+/// TLS configuration of a client.
+#[derive(Clone)]
+pub struct ClientTls {
+    connector: TlsConnector,
+    server_name: ServerName<'static>,
+}
+
+impl ClientTls {
+    /// Trusts the certificates in the PEM file `ca_certificate` (which can be the self-signed
+    /// certificate of the server) and expects that the server has the name `server_name`.
+    pub fn load(ca_certificate: &Path, server_name: &str) -> anyhow::Result<Self> {
+        let mut roots = RootCertStore::empty();
+        for certificate in CertificateDer::pem_file_iter(ca_certificate)? {
+            roots.add(certificate?)?;
+        }
+        let config = ClientConfig::builder()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Ok(Self {
+            connector: TlsConnector::from(Arc::new(config)),
+            server_name: ServerName::try_from(server_name.to_string())?,
+        })
+    }
+
+    /// Performs the TLS handshake with the server.
+    pub async fn connect(&self, stream: TcpStream) -> std::io::Result<(ReadHalf, WriteHalf)> {
+        let stream = self
+            .connector
+            .connect(self.server_name.clone(), stream)
+            .await?;
+        Ok(split(stream))
+    }
+}
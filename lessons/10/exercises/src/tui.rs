@@ -0,0 +1,355 @@
+use crate::codec::Codec;
+use crate::messages::{ClientToServerMsg, Protocol, ServerToClientMsg, PROTOCOL_VERSION};
+use crate::reader::MessageReader;
+use crate::tls::{ClientTls, ReadHalf, WriteHalf};
+use crate::writer::MessageWriter;
+use std::collections::VecDeque;
+use std::fmt::{Display, Formatter};
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::time::{Instant, MissedTickBehavior};
+
+/// Error sent by the server when someone else has joined with the same name.
+const NAME_TAKEN: &str = "Username already taken";
+
+type Reader = MessageReader<ServerToClientMsg, ReadHalf>;
+type Writer = MessageWriter<ClientToServerMsg, WriteHalf>;
+
+/// Configuration of a chat client.
+#[derive(Clone)]
+pub struct ClientOpts {
+    pub address: SocketAddr,
+    pub name: String,
+    pub password: Option<String>,
+    /// Encrypts the connection if the server requires TLS.
+    pub tls: Option<ClientTls>,
+    /// Codec used after joining the chat.
+    pub codec: Codec,
+    /// How often a ping is sent, so that the server does not disconnect an idle client.
+    pub keepalive: Duration,
+    /// Delay before the first reconnection attempt, it is doubled after each failed attempt.
+    pub min_backoff: Duration,
+    /// Maximum delay between reconnection attempts.
+    pub max_backoff: Duration,
+}
+
+impl ClientOpts {
+    pub fn new(address: SocketAddr, name: &str) -> Self {
+        Self {
+            address,
+            name: name.to_string(),
+            password: None,
+            tls: None,
+            codec: Codec::Json,
+            keepalive: Duration::from_secs(1),
+            min_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Something that should be shown to the user.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// The client has joined the chat
+    Connected,
+    /// The connection was lost, the client will try to reconnect after `delay`
+    Reconnecting {
+        delay: Duration,
+    },
+    Message {
+        from: String,
+        message: String,
+    },
+    UserList(Vec<String>),
+    /// Response to `/ping`, with the round-trip time
+    Pong(Duration),
+    Error(String),
+    /// Any other message from the server
+    Notice(String),
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Event::Connected => write!(f, "* Connected"),
+            Event::Reconnecting { delay } => {
+                write!(f, "* Disconnected, reconnecting in {}ms", delay.as_millis())
+            }
+            Event::Message { from, message } => write!(f, "<{from}> {message}"),
+            Event::UserList(users) => write!(f, "* Users: {}", users.join(", ")),
+            Event::Pong(rtt) => write!(f, "* Pong ({}ms)", rtt.as_millis()),
+            Event::Error(error) => write!(f, "! {error}"),
+            Event::Notice(notice) => write!(f, "* {notice}"),
+        }
+    }
+}
+
+/// A parsed line of user input.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Send(ClientToServerMsg),
+    Quit,
+}
+
+/// Parses a line typed by the user. Lines that do not start with `/` are broadcasted.
+///
+/// Supported commands: `/dm <user> <message>`, `/list`, `/ping` and `/quit`.
+pub fn parse_command(line: &str) -> Result<Command, String> {
+    let Some(command) = line.strip_prefix('/') else {
+        return Ok(Command::Send(ClientToServerMsg::Broadcast {
+            message: line.to_string(),
+        }));
+    };
+    let (command, args) = command.split_once(' ').unwrap_or((command, ""));
+    let msg = match command {
+        "dm" => match args.trim_start().split_once(' ') {
+            Some((to, message)) if !message.is_empty() => ClientToServerMsg::SendDM {
+                to: to.to_string(),
+                message: message.to_string(),
+            },
+            _ => return Err("Usage: /dm <user> <message>".to_string()),
+        },
+        "list" => ClientToServerMsg::ListUsers,
+        "ping" => ClientToServerMsg::Ping,
+        "quit" => return Ok(Command::Quit),
+        _ => return Err(format!("Unknown command /{command}")),
+    };
+    Ok(Command::Send(msg))
+}
+
+/// Why a connection has ended.
+enum Disconnect {
+    /// The user wants to quit
+    Quit,
+    /// The connection was lost
+    Lost,
+}
+
+/// Runs a chat client that executes the lines from `input` and reports what happened to `events`.
+///
+/// When the connection is lost, the client reconnects with an exponential backoff. It stops when
+/// `input` is closed or the user types `/quit`, and fails if the server rejects its `Join`.
+/// After a reconnection, the server may not have noticed yet that the previous connection was
+/// lost, so the name being taken is retried as well.
+pub async fn run_client(
+    opts: ClientOpts,
+    mut input: UnboundedReceiver<String>,
+    events: UnboundedSender<Event>,
+) -> anyhow::Result<()> {
+    let mut backoff = opts.min_backoff;
+    let mut joined = false;
+    loop {
+        if let Ok((rx, tx)) = connect(&opts).await {
+            let mut reader = Reader::new(rx);
+            let mut writer = Writer::new(tx);
+            match join(&opts, &mut reader, &mut writer).await {
+                Ok(true) => {
+                    joined = true;
+                    backoff = opts.min_backoff;
+                    let _ = events.send(Event::Connected);
+                    let result = run_session(&opts, reader, writer, &mut input, &events).await;
+                    if let Disconnect::Quit = result {
+                        return Ok(());
+                    }
+                }
+                Ok(false) => {}
+                Err(error) if joined && error.to_string() == NAME_TAKEN => {
+                    let _ = events.send(Event::Error(error.to_string()));
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        let _ = events.send(Event::Reconnecting { delay: backoff });
+        let deadline = tokio::time::sleep(backoff);
+        tokio::pin!(deadline);
+        loop {
+            tokio::select! {
+                _ = &mut deadline => break,
+                line = input.recv() => match line.as_deref().map(parse_command) {
+                    None | Some(Ok(Command::Quit)) => return Ok(()),
+                    Some(Ok(Command::Send(_))) => {
+                        let _ = events.send(Event::Error("Not connected".to_string()));
+                    }
+                    Some(Err(error)) => {
+                        let _ = events.send(Event::Error(error));
+                    }
+                },
+            }
+        }
+        backoff = (backoff * 2).min(opts.max_backoff);
+    }
+}
+
+async fn connect(opts: &ClientOpts) -> std::io::Result<(ReadHalf, WriteHalf)> {
+    let stream = TcpStream::connect(opts.address).await?;
+    match &opts.tls {
+        Some(tls) => tls.connect(stream).await,
+        None => {
+            let (rx, tx) = stream.into_split();
+            Ok((Box::new(rx), Box::new(tx)))
+        }
+    }
+}
+
+/// Joins the chat. Returns `false` if the connection was lost, which can be retried, and an error
+/// if the server has rejected the client.
+async fn join(opts: &ClientOpts, reader: &mut Reader, writer: &mut Writer) -> anyhow::Result<bool> {
+    let msg = ClientToServerMsg::Join {
+        name: opts.name.clone(),
+        password: opts.password.clone(),
+        protocol: Some(Protocol {
+            version: PROTOCOL_VERSION,
+            codec: opts.codec,
+        }),
+    };
+    if writer.send(msg).await.is_err() {
+        return Ok(false);
+    }
+    match reader.recv().await {
+        Some(Ok(ServerToClientMsg::Welcome)) => {
+            reader.set_codec(opts.codec);
+            writer.set_codec(opts.codec);
+            Ok(true)
+        }
+        Some(Ok(ServerToClientMsg::Error(error))) => Err(anyhow::anyhow!(error)),
+        Some(Ok(msg)) => Err(anyhow::anyhow!("Unexpected message {msg:?}")),
+        Some(Err(_)) | None => Ok(false),
+    }
+}
+
+async fn run_session(
+    opts: &ClientOpts,
+    mut reader: Reader,
+    mut writer: Writer,
+    input: &mut UnboundedReceiver<String>,
+    events: &UnboundedSender<Event>,
+) -> Disconnect {
+    // Start times of pings sent by the user, `None` for keepalive pings whose `Pong` is not shown
+    let mut pings = VecDeque::new();
+    let mut keepalive = tokio::time::interval_at(Instant::now() + opts.keepalive, opts.keepalive);
+    keepalive.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        let msg = tokio::select! {
+            line = input.recv() => {
+                let Some(line) = line else {
+                    return Disconnect::Quit;
+                };
+                match parse_command(&line) {
+                    Ok(Command::Send(msg)) => {
+                        if let ClientToServerMsg::Ping = msg {
+                            pings.push_back(Some(Instant::now()));
+                        }
+                        msg
+                    }
+                    Ok(Command::Quit) => return Disconnect::Quit,
+                    Err(error) => {
+                        let _ = events.send(Event::Error(error));
+                        continue;
+                    }
+                }
+            }
+            msg = reader.recv() => {
+                let Some(Ok(msg)) = msg else {
+                    return Disconnect::Lost;
+                };
+                let event = match msg {
+                    ServerToClientMsg::Pong => match pings.pop_front() {
+                        Some(Some(start)) => Event::Pong(start.elapsed()),
+                        _ => continue,
+                    },
+                    // Receipts of sent DMs are not shown
+                    ServerToClientMsg::DMSent { .. } | ServerToClientMsg::Receipt { .. } => continue,
+                    msg => to_event(msg),
+                };
+                let _ = events.send(event);
+                continue;
+            }
+            _ = keepalive.tick() => {
+                pings.push_back(None);
+                ClientToServerMsg::Ping
+            }
+        };
+        if writer.send(msg).await.is_err() {
+            return Disconnect::Lost;
+        }
+    }
+}
+
+fn to_event(msg: ServerToClientMsg) -> Event {
+    match msg {
+        ServerToClientMsg::Message { from, message }
+        | ServerToClientMsg::DirectMessage { from, message, .. } => {
+            Event::Message { from, message }
+        }
+        ServerToClientMsg::UserList { mut users } => {
+            users.sort();
+            Event::UserList(users)
+        }
+        ServerToClientMsg::Error(error) => Event::Error(error),
+        ServerToClientMsg::RoomMessage {
+            room,
+            from,
+            message,
+        } => Event::Message {
+            from: format!("{from}@{room}"),
+            message,
+        },
+        msg => Event::Notice(format!("{msg:?}")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_command, Command};
+    use crate::messages::ClientToServerMsg;
+
+    #[test]
+    fn parse_broadcast() {
+        assert_eq!(
+            parse_command("hello /dm"),
+            Ok(Command::Send(ClientToServerMsg::Broadcast {
+                message: "hello /dm".to_string()
+            }))
+        );
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(
+            parse_command("/dm Ferris hi there"),
+            Ok(Command::Send(ClientToServerMsg::SendDM {
+                to: "Ferris".to_string(),
+                message: "hi there".to_string()
+            }))
+        );
+        assert_eq!(
+            parse_command("/list"),
+            Ok(Command::Send(ClientToServerMsg::ListUsers))
+        );
+        assert_eq!(
+            parse_command("/ping"),
+            Ok(Command::Send(ClientToServerMsg::Ping))
+        );
+        assert_eq!(parse_command("/quit"), Ok(Command::Quit));
+    }
+
+    #[test]
+    fn parse_invalid_commands() {
+        assert_eq!(
+            parse_command("/dm Ferris"),
+            Err("Usage: /dm <user> <message>".to_string())
+        );
+        assert_eq!(
+            parse_command("/dm"),
+            Err("Usage: /dm <user> <message>".to_string())
+        );
+        assert_eq!(
+            parse_command("/foo bar"),
+            Err("Unknown command /foo".to_string())
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use crate::messages::ServerToClientMsg;
+use crate::reader::MessageTooLarge;
+use crate::tls::{split, ReadHalf, Stream, WriteHalf};
+use futures_util::{SinkExt, StreamExt};
+use std::future::Future;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, DuplexStream};
+use tokio_tungstenite::tungstenite::error::CapacityError;
+use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+use tokio_tungstenite::tungstenite::protocol::{CloseFrame, WebSocketConfig};
+use tokio_tungstenite::tungstenite::{Error, Message};
+use tokio_tungstenite::WebSocketStream;
+
+/// How long the server waits for a client to finish the WebSocket handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(2);
+/// Size of the buffers between the WebSocket and the connection returned by [accept].
+const PIPE_SIZE: usize = 64 * 1024;
+
+/// Performs the WebSocket handshake.
+///
+/// Returns a connection that speaks the newline-delimited JSON protocol (like a TCP client) and
+/// a future that has to run alongside it, which turns each text frame into a line and each line
+/// into a text frame. The future ends when either the WebSocket or the connection is closed.
+///
+/// Frames and messages larger than `max_message_size` are not buffered, the client receives an
+/// error "Message is too large" and it is disconnected.
+pub async fn accept<S: Stream>(
+    stream: S,
+    max_message_size: usize,
+) -> anyhow::Result<(
+    (ReadHalf, WriteHalf),
+    impl Future<Output = anyhow::Result<()>>,
+)> {
+    let config = WebSocketConfig {
+        max_message_size: Some(max_message_size),
+        max_frame_size: Some(max_message_size),
+        ..WebSocketConfig::default()
+    };
+    let accepted = tokio_tungstenite::accept_async_with_config(stream, Some(config));
+    let websocket = tokio::time::timeout(HANDSHAKE_TIMEOUT, accepted).await??;
+    let (connection, pipe) = tokio::io::duplex(PIPE_SIZE);
+    Ok((split(connection), forward(websocket, pipe)))
+}
+
+async fn forward<S: Stream>(
+    websocket: WebSocketStream<S>,
+    pipe: DuplexStream,
+) -> anyhow::Result<()> {
+    let (mut frames_tx, mut frames_rx) = websocket.split();
+    let (lines_rx, mut lines_tx) = tokio::io::split(pipe);
+    let mut lines = BufReader::new(lines_rx).lines();
+    loop {
+        tokio::select! {
+            frame = frames_rx.next() => match frame {
+                Some(Ok(Message::Text(text))) => {
+                    // JSON strings cannot contain raw line breaks, so the ones in the frame are
+                    // only whitespace between tokens
+                    let mut line = text.replace(['\n', '\r'], " ").into_bytes();
+                    line.push(b'\n');
+                    lines_tx.write_all(&line).await?;
+                }
+                Some(Ok(Message::Binary(_))) => {
+                    anyhow::bail!("Binary WebSocket frames are not supported")
+                }
+                // Pings are answered by tungstenite, a close frame is followed by the end of
+                // the stream
+                Some(Ok(_)) => {}
+                Some(Err(Error::Capacity(CapacityError::MessageTooLong { .. }))) => {
+                    // The rest of the message is not read, so the client cannot continue
+                    let error = ServerToClientMsg::Error(MessageTooLarge.to_string());
+                    frames_tx.send(Message::Text(serde_json::to_string(&error)?)).await?;
+                    let close = CloseFrame {
+                        code: CloseCode::Size,
+                        reason: "".into(),
+                    };
+                    let _ = frames_tx.send(Message::Close(Some(close))).await;
+                    return Ok(());
+                }
+                Some(Err(error)) => return Err(error.into()),
+                None => return Ok(()),
+            },
+            line = lines.next_line() => match line? {
+                Some(line) => frames_tx.send(Message::Text(line)).await?,
+                None => {
+                    // The client might have already disconnected
+                    let _ = frames_tx.close().await;
+                    return Ok(());
+                }
+            },
+        }
+    }
+}
@@ -1,9 +1,11 @@
+use crate::codec::Codec;
 use serde::Serialize;
 use std::marker::PhantomData;
 use tokio::io::{AsyncWrite, AsyncWriteExt};
 
 pub struct MessageWriter<T, W> {
     stream: W,
+    codec: Codec,
     _phantom: PhantomData<T>,
 }
 
@@ -11,14 +13,19 @@ impl<T: Serialize, W: AsyncWrite + Unpin> MessageWriter<T, W> {
     pub fn new(stream: W) -> Self {
         Self {
             stream,
+            codec: Codec::Json,
             _phantom: Default::default(),
         }
     }
 
+    /// Encodes the following messages with `codec`.
+    pub fn set_codec(&mut self, codec: Codec) {
+        self.codec = codec;
+    }
+
     pub async fn send(&mut self, msg: T) -> anyhow::Result<()> {
-        let serialized = serde_json::to_vec(&msg)?;
+        let serialized = self.codec.encode(&msg)?;
         self.stream.write_all(&serialized).await?;
-        self.stream.write_all(b"\n").await?;
         self.stream.flush().await?;
         Ok(())
     }